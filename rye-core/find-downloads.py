@@ -0,0 +1,209 @@
+import re
+import sys
+from urllib.parse import unquote
+
+
+RELEASE_URL = "https://api.github.com/repos/indygreg/python-build-standalone/releases"
+FLAVOR_PREFERENCES = [
+    "shared-pgo",
+    "shared-noopt",
+    "shared-noopt",
+    "static-noopt",
+    "gnu-pgo+lto",
+    "gnu-lto",
+    "gnu-pgo",
+    "pgo+lto",
+    "lto",
+    "pgo",
+]
+HIDDEN_FLAVORS = [
+    "debug",
+    "noopt",
+    "install_only",
+    "freethreaded+pgo+lto",
+    "freethreaded+lto",
+    "freethreaded+pgo",
+    "freethreaded+noopt",
+    "freethreaded+debug",
+]
+SPECIAL_TRIPLES = {
+    "macos": "x86_64-apple-darwin",
+    "linux64": "x86_64-unknown-linux",
+    "windows-amd64": "x86_64-pc-windows",
+    "windows-x86": "i686-pc-windows",
+    "linux64-musl": "x86_64-unknown-linux-musl",
+}
+
+# matches these: https://doc.rust-lang.org/std/env/consts/constant.ARCH.html
+ARCH_MAPPING = {
+    "x86_64": "x86_64",
+    "x86": "x86",
+    "i686": "x86",
+    "aarch64": "aarch64",
+    "armv7": "arm",
+    "riscv64": "riscv64",
+}
+
+# matches these: https://doc.rust-lang.org/std/env/consts/constant.OS.html
+PLATFORM_MAPPING = {
+    "darwin": "macos",
+    "windows": "windows",
+    "linux": "linux",
+}
+
+_filename_re = re.compile(
+    r"""(?x)
+    ^
+        cpython-(?P<ver>\d+\.\d+\.\d+?)
+        (?:\+\d+)?
+        -(?P<triple>.*?)
+        (?:-[\dT]+)?\.tar\.(?:gz|zst)
+    $
+"""
+)
+_suffix_re = re.compile(
+    r"""(?x)^(.*?)-(%s)$"""
+    % (
+        "|".join(
+            map(
+                re.escape,
+                sorted(FLAVOR_PREFERENCES + HIDDEN_FLAVORS, key=len, reverse=True),
+            )
+        )
+    )
+)
+
+
+def parse_filename(filename):
+    match = _filename_re.match(filename)
+    if match is None:
+        return
+    version, triple = match.groups()
+    if triple.endswith("-full"):
+        triple = triple[:-5]
+    match = _suffix_re.match(triple)
+    if match is not None:
+        triple, suffix = match.groups()
+    else:
+        suffix = None
+    return (version, triple, suffix)
+
+
+def get_variants(flavor):
+    """Returns the build variants a flavor is published under.  `None`
+    stands for the default build."""
+    if flavor is None:
+        return [None]
+    if flavor.startswith("freethreaded") or "debug" in flavor:
+        return []
+    if flavor.startswith("static"):
+        return [None, "static"]
+    return [None]
+
+
+def normalize_triple(triple):
+    if "-static" in triple:
+        return
+    triple = SPECIAL_TRIPLES.get(triple, triple)
+    pieces = triple.split("-")
+    try:
+        arch = ARCH_MAPPING.get(pieces[0])
+        if arch is None:
+            return
+        platform = PLATFORM_MAPPING.get(pieces[2])
+        if platform is None:
+            return
+    except IndexError:
+        return
+    abi = pieces[3] if len(pieces) > 3 else None
+    # soft float builds do not run on the common hard float arm systems
+    if abi == "gnueabi":
+        return
+    if abi == "musl":
+        platform = "linux-musl"
+    return "%s-%s" % (arch, platform)
+
+
+def iter_release_assets():
+    """Yields the download URLs of all release assets from the GitHub API."""
+    import requests
+
+    token = open("token.txt").read().strip()
+    headers = {
+        "X-GitHub-Api-Version": "2022-11-28",
+        "Authorization": "Bearer " + token,
+    }
+    sess = requests.Session()
+    for page in range(1, 100):
+        resp = sess.get("%s?page=%d" % (RELEASE_URL, page), headers=headers)
+        rows = resp.json()
+        if not rows:
+            break
+        for row in rows:
+            for asset in row["assets"]:
+                yield asset["browser_download_url"]
+
+
+def iter_listed_assets(filename):
+    """Yields the download URLs listed in a file, newest release first.  A
+    previously generated table works as well."""
+    with open(filename) as f:
+        for line in f:
+            for url in re.findall(r'https://[^"\s]+', line):
+                yield url
+
+
+if len(sys.argv) > 1:
+    assets = iter_listed_assets(sys.argv[1])
+else:
+    assets = iter_release_assets()
+
+results = {}
+for url in assets:
+    base_name = unquote(url.rsplit("/")[-1])
+    if base_name.endswith(".sha256"):
+        continue
+    info = parse_filename(base_name)
+    if info is None:
+        continue
+    py_ver, triple, flavor = info
+    if "-static" in triple:
+        continue
+    triple = normalize_triple(triple)
+    if triple is None:
+        continue
+    for variant in get_variants(flavor):
+        results.setdefault((py_ver, variant), []).append((triple, flavor, url))
+
+
+def _sort_key(info):
+    triple, flavor, url = info
+    try:
+        pref = FLAVOR_PREFERENCES.index(flavor)
+    except ValueError:
+        pref = len(FLAVOR_PREFERENCES)
+    return triple, pref
+
+
+final_results = {}
+for (py_ver, variant), choices in results.items():
+    choices.sort(key=_sort_key)
+    urls = {}
+    for triple, flavor, url in choices:
+        key = tuple(triple.split('-', 1))
+        if key in urls:
+            continue
+        urls[key] = url
+    kind = "cpython" if variant is None else "cpython-" + variant
+    final_results[tuple(map(int, py_ver.split('.'))), kind] = urls
+
+
+print("// generated code, do not edit")
+print("use std::borrow::Cow;")
+print("pub const CPYTHON_VERSIONS: &[(PythonVersion, &str, &str, &str)] = &[")
+for (py_ver, kind), choices in sorted(
+    final_results.items(), key=lambda x: (x[0][0], x[0][1] == "cpython"), reverse=True
+):
+    for (arch, platform), url in sorted(choices.items()):
+        print('    (PythonVersion { kind: Cow::Borrowed("%s"), major: %d, minor: %d, patch: %d, suffix: None }, "%s", "%s", "%s"),' % ((kind,) + py_ver + (arch, platform, url)))
+print("];")
@@ -0,0 +1,723 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env::consts::ARCH;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs};
+
+use anyhow::{bail, Context, Error};
+use console::style;
+use indicatif::{ProgressBar, ProgressStyle};
+use sha2::{Digest, Sha256};
+
+use crate::cache::{get_cache_dir, touch};
+use crate::config::{
+    apply_env_config, get_app_dir, get_canonical_py_path, get_py_bin, Config, EnvTarget,
+};
+use crate::error::{ErrorKind, Failure};
+use crate::filelock::FileLock;
+use crate::shell::is_on_path;
+use crate::signals;
+use crate::sources::{
+    current_platform, get_download_url, has_downloads, no_builds_error, PythonVersion,
+    PythonVersionRequest,
+};
+use crate::timings;
+use crate::utils::{make_curl_handle, unpack_tarball, write_atomic, CommandOutput};
+
+/// Scripts of a virtualenv that never get a shim of their own, without
+/// version suffix (`python3.11` counts as `python`).
+///
+/// The python shims exist anyways and pip is not installed into virtualenvs.
+const VENV_SHIM_SKIP: &[&str] = &[
+    "python",
+    "pythonw",
+    "pip",
+    "activate",
+    "activate.csh",
+    "activate.fish",
+    "activate.nu",
+    "activate.ps1",
+    "activate_this.py",
+    "deactivate",
+];
+
+/// Which virtualenvs the shims of `behavior.venv-shims` point into, relative
+/// to the rye home folder.
+const VENV_SHIM_REGISTRY: &str = "venv-shims.json";
+
+pub const SELF_PYTHON_VERSION: PythonVersionRequest = PythonVersionRequest {
+    kind: Some(Cow::Borrowed("cpython")),
+    major: 3,
+    minor: Some(10),
+    patch: None,
+    suffix: None,
+};
+const SELF_SITE_PACKAGES: &str = "python3.10/site-packages";
+
+/// Bump this whenever the contents of the self venv change so that existing
+/// installations are rebuilt after an upgrade.
+const SELF_VERSION: u64 = 1;
+
+/// The tools rye installs into the self venv.
+///
+/// Their versions can be pinned in the `[internal-tools]` section of the config.
+pub const SELF_TOOLS: &[&str] = &["pip", "pip-tools", "virtualenv", "unearth"];
+
+/// Scripts that need to exist in the self venv.
+const SELF_SCRIPTS: &[&str] = &["python", "pip", "pip-compile", "pip-sync", "virtualenv"];
+
+/// Bootstraps the venv for rye itself
+///
+/// An existing venv that is incomplete or was created by a different version
+/// of rye is rebuilt.
+pub fn ensure_self_venv(output: CommandOutput) -> Result<PathBuf, Error> {
+    let app_dir = get_app_dir().context("could not get app dir")?;
+    let dir = app_dir.join("self");
+    let _lock = FileLock::acquire(&dir, output)?;
+    let _defer = signals::defer_interrupts();
+    let _timing = timings::phase("bootstrap");
+    let first_run = !dir.is_dir();
+    if dir.is_dir() {
+        match find_self_venv_issue(&dir) {
+            None => {
+                if read_installed_tools(&dir) != get_self_tool_requirements()? {
+                    if output != CommandOutput::Quiet {
+                        eprintln!("Updating rye internals (internal tool versions changed)");
+                    }
+                    install_self_tools(&dir, output)?;
+                }
+                return Ok(dir);
+            }
+            Some(issue) => {
+                if output != CommandOutput::Quiet {
+                    eprintln!("Rebuilding rye internals ({})", issue);
+                }
+                fs::remove_dir_all(&dir).context("failed to remove outdated self venv")?;
+            }
+        }
+    } else if output != CommandOutput::Quiet {
+        eprintln!("Bootstrapping rye internals");
+    }
+
+    let version = fetch(&SELF_PYTHON_VERSION, output).with_context(|| {
+        format!(
+            "failed to fetch internal cpython toolchain {}",
+            SELF_PYTHON_VERSION
+        )
+    })?;
+    let py_bin = get_py_bin(&version)?;
+
+    // initialize the virtualenv
+    let mut venv_cmd = Command::new(&py_bin);
+    venv_cmd.arg("-mvenv");
+    venv_cmd.arg("--upgrade-deps");
+    venv_cmd.arg(&dir);
+
+    apply_env_config(&mut venv_cmd, EnvTarget::Tools)?;
+    let status = signals::status(&mut venv_cmd)
+        .with_context(|| format!("unable to create self venv using {}", py_bin.display()))?;
+    if !status.success() {
+        bail!("failed to initialize virtualenv in {}", dir.display());
+    }
+
+    install_self_tools(&dir, output)?;
+
+    // create shims
+    let shims = app_dir.join("shims");
+    fs::remove_dir_all(&shims).ok();
+    fs::create_dir_all(&shims).context("tried to crate shim folder")?;
+    let this = env::current_exe()?;
+    link_shim(&this, &shims.join("python")).context("tried to link python shim")?;
+    link_shim(&this, &shims.join("python3")).context("tried to link python3 shim")?;
+
+    // written last so that a partially bootstrapped venv is detected as such
+    fs::write(dir.join("tool-version"), SELF_VERSION.to_string())
+        .context("could not write self venv version")?;
+
+    if first_run && output != CommandOutput::Quiet && !is_on_path(&shims) {
+        eprintln!(
+            "{} to use the python shims, add {} to your PATH with `rye self setup-path`",
+            style("note:").cyan(),
+            shims.display()
+        );
+    }
+
+    Ok(dir)
+}
+
+/// Links a shim to the rye executable.
+fn link_shim(this: &Path, shim: &Path) -> std::io::Result<()> {
+    // on linux symlinks cause us to mis-detect the shim because
+    // it points to the actual executable.  So there we need to do
+    // hardlinks instead.
+    #[cfg(target_os = "linux")]
+    {
+        fs::hard_link(this, shim)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        std::os::unix::fs::symlink(this, shim)
+    }
+}
+
+/// Whether a file in the shims folder is a shim for a virtualenv script.
+///
+/// These are links to rye itself (unlike the symlinks of installed tools)
+/// that rye recorded in its shim registry, and can be replaced by an
+/// installed tool of the same name.  Other files are never touched.
+pub fn is_venv_shim(shim: &Path) -> bool {
+    match get_app_dir() {
+        Ok(app_dir) => is_registered_venv_shim(shim, &read_venv_shim_registry(app_dir)),
+        Err(_) => false,
+    }
+}
+
+fn is_registered_venv_shim(shim: &Path, registry: &BTreeMap<String, BTreeSet<PathBuf>>) -> bool {
+    let name = shim.file_name().and_then(|x| x.to_str()).unwrap_or("");
+    name != "python" && name != "python3" && registry.contains_key(name) && is_rye_link(shim)
+}
+
+fn read_venv_shim_registry(app_dir: &Path) -> BTreeMap<String, BTreeSet<PathBuf>> {
+    fs::read(app_dir.join(VENV_SHIM_REGISTRY))
+        .ok()
+        .and_then(|x| serde_json::from_slice(&x).ok())
+        .unwrap_or_default()
+}
+
+/// Whether a shim is a link to rye, see [`link_shim`].
+fn is_rye_link(shim: &Path) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // a hard link, so rye itself is another name of the file
+        shim.symlink_metadata()
+            .is_ok_and(|x| x.is_file() && x.nlink() > 1)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        env::current_exe()
+            .ok()
+            .zip(fs::read_link(shim).ok())
+            .is_some_and(|(this, target)| this == target)
+    }
+}
+
+/// The shims [`link_venv_shims`] added and removed.
+#[derive(Debug, Default)]
+pub struct VenvShimChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Adds shims for the scripts in the virtualenv to the shims folder.
+///
+/// Scripts that already have a shim (the python shims or installed tools) are
+/// left alone.  Outside of a project these shims fall through to whatever
+/// they shadow on the `PATH`.  Shims that no virtualenv has the script for
+/// any more are removed.
+pub fn link_venv_shims(venv: &Path) -> Result<VenvShimChanges, Error> {
+    let app_dir = get_app_dir()?;
+    let shims = app_dir.join("shims");
+    fs::create_dir_all(&shims).context("tried to create shim folder")?;
+    let registry_path = app_dir.join(VENV_SHIM_REGISTRY);
+    let mut registry = read_venv_shim_registry(app_dir);
+    let this = env::current_exe()?;
+    let mut rv = VenvShimChanges::default();
+    for entry in fs::read_dir(venv.join("bin"))? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if is_skipped_venv_script(&name) || !is_executable(&entry.path()) {
+            continue;
+        }
+        let shim = shims.join(&name);
+        if shim.exists() || shim.is_symlink() {
+            // only take over shims of other virtualenvs, not installed tools
+            if is_registered_venv_shim(&shim, &registry) {
+                registry.entry(name).or_default().insert(venv.to_path_buf());
+            }
+            continue;
+        }
+        link_shim(&this, &shim).with_context(|| format!("tried to link {} shim", name))?;
+        registry
+            .entry(name.clone())
+            .or_default()
+            .insert(venv.to_path_buf());
+        rv.added.push(name);
+    }
+
+    // the pruned names were in the registry
+    for name in prune_venv_shim_registry(&mut registry) {
+        let shim = shims.join(&name);
+        if is_rye_link(&shim) {
+            fs::remove_file(&shim).with_context(|| format!("tried to remove {} shim", name))?;
+            rv.removed.push(name);
+        }
+    }
+    write_atomic(&registry_path, serde_json::to_vec_pretty(&registry)?)
+        .context("tried to write venv shim registry")?;
+    rv.added.sort();
+    Ok(rv)
+}
+
+/// Whether a script of a virtualenv never gets a shim (see [`VENV_SHIM_SKIP`]).
+fn is_skipped_venv_script(name: &str) -> bool {
+    let unversioned = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    VENV_SHIM_SKIP.contains(&name) || VENV_SHIM_SKIP.contains(&unversioned)
+}
+
+/// Forgets the virtualenvs that no longer have the script of a shim.
+///
+/// Returns the shims no virtualenv has the script for.
+fn prune_venv_shim_registry(registry: &mut BTreeMap<String, BTreeSet<PathBuf>>) -> Vec<String> {
+    let mut rv = Vec::new();
+    registry.retain(|name, venvs| {
+        venvs.retain(|venv| is_executable(&venv.join("bin").join(name)));
+        if venvs.is_empty() {
+            rv.push(name.clone());
+        }
+        !venvs.is_empty()
+    });
+    rv
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|x| x.is_file() && x.permissions().mode() & 0o111 != 0)
+}
+
+/// Returns the requirements for the internal tools.
+///
+/// A version pinned in the config as a plain version (eg: `7.4`) matches all
+/// releases with that prefix, otherwise it is used as version specifier.
+pub fn get_self_tool_requirements() -> Result<Vec<String>, Error> {
+    let config = Config::current()?;
+    Ok(SELF_TOOLS
+        .iter()
+        .map(|name| match config.internal_tool_version(name) {
+            Some(version) if version.starts_with(|c: char| c.is_ascii_digit()) => {
+                format!("{}=={}.*", name, version.trim_end_matches(".*"))
+            }
+            Some(version) => format!("{}{}", name, version),
+            None => name.to_string(),
+        })
+        .collect())
+}
+
+fn read_installed_tools(dir: &Path) -> Vec<String> {
+    fs::read_to_string(dir.join("tools.txt"))
+        .unwrap_or_default()
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+}
+
+/// Installs or upgrades the internal tools in the self venv.
+///
+/// pip is upgraded first so that the remaining tools are installed with the
+/// new version.
+pub fn install_self_tools(dir: &Path, output: CommandOutput) -> Result<(), Error> {
+    let requirements = get_self_tool_requirements()?;
+    let (pip, rest) = requirements.split_first().expect("no internal tools");
+
+    if output != CommandOutput::Quiet {
+        eprintln!("Upgrading pip");
+    }
+    pip_install_self(dir, output, std::slice::from_ref(pip)).context("unable to upgrade pip")?;
+
+    if output != CommandOutput::Quiet {
+        eprintln!("Installing internal dependencies");
+    }
+    pip_install_self(dir, output, rest).context("unable to install internal dependencies")?;
+
+    fs::write(dir.join("tools.txt"), requirements.join("\n"))
+        .context("could not record internal tool versions")?;
+    Ok(())
+}
+
+fn pip_install_self(dir: &Path, output: CommandOutput, reqs: &[String]) -> Result<(), Error> {
+    let mut pip_install_cmd = Command::new(dir.join("bin/pip"));
+    pip_install_cmd.arg("install");
+    pip_install_cmd.arg("--upgrade");
+    pip_install_cmd.args(reqs);
+    if output == CommandOutput::Verbose {
+        pip_install_cmd.arg("--verbose");
+    } else {
+        pip_install_cmd.arg("--quiet");
+        pip_install_cmd.env("PYTHONWARNINGS", "ignore");
+    }
+    apply_env_config(&mut pip_install_cmd, EnvTarget::Tools)?;
+    let status = signals::status(&mut pip_install_cmd).context("unable to run pip")?;
+    if !status.success() {
+        bail!("pip install {} failed", reqs.join(" "));
+    }
+    Ok(())
+}
+
+/// Checks if the self venv is usable and explains the problem if not.
+pub fn find_self_venv_issue(dir: &Path) -> Option<String> {
+    let version = fs::read_to_string(dir.join("tool-version")).ok();
+    match version.as_deref().map(|x| x.trim().parse::<u64>()) {
+        Some(Ok(version)) if version == SELF_VERSION => {}
+        Some(_) => return Some("created by a different version of rye".into()),
+        None => return Some("bootstrapping did not complete".into()),
+    }
+    // `exists` follows symlinks which catches a removed python toolchain
+    SELF_SCRIPTS
+        .iter()
+        .find(|script| !dir.join("bin").join(script).exists())
+        .map(|script| format!("{} is missing", script))
+}
+
+/// Returns the pip runner for the self venv
+pub fn get_pip_runner(venv: &Path) -> PathBuf {
+    get_pip_module(venv).join("__pip-runner__.py")
+}
+
+/// Returns the pip module for the self venv
+pub fn get_pip_module(venv: &Path) -> PathBuf {
+    let mut rv = venv.to_path_buf();
+    rv.push("lib");
+    rv.push(SELF_SITE_PACKAGES);
+    rv.push("pip");
+    rv
+}
+
+/// Returns the version of an internal tool installed in the self venv.
+pub fn get_self_tool_version(venv: &Path, name: &str) -> Option<String> {
+    let prefix = format!("{}-", name.replace('-', "_"));
+    let site_packages = venv.join("lib").join(SELF_SITE_PACKAGES);
+    fs::read_dir(site_packages)
+        .ok()?
+        .filter_map(|x| x.ok())
+        .find_map(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            file_name
+                .strip_suffix(".dist-info")
+                .and_then(|x| x.strip_prefix(&prefix))
+                .map(|x| x.to_string())
+        })
+}
+
+/// Returns the exact python version of the self venv.
+pub fn get_self_python_version(venv: &Path) -> Option<String> {
+    fs::read_to_string(venv.join("pyvenv.cfg"))
+        .ok()?
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| key.trim() == "version")
+        .map(|(_, value)| value.trim().to_string())
+}
+
+/// Downloads a toolchain archive into memory.
+fn download_archive(
+    url: &str,
+    version: &PythonVersion,
+    output: CommandOutput,
+) -> Result<Vec<u8>, Error> {
+    let mut archive_buffer = Vec::new();
+    if output == CommandOutput::Verbose {
+        eprintln!("download url: {}", url);
+    }
+    if output != CommandOutput::Quiet {
+        eprintln!("{} {}", style("Downloading").cyan(), version);
+    }
+
+    let mut handle = make_curl_handle(url)?;
+    handle.progress(true)?;
+
+    let write_archive = &mut archive_buffer;
+    {
+        let mut transfer = handle.transfer();
+        let mut pb = None;
+        transfer.progress_function(move |a, b, _, _| {
+            // returning false aborts the transfer
+            if signals::interrupted().is_some() {
+                return false;
+            }
+            if output == CommandOutput::Quiet {
+                return true;
+            }
+
+            let (down_len, down_pos) = (a as u64, b as u64);
+            if down_len > 0 {
+                if down_pos < down_len {
+                    if pb.is_none() {
+                        let pb_config = ProgressBar::new(down_len);
+                        pb_config.set_style(
+                            ProgressStyle::with_template("{wide_bar} {bytes:>7}/{total_bytes:7}")
+                                .unwrap(),
+                        );
+                        pb = Some(pb_config);
+                    }
+                    pb.as_ref().unwrap().set_position(down_pos);
+                } else if pb.is_some() {
+                    pb.take().unwrap().finish_and_clear();
+                }
+            }
+            true
+        })?;
+        transfer.write_function(move |data| {
+            write_archive.write_all(data).unwrap();
+            Ok(data.len())
+        })?;
+        transfer.perform().with_context(|| {
+            Failure::new(ErrorKind::Network, format!("download of {} failed", url))
+        })?;
+    }
+    Ok(archive_buffer)
+}
+
+/// Stores a downloaded archive in the cache, failures are not fatal.
+fn store_cached_archive(path: &Path, contents: &[u8]) {
+    let staging = path.with_extension(format!("tmp-{}", std::process::id()));
+    let rv = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&staging, contents))
+        .and_then(|_| fs::rename(&staging, path));
+    if rv.is_err() {
+        fs::remove_file(&staging).ok();
+    }
+}
+
+/// Fetches a version if missing.
+pub fn fetch(
+    version: &PythonVersionRequest,
+    output: CommandOutput,
+) -> Result<PythonVersion, Error> {
+    let _timing = timings::phase("toolchain fetch");
+    if let Ok(version) = PythonVersion::try_from(version.clone()) {
+        let py_path = get_canonical_py_path(&version)?;
+        let _lock = FileLock::acquire(&py_path, output)?;
+        if py_path.is_dir() || py_path.is_file() {
+            if output == CommandOutput::Verbose {
+                eprintln!("Python version already downloaded. Skipping.");
+            }
+            return Ok(version);
+        }
+    }
+
+    let (version, url) = match get_download_url(version, current_platform(), ARCH) {
+        Some(result) => result,
+        None if !has_downloads(current_platform(), ARCH) => bail!(Failure::new(
+            ErrorKind::ToolchainMissing,
+            no_builds_error(current_platform(), ARCH).to_string()
+        )),
+        None => bail!(Failure::new(
+            ErrorKind::ToolchainMissing,
+            format!("unknown version {}", version)
+        )),
+    };
+    let (url, sha256) = get_toolchain_source(&version, url)?;
+
+    let target_dir = get_canonical_py_path(&version)?;
+    let _lock = FileLock::acquire(&target_dir, output)?;
+    if output == CommandOutput::Verbose {
+        eprintln!("target dir: {}", target_dir.display());
+    }
+    if target_dir.is_dir() {
+        if output == CommandOutput::Verbose {
+            eprintln!("Python version already downloaded. Skipping.");
+        }
+        return Ok(version);
+    }
+
+    // a partially installed toolchain would later be mistaken for a
+    // complete one
+    let _defer = signals::defer_interrupts();
+    if let Err(err) = install_toolchain(&url, sha256.as_deref(), &version, &target_dir, output) {
+        fs::remove_dir_all(&target_dir).ok();
+        return Err(err);
+    }
+
+    if output != CommandOutput::Quiet {
+        eprintln!("{} Downloaded {}", style("success:").green(), version);
+    }
+
+    Ok(version)
+}
+
+/// Downloads (or takes from the cache) and unpacks a toolchain.
+fn install_toolchain(
+    url: &str,
+    sha256: Option<&str>,
+    version: &PythonVersion,
+    target_dir: &Path,
+    output: CommandOutput,
+) -> Result<(), Error> {
+    fs::create_dir_all(target_dir)
+        .with_context(|| format!("failed to create target folder {}", target_dir.display()))?;
+
+    // archives are kept in the cache so that reinstalling a toolchain does
+    // not need to hit the network again.
+    let cached_archive = get_cache_dir()?
+        .join("downloads")
+        .join(url.rsplit('/').next().unwrap_or("archive"));
+    let (archive_buffer, from_cache) = match fs::read(&cached_archive) {
+        Ok(contents)
+            if sha256.is_none_or(|expected| {
+                format!("{:x}", Sha256::digest(&contents)).eq_ignore_ascii_case(expected)
+            }) =>
+        {
+            if output == CommandOutput::Verbose {
+                eprintln!("using cached archive {}", cached_archive.display());
+            }
+            touch(&cached_archive);
+            (contents, true)
+        }
+        _ => (download_archive(url, version, output)?, false),
+    };
+
+    if let Some(expected) = sha256 {
+        let actual = format!("{:x}", Sha256::digest(&archive_buffer));
+        if !actual.eq_ignore_ascii_case(expected) {
+            bail!(
+                "checksum mismatch for {}: expected {}, got {}",
+                url,
+                expected,
+                actual
+            );
+        }
+    }
+    if !from_cache {
+        store_cached_archive(&cached_archive, &archive_buffer);
+    }
+
+    signals::check_interrupted()?;
+    unpack_tarball(&archive_buffer, target_dir, 1)
+        .with_context(|| format!("unpacking of downloaded tarball {} failed", url))?;
+    Ok(())
+}
+
+/// Determines the URL and the expected checksum for a toolchain download.
+///
+/// By default toolchains come from the python-build-standalone releases on
+/// GitHub, the config can point them to a mirror instead.
+fn get_toolchain_source(
+    version: &PythonVersion,
+    default_url: &str,
+) -> Result<(String, Option<String>), Error> {
+    let config = Config::current()?;
+    let toolchain_override = config.toolchain_override(version).unwrap_or_default();
+    let mut pieces = default_url.rsplit('/');
+    let filename = pieces.next().unwrap_or_default();
+    let release = pieces.next().unwrap_or_default();
+
+    let url = match (toolchain_override.url, config.download_url_template()) {
+        (Some(url), _) => url,
+        (None, Some(template)) => template
+            .replace("{release}", release)
+            .replace("{filename}", filename)
+            .replace(
+                "{version}",
+                &format!("{}.{}.{}", version.major, version.minor, version.patch),
+            )
+            .replace("{platform}", current_platform())
+            .replace("{arch}", ARCH),
+        (None, None) => default_url.to_string(),
+    };
+
+    let sha256 = match toolchain_override.sha256 {
+        Some(sha256) => Some(sha256),
+        // the only character that needs quoting in the release file names is `+`
+        None => find_checksum(&config.checksum_files(), &filename.replace("%2B", "+"))?,
+    };
+
+    Ok((url, sha256))
+}
+
+/// Looks up the checksum of a file in `sha256sum` formatted files.
+fn find_checksum(files: &[PathBuf], filename: &str) -> Result<Option<String>, Error> {
+    for path in files {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read checksum file {}", path.display()))?;
+        for line in contents.lines() {
+            if let Some((sha256, name)) = line.split_once(char::is_whitespace) {
+                if name.trim_start().trim_start_matches('*') == filename {
+                    return Ok(Some(sha256.to_string()));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[test]
+fn test_is_skipped_venv_script() {
+    for name in [
+        "python",
+        "python3",
+        "python3.11",
+        "pip",
+        "pip3.12",
+        "activate.fish",
+    ] {
+        assert!(is_skipped_venv_script(name), "{}", name);
+    }
+    for name in [
+        "pip-audit",
+        "pipx",
+        "pytest",
+        "python-config",
+        "activate-global",
+    ] {
+        assert!(!is_skipped_venv_script(name), "{}", name);
+    }
+}
+
+#[test]
+fn test_prune_venv_shim_registry() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+    for venv in [&a, &b] {
+        fs::create_dir_all(venv.join("bin")).unwrap();
+    }
+    let script = a.join("bin/pytest");
+    fs::write(&script, "").unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut registry = BTreeMap::new();
+    registry.insert("pytest".to_string(), BTreeSet::from([a.clone(), b.clone()]));
+    registry.insert("alembic".to_string(), BTreeSet::from([a.clone()]));
+    assert_eq!(prune_venv_shim_registry(&mut registry), ["alembic"]);
+    assert_eq!(registry.len(), 1);
+    assert_eq!(registry["pytest"], BTreeSet::from([a]));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_is_registered_venv_shim() {
+    let tmp = tempfile::tempdir().unwrap();
+    let rye = tmp.path().join("rye");
+    fs::write(&rye, "").unwrap();
+    let shims = tmp.path().join("shims");
+    fs::create_dir(&shims).unwrap();
+    link_shim(&rye, &shims.join("pytest")).unwrap();
+    link_shim(&rye, &shims.join("python")).unwrap();
+    link_shim(&rye, &shims.join("unregistered")).unwrap();
+    fs::write(shims.join("my-script"), "#!/bin/sh\n").unwrap();
+
+    let registry: BTreeMap<_, _> = ["pytest", "python", "my-script"]
+        .into_iter()
+        .map(|x| (x.to_string(), BTreeSet::from([tmp.path().join("venv")])))
+        .collect();
+    assert!(is_registered_venv_shim(&shims.join("pytest"), &registry));
+    assert!(!is_registered_venv_shim(&shims.join("python"), &registry));
+    assert!(!is_registered_venv_shim(
+        &shims.join("unregistered"),
+        &registry
+    ));
+    // files of the user are left alone, even under a registered name
+    assert!(!is_registered_venv_shim(
+        &shims.join("my-script"),
+        &registry
+    ));
+}
@@ -0,0 +1,227 @@
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Header search paths used to look for development headers.
+const INCLUDE_DIRS: [&str; 4] = [
+    "/usr/include",
+    "/usr/local/include",
+    "/opt/homebrew/include",
+    "/opt/local/include",
+];
+
+/// A system prerequisite for building packages from source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BuildDep {
+    /// A C compiler.
+    Compiler,
+    /// The headers of the Python interpreter.
+    PythonHeaders,
+    /// The libffi development headers.
+    Libffi,
+    /// The OpenSSL development headers.
+    Openssl,
+    /// The Rust toolchain.
+    Rust,
+}
+
+impl fmt::Display for BuildDep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BuildDep::Compiler => "C compiler",
+            BuildDep::PythonHeaders => "Python headers",
+            BuildDep::Libffi => "libffi headers",
+            BuildDep::Openssl => "OpenSSL headers",
+            BuildDep::Rust => "Rust toolchain",
+        })
+    }
+}
+
+/// The package manager flavor of the operating system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OsFamily {
+    Debian,
+    Fedora,
+    Alpine,
+    Arch,
+    Suse,
+    Macos,
+    Unknown,
+}
+
+impl OsFamily {
+    /// Detects the flavor of the current system from `/etc/os-release`.
+    pub fn current() -> OsFamily {
+        if cfg!(target_os = "macos") {
+            return OsFamily::Macos;
+        }
+        let contents = fs::read_to_string("/etc/os-release").unwrap_or_default();
+        let ids: Vec<&str> = contents
+            .lines()
+            .filter_map(|x| x.strip_prefix("ID=").or_else(|| x.strip_prefix("ID_LIKE=")))
+            .flat_map(|x| x.trim_matches('"').split_whitespace())
+            .collect();
+        for id in ids {
+            match id {
+                "debian" | "ubuntu" => return OsFamily::Debian,
+                "fedora" | "rhel" | "centos" => return OsFamily::Fedora,
+                "alpine" => return OsFamily::Alpine,
+                "arch" => return OsFamily::Arch,
+                "suse" | "opensuse" => return OsFamily::Suse,
+                _ => {}
+            }
+        }
+        OsFamily::Unknown
+    }
+}
+
+static DETECTORS: Lazy<Vec<(BuildDep, Regex)>> = Lazy::new(|| {
+    vec![
+        (
+            BuildDep::Compiler,
+            Regex::new(
+                r"(?:unable to execute|command) '[^']*(?:gcc|cc|clang)'(?: failed)?: No such file or directory|\b(?:gcc|cc|clang): (?:command )?not found",
+            )
+            .unwrap(),
+        ),
+        (
+            BuildDep::PythonHeaders,
+            Regex::new(r"Python\.h: No such file or directory|'Python\.h' file not found").unwrap(),
+        ),
+        (
+            BuildDep::Libffi,
+            Regex::new(r"ffi\.h: No such file or directory|'ffi\.h' file not found|Package libffi was not found").unwrap(),
+        ),
+        (
+            BuildDep::Openssl,
+            Regex::new(r"openssl/\w+\.h: No such file or directory|'openssl/\w+\.h' file not found|(?i:could not find (?:directory of )?openssl)").unwrap(),
+        ),
+        (
+            BuildDep::Rust,
+            Regex::new(r"can't find Rust compiler|\bcargo: (?:command )?not found|Cargo, the Rust package manager, is not installed").unwrap(),
+        ),
+    ]
+});
+
+/// Looks for the telltale signs of missing prerequisites in build output.
+pub fn detect_missing_build_deps(output: &str) -> Vec<BuildDep> {
+    DETECTORS
+        .iter()
+        .filter(|(_, re)| re.is_match(output))
+        .map(|(dep, _)| *dep)
+        .collect()
+}
+
+impl BuildDep {
+    /// All known prerequisites.
+    pub fn all() -> [BuildDep; 5] {
+        [
+            BuildDep::Compiler,
+            BuildDep::PythonHeaders,
+            BuildDep::Libffi,
+            BuildDep::Openssl,
+            BuildDep::Rust,
+        ]
+    }
+
+    /// Checks if the prerequisite is installed.
+    ///
+    /// Python headers are looked for in the given toolchain folder.
+    pub fn is_installed(&self, toolchain: Option<&Path>) -> bool {
+        match self {
+            BuildDep::Compiler => ["cc", "gcc", "clang"].iter().any(|x| find_on_path(x)),
+            BuildDep::PythonHeaders => toolchain
+                .and_then(|x| x.join("install/include").read_dir().ok())
+                .into_iter()
+                .flatten()
+                .filter_map(|x| x.ok())
+                .any(|x| x.path().join("Python.h").is_file()),
+            BuildDep::Libffi => find_header("ffi.h"),
+            BuildDep::Openssl => find_header("openssl/ssl.h"),
+            BuildDep::Rust => {
+                find_on_path("cargo")
+                    || simple_home_dir::home_dir()
+                        .is_some_and(|x| x.join(".cargo/bin/cargo").is_file())
+            }
+        }
+    }
+
+    /// Explains how to install the prerequisite.
+    pub fn remediation(&self, os: OsFamily) -> &'static str {
+        match (self, os) {
+            (BuildDep::Compiler, OsFamily::Debian) => "sudo apt-get install build-essential",
+            (BuildDep::Compiler, OsFamily::Fedora) => "sudo dnf install gcc gcc-c++ make",
+            (BuildDep::Compiler, OsFamily::Alpine) => "apk add build-base",
+            (BuildDep::Compiler, OsFamily::Arch) => "sudo pacman -S base-devel",
+            (BuildDep::Compiler, OsFamily::Suse) => "sudo zypper install gcc gcc-c++ make",
+            (BuildDep::Compiler, OsFamily::Macos) => "xcode-select --install",
+            (BuildDep::Compiler, OsFamily::Unknown) => "install gcc or clang",
+            (BuildDep::PythonHeaders, OsFamily::Debian) => "sudo apt-get install python3-dev",
+            (BuildDep::PythonHeaders, OsFamily::Fedora) => "sudo dnf install python3-devel",
+            (BuildDep::PythonHeaders, OsFamily::Alpine) => "apk add python3-dev",
+            (BuildDep::PythonHeaders, OsFamily::Suse) => "sudo zypper install python3-devel",
+            (BuildDep::PythonHeaders, _) => {
+                "use a toolchain fetched by rye, they ship with headers"
+            }
+            (BuildDep::Libffi, OsFamily::Debian) => "sudo apt-get install libffi-dev",
+            (BuildDep::Libffi, OsFamily::Fedora) => "sudo dnf install libffi-devel",
+            (BuildDep::Libffi, OsFamily::Alpine) => "apk add libffi-dev",
+            (BuildDep::Libffi, OsFamily::Arch) => "sudo pacman -S libffi",
+            (BuildDep::Libffi, OsFamily::Suse) => "sudo zypper install libffi-devel",
+            (BuildDep::Libffi, OsFamily::Macos) => "brew install libffi",
+            (BuildDep::Libffi, OsFamily::Unknown) => "install the libffi development package",
+            (BuildDep::Openssl, OsFamily::Debian) => "sudo apt-get install libssl-dev",
+            (BuildDep::Openssl, OsFamily::Fedora) => "sudo dnf install openssl-devel",
+            (BuildDep::Openssl, OsFamily::Alpine) => "apk add openssl-dev",
+            (BuildDep::Openssl, OsFamily::Arch) => "sudo pacman -S openssl",
+            (BuildDep::Openssl, OsFamily::Suse) => "sudo zypper install libopenssl-devel",
+            (BuildDep::Openssl, OsFamily::Macos) => "brew install openssl",
+            (BuildDep::Openssl, OsFamily::Unknown) => "install the OpenSSL development package",
+            (BuildDep::Rust, _) => "install rustup from https://rustup.rs",
+        }
+    }
+}
+
+fn find_on_path(name: &str) -> bool {
+    env::var_os("PATH")
+        .map(|x| env::split_paths(&x).collect::<Vec<PathBuf>>())
+        .unwrap_or_default()
+        .into_iter()
+        .any(|x| x.join(name).is_file())
+}
+
+/// Looks for a header in the include folders and one level below them,
+/// which covers multiarch folders like `/usr/include/x86_64-linux-gnu`.
+fn find_header(header: &str) -> bool {
+    INCLUDE_DIRS.iter().map(Path::new).any(|dir| {
+        dir.join(header).is_file()
+            || dir
+                .read_dir()
+                .into_iter()
+                .flatten()
+                .filter_map(|x| x.ok())
+                .any(|x| x.path().join(header).is_file())
+    })
+}
+
+#[test]
+fn test_detect_missing_build_deps() {
+    let output = "\
+        src/c/_cffi_backend.c:15:10: fatal error: ffi.h: No such file or directory\n\
+        error: command 'x86_64-linux-gnu-gcc' failed: No such file or directory\n";
+    assert_eq!(
+        detect_missing_build_deps(output),
+        vec![BuildDep::Compiler, BuildDep::Libffi]
+    );
+    assert_eq!(
+        detect_missing_build_deps("fatal error: 'openssl/opensslv.h' file not found"),
+        vec![BuildDep::Openssl]
+    );
+    assert_eq!(
+        detect_missing_build_deps("error: can't find Rust compiler"),
+        vec![BuildDep::Rust]
+    );
+    assert!(detect_missing_build_deps("Successfully installed six-1.16.0").is_empty());
+}
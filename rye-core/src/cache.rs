@@ -0,0 +1,315 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Error};
+use console::style;
+use walkdir::WalkDir;
+
+use crate::config::{get_app_dir, Config};
+use crate::utils::CommandOutput;
+
+/// The caches below the cache folder whose entries can be evicted.
+///
+/// Every direct child of `overlays`, `tools` and `downloads` is an entry,
+/// for the pip and index caches every file is one.
+const CACHES: &[(&str, bool)] = &[
+    ("overlays", false),
+    ("tools", false),
+    ("downloads", false),
+    ("pip", true),
+    ("index", true),
+];
+
+/// Marker file recording when the cache was last pruned automatically.
+const LAST_PRUNE_MARKER: &str = ".last-prune";
+
+/// How often the cache is pruned automatically.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Returns the folder rye keeps its caches in (`~/.rye/cache`).
+pub fn get_cache_dir() -> Result<PathBuf, Error> {
+    Ok(get_app_dir()?.join("cache"))
+}
+
+/// Marks a cache entry as used so eviction keeps it around longer.
+pub fn touch(path: &Path) {
+    let target = if path.is_dir() {
+        path.join(".last-used")
+    } else {
+        path.to_path_buf()
+    };
+    let rv = if target.is_file() {
+        File::options().append(true).open(&target)
+    } else {
+        File::create(&target)
+    };
+    if let Ok(file) = rv {
+        file.set_modified(SystemTime::now()).ok();
+    }
+}
+
+/// Limits the cache is pruned to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PruneLimits {
+    /// The total size entries may take up.
+    pub max_size: Option<u64>,
+    /// How long entries are kept after they were last used.
+    pub max_age: Option<Duration>,
+}
+
+impl PruneLimits {
+    /// The limits from the `[cache]` section of the config.
+    pub fn from_config(config: &Config) -> Result<PruneLimits, Error> {
+        Ok(PruneLimits {
+            max_size: config
+                .cache_max_size()
+                .map(parse_size)
+                .transpose()
+                .context("invalid cache.max-size in config")?,
+            max_age: config
+                .cache_max_age()
+                .map(parse_age)
+                .transpose()
+                .context("invalid cache.max-age in config")?,
+        })
+    }
+
+    /// Is any limit set?
+    pub fn is_empty(&self) -> bool {
+        self.max_size.is_none() && self.max_age.is_none()
+    }
+}
+
+/// What pruning the cache did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PruneResult {
+    /// The number of entries removed.
+    pub removed: usize,
+    /// The bytes freed.
+    pub freed: u64,
+    /// The bytes the cache still takes up.
+    pub remaining: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// Evicts cache entries exceeding the limits.
+///
+/// Entries not used within `max_age` are removed first, then the least
+/// recently used ones until the cache fits into `max_size`.
+pub fn prune_cache(limits: &PruneLimits) -> Result<PruneResult, Error> {
+    let cache_dir = get_cache_dir()?;
+    let mut entries = Vec::new();
+    for (name, per_file) in CACHES {
+        collect_entries(&cache_dir.join(name), *per_file, &mut entries)?;
+    }
+    entries.sort_by_key(|x| x.last_used);
+
+    let now = SystemTime::now();
+    let mut rv = PruneResult {
+        remaining: entries.iter().map(|x| x.size).sum(),
+        ..PruneResult::default()
+    };
+    for entry in entries {
+        let expired = limits.max_age.is_some_and(|max_age| {
+            now.duration_since(entry.last_used)
+                .is_ok_and(|age| age > max_age)
+        });
+        let too_big = limits.max_size.is_some_and(|x| rv.remaining > x);
+        if !expired && !too_big {
+            continue;
+        }
+        let removed = if entry.path.is_dir() {
+            fs::remove_dir_all(&entry.path)
+        } else {
+            fs::remove_file(&entry.path)
+        };
+        removed.with_context(|| format!("unable to remove {}", entry.path.display()))?;
+        rv.removed += 1;
+        rv.freed += entry.size;
+        rv.remaining -= entry.size;
+    }
+    Ok(rv)
+}
+
+/// Prunes the cache to the configured limits if that has not happened
+/// within the last day.
+pub fn prune_cache_if_due(output: CommandOutput) -> Result<(), Error> {
+    let limits = PruneLimits::from_config(Config::current()?)?;
+    let cache_dir = get_cache_dir()?;
+    let marker = cache_dir.join(LAST_PRUNE_MARKER);
+    let due = fs::metadata(&marker)
+        .and_then(|x| x.modified())
+        .ok()
+        .and_then(|x| SystemTime::now().duration_since(x).ok())
+        .is_none_or(|x| x > PRUNE_INTERVAL);
+    if limits.is_empty() || !due || !cache_dir.is_dir() {
+        return Ok(());
+    }
+
+    let rv = prune_cache(&limits)?;
+    fs::write(&marker, b"")?;
+    if rv.removed > 0 && output != CommandOutput::Quiet {
+        eprintln!(
+            "Pruned {} cache entries ({} freed)",
+            rv.removed,
+            style(format_size(rv.freed)).cyan()
+        );
+    }
+    Ok(())
+}
+
+fn collect_entries(dir: &Path, per_file: bool, entries: &mut Vec<CacheEntry>) -> Result<(), Error> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    if per_file {
+        for entry in WalkDir::new(dir) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                let meta = entry.metadata()?;
+                entries.push(CacheEntry {
+                    path: entry.into_path(),
+                    size: meta.len(),
+                    last_used: meta.modified()?,
+                });
+            }
+        }
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // skip entries which are still being created
+        if path
+            .extension()
+            .is_some_and(|x| x.to_string_lossy().starts_with("tmp-"))
+        {
+            continue;
+        }
+        let last_used = fs::metadata(path.join(".last-used"))
+            .or_else(|_| fs::metadata(&path))?
+            .modified()?;
+        entries.push(CacheEntry {
+            size: dir_size(&path),
+            path,
+            last_used,
+        });
+    }
+    Ok(())
+}
+
+/// The size of the files in a folder.
+///
+/// Symlinks are not followed, not even when `path` is one (like a virtualenv
+/// linked to a conda environment).  walkdir 2.3 always follows the root and
+/// has no `follow_root_links(false)` yet, so that case is handled here.
+pub(crate) fn dir_size(path: &Path) -> u64 {
+    if path.is_symlink() {
+        return 0;
+    }
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|x| x.ok())
+        .filter(|x| x.file_type().is_file())
+        .filter_map(|x| x.metadata().ok())
+        .map(|x| x.len())
+        .sum()
+}
+
+/// Parses a size like `500MB` or `5G` (units are powers of 1024).
+pub fn parse_size(value: &str) -> Result<u64, Error> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = match number.parse() {
+        Ok(number) => number,
+        Err(_) => bail!("invalid size '{}'", value),
+    };
+    let unit = unit.trim().to_ascii_uppercase();
+    let factor: u64 = match unit.strip_suffix('B').unwrap_or(&unit) {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => bail!("invalid size '{}' (expected a unit like MB or GB)", value),
+    };
+    Ok((number * factor as f64) as u64)
+}
+
+/// Parses an age like `10m`, `12h`, `30d` or `2w`.
+pub fn parse_age(value: &str) -> Result<Duration, Error> {
+    let value = value.trim();
+    let split = value.char_indices().last().map_or(0, |x| x.0);
+    let (number, unit) = value.split_at(split);
+    let minutes: u64 = match unit {
+        "m" => 1,
+        "h" => 60,
+        "d" => 60 * 24,
+        "w" => 60 * 24 * 7,
+        _ => bail!(
+            "invalid age '{}' (expected a unit like m, h, d or w)",
+            value
+        ),
+    };
+    match number.parse::<u64>() {
+        Ok(number) => Ok(Duration::from_secs(number * minutes * 60)),
+        Err(_) => bail!("invalid age '{}'", value),
+    }
+}
+
+/// Formats a size for humans.
+pub fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[test]
+fn test_parse_limits() {
+    assert_eq!(parse_size("5GB").unwrap(), 5 << 30);
+    assert_eq!(parse_size("1.5g").unwrap(), 3 << 29);
+    assert_eq!(parse_size("100").unwrap(), 100);
+    assert_eq!(parse_size("20 MB").unwrap(), 20 << 20);
+    assert!(parse_size("lots").is_err());
+    assert!(parse_size("5XB").is_err());
+    assert_eq!(parse_age("30d").unwrap(), Duration::from_secs(30 * 86400));
+    assert_eq!(parse_age("12h").unwrap(), Duration::from_secs(12 * 3600));
+    assert_eq!(parse_age("10m").unwrap(), Duration::from_secs(600));
+    assert!(parse_age("30").is_err());
+    assert!(parse_age("3é").is_err());
+    assert!(parse_age("é").is_err());
+    assert!(parse_age("").is_err());
+    assert_eq!(format_size(1536), "1.5 KB");
+    assert_eq!(format_size(12), "12 B");
+}
+
+#[test]
+fn test_dir_size() {
+    let tmp = tempfile::tempdir().unwrap();
+    let env = tmp.path().join("env");
+    fs::create_dir_all(env.join("lib")).unwrap();
+    fs::write(env.join("lib/module.py"), "x = 1\n").unwrap();
+    let venv = tmp.path().join(".venv");
+    std::os::unix::fs::symlink(&env, &venv).unwrap();
+    assert_eq!(dir_size(&env), 6);
+    assert_eq!(dir_size(&venv), 0);
+}
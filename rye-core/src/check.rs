@@ -0,0 +1,993 @@
+//! Consistency checks for projects, as run by `rye check`.
+//!
+//! Every check inspects the project (or the workspace it belongs to) without
+//! changing anything or touching the network and reports its findings as a
+//! [`CheckReport`].  The checks deemed cheap and reliable run by default,
+//! the heuristic ones only on request (see [`CheckKind::is_default`]).
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use anyhow::{Context, Error};
+use pep440_rs::{Operator, Version, VersionSpecifiers};
+use pep508_rs::{Requirement, VersionOrUrl};
+use serde::{Serialize, Serializer};
+
+use crate::config::load_python_version_requests;
+use crate::imports::{find_transitive_imports, find_unused_dependencies};
+use crate::lock::{
+    find_locked_line, hash_lock_inputs_of, parse_lockfile, recorded_lock_inputs, LockMode,
+    LockOptions, Lockfile,
+};
+use crate::pyproject::{normalize_package_name, DependencyKind, DependencyPolicy, PyProject};
+use crate::sources::{matches_version, PythonVersionRequest};
+use crate::sync::{find_stale_venv, get_venv_python_version};
+
+/// A check `rye check` can run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckKind {
+    /// The lockfiles exist and pin every declared dependency to a version
+    /// that satisfies it.
+    Lock,
+    /// The virtualenv is in sync with the lockfiles and the pinned toolchain.
+    Venv,
+    /// The `requires-python` ranges agree with the toolchain and each other.
+    RequiresPython,
+    /// The workspace projects do not ask for incompatible versions of the
+    /// same dependency.
+    WorkspaceConstraints,
+    /// Every dependency is imported somewhere.
+    UnusedDeps,
+    /// Every imported package is declared.
+    MissingDeps,
+    /// The declared dependencies follow the `tool.rye.policy` rules.
+    Policy,
+    /// The lockfiles staged in git were locked from the staged
+    /// `pyproject.toml` files.
+    Staged,
+}
+
+impl CheckKind {
+    /// All checks in the order they run in.
+    pub fn all() -> [CheckKind; 8] {
+        [
+            CheckKind::Lock,
+            CheckKind::Venv,
+            CheckKind::RequiresPython,
+            CheckKind::WorkspaceConstraints,
+            CheckKind::Policy,
+            CheckKind::UnusedDeps,
+            CheckKind::MissingDeps,
+            CheckKind::Staged,
+        ]
+    }
+
+    /// The name of the check on the command line and in JSON output.
+    pub fn name(self) -> &'static str {
+        match self {
+            CheckKind::Lock => "lock",
+            CheckKind::Venv => "venv",
+            CheckKind::RequiresPython => "requires-python",
+            CheckKind::WorkspaceConstraints => "workspace-constraints",
+            CheckKind::UnusedDeps => "unused-deps",
+            CheckKind::MissingDeps => "missing-deps",
+            CheckKind::Policy => "policy",
+            CheckKind::Staged => "staged",
+        }
+    }
+
+    /// Does the check run when no checks are selected?
+    ///
+    /// The dependency checks scan the source code and can report false
+    /// positives, so they are opt-in.  The staged check only makes sense
+    /// right before a commit.
+    pub fn is_default(self) -> bool {
+        !matches!(
+            self,
+            CheckKind::UnusedDeps | CheckKind::MissingDeps | CheckKind::Staged
+        )
+    }
+
+    /// Runs the check.
+    pub fn run(self, pyproject: &PyProject, options: &CheckOptions) -> Result<CheckReport, Error> {
+        let mut report = CheckReport::new(self);
+        match self {
+            CheckKind::Lock => check_lock(pyproject, options, &mut report)?,
+            CheckKind::Venv => check_venv(pyproject, &mut report)?,
+            CheckKind::RequiresPython => check_requires_python(pyproject, &mut report)?,
+            CheckKind::WorkspaceConstraints => check_workspace_constraints(pyproject, &mut report)?,
+            CheckKind::Staged => check_staged(pyproject, options, &mut report)?,
+            CheckKind::Policy => {
+                if load_dependency_policy(pyproject)?.is_empty() {
+                    report.skip("no policy in tool.rye.policy");
+                }
+                for violation in find_policy_violations(pyproject)? {
+                    report.error(violation);
+                }
+            }
+            CheckKind::UnusedDeps => {
+                for dep in find_unused_dependencies(pyproject)? {
+                    report.warning(format!("{} appears unused", dep.name));
+                }
+            }
+            CheckKind::MissingDeps => {
+                if !pyproject.venv_path().is_dir() {
+                    report.skip("no virtualenv to look up installed packages in");
+                }
+                for import in find_transitive_imports(pyproject)? {
+                    report.warning(format!(
+                        "{} is imported but {} is not a declared dependency",
+                        import.module, import.distribution
+                    ));
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CheckKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CheckKind::all()
+            .into_iter()
+            .find(|x| x.name() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown check '{}'", s))
+    }
+}
+
+impl Serialize for CheckKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// Settings for the checks.
+#[derive(Debug, Clone, Default)]
+pub struct CheckOptions {
+    /// Have the `staged` check look at the files of the checkout if they
+    /// changed since this git revision, instead of at the staged ones.  This
+    /// is for CI, where nothing is staged.
+    pub since: Option<String>,
+    /// The target of the lockfiles to check, see
+    /// [`LockOptions::lockfile_name`].
+    pub lock_options: LockOptions,
+}
+
+/// The outcome of a check, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckStatus {
+    Ok,
+    /// The check does not apply to the project.
+    Skipped,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Skipped => "skipped",
+            CheckStatus::Warning => "warning",
+            CheckStatus::Error => "error",
+        })
+    }
+}
+
+/// Where a finding of a check is, for annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    /// The line (1-based), if known.
+    pub line: Option<usize>,
+}
+
+impl Location {
+    fn file(file: &Path) -> Location {
+        Location {
+            file: file.to_path_buf(),
+            line: None,
+        }
+    }
+}
+
+/// A single finding of a check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub message: String,
+    /// The file (and line) the finding refers to, if any.
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Serialize for Finding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.message)
+    }
+}
+
+/// The findings of a single check.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    #[serde(rename = "name")]
+    pub kind: CheckKind,
+    /// The worst outcome of all findings.
+    pub status: CheckStatus,
+    pub messages: Vec<Finding>,
+}
+
+impl CheckReport {
+    fn new(kind: CheckKind) -> CheckReport {
+        CheckReport {
+            kind,
+            status: CheckStatus::Ok,
+            messages: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, status: CheckStatus, message: String, location: Option<Location>) {
+        self.status = self.status.max(status);
+        let finding = Finding { message, location };
+        if !self.messages.contains(&finding) {
+            self.messages.push(finding);
+        }
+    }
+
+    fn skip(&mut self, message: impl Into<String>) {
+        self.add(CheckStatus::Skipped, message.into(), None);
+    }
+
+    fn warning(&mut self, message: impl Into<String>) {
+        self.add(CheckStatus::Warning, message.into(), None);
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.add(CheckStatus::Error, message.into(), None);
+    }
+
+    fn error_at(&mut self, location: Location, message: impl Into<String>) {
+        self.add(CheckStatus::Error, message.into(), Some(location));
+    }
+}
+
+/// Runs the given checks.
+///
+/// A check which fails to run is reported as an error rather than aborting
+/// the others.
+pub fn run_checks(
+    pyproject: &PyProject,
+    kinds: &[CheckKind],
+    options: &CheckOptions,
+) -> Vec<CheckReport> {
+    kinds
+        .iter()
+        .map(|&kind| {
+            kind.run(pyproject, options).unwrap_or_else(|err| {
+                let mut report = CheckReport::new(kind);
+                report.error(format!("{:#}", err));
+                report
+            })
+        })
+        .collect()
+}
+
+/// The projects the checks look at: all workspace members or just the
+/// project itself.
+fn load_projects(pyproject: &PyProject) -> Result<Vec<PyProject>, Error> {
+    match pyproject.workspace() {
+        Some(workspace) => workspace.iter_projects().collect(),
+        None => Ok(vec![PyProject::load(&pyproject.toml_path())?]),
+    }
+}
+
+/// Finds the line (1-based) of a TOML file that sets a key.
+fn find_key_line(path: &Path, key: &str) -> Option<usize> {
+    fs::read_to_string(path)
+        .ok()?
+        .lines()
+        .position(|line| {
+            line.trim_start()
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        })
+        .map(|idx| idx + 1)
+}
+
+fn project_label(project: &PyProject) -> String {
+    project.name().unwrap_or("project").to_string()
+}
+
+fn check_lock(
+    pyproject: &PyProject,
+    options: &CheckOptions,
+    report: &mut CheckReport,
+) -> Result<(), Error> {
+    let projects = load_projects(pyproject)?;
+    let members: HashSet<_> = projects
+        .iter()
+        .filter_map(|x| x.normalized_name())
+        .collect();
+
+    for lock_mode in [LockMode::Production, LockMode::Dev] {
+        let filename = options.lock_options.lockfile_name(lock_mode)?;
+        let path = pyproject.lockfile_dir().join(&filename);
+        if !path.is_file() {
+            report.error(format!("{} is missing, run `rye lock`", filename));
+            continue;
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("unable to read lockfile {}", path.display()))?;
+        let lockfile = Lockfile::from_packages(parse_lockfile(&contents));
+        for project in &projects {
+            let mut kinds = vec![DependencyKind::Normal];
+            if lock_mode == LockMode::Dev {
+                kinds.push(DependencyKind::Dev);
+            }
+            for req in kinds.into_iter().flat_map(|x| project.iter_dependencies(x)) {
+                let name = normalize_package_name(&req.name);
+                // dependencies with markers are legitimately missing on
+                // other platforms
+                if req.marker.is_some() || members.contains(&name) {
+                    continue;
+                }
+                let locked = match lockfile.get(&name) {
+                    Some(locked) => locked,
+                    None => {
+                        report.error_at(
+                            Location::file(&path),
+                            format!("{} is not in {}, run `rye lock`", req.name, filename),
+                        );
+                        continue;
+                    }
+                };
+                let version = locked
+                    .version
+                    .as_deref()
+                    .and_then(|x| Version::from_str(x).ok());
+                if let (Some(VersionOrUrl::VersionSpecifier(spec)), Some(version)) =
+                    (&req.version_or_url, version)
+                {
+                    if !version.any_prerelease() && !spec.contains(&version) {
+                        report.error_at(
+                            Location {
+                                file: path.clone(),
+                                line: find_locked_line(&contents, &name),
+                            },
+                            format!(
+                                "{} pins {} {} which does not satisfy {}, run `rye lock`",
+                                filename, req.name, version, spec
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_staged(
+    pyproject: &PyProject,
+    options: &CheckOptions,
+    report: &mut CheckReport,
+) -> Result<(), Error> {
+    let root = pyproject.workspace_path();
+    if git(&root, &["rev-parse", "--is-inside-work-tree"])?.is_none() {
+        report.skip("not in a git repository");
+        return Ok(());
+    }
+    let changed = match options.since {
+        Some(ref rev) => match git(&root, &["diff", "--name-only", "--relative", rev, "--"])? {
+            Some(changed) => changed,
+            None => {
+                report.error(format!("unable to compare with git revision '{}'", rev));
+                return Ok(());
+            }
+        },
+        None => git(&root, &["diff", "--cached", "--name-only", "--relative"])?.unwrap_or_default(),
+    };
+    let toml_paths: Vec<PathBuf> = match pyproject.workspace() {
+        Some(workspace) => workspace
+            .iter_projects()
+            .map(|x| x.map(|x| x.toml_path().into_owned()))
+            .collect::<Result<_, _>>()?,
+        None => vec![pyproject.toml_path().into_owned()],
+    };
+    let lockfile_dir = pyproject.lockfile_dir();
+    let lockfiles = [
+        lockfile_dir.join(options.lock_options.lockfile_name(LockMode::Production)?),
+        lockfile_dir.join(options.lock_options.lockfile_name(LockMode::Dev)?),
+    ];
+    let rel_path = |path: &Path| {
+        path.strip_prefix(&root)
+            .ok()
+            .map(|x| x.to_string_lossy().replace('\\', "/"))
+    };
+    // only changes to the dependencies or lockfiles are checked
+    let touched = toml_paths
+        .iter()
+        .chain(&lockfiles)
+        .filter_map(|x| rel_path(x))
+        .any(|x| changed.lines().any(|y| y == x));
+    if !touched {
+        return Ok(());
+    }
+    // the staged contents, or with `since` the ones in the checkout
+    let read = |path: &Path| -> Result<Option<String>, Error> {
+        match options.since {
+            Some(_) => match fs::read_to_string(path) {
+                Ok(contents) => Ok(Some(contents)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err).with_context(|| format!("unable to read {}", path.display())),
+            },
+            None => match rel_path(path) {
+                Some(rel) => git(&root, &["show", &format!(":./{}", rel)]),
+                None => Ok(None),
+            },
+        }
+    };
+
+    // what the lockfiles should have been locked from
+    let mut files = Vec::new();
+    for path in &toml_paths {
+        // a member that is not tracked still went into the lockfiles
+        let contents = match read(path)? {
+            Some(contents) => contents,
+            None => fs::read_to_string(path)
+                .with_context(|| format!("unable to read {}", path.display()))?,
+        };
+        files.push((path.clone(), contents));
+    }
+    let current =
+        match hash_lock_inputs_of(&root, files.iter().map(|(x, y)| (x.clone(), y.as_str()))) {
+            Ok(current) => current,
+            Err(err) => {
+                report.error(format!("{:#}", err));
+                return Ok(());
+            }
+        };
+
+    let staged = options.since.is_none();
+    for lockfile in &lockfiles {
+        let rel = match rel_path(lockfile) {
+            Some(rel) => rel,
+            None => {
+                report.skip(format!("{} is not in the repository", lockfile.display()));
+                continue;
+            }
+        };
+        let contents = read(lockfile)?;
+        if let Some(problem) = lockfile_problem(&rel, contents.as_deref(), &current, staged) {
+            report.error_at(Location::file(lockfile), problem);
+        }
+    }
+    Ok(())
+}
+
+/// Checks the contents of a lockfile (`None` if it is missing) against the
+/// hash of the lock inputs of the `pyproject.toml` files.  `staged` picks
+/// the wording for the staged files rather than the ones of the checkout.
+fn lockfile_problem(
+    rel_path: &str,
+    contents: Option<&str>,
+    current: &str,
+    staged: bool,
+) -> Option<String> {
+    let (the, which, action) = if staged {
+        ("the staged ", "staged ", "stage")
+    } else {
+        ("", "", "commit")
+    };
+    let recorded = match contents {
+        Some(contents) => recorded_lock_inputs(contents),
+        None if staged => {
+            return Some(format!(
+                "{} is not staged, run `rye lock` and stage it",
+                rel_path
+            ))
+        }
+        None => {
+            return Some(format!(
+                "{} is missing, run `rye lock` and commit it",
+                rel_path
+            ))
+        }
+    };
+    match recorded {
+        Some(recorded) if recorded == current => None,
+        Some(_) => Some(format!(
+            "{}{} was not locked from the {}pyproject.toml files, \
+             run `rye lock` and {} it",
+            the, rel_path, which, action
+        )),
+        None => Some(format!(
+            "{}{} does not record what it was locked from, \
+             run `rye lock` and {} it",
+            the, rel_path, action
+        )),
+    }
+}
+
+/// Runs git and returns its output, or `None` if it fails.
+fn git(root: &Path, args: &[&str]) -> Result<Option<String>, Error> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(root)
+        .output()
+        .context("unable to run git")?;
+    Ok(output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+fn check_venv(pyproject: &PyProject, report: &mut CheckReport) -> Result<(), Error> {
+    if !pyproject.venv_path().is_dir() {
+        report.skip("no virtualenv, run `rye sync` to create it");
+        return Ok(());
+    }
+    let python = match get_venv_python_version(pyproject) {
+        Some(python) => python,
+        None => {
+            report.warning("the virtualenv was not created by rye");
+            return Ok(());
+        }
+    };
+    if find_stale_venv(pyproject)?.is_some() {
+        report.warning("the virtualenv is out of date, run `rye sync`");
+    }
+    if let Some(requests) = load_python_version_requests() {
+        if !requests.iter().any(|x| matches_version(x, &python)) {
+            report.warning(format!(
+                "the virtualenv uses {} but {} is pinned, run `rye sync`",
+                python, requests[0]
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The python versions a toolchain request allows, as specifier.
+fn request_to_specifiers(req: &PythonVersionRequest) -> Option<VersionSpecifiers> {
+    let spec = match (req.minor, req.patch) {
+        (Some(minor), Some(patch)) => format!("=={}.{}.{}", req.major, minor, patch),
+        (Some(minor), None) => format!("=={}.{}.*", req.major, minor),
+        _ => format!("=={}.*", req.major),
+    };
+    VersionSpecifiers::from_str(&spec).ok()
+}
+
+fn check_requires_python(pyproject: &PyProject, report: &mut CheckReport) -> Result<(), Error> {
+    let venv_python = get_venv_python_version(pyproject);
+    let pinned = load_python_version_requests().and_then(|x| x.into_iter().next());
+    let mut ranges = Vec::new();
+
+    for project in load_projects(pyproject)? {
+        let raw = match project.requires_python() {
+            Some(raw) => raw,
+            None => continue,
+        };
+        let label = project_label(&project);
+        let location = Location {
+            file: project.toml_path().into_owned(),
+            line: find_key_line(&project.toml_path(), "requires-python"),
+        };
+        let spec = match VersionSpecifiers::from_str(raw) {
+            Ok(spec) => spec,
+            Err(err) => {
+                report.error_at(
+                    location,
+                    format!("{} has an invalid requires-python: {}", label, err),
+                );
+                continue;
+            }
+        };
+        if let Some(ref python) = venv_python {
+            let version = Version::from_release(vec![
+                python.major as usize,
+                python.minor as usize,
+                python.patch as usize,
+            ]);
+            if !spec.contains(&version) {
+                report.error_at(
+                    location.clone(),
+                    format!(
+                        "{} requires python {} but the virtualenv uses {}",
+                        label, spec, python
+                    ),
+                );
+            }
+        }
+        if let Some(ref pinned) = pinned {
+            if let Some(pinned_spec) = request_to_specifiers(pinned) {
+                if !has_common_version(&[&spec, &pinned_spec]) {
+                    report.error_at(
+                        location.clone(),
+                        format!(
+                            "{} requires python {} but {} is pinned",
+                            label, spec, pinned
+                        ),
+                    );
+                }
+            }
+        }
+        ranges.push(spec);
+    }
+
+    if ranges.is_empty() {
+        report.skip("no project declares requires-python");
+    } else if !has_common_version(&ranges.iter().collect::<Vec<_>>()) {
+        report.error("the requires-python ranges of the workspace projects do not overlap");
+    }
+    Ok(())
+}
+
+fn check_workspace_constraints(
+    pyproject: &PyProject,
+    report: &mut CheckReport,
+) -> Result<(), Error> {
+    if pyproject.workspace().is_none() {
+        report.skip("the project is not part of a workspace");
+        return Ok(());
+    }
+    let mut constraints: BTreeMap<String, Vec<(String, VersionSpecifiers)>> = BTreeMap::new();
+    for project in load_projects(pyproject)? {
+        let label = project_label(&project);
+        for kind in [DependencyKind::Normal, DependencyKind::Dev] {
+            for req in project.iter_dependencies(kind) {
+                if let Some(VersionOrUrl::VersionSpecifier(spec)) = req.version_or_url {
+                    constraints
+                        .entry(normalize_package_name(&req.name))
+                        .or_default()
+                        .push((label.clone(), spec));
+                }
+            }
+        }
+    }
+    for (name, wanted) in constraints {
+        let specs: Vec<_> = wanted.iter().map(|x| &x.1).collect();
+        if !has_common_version(&specs) {
+            report.error(format!(
+                "no version of {} satisfies all projects: {}",
+                name,
+                wanted
+                    .iter()
+                    .map(|(project, spec)| format!("{} wants {}", project, spec))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Loads the dependency policy that applies to a project.
+///
+/// In a workspace the policy of the workspace root applies to all members.
+pub fn load_dependency_policy(pyproject: &PyProject) -> Result<DependencyPolicy, Error> {
+    match pyproject.workspace() {
+        Some(workspace) => {
+            Ok(PyProject::load(&workspace.path().join("pyproject.toml"))?.dependency_policy())
+        }
+        None => Ok(pyproject.dependency_policy()),
+    }
+}
+
+/// Returns the ways in which a requirement breaks the policy.
+///
+/// Direct references are pinned by their URL and always pass.
+pub fn check_requirement_policy(policy: &DependencyPolicy, req: &Requirement) -> Vec<String> {
+    let mut rv = Vec::new();
+    if policy.allow.contains(&normalize_package_name(&req.name)) {
+        return rv;
+    }
+    let spec = match req.version_or_url {
+        Some(VersionOrUrl::VersionSpecifier(ref spec)) => spec.iter().collect::<Vec<_>>(),
+        Some(VersionOrUrl::Url(_)) => return rv,
+        None => Vec::new(),
+    };
+    if policy.forbid_wildcards {
+        if spec.is_empty() {
+            rv.push(format!("{} allows any version", req.name));
+        } else if spec.iter().any(|x| *x.operator() == Operator::EqualStar) {
+            rv.push(format!(
+                "{} uses a wildcard version",
+                format_constraint(req)
+            ));
+        }
+    }
+    if policy.require_upper_bound
+        && !spec.iter().any(|x| {
+            matches!(
+                x.operator(),
+                Operator::Equal
+                    | Operator::EqualStar
+                    | Operator::ExactEqual
+                    | Operator::TildeEqual
+                    | Operator::LessThan
+                    | Operator::LessThanEqual
+            )
+        })
+    {
+        rv.push(format!("{} has no upper bound", format_constraint(req)));
+    }
+    rv
+}
+
+fn format_constraint(req: &Requirement) -> String {
+    match req.version_or_url {
+        Some(VersionOrUrl::VersionSpecifier(ref spec)) => format!("{}{}", req.name, spec),
+        _ => req.name.clone(),
+    }
+}
+
+/// Finds the dependencies of the project (or all workspace projects) which
+/// break the dependency policy.
+pub fn find_policy_violations(pyproject: &PyProject) -> Result<Vec<String>, Error> {
+    let policy = load_dependency_policy(pyproject)?;
+    let mut rv = Vec::new();
+    if policy.is_empty() {
+        return Ok(rv);
+    }
+    let projects = load_projects(pyproject)?;
+    let multiple = projects.len() > 1;
+    for project in &projects {
+        let mut kinds = vec![DependencyKind::Normal, DependencyKind::Dev];
+        kinds.extend(
+            project
+                .optional_dependency_sections()
+                .into_iter()
+                .map(|x| DependencyKind::Optional(x.into())),
+        );
+        for req in kinds.into_iter().flat_map(|x| project.iter_dependencies(x)) {
+            for violation in check_requirement_policy(&policy, &req) {
+                if multiple {
+                    rv.push(format!("{}: {}", project_label(project), violation));
+                } else {
+                    rv.push(violation);
+                }
+            }
+        }
+    }
+    Ok(rv)
+}
+
+/// Is there a version that satisfies all of the specifiers?
+///
+/// This tries the versions the specifiers mention and versions just above
+/// them, which finds a common version for all but contrived ranges.
+pub fn has_common_version(specs: &[&VersionSpecifiers]) -> bool {
+    let mut candidates = Vec::new();
+    for spec in specs.iter().flat_map(|x| x.iter()) {
+        let version = spec.version();
+        let mut above = Version::from_release(version.release.clone());
+        above.epoch = version.epoch;
+        above.release.extend([0, 0, 0, 1]);
+        candidates.push(version.clone());
+        candidates.push(above);
+    }
+    candidates.is_empty()
+        || candidates
+            .iter()
+            .any(|version| specs.iter().all(|spec| spec.contains(version)))
+}
+
+#[test]
+fn test_has_common_version() {
+    let spec = |x: &str| VersionSpecifiers::from_str(x).unwrap();
+    assert!(has_common_version(&[&spec(">=2.0"), &spec("<3")]));
+    assert!(has_common_version(&[&spec(">1,<2"), &spec(">1.5")]));
+    assert!(has_common_version(&[&spec("~=1.4"), &spec("!=1.4")]));
+    assert!(!has_common_version(&[&spec("<2"), &spec(">=2")]));
+    assert!(!has_common_version(&[&spec("==1.0"), &spec("==1.1")]));
+    assert!(has_common_version(&[&spec(">=3.8"), &spec("==3.11.*")]));
+}
+
+#[test]
+fn test_check_requirement_policy() {
+    let policy = DependencyPolicy {
+        require_upper_bound: true,
+        forbid_wildcards: true,
+        allow: vec!["black".into()],
+        ..Default::default()
+    };
+    let check = |x: &str| check_requirement_policy(&policy, &Requirement::from_str(x).unwrap());
+    assert!(check("flask~=2.2").is_empty());
+    assert!(check("flask>=2.2,<3").is_empty());
+    assert!(check("black").is_empty());
+    assert!(check("my-lib @ file:///w/my-lib").is_empty());
+    assert_eq!(check("flask>=2.2"), vec!["flask>=2.2 has no upper bound"]);
+    assert_eq!(
+        check("flask==2.*"),
+        vec!["flask==2.* uses a wildcard version"]
+    );
+    assert_eq!(
+        check("flask"),
+        vec!["flask allows any version", "flask has no upper bound"]
+    );
+}
+
+#[test]
+fn test_lockfile_problem() {
+    assert_eq!(
+        lockfile_problem(
+            "requirements.lock",
+            Some("# inputs: abc\nflask==2.3.2\n"),
+            "abc",
+            true
+        ),
+        None
+    );
+    assert_eq!(
+        lockfile_problem("requirements.lock", Some("# inputs: abc\n"), "def", true).as_deref(),
+        Some(
+            "the staged requirements.lock was not locked from the staged pyproject.toml files, \
+             run `rye lock` and stage it"
+        )
+    );
+    assert_eq!(
+        lockfile_problem("requirements.lock", Some("# inputs: abc\n"), "def", false).as_deref(),
+        Some(
+            "requirements.lock was not locked from the pyproject.toml files, \
+             run `rye lock` and commit it"
+        )
+    );
+    assert!(
+        lockfile_problem("requirements.lock", Some("flask==2.3.2\n"), "abc", true)
+            .unwrap()
+            .contains("does not record")
+    );
+    assert!(lockfile_problem("requirements.lock", None, "abc", true)
+        .unwrap()
+        .contains("is not staged"));
+    assert!(lockfile_problem("requirements.lock", None, "abc", false)
+        .unwrap()
+        .contains("is missing"));
+}
+
+#[test]
+fn test_check_staged_since() {
+    // a project committed in one checkout and checked in a clone elsewhere,
+    // as in CI
+    let tmp = tempfile::tempdir().unwrap();
+    let origin = tmp.path().join("origin");
+    fs::create_dir(&origin).unwrap();
+    let git = |dir: &Path, args: &[&str]| {
+        Command::new("git")
+            .args(["-c", "user.name=rye", "-c", "user.email=rye@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .is_ok_and(|x| x.status.success())
+    };
+    if !git(&origin, &["init", "-q"]) {
+        // no git around
+        return;
+    }
+    let toml = "[project]\nname = \"my-app\"\ndependencies = [\"flask>=2.0\"]\n";
+    let lockfile = |toml: &str| {
+        let hash = hash_lock_inputs_of(
+            Path::new("/somewhere"),
+            [(PathBuf::from("/somewhere/pyproject.toml"), toml)],
+        )
+        .unwrap();
+        format!("# generated by rye\n# inputs: {}\nflask==2.3.2\n", hash)
+    };
+    fs::write(origin.join("pyproject.toml"), toml).unwrap();
+    fs::write(origin.join("requirements.lock"), lockfile(toml)).unwrap();
+    fs::write(origin.join("requirements-dev.lock"), lockfile(toml)).unwrap();
+    assert!(git(&origin, &["add", "."]));
+    assert!(git(&origin, &["commit", "-qm", "base"]));
+
+    let changed = toml.replace("2.0", "2.3");
+    fs::write(origin.join("pyproject.toml"), &changed).unwrap();
+    fs::write(origin.join("requirements.lock"), lockfile(&changed)).unwrap();
+    assert!(git(&origin, &["commit", "-qam", "bump flask"]));
+
+    let clone = tmp.path().join("clone");
+    assert!(git(
+        tmp.path(),
+        &["clone", "-q", origin.to_str().unwrap(), "clone"]
+    ));
+    let run = || {
+        let pyproject = PyProject::load(&clone.join("pyproject.toml")).unwrap();
+        let options = CheckOptions {
+            since: Some("HEAD~1".into()),
+            ..CheckOptions::default()
+        };
+        CheckKind::Staged.run(&pyproject, &options).unwrap()
+    };
+    // the production lockfile was updated, the dev one was forgotten
+    let report = run();
+    assert_eq!(report.status, CheckStatus::Error);
+    assert_eq!(
+        report.messages[0].message,
+        "requirements-dev.lock was not locked from the pyproject.toml files, \
+         run `rye lock` and commit it"
+    );
+    assert_eq!(report.messages.len(), 1);
+
+    fs::write(clone.join("requirements-dev.lock"), lockfile(&changed)).unwrap();
+    assert_eq!(run().status, CheckStatus::Ok);
+
+    // nothing staged, so without `since` the check has nothing to look at
+    let pyproject = PyProject::load(&clone.join("pyproject.toml")).unwrap();
+    let report = CheckKind::Staged
+        .run(&pyproject, &CheckOptions::default())
+        .unwrap();
+    assert_eq!(report.status, CheckStatus::Ok);
+    let report = CheckKind::Staged
+        .run(
+            &pyproject,
+            &CheckOptions {
+                since: Some("no-such-rev".into()),
+                ..CheckOptions::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(report.status, CheckStatus::Error);
+}
+
+#[test]
+fn test_check_target_lockfiles() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("pyproject.toml");
+    fs::write(
+        &path,
+        "[project]\nname = \"app\"\ndependencies = [\"flask>=2\"]\n",
+    )
+    .unwrap();
+    fs::write(
+        tmp.path().join("requirements-linux-x86_64.lock"),
+        "flask==1.1.4\n",
+    )
+    .unwrap();
+    let pyproject = PyProject::load(&path).unwrap();
+    let options = CheckOptions {
+        lock_options: LockOptions {
+            target_platform: Some("linux-x86_64".into()),
+            ..LockOptions::default()
+        },
+        ..CheckOptions::default()
+    };
+    let report = CheckKind::Lock.run(&pyproject, &options).unwrap();
+    let messages: Vec<_> = report.messages.iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "requirements-linux-x86_64.lock pins flask 1.1.4 which does not satisfy >=2, \
+             run `rye lock`",
+            "requirements-dev-linux-x86_64.lock is missing, run `rye lock`",
+        ]
+    );
+}
+
+#[test]
+fn test_find_key_line() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("pyproject.toml");
+    fs::write(
+        &path,
+        "[project]\nname = \"my-app\"\nrequires-python-note = 1\n  requires-python = \">= 3.8\"\n",
+    )
+    .unwrap();
+    assert_eq!(find_key_line(&path, "requires-python"), Some(4));
+    assert_eq!(find_key_line(&path, "dependencies"), None);
+}
@@ -0,0 +1,120 @@
+//! Syncing into existing conda environments.
+//!
+//! Instead of creating a virtualenv, `rye sync` can install the locked
+//! packages into a conda environment so that native libraries installed
+//! through conda can be used.  The `.venv` of the project becomes a symlink
+//! to the environment so everything else finds it in the usual place.
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, bail, Context, Error};
+use serde::Deserialize;
+
+use crate::sources::PythonVersion;
+
+/// Which conda environment to sync into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CondaEnvRequest {
+    /// The environment that is currently activated.
+    Active,
+    /// An environment by name, or by path if it contains a slash.
+    Named(String),
+}
+
+impl fmt::Display for CondaEnvRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CondaEnvRequest::Active => f.write_str("active conda environment"),
+            CondaEnvRequest::Named(name) => write!(f, "conda environment '{}'", name),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CondaInfo {
+    root_prefix: PathBuf,
+    envs: Vec<PathBuf>,
+}
+
+/// Returns the conda executable.
+fn get_conda_bin() -> PathBuf {
+    // set by conda's shell integration, and conda itself is not necessarily
+    // on the path.
+    env::var_os("CONDA_EXE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("conda"))
+}
+
+/// Checks that a folder is the prefix of a conda environment.
+fn is_conda_prefix(path: &Path) -> bool {
+    path.join("conda-meta").is_dir()
+}
+
+/// Finds the prefix of the requested conda environment.
+pub fn find_conda_env(request: &CondaEnvRequest) -> Result<PathBuf, Error> {
+    let name = match request {
+        CondaEnvRequest::Active => {
+            return match env::var_os("CONDA_PREFIX") {
+                Some(prefix) if !prefix.is_empty() => Ok(PathBuf::from(prefix)),
+                _ => bail!("no conda environment is active"),
+            }
+        }
+        CondaEnvRequest::Named(name) => name,
+    };
+    if name.contains('/') {
+        let prefix = Path::new(name)
+            .canonicalize()
+            .with_context(|| format!("conda environment {} does not exist", name))?;
+        if !is_conda_prefix(&prefix) {
+            bail!("{} is not a conda environment", prefix.display());
+        }
+        return Ok(prefix);
+    }
+
+    let out = Command::new(get_conda_bin())
+        .arg("info")
+        .arg("--json")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+        .context("unable to run conda, is it installed?")?;
+    if !out.status.success() {
+        bail!("failed to list conda environments");
+    }
+    let info: CondaInfo =
+        serde_json::from_slice(&out.stdout).context("unexpected output from conda info")?;
+    if name == "base" {
+        return Ok(info.root_prefix);
+    }
+    info.envs
+        .into_iter()
+        .find(|x| x != &info.root_prefix && x.file_name().is_some_and(|x| x == name.as_str()))
+        .ok_or_else(|| anyhow!("unknown conda environment '{}'", name))
+}
+
+/// Returns the python version of a conda environment.
+pub fn get_conda_python_version(prefix: &Path) -> Result<PythonVersion, Error> {
+    let out = Command::new(prefix.join("bin/python"))
+        .arg("-c")
+        .arg("import sys; print('%d.%d.%d' % sys.version_info[:3])")
+        .stdout(Stdio::piped())
+        .output()
+        .with_context(|| format!("conda environment {} has no python", prefix.display()))?;
+    let version = String::from_utf8_lossy(&out.stdout);
+    let mut parts = version.trim().splitn(3, '.').map(|x| x.parse::<u8>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch))) => Ok(PythonVersion {
+            kind: "cpython".into(),
+            major,
+            minor,
+            patch,
+            suffix: None,
+        }),
+        _ => bail!(
+            "unable to determine python version of conda environment {}",
+            prefix.display()
+        ),
+    }
+}
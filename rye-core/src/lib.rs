@@ -0,0 +1,59 @@
+//! The core of rye as a library.
+//!
+//! This crate contains the functionality the `rye` command line tool is
+//! built on: managing Python toolchains ([`Toolchain`]), reading and
+//! updating lockfiles ([`Lockfile`]), syncing virtualenvs ([`sync()`] with
+//! [`SyncOptions`]) and editing `pyproject.toml` files ([`PyProject`]).
+//!
+//! The types re-exported from the crate root are the most convenient entry
+//! points, the modules expose the rest.  None of it is covered by any
+//! stability guarantee yet and may change between releases.
+//!
+//! Most operations report progress on stderr the way the command line tool
+//! does.  Pass [`CommandOutput::Quiet`] to silence them.
+//!
+//! ```no_run
+//! use rye_core::{sync, CommandOutput, SyncMode, SyncOptions, Toolchain};
+//!
+//! let toolchain = Toolchain::fetch(&"3.11".parse()?, CommandOutput::Quiet)?;
+//! println!("python at {}", toolchain.python_bin()?.display());
+//!
+//! sync(SyncOptions {
+//!     mode: SyncMode::Regular,
+//!     output: CommandOutput::Quiet,
+//!     ..SyncOptions::default()
+//! })?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+pub mod bootstrap;
+pub mod builddeps;
+pub mod cache;
+pub mod check;
+pub mod conda;
+pub mod config;
+pub mod disk;
+pub mod error;
+pub mod filelock;
+pub mod imports;
+pub mod index;
+pub mod installer;
+pub mod lock;
+pub mod namespaces;
+pub mod overlay;
+pub mod pylock;
+pub mod pyproject;
+pub mod shell;
+pub mod signals;
+pub mod sources;
+pub mod sync;
+pub mod timings;
+pub mod toolchain;
+pub mod utils;
+pub mod workspace;
+
+pub use crate::lock::{LockChange, LockMode, LockOptions, LockedPackage, Lockfile};
+pub use crate::pyproject::{DependencyKind, PyProject, UnsafePackages, Workspace};
+pub use crate::sources::{PythonVersion, PythonVersionRequest};
+pub use crate::sync::{sync, SyncMode, SyncOptions};
+pub use crate::toolchain::Toolchain;
+pub use crate::utils::CommandOutput;
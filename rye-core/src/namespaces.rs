@@ -0,0 +1,315 @@
+//! Namespace packages spread over the local packages of a project.
+//!
+//! Workspace members often share a namespace (`acme.billing` and
+//! `acme.shipping`).  setuptools installs editables through an import hook by
+//! default which claims the whole top-level name, so the other portions of the
+//! namespace become invisible.  Its strict mode links the files instead which
+//! keeps the namespace composable.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Error};
+
+use crate::lock::resolve_local_reference;
+use crate::pyproject::PyProject;
+
+/// Folders of flat layout projects which are never namespaces.
+const NOT_NAMESPACES: &[&str] = &[
+    "benchmarks",
+    "build",
+    "dist",
+    "doc",
+    "docs",
+    "examples",
+    "scripts",
+    "test",
+    "tests",
+];
+
+/// Prints the modules it is given which cannot be found.
+const FIND_MISSING_SCRIPT: &str = r#"
+import importlib.util
+import sys
+
+for name in sys.argv[1:]:
+    try:
+        found = importlib.util.find_spec(name) is not None
+    except Exception:
+        found = False
+    if not found:
+        print(name)
+"#;
+
+/// The part of a namespace package a local package provides, for instance
+/// `acme.billing` of the namespace `acme`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespacePortion {
+    pub namespace: String,
+    /// The dotted name of the module.
+    pub module: String,
+    /// The root of the local package.
+    pub project: PathBuf,
+}
+
+/// Finds the namespace packages in the local packages of a project, that is
+/// in all members of a workspace.
+pub fn find_namespace_portions(pyproject: &PyProject) -> Result<Vec<NamespacePortion>, Error> {
+    let roots = match pyproject.workspace() {
+        Some(workspace) => workspace
+            .iter_projects()
+            .map(|x| x.map(|x| x.root_path().into_owned()))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![pyproject.root_path().into_owned()],
+    };
+    let mut rv = Vec::new();
+    for root in roots {
+        for (namespace, module) in find_portions_in(&root) {
+            rv.push(NamespacePortion {
+                namespace,
+                module,
+                project: root.clone(),
+            });
+        }
+    }
+    Ok(rv)
+}
+
+/// The per-requirement pip option which makes setuptools keep namespace
+/// packages intact in editable installs.
+pub const STRICT_EDITABLE: &str = "--config-settings editable_mode=strict";
+
+/// Switches the `-e` lines of a requirements file which refer to a local
+/// package providing a namespace portion to strict editable mode.
+///
+/// The setting is attached to the lines rather than passed to pip as a whole
+/// as other build backends (meson-python, scikit-build-core) reject settings
+/// they do not know.  For the same reason only setuptools projects get it.
+/// Relative references are resolved against `base`.
+pub fn with_strict_editables(
+    requirements: &str,
+    base: &Path,
+    portions: &[NamespacePortion],
+) -> String {
+    let projects: Vec<_> = portions
+        .iter()
+        .map(|x| &x.project)
+        .filter(|x| uses_setuptools(x))
+        .map(|x| normalize(x))
+        .collect();
+    let mut rv = String::new();
+    for line in requirements.lines() {
+        // comments have to stay at the end of the line
+        let req = match line.find(" #") {
+            Some(idx) => line[..idx].trim_end(),
+            None => line,
+        };
+        let comment = &line[req.len()..];
+        let strict = !req.contains(STRICT_EDITABLE)
+            && req
+                .strip_prefix("-e ")
+                .and_then(|x| x.split_whitespace().next())
+                .and_then(|x| resolve_reference(x, base))
+                .is_some_and(|x| projects.contains(&normalize(&x)));
+        if strict {
+            rv.push_str(&format!("{} {}{}", req, STRICT_EDITABLE, comment));
+        } else {
+            rv.push_str(line);
+        }
+        rv.push('\n');
+    }
+    rv
+}
+
+/// Resolves the reference of an `-e` line to a folder.
+fn resolve_reference(reference: &str, base: &Path) -> Option<PathBuf> {
+    if reference.starts_with("file:") {
+        resolve_local_reference(reference, base).ok()
+    } else {
+        Some(base.join(reference))
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Is the project built with setuptools, explicitly or as the default?
+fn uses_setuptools(project: &Path) -> bool {
+    PyProject::load(&project.join("pyproject.toml"))
+        .ok()
+        .is_none_or(|x| {
+            x.build_backend()
+                .is_none_or(|x| x.starts_with("setuptools"))
+        })
+}
+
+/// Returns the portions the given interpreter cannot import.
+pub fn find_shadowed_portions(
+    python: &Path,
+    portions: &[NamespacePortion],
+) -> Result<Vec<NamespacePortion>, Error> {
+    if portions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let out = Command::new(python)
+        .arg("-c")
+        .arg(FIND_MISSING_SCRIPT)
+        .args(portions.iter().map(|x| &x.module))
+        .env("PYTHONWARNINGS", "ignore")
+        .output()
+        .context("unable to check namespace packages")?;
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let missing: Vec<_> = stdout.lines().map(|x| x.trim()).collect();
+    Ok(portions
+        .iter()
+        .filter(|x| missing.contains(&x.module.as_str()))
+        .cloned()
+        .collect())
+}
+
+/// Finds the namespaces and their portions in a project folder.
+///
+/// A namespace is a folder without `__init__.py` holding packages (and with
+/// a `src` layout also modules).  Only the first level is looked at.
+fn find_portions_in(root: &Path) -> Vec<(String, String)> {
+    let (base, flat) = if root.join("src").is_dir() {
+        (root.join("src"), false)
+    } else {
+        (root.to_path_buf(), true)
+    };
+    let mut rv = Vec::new();
+    for dir in read_dir_sorted(&base) {
+        let namespace = match module_name(&dir) {
+            Some(name) if dir.is_dir() => name,
+            _ => continue,
+        };
+        if dir.join("__init__.py").is_file() || (flat && NOT_NAMESPACES.contains(&namespace)) {
+            continue;
+        }
+        for child in read_dir_sorted(&dir) {
+            let is_portion = if child.is_dir() {
+                child.join("__init__.py").is_file()
+            } else {
+                !flat && child.extension().is_some_and(|x| x == "py")
+            };
+            if let (true, Some(name)) = (is_portion, module_name(&child)) {
+                rv.push((namespace.to_string(), format!("{}.{}", namespace, name)));
+            }
+        }
+    }
+    rv
+}
+
+fn read_dir_sorted(dir: &Path) -> Vec<PathBuf> {
+    let mut rv: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .collect();
+    rv.sort();
+    rv
+}
+
+/// The module name of a file or folder if it is a valid identifier.
+fn module_name(path: &Path) -> Option<&str> {
+    let name = if path.is_dir() {
+        path.file_name()?.to_str()?
+    } else {
+        path.file_stem()?.to_str()?
+    };
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    if (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|x| x.is_ascii_alphanumeric() || x == '_')
+    {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_find_portions_in() {
+    let dir = tempfile::tempdir().unwrap();
+    let touch = |path: &str| {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    };
+
+    // flat layout: only packages count and test folders are skipped
+    touch("billing/acme/billing/__init__.py");
+    touch("billing/acme/helpers.py");
+    touch("billing/tests/unit/__init__.py");
+    touch("billing/regular/__init__.py");
+    touch("billing/regular/sub/__init__.py");
+    assert_eq!(
+        find_portions_in(&dir.path().join("billing")),
+        vec![("acme".to_string(), "acme.billing".to_string())]
+    );
+
+    // src layout: modules count as well
+    touch("shipping/src/acme/shipping/__init__.py");
+    touch("shipping/src/acme/rates.py");
+    touch("shipping/src/acme/not-a-module.py");
+    assert_eq!(
+        find_portions_in(&dir.path().join("shipping")),
+        vec![
+            ("acme".to_string(), "acme.rates".to_string()),
+            ("acme".to_string(), "acme.shipping".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_with_strict_editables() {
+    let dir = tempfile::tempdir().unwrap();
+    let ws = dir.path();
+    for (member, backend) in [
+        ("billing", "setuptools.build_meta"),
+        ("shipping", "mesonpy"),
+        ("plain", "setuptools.build_meta"),
+    ] {
+        fs::create_dir_all(ws.join(member)).unwrap();
+        fs::write(
+            ws.join(member).join("pyproject.toml"),
+            format!(
+                "[project]\nname = \"{}\"\n[build-system]\nbuild-backend = \"{}\"\n",
+                member, backend
+            ),
+        )
+        .unwrap();
+    }
+    let portions: Vec<_> = ["billing", "shipping"]
+        .iter()
+        .map(|x| NamespacePortion {
+            namespace: "acme".into(),
+            module: format!("acme.{}", x),
+            project: ws.join(x),
+        })
+        .collect();
+    let requirements = format!(
+        "-e file:billing  # via acme-api\n\
+         -e file:shipping\n\
+         -e file:plain\n\
+         -e {}\n\
+         numpy==2.0.0\n",
+        ws.join("billing").display()
+    );
+    assert_eq!(
+        with_strict_editables(&requirements, ws, &portions),
+        format!(
+            "-e file:billing --config-settings editable_mode=strict  # via acme-api\n\
+             -e file:shipping\n\
+             -e file:plain\n\
+             -e {} --config-settings editable_mode=strict\n\
+             numpy==2.0.0\n",
+            ws.join("billing").display()
+        )
+    );
+    // already strict lines are left alone
+    let strict = with_strict_editables(&requirements, ws, &portions);
+    assert_eq!(with_strict_editables(&strict, ws, &portions), strict);
+}
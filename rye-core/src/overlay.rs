@@ -0,0 +1,105 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Error};
+use console::style;
+use sha2::{Digest, Sha256};
+
+use crate::bootstrap::ensure_self_venv;
+use crate::cache::{get_cache_dir, touch};
+use crate::config::{apply_env_config, EnvTarget};
+use crate::signals;
+use crate::utils::CommandOutput;
+
+/// Returns a folder with extra requirements installed for a virtualenv.
+///
+/// The virtualenv itself is not modified, the packages are installed into a
+/// separate folder which is put on `PYTHONPATH` (see [`overlay_env`]).
+/// Overlays are cached in `~/.rye/cache/overlays`, keyed by the interpreter of the
+/// virtualenv and the requirements.
+pub fn ensure_overlay(
+    venv: &Path,
+    requirements: &[String],
+    output: CommandOutput,
+) -> Result<PathBuf, Error> {
+    let python = venv.join("bin/python");
+    let interpreter = fs::canonicalize(&python)
+        .with_context(|| format!("unable to find interpreter {}", python.display()))?;
+    let mut requirements = requirements.to_vec();
+    requirements.sort();
+    let mut hasher = Sha256::new();
+    hasher.update(interpreter.as_os_str().to_string_lossy().as_bytes());
+    for req in &requirements {
+        hasher.update(b"\n");
+        hasher.update(req.as_bytes());
+    }
+    let key = format!("{:x}", hasher.finalize());
+    let dir = get_cache_dir()?.join("overlays").join(&key[..16]);
+    if dir.is_dir() {
+        touch(&dir);
+        return Ok(dir);
+    }
+
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "Creating overlay with {}",
+            style(requirements.join(", ")).cyan()
+        );
+    }
+    let self_venv = ensure_self_venv(output)?;
+    let _defer = signals::defer_interrupts();
+    let staging = dir.with_extension(format!("tmp-{}", std::process::id()));
+    let mut pip_cmd = Command::new(self_venv.join("bin/pip"));
+    pip_cmd
+        .arg("--python")
+        .arg(&python)
+        .arg("install")
+        .arg("--target")
+        .arg(staging.join("site-packages"))
+        .args(&requirements)
+        .env("PYTHONWARNINGS", "ignore");
+    if output == CommandOutput::Verbose {
+        pip_cmd.arg("--verbose");
+    } else {
+        pip_cmd.arg("-q");
+    }
+    apply_env_config(&mut pip_cmd, EnvTarget::Tools)?;
+    let status = signals::status(&mut pip_cmd).context("unable to run pip")?;
+    if !status.success() {
+        fs::remove_dir_all(&staging).ok();
+        bail!("failed to install overlay requirements");
+    }
+
+    // pip places scripts next to the packages, move them where PATH expects them
+    let scripts = staging.join("site-packages/bin");
+    if scripts.is_dir() {
+        fs::rename(&scripts, staging.join("bin"))?;
+    }
+    // another process might have won the race, its overlay is just as good
+    if fs::rename(&staging, &dir).is_err() {
+        fs::remove_dir_all(&staging).ok();
+    }
+    Ok(dir)
+}
+
+/// The environment variables which put an overlay on top of the virtualenv.
+pub fn overlay_env(overlay: &Path) -> Vec<(&'static str, OsString)> {
+    let prepend = |path: PathBuf, var: &str| {
+        let mut rv = path.into_os_string();
+        if let Some(existing) = env::var_os(var) {
+            rv.push(":");
+            rv.push(existing);
+        }
+        rv
+    };
+    vec![
+        (
+            "PYTHONPATH",
+            prepend(overlay.join("site-packages"), "PYTHONPATH"),
+        ),
+        ("PATH", prepend(overlay.join("bin"), "PATH")),
+    ]
+}
@@ -0,0 +1,369 @@
+//! Reading and writing `pylock.toml` files (PEP 751).
+//!
+//! rye's own lockfiles stay requirements files.  A `pylock.toml` lists the
+//! distribution files of every package explicitly, so exporting looks them
+//! up on the index.  Importing turns a `pylock.toml` back into requirements
+//! pip can install.
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Error};
+use toml_edit::{
+    value, Array, ArrayOfTables, Datetime, Document, InlineTable, Item, Table, TableLike, Value,
+};
+
+use crate::index::{find_release_files, ReleaseFile};
+use crate::lock::{resolve_local_reference, LockedPackage, Lockfile};
+use crate::pyproject::PyProject;
+use crate::utils::redact_url;
+
+/// Converts a lockfile into the contents of a `pylock.toml`.
+///
+/// Local packages in the lockfile are relative to `workspace_root` and are
+/// written relative to `base`, the folder the `pylock.toml` ends up in.
+pub fn export_pylock(
+    lockfile: &Lockfile,
+    requires_python: Option<&str>,
+    workspace_root: &Path,
+    base: &Path,
+) -> Result<String, Error> {
+    let mut doc = Document::new();
+    doc["lock-version"] = value("1.0");
+    if let Some(requires_python) = requires_python {
+        doc["requires-python"] = value(requires_python);
+    }
+    doc["created-by"] = value("rye");
+
+    let mut packages = ArrayOfTables::new();
+    for pkg in lockfile.packages() {
+        let mut table = Table::new();
+        match pkg.version {
+            Some(ref version) => {
+                table["name"] = value(&pkg.name);
+                table["version"] = value(version);
+                add_release_files(&mut table, pkg, version)?;
+            }
+            None => {
+                let path = match pkg.name.strip_prefix("-e ") {
+                    Some(reference) => resolve_local_reference(reference, workspace_root)?,
+                    None => bail!(
+                        "unable to export direct reference {} to pylock.toml",
+                        pkg.name
+                    ),
+                };
+                let name = PyProject::load(&path.join("pyproject.toml"))
+                    .ok()
+                    .and_then(|x| x.normalized_name())
+                    .ok_or_else(|| anyhow!("unable to determine name of {}", path.display()))?;
+                let rel_path = pathdiff::diff_paths(&path, base).unwrap_or(path);
+                let rel_path = match rel_path.to_str() {
+                    Some("") => ".".to_string(),
+                    _ => rel_path.display().to_string(),
+                };
+                let mut directory = InlineTable::new();
+                directory.insert("path", rel_path.into());
+                directory.insert("editable", true.into());
+                table["name"] = value(name);
+                table["directory"] = value(directory);
+            }
+        }
+        packages.push(table);
+    }
+    doc["packages"] = Item::ArrayOfTables(packages);
+    Ok(doc.to_string())
+}
+
+/// Adds the index and the sdist and wheels of a release on it to a package.
+///
+/// The release is looked up on the configured indexes.  If the lockfile has
+/// hashes, only the files matching them are listed.  Credentials of private
+/// indexes are left out of the URLs.
+fn add_release_files(table: &mut Table, pkg: &LockedPackage, version: &str) -> Result<(), Error> {
+    let release = find_release_files(&pkg.name, version)?.ok_or_else(|| {
+        anyhow!(
+            "{}=={} was not found on the configured indexes",
+            pkg.name,
+            version
+        )
+    })?;
+    table["index"] = value(redact_url(&release.index_url));
+    let mut wheels = Array::new();
+    for file in &release.files {
+        let sha256 = match file.digests.get("sha256") {
+            Some(sha256) => sha256,
+            None => continue,
+        };
+        if !pkg.hashes.is_empty() && !pkg.hashes.contains(&format!("sha256:{}", sha256)) {
+            continue;
+        }
+        let entry = release_file_entry(file, sha256);
+        match file.packagetype.as_str() {
+            "sdist" => table["sdist"] = value(entry),
+            "bdist_wheel" => {
+                wheels.push_formatted(Value::InlineTable(entry).decorated("\n  ", ""));
+            }
+            _ => {}
+        }
+    }
+    if !wheels.is_empty() {
+        wheels.set_trailing("\n");
+        wheels.set_trailing_comma(true);
+        table["wheels"] = value(wheels);
+    }
+    if table.get("sdist").is_none() && !table.contains_key("wheels") {
+        bail!("no files of {}=={} found on the index", pkg.name, version);
+    }
+    Ok(())
+}
+
+fn release_file_entry(file: &ReleaseFile, sha256: &str) -> InlineTable {
+    let mut entry = InlineTable::new();
+    entry.insert("name", file.filename.as_str().into());
+    entry.insert("url", redact_url(&file.url).into());
+    if let Some(size) = file.size {
+        entry.insert("size", (size as i64).into());
+    }
+    if let Some(upload_time) = file
+        .upload_time_iso_8601
+        .as_deref()
+        .and_then(|x| Datetime::from_str(x).ok())
+    {
+        entry.insert("upload-time", upload_time.into());
+    }
+    let mut hashes = InlineTable::new();
+    hashes.insert("sha256", sha256.into());
+    entry.insert("hashes", hashes.into());
+    entry
+}
+
+/// The requirements files a `pylock.toml` is installed from.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PylockRequirements {
+    /// The packages from indexes and archives, with their hashes if the
+    /// `pylock.toml` has them.
+    pub requirements: String,
+    /// The local directories and VCS checkouts if the other packages are
+    /// hashed.  pip cannot check hashes for these, so they have to be
+    /// installed on their own, without dependencies.
+    pub unhashed: String,
+}
+
+/// Turns a `pylock.toml` into requirements files for pip.
+///
+/// pip checks the hashes of either all requirements or none.  If only some
+/// of the packages from indexes have hashes, the conversion fails rather
+/// than installing all of them unchecked.
+///
+/// Paths in the `pylock.toml` are relative to `base`, the folder it is in.
+pub fn pylock_to_requirements(contents: &str, base: &Path) -> Result<PylockRequirements, Error> {
+    let doc = contents
+        .parse::<Document>()
+        .context("invalid pylock.toml")?;
+    match doc.get("lock-version").and_then(|x| x.as_str()) {
+        Some(version) if version == "1" || version.starts_with("1.") => {}
+        Some(version) => bail!("unsupported pylock.toml lock-version {}", version),
+        None => bail!("pylock.toml has no lock-version"),
+    }
+
+    let mut lines = Vec::new();
+    let packages = doc.get("packages").and_then(|x| x.as_array_of_tables());
+    for pkg in packages.into_iter().flatten() {
+        let name = pkg
+            .get("name")
+            .and_then(|x| x.as_str())
+            .ok_or_else(|| anyhow!("package without name in pylock.toml"))?;
+        let (mut line, hashes) = pylock_requirement(pkg, name, base)?;
+        if let Some(marker) = pkg.get("marker").and_then(|x| x.as_str()) {
+            line.push_str(&format!(" ; {}", marker));
+        }
+        let hashable = !pkg.contains_key("directory") && !pkg.contains_key("vcs");
+        lines.push((name, line, hashes, hashable));
+    }
+
+    let hashed = lines.iter().any(|x| !x.2.is_empty());
+    if hashed {
+        let missing: Vec<_> = lines
+            .iter()
+            .filter(|x| x.3 && x.2.is_empty())
+            .map(|x| x.0)
+            .collect();
+        if !missing.is_empty() {
+            bail!(
+                "pylock.toml has hashes for some packages but not for {}, \
+                 pip can only check the hashes of all packages or none",
+                missing.join(", ")
+            );
+        }
+    }
+
+    let mut rv = PylockRequirements::default();
+    for (_, line, hashes, hashable) in lines {
+        let out = if hashed && !hashable {
+            &mut rv.unhashed
+        } else {
+            &mut rv.requirements
+        };
+        out.push_str(&line);
+        for hash in hashes {
+            out.push_str(&format!(" --hash={}", hash));
+        }
+        out.push('\n');
+    }
+    Ok(rv)
+}
+
+/// Builds the requirement for a package of a `pylock.toml`.
+///
+/// Returns the requirement and the hashes of its files.
+fn pylock_requirement(
+    pkg: &Table,
+    name: &str,
+    base: &Path,
+) -> Result<(String, Vec<String>), Error> {
+    let resolve = |path: &str| match path {
+        "." => base.display().to_string(),
+        _ => base.join(path).display().to_string(),
+    };
+    if let Some(directory) = pkg.get("directory") {
+        let path = directory
+            .get("path")
+            .and_then(|x| x.as_str())
+            .ok_or_else(|| anyhow!("directory of {} has no path", name))?;
+        let editable = directory
+            .get("editable")
+            .and_then(|x| x.as_bool())
+            .unwrap_or(false);
+        return Ok(if editable {
+            (format!("-e {}", resolve(path)), Vec::new())
+        } else {
+            (format!("{} @ file://{}", name, resolve(path)), Vec::new())
+        });
+    }
+    if let Some(vcs) = pkg.get("vcs") {
+        let kind = vcs.get("type").and_then(|x| x.as_str()).unwrap_or("git");
+        let url = vcs
+            .get("url")
+            .and_then(|x| x.as_str())
+            .ok_or_else(|| anyhow!("vcs source of {} has no url", name))?;
+        let commit = vcs
+            .get("commit-id")
+            .and_then(|x| x.as_str())
+            .ok_or_else(|| anyhow!("vcs source of {} has no commit-id", name))?;
+        let mut line = format!("{} @ {}+{}@{}", name, kind, url, commit);
+        if let Some(subdirectory) = vcs.get("subdirectory").and_then(|x| x.as_str()) {
+            line.push_str(&format!("#subdirectory={}", subdirectory));
+        }
+        return Ok((line, Vec::new()));
+    }
+    if let Some(archive) = pkg.get("archive") {
+        let location = match (
+            archive.get("url").and_then(|x| x.as_str()),
+            archive.get("path").and_then(|x| x.as_str()),
+        ) {
+            (Some(url), _) => url.to_string(),
+            (None, Some(path)) => format!("file://{}", resolve(path)),
+            (None, None) => bail!("archive of {} has neither url nor path", name),
+        };
+        let hashes = archive.as_table_like().map(file_hashes).unwrap_or_default();
+        return Ok((format!("{} @ {}", name, location), hashes));
+    }
+
+    let version = pkg
+        .get("version")
+        .and_then(|x| x.as_str())
+        .ok_or_else(|| anyhow!("package {} has no version or source", name))?;
+    let mut hashes = Vec::new();
+    if let Some(sdist) = pkg.get("sdist").and_then(|x| x.as_table_like()) {
+        hashes.extend(file_hashes(sdist));
+    }
+    for wheel in pkg
+        .get("wheels")
+        .and_then(|x| x.as_array())
+        .into_iter()
+        .flatten()
+    {
+        if let Some(wheel) = wheel.as_inline_table() {
+            hashes.extend(file_hashes(wheel));
+        }
+    }
+    Ok((format!("{}=={}", name, version), hashes))
+}
+
+/// Returns the hashes of a file entry in pip's `algorithm:digest` form.
+fn file_hashes(file: &dyn TableLike) -> Vec<String> {
+    file.get("hashes")
+        .and_then(|x| x.as_table_like())
+        .map(|hashes| {
+            hashes
+                .iter()
+                .filter_map(|(algo, digest)| Some(format!("{}:{}", algo, digest.as_str()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_pylock_to_requirements() {
+    let contents = r#"
+lock-version = "1.0"
+created-by = "rye"
+
+[[packages]]
+name = "flask"
+version = "2.2.3"
+wheels = [{ name = "Flask-2.2.3-py3-none-any.whl", hashes = { sha256 = "abc" } }]
+
+[[packages]]
+name = "tomli"
+version = "2.0.1"
+marker = "python_version < '3.11'"
+sdist = { name = "tomli-2.0.1.tar.gz", hashes = { sha256 = "def" } }
+"#;
+    assert_eq!(
+        pylock_to_requirements(contents, Path::new("/w"))
+            .unwrap()
+            .requirements,
+        "flask==2.2.3 --hash=sha256:abc\n\
+         tomli==2.0.1 ; python_version < '3.11' --hash=sha256:def\n"
+    );
+
+    // local packages cannot be hashed and are installed separately
+    let contents = r#"
+lock-version = "1.0"
+
+[[packages]]
+name = "flask"
+version = "2.2.3"
+wheels = [{ name = "Flask-2.2.3-py3-none-any.whl", hashes = { sha256 = "abc" } }]
+
+[[packages]]
+name = "my-app"
+directory = { path = ".", editable = true }
+"#;
+    assert_eq!(
+        pylock_to_requirements(contents, Path::new("/w")).unwrap(),
+        PylockRequirements {
+            requirements: "flask==2.2.3 --hash=sha256:abc\n".into(),
+            unhashed: "-e /w\n".into(),
+        }
+    );
+
+    // without any hashes everything is installed together
+    let unhashed = contents.replace(", hashes = { sha256 = \"abc\" }", "");
+    assert_eq!(
+        pylock_to_requirements(&unhashed, Path::new("/w")).unwrap(),
+        PylockRequirements {
+            requirements: "flask==2.2.3\n-e /w\n".into(),
+            unhashed: "".into(),
+        }
+    );
+
+    // a package from the index without hashes is never silently unchecked
+    let partial = format!(
+        "{}\n[[packages]]\nname = \"six\"\nversion = \"1.16.0\"\n",
+        contents
+    );
+    let err = pylock_to_requirements(&partial, Path::new("/w")).unwrap_err();
+    assert!(err.to_string().contains("not for six"));
+    assert!(pylock_to_requirements("lock-version = \"2.0\"", Path::new("/w")).is_err());
+}
@@ -0,0 +1,1360 @@
+use core::fmt;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::prelude::{MetadataExt, OsStrExt};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Error};
+use globset::Glob;
+use nix::unistd::{access, AccessFlags};
+use once_cell::sync::{Lazy, OnceCell};
+use pep508_rs::Requirement;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use toml_edit::{Array, Document, Item, Table, TableLike, Value};
+
+use crate::cache::get_cache_dir;
+use crate::conda::CondaEnvRequest;
+use crate::config::Config;
+use crate::sources::PythonVersionRequest;
+use crate::utils::{format_requirement, parse_env_file};
+
+static NORMALIZATION_SPLIT_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"[-_.]+").unwrap());
+static EXPLICIT_PROJECT_ROOT: OnceCell<PathBuf> = OnceCell::new();
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DependencyKind<'a> {
+    Normal,
+    Dev,
+    Optional(Cow<'a, str>),
+    /// The requirements of the build backend (`build-system.requires`).
+    BuildSystem,
+}
+
+/// A reference to a script
+#[derive(Clone, Debug)]
+pub enum Script {
+    /// A command alias
+    Cmd(Vec<String>),
+    /// External script reference
+    External(PathBuf),
+    /// A sequence of other scripts
+    Chain(Vec<String>),
+    /// A python function to call (`module:function`), like a console script
+    Call(String),
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Script::Cmd(args) => {
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", shlex::quote(arg))?;
+                }
+                Ok(())
+            }
+            Script::External(ref script) => write!(f, "external: {}", script.display()),
+            Script::Chain(ref scripts) => write!(f, "chain: {}", scripts.join(", ")),
+            Script::Call(ref target) => write!(f, "call: {}", target),
+        }
+    }
+}
+
+/// Merges the dependency notes of several projects.  Members that explain
+/// the same package differently get both notes.
+pub fn merge_dependency_notes<'a>(
+    projects: impl IntoIterator<Item = &'a PyProject>,
+) -> Result<BTreeMap<String, String>, Error> {
+    let mut rv: BTreeMap<String, String> = BTreeMap::new();
+    for project in projects {
+        for (name, note) in project.dependency_notes()? {
+            match rv.get_mut(&name) {
+                Some(existing) if existing.split("; ").any(|x| x == note) => {}
+                Some(existing) => {
+                    existing.push_str("; ");
+                    existing.push_str(&note);
+                }
+                None => {
+                    rv.insert(name, note);
+                }
+            }
+        }
+    }
+    Ok(rv)
+}
+
+/// A replacement for a dependency from `tool.rye.lock.patches`.
+#[derive(Debug, Clone)]
+pub struct Patch {
+    pub name: String,
+    pub source: PatchSource,
+}
+
+/// Where a patched dependency comes from.
+#[derive(Debug, Clone)]
+pub enum PatchSource {
+    /// A local checkout, installed as editable package.
+    Path(PathBuf),
+    /// A git repository, optionally at a specific revision.
+    Git { url: String, rev: Option<String> },
+}
+
+/// Which of the packages pip-compile considers unsafe (`pip`, `setuptools`
+/// and `distribute`) end up in the lockfiles, from `tool.rye.lock.allow-unsafe`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum UnsafePackages {
+    /// All of them are pinned.
+    #[default]
+    All,
+    /// They are left out, like pip-compile does by default.
+    None,
+    /// Only these are pinned.
+    Only(Vec<String>),
+}
+
+/// The rules for declared dependencies from `tool.rye.policy`.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyPolicy {
+    /// Every version constraint needs an upper bound.
+    pub require_upper_bound: bool,
+    /// Dependencies must not allow any version, neither by leaving out the
+    /// constraint nor with a wildcard like `==2.*`.
+    pub forbid_wildcards: bool,
+    /// `rye add` refuses dependencies which break the policy.
+    pub enforce_on_add: bool,
+    /// The dependencies the policy does not apply to.
+    pub allow: Vec<String>,
+}
+
+impl DependencyPolicy {
+    /// Does the policy have any rules?
+    pub fn is_empty(&self) -> bool {
+        !self.require_upper_bound && !self.forbid_wildcards
+    }
+}
+
+#[derive(Debug)]
+pub struct Workspace {
+    root: PathBuf,
+    members: Vec<String>,
+}
+
+impl Workspace {
+    /// Loads a workspace from a pyproject.toml and path
+    pub fn from_workspace_section_and_path(workspace: &dyn TableLike, path: &Path) -> Workspace {
+        Workspace {
+            root: path.to_path_buf(),
+            members: workspace
+                .get("members")
+                .and_then(|x| x.as_array())
+                .map(|x| {
+                    x.iter()
+                        .filter_map(|item| item.as_str().map(|x| x.to_string()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Discovers a pyproject toml
+    #[allow(unused)]
+    pub fn discover_from_path(path: &Path) -> Option<Workspace> {
+        let mut here = path;
+
+        loop {
+            let project_file = here.join("pyproject.toml");
+            if project_file.is_file() {
+                if let Ok(contents) = fs::read_to_string(&project_file) {
+                    if let Ok(doc) = contents.parse::<Document>() {
+                        if let Some(workspace) = Workspace::try_load_from_toml(&doc, here) {
+                            return Some(workspace);
+                        }
+                    }
+                }
+            }
+
+            if is_discovery_boundary(here) {
+                break;
+            }
+            here = match here.parent() {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+
+        None
+    }
+
+    /// The path of the workspace
+    pub fn path(&self) -> Cow<'_, Path> {
+        Cow::Borrowed(&self.root)
+    }
+
+    /// The folder the lockfiles of the workspace are written to (see
+    /// [`PyProject::lockfile_dir`]).
+    pub fn lockfile_dir(&self) -> Cow<'_, Path> {
+        match read_only_fallback_dir(&self.root) {
+            Some(dir) => dir.into(),
+            None => self.path(),
+        }
+    }
+
+    /// Checks if a project is a member of the declared workspace.
+    pub fn is_member(&self, path: &Path) -> bool {
+        let canonicalized = self.root.join(path);
+        if let Ok(relative) = path.strip_prefix(canonicalized) {
+            if relative == Path::new("") || self.members.is_empty() {
+                true
+            } else {
+                let path = relative.to_string_lossy();
+                for pattern in &self.members {
+                    if let Ok(glob) = Glob::new(pattern) {
+                        if glob.compile_matcher().is_match(&*path) {
+                            return true;
+                        }
+                    }
+                }
+                false
+            }
+        } else {
+            false
+        }
+    }
+
+    /// Iterates through all projects in the workspace.
+    pub fn iter_projects<'a>(
+        self: &'a Arc<Self>,
+    ) -> impl Iterator<Item = Result<PyProject, Error>> + 'a {
+        walkdir::WalkDir::new(&self.root)
+            .into_iter()
+            .filter_map(move |entry| match entry {
+                Ok(entry) => {
+                    if entry.file_type().is_file()
+                        && entry.file_name() == OsStr::new("pyproject.toml")
+                    {
+                        let project =
+                            match PyProject::load_with_workspace(entry.path(), self.clone()) {
+                                Ok(Some(project)) => project,
+                                Ok(None) => return None,
+                                Err(err) => return Some(Err(err)),
+                            };
+                        if self.is_member(entry.path().parent().unwrap()) {
+                            return Some(Ok(project));
+                        }
+                    }
+                    None
+                }
+                Err(err) => Some(Err(err.into())),
+            })
+    }
+
+    fn try_load_from_toml(doc: &Document, path: &Path) -> Option<Workspace> {
+        doc.get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("workspace"))
+            .and_then(|x| x.as_table_like())
+            .map(|workspace| Workspace::from_workspace_section_and_path(workspace, path))
+    }
+
+    /// Returns the virtualenv path of the workspace.
+    pub fn venv_path(&self) -> Cow<'_, Path> {
+        Cow::Owned(venv_path_for(&self.root))
+    }
+}
+
+/// Helps working with pyproject.toml files
+#[derive(Debug)]
+pub struct PyProject {
+    root: PathBuf,
+    workspace: Option<Arc<Workspace>>,
+    doc: Document,
+}
+
+impl PyProject {
+    /// Discovers and loads a pyproject toml.
+    pub fn discover() -> Result<PyProject, Error> {
+        let pyproject_toml = match find_project_root() {
+            Some(root) => root.join("pyproject.toml"),
+            None => bail!("did not find pyproject.toml"),
+        };
+        Self::load(&pyproject_toml)
+    }
+
+    /// Loads a pyproject toml.
+    pub fn load(filename: &Path) -> Result<PyProject, Error> {
+        let root = filename.parent().unwrap_or(Path::new("."));
+        let doc = fs::read_to_string(filename)?
+            .parse::<Document>()
+            .with_context(|| {
+                format!(
+                    "failed to parse pyproject.toml from {}",
+                    &filename.display()
+                )
+            })?;
+        let mut workspace = Workspace::try_load_from_toml(&doc, root).map(Arc::new);
+
+        if workspace.is_none() && !is_auto_discovery_disabled() && !has_discovery_stop(&doc) {
+            workspace = Workspace::discover_from_path(root).map(Arc::new);
+        }
+
+        if let Some(ref workspace) = workspace {
+            if !workspace.is_member(root) {
+                bail!(
+                    "project {} is not part of pyproject workspace {}",
+                    filename.display(),
+                    workspace.path().display()
+                );
+            }
+        }
+
+        Ok(PyProject {
+            root: root.to_owned(),
+            workspace,
+            doc,
+        })
+    }
+
+    /// Loads a pyproject toml with a given workspace.
+    ///
+    /// If the project is not a member of the workspace, `None` is returned.
+    pub fn load_with_workspace(
+        filename: &Path,
+        workspace: Arc<Workspace>,
+    ) -> Result<Option<PyProject>, Error> {
+        let root = filename.parent().unwrap_or(Path::new("."));
+        let doc = fs::read_to_string(filename)?
+            .parse::<Document>()
+            .with_context(|| {
+                format!(
+                    "failed to parse pyproject.toml from {} in context of workspace {}",
+                    &filename.display(),
+                    workspace.path().display(),
+                )
+            })?;
+
+        if !workspace.is_member(root) {
+            return Ok(None);
+        }
+
+        Ok(Some(PyProject {
+            root: root.to_owned(),
+            workspace: Some(workspace),
+            doc,
+        }))
+    }
+
+    /// Returns a reference to the workspace.
+    ///
+    /// If something isn't a workspace, it's not returned.
+    pub fn workspace(&self) -> Option<&Arc<Workspace>> {
+        self.workspace.as_ref()
+    }
+
+    /// Returns the project root path
+    pub fn root_path(&self) -> Cow<'_, Path> {
+        Cow::Borrowed(&self.root)
+    }
+
+    /// Returns the project workspace path.
+    pub fn workspace_path(&self) -> Cow<'_, Path> {
+        self.workspace
+            .as_ref()
+            .map(|x| x.path())
+            .unwrap_or_else(|| self.root_path())
+    }
+
+    /// Returns the path to the toml file.
+    pub fn toml_path(&self) -> Cow<'_, Path> {
+        Cow::Owned(self.root.join("pyproject.toml"))
+    }
+
+    /// Returns the location of the virtualenv.
+    pub fn venv_path(&self) -> Cow<'_, Path> {
+        match self.workspace() {
+            Some(ws) => ws.venv_path(),
+            None => venv_path_for(&self.root).into(),
+        }
+    }
+
+    /// Returns the folder the lockfiles are read from and written to.
+    ///
+    /// This is the workspace path, unless it is read-only and
+    /// `behavior.read-only-fallback` redirects them.
+    pub fn lockfile_dir(&self) -> Cow<'_, Path> {
+        match read_only_fallback_dir(&self.workspace_path()) {
+            Some(dir) => dir.into(),
+            None => self.workspace_path(),
+        }
+    }
+
+    /// Returns the virtualenv bin path of the virtualenv.
+    pub fn venv_bin_path(&self) -> Cow<'_, Path> {
+        Cow::Owned(self.venv_path().join("bin"))
+    }
+
+    /// Returns the project name.
+    pub fn name(&self) -> Option<&str> {
+        self.doc
+            .get("project")
+            .and_then(|x| x.get("name"))
+            .and_then(|x| x.as_str())
+    }
+
+    /// Returns the normalized name.
+    pub fn normalized_name(&self) -> Option<String> {
+        self.name().map(normalize_package_name)
+    }
+
+    /// Returns the `requires-python` specifier of the project.
+    pub fn requires_python(&self) -> Option<&str> {
+        self.doc
+            .get("project")
+            .and_then(|x| x.get("requires-python"))
+            .and_then(|x| x.as_str())
+    }
+
+    /// Returns the build backend declared in `[build-system]`.
+    pub fn build_backend(&self) -> Option<&str> {
+        self.doc
+            .get("build-system")
+            .and_then(|x| x.get("build-backend"))
+            .and_then(|x| x.as_str())
+    }
+
+    /// Is this a maturin project with a native extension module?
+    pub fn is_maturin(&self) -> bool {
+        self.build_backend() == Some("maturin")
+    }
+
+    /// Returns the toolchain requested in `[tool.rye]`.
+    ///
+    /// This can be a single version or a list of versions in order of
+    /// preference.
+    pub fn toolchain_requests(&self) -> Option<Vec<PythonVersionRequest>> {
+        let value = self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("toolchain"))?;
+        let rv: Vec<_> = if let Some(ver) = value.as_str() {
+            ver.parse().ok().into_iter().collect()
+        } else {
+            value
+                .as_array()?
+                .iter()
+                .filter_map(|x| x.as_str()?.parse().ok())
+                .collect()
+        };
+        if rv.is_empty() {
+            None
+        } else {
+            Some(rv)
+        }
+    }
+
+    /// Sets the toolchain in `[tool.rye]`.
+    pub fn set_toolchain(&mut self, version: &str) {
+        self.rye_section_mut()["toolchain"] = toml_edit::value(version);
+    }
+
+    /// Returns the `[tool.rye]` section, creating it if necessary.
+    fn rye_section_mut(&mut self) -> &mut Item {
+        let tool = &mut self.doc["tool"];
+        if tool.is_none() {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            *tool = Item::Table(table);
+        }
+        let rye = &mut tool["rye"];
+        if rye.is_none() {
+            *rye = Item::Table(Table::new());
+        }
+        rye
+    }
+
+    /// Returns the console scripts declared in `[project.scripts]`.
+    ///
+    /// Each entry is the script name and its `module:function` reference.
+    pub fn console_scripts(&self) -> Vec<(String, String)> {
+        self.doc
+            .get("project")
+            .and_then(|x| x.get("scripts"))
+            .and_then(|x| x.as_table_like())
+            .map(|tbl| {
+                tbl.iter()
+                    .filter_map(|(name, item)| Some((name.to_string(), item.as_str()?.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Looks up a script
+    pub fn get_script_cmd(&self, key: &str) -> Option<Script> {
+        let external = self.venv_bin_path().join(key);
+        if external.metadata().is_ok_and(|x| x.mode() & 0o001 != 0) {
+            return Some(Script::External(external));
+        }
+        self.get_configured_script(key)
+    }
+
+    /// Looks up a script defined in the `[tool.rye.scripts]` section.
+    pub fn get_configured_script(&self, key: &str) -> Option<Script> {
+        let mut value = self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("scripts"))
+            .and_then(|x| x.get(key))?;
+        // scripts with settings are tables with the command in `cmd`
+        if value.is_table_like() {
+            if let Some(chain) = value.get("chain").and_then(|x| x.as_array()) {
+                return Some(Script::Chain(
+                    chain
+                        .iter()
+                        .filter_map(|x| x.as_str())
+                        .map(|x| x.to_string())
+                        .collect(),
+                ));
+            }
+            if let Some(target) = value.get("call").and_then(|x| x.as_str()) {
+                return Some(Script::Call(target.to_string()));
+            }
+            value = value.get("cmd")?;
+        }
+        if let Some(cmd) = value.as_str() {
+            shlex::split(cmd).map(Script::Cmd)
+        } else {
+            value.as_array().map(|cmd| {
+                Script::Cmd(
+                    cmd.iter()
+                        .map(|x| {
+                            x.as_str()
+                                .map(|x| x.to_string())
+                                .unwrap_or_else(|| x.to_string())
+                        })
+                        .collect(),
+                )
+            })
+        }
+    }
+
+    /// Returns the environment configured for a script.
+    ///
+    /// The variables from `env-file` are returned first and the ones from
+    /// `env` after them so that the latter win when applied in order.  Values
+    /// from the env file do not override variables that are already set in
+    /// the environment.  Without `load_env_file` the env file is ignored.
+    pub fn get_script_env(
+        &self,
+        key: &str,
+        load_env_file: bool,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let script = match self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("scripts"))
+            .and_then(|x| x.get(key))
+            .and_then(|x| x.as_table_like())
+        {
+            Some(script) => script,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut rv = Vec::new();
+        if let Some(env_file) = script.get("env-file").and_then(|x| x.as_str()) {
+            if load_env_file {
+                let path = self.root_path().join(env_file);
+                let contents = fs::read_to_string(&path)
+                    .with_context(|| format!("unable to read env file {}", path.display()))?;
+                rv.extend(
+                    parse_env_file(&contents)
+                        .with_context(|| format!("invalid env file {}", path.display()))?
+                        .into_iter()
+                        .filter(|(key, _)| env::var_os(key).is_none()),
+                );
+            }
+        }
+        if let Some(env) = script.get("env").and_then(|x| x.as_table_like()) {
+            for (key, value) in env.iter() {
+                let value = value
+                    .as_str()
+                    .map(|x| x.to_string())
+                    .or_else(|| value.as_value().map(|x| x.to_string().trim().to_string()))
+                    .unwrap_or_default();
+                rv.push((key.to_string(), value));
+            }
+        }
+        Ok(rv)
+    }
+
+    /// Returns a list of known scripts.
+    pub fn list_scripts(&self) -> HashSet<String> {
+        let mut rv = match self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("scripts"))
+            .and_then(|x| x.as_table_like())
+        {
+            Some(tbl) => tbl.iter().map(|x| x.0.to_string()).collect(),
+            None => HashSet::new(),
+        };
+        for entry in fs::read_dir(self.venv_bin_path())
+            .ok()
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            if entry.metadata().is_ok_and(|x| (x.mode() & 0o001) != 0) {
+                rv.insert(
+                    entry
+                        .path()
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string(),
+                );
+            }
+        }
+        rv
+    }
+
+    /// Adds a dependency.
+    pub fn add_dependency(&mut self, req: &Requirement, kind: DependencyKind) -> Result<(), Error> {
+        let dependencies = match kind {
+            DependencyKind::Normal => &mut self.doc["project"]["dependencies"],
+            DependencyKind::Dev => &mut self.doc["tool"]["rye"]["dev-dependencies"],
+            DependencyKind::Optional(ref section) => {
+                // add this as a proper non-inline table if it's missing
+                let table = &mut self.doc["project"]["optional-dependencies"];
+                if table.is_none() {
+                    *table = Item::Table(Table::new());
+                }
+                &mut table[section as &str]
+            }
+            DependencyKind::BuildSystem => &mut self.doc["build-system"]["requires"],
+        };
+        if dependencies.is_none() {
+            *dependencies = Item::Value(Value::Array(Array::new()));
+        }
+        set_dependency(
+            dependencies
+                .as_array_mut()
+                .ok_or_else(|| anyhow!("dependencies in pyproject.toml are malformed"))?,
+            req,
+        );
+        Ok(())
+    }
+
+    /// Removes a dependency
+    pub fn remove_dependency(
+        &mut self,
+        req: &Requirement,
+        kind: DependencyKind,
+    ) -> Result<Option<Requirement>, Error> {
+        let dependencies = match kind {
+            DependencyKind::Normal => &mut self.doc["project"]["dependencies"],
+            DependencyKind::Dev => &mut self.doc["tool"]["rye"]["dev-dependencies"],
+            DependencyKind::Optional(ref section) => {
+                &mut self.doc["project"]["optional-dependencies"][section as &str]
+            }
+            DependencyKind::BuildSystem => &mut self.doc["build-system"]["requires"],
+        };
+        if !dependencies.is_none() {
+            Ok(remove_dependency(
+                dependencies
+                    .as_array_mut()
+                    .ok_or_else(|| anyhow!("dependencies in pyproject.toml are malformed"))?,
+                req,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Replaces exactly the given requirement with a new one.
+    ///
+    /// Unlike [`add_dependency`](Self::add_dependency) this leaves other
+    /// entries for the same package (eg: ones split by markers) alone.
+    /// Returns `false` if the requirement was not found.
+    pub fn replace_dependency(
+        &mut self,
+        old: &Requirement,
+        new: &Requirement,
+        kind: DependencyKind,
+    ) -> Result<bool, Error> {
+        let dependencies = match kind {
+            DependencyKind::Normal => &mut self.doc["project"]["dependencies"],
+            DependencyKind::Dev => &mut self.doc["tool"]["rye"]["dev-dependencies"],
+            DependencyKind::Optional(ref section) => {
+                &mut self.doc["project"]["optional-dependencies"][section as &str]
+            }
+            DependencyKind::BuildSystem => &mut self.doc["build-system"]["requires"],
+        };
+        if dependencies.is_none() {
+            return Ok(false);
+        }
+        Ok(replace_dependency(
+            dependencies
+                .as_array_mut()
+                .ok_or_else(|| anyhow!("dependencies in pyproject.toml are malformed"))?,
+            old,
+            new,
+        ))
+    }
+
+    /// Iterates over all dependencies.
+    pub fn iter_dependencies(
+        &self,
+        kind: DependencyKind,
+    ) -> impl Iterator<Item = Requirement> + '_ {
+        let sec = match kind {
+            DependencyKind::Normal => self.doc.get("project").and_then(|x| x.get("dependencies")),
+            DependencyKind::Dev => self
+                .doc
+                .get("tool")
+                .and_then(|x| x.get("rye"))
+                .and_then(|x| x.get("dev-dependencies")),
+            DependencyKind::Optional(ref section) => self
+                .doc
+                .get("project")
+                .and_then(|x| x.get("optional-dependencies"))
+                .and_then(|x| x.get(section as &str)),
+            DependencyKind::BuildSystem => {
+                self.doc.get("build-system").and_then(|x| x.get("requires"))
+            }
+        };
+        sec.and_then(|x| x.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|x| x.as_str())
+            .filter_map(|x| Requirement::from_str(x).ok())
+    }
+
+    /// The dependencies which `rye check` does not report as unused.
+    pub fn check_allow_unused(&self) -> Vec<String> {
+        self.doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("check"))
+            .and_then(|x| x.get("allow-unused"))
+            .and_then(|x| x.as_array())
+            .map(|x| {
+                x.iter()
+                    .filter_map(|x| x.as_str())
+                    .map(|x| x.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The requirements from `tool.rye.lock.override-dependencies` which are
+    /// forced onto every package during resolution.
+    pub fn lock_override_dependencies(&self) -> Result<Vec<Requirement>, Error> {
+        self.doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("lock"))
+            .and_then(|x| x.get("override-dependencies"))
+            .and_then(|x| x.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|x| x.as_str())
+            .map(|x| {
+                Requirement::from_str(x)
+                    .with_context(|| format!("invalid override dependency '{}'", x))
+            })
+            .collect()
+    }
+
+    /// Which unsafe packages to pin according to `tool.rye.lock.allow-unsafe`.
+    ///
+    /// This is `true` (the default), `false` or a list of the packages.
+    pub fn lock_allow_unsafe(&self) -> Result<UnsafePackages, Error> {
+        let value = match self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("lock"))
+            .and_then(|x| x.get("allow-unsafe"))
+        {
+            Some(value) => value,
+            None => return Ok(UnsafePackages::default()),
+        };
+        if let Some(allow) = value.as_bool() {
+            return Ok(if allow {
+                UnsafePackages::All
+            } else {
+                UnsafePackages::None
+            });
+        }
+        value
+            .as_array()
+            .and_then(|x| {
+                x.iter()
+                    .map(|x| x.as_str().map(normalize_package_name))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .map(UnsafePackages::Only)
+            .ok_or_else(|| {
+                anyhow!("tool.rye.lock.allow-unsafe must be a boolean or a list of packages")
+            })
+    }
+
+    /// The notes on why dependencies are needed from
+    /// `tool.rye.dependency-notes`, keyed by normalized package name.
+    pub fn dependency_notes(&self) -> Result<BTreeMap<String, String>, Error> {
+        let notes = match self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("dependency-notes"))
+        {
+            Some(notes) => notes
+                .as_table_like()
+                .ok_or_else(|| anyhow!("tool.rye.dependency-notes must be a table"))?,
+            None => return Ok(BTreeMap::new()),
+        };
+        notes
+            .iter()
+            .map(|(name, note)| {
+                let note = note.as_str().ok_or_else(|| {
+                    anyhow!(
+                        "the note on {} in tool.rye.dependency-notes must be a string",
+                        name
+                    )
+                })?;
+                Ok((normalize_package_name(name), note.trim().to_string()))
+            })
+            .collect()
+    }
+
+    /// The dependency notes of all projects in the workspace, or of just
+    /// this project outside of one.
+    pub fn workspace_dependency_notes(&self) -> Result<BTreeMap<String, String>, Error> {
+        match self.workspace() {
+            Some(workspace) => {
+                let members = workspace.iter_projects().collect::<Result<Vec<_>, _>>()?;
+                merge_dependency_notes(&members)
+            }
+            None => merge_dependency_notes([self]),
+        }
+    }
+
+    /// The dependencies which are replaced by local checkouts or git forks
+    /// through `tool.rye.lock.patches`.
+    pub fn lock_patches(&self) -> Result<Vec<Patch>, Error> {
+        let patches = match self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("lock"))
+            .and_then(|x| x.get("patches"))
+            .and_then(|x| x.as_table_like())
+        {
+            Some(patches) => patches,
+            None => return Ok(Vec::new()),
+        };
+        let mut rv = Vec::new();
+        for (name, value) in patches.iter() {
+            let source = match (
+                value.get("path").and_then(|x| x.as_str()),
+                value.get("git").and_then(|x| x.as_str()),
+            ) {
+                (Some(path), None) => PatchSource::Path(self.root_path().join(path)),
+                (None, Some(url)) => PatchSource::Git {
+                    url: url.to_string(),
+                    rev: value
+                        .get("rev")
+                        .and_then(|x| x.as_str())
+                        .map(|x| x.to_string()),
+                },
+                _ => bail!("patch for '{}' needs either a path or a git source", name),
+            };
+            rv.push(Patch {
+                name: name.to_string(),
+                source,
+            });
+        }
+        Ok(rv)
+    }
+
+    /// The conda environment `rye sync` installs into instead of creating a
+    /// virtualenv, from `tool.rye.conda-env` (`true` for the active one).
+    pub fn conda_env(&self) -> Option<CondaEnvRequest> {
+        let value = self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("conda-env"))?;
+        match value.as_bool() {
+            Some(true) => Some(CondaEnvRequest::Active),
+            Some(false) => None,
+            None => value.as_str().map(|x| {
+                if x.contains('/') {
+                    CondaEnvRequest::Named(self.root_path().join(x).display().to_string())
+                } else {
+                    CondaEnvRequest::Named(x.to_string())
+                }
+            }),
+        }
+    }
+
+    /// The dependency policy from `tool.rye.policy`.
+    pub fn dependency_policy(&self) -> DependencyPolicy {
+        let policy = match self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("policy"))
+        {
+            Some(policy) => policy,
+            None => return DependencyPolicy::default(),
+        };
+        let flag = |key| policy.get(key).and_then(|x| x.as_bool()).unwrap_or(false);
+        DependencyPolicy {
+            require_upper_bound: flag("require-upper-bound"),
+            forbid_wildcards: flag("forbid-wildcards"),
+            enforce_on_add: flag("enforce-on-add"),
+            allow: policy
+                .get("allow")
+                .and_then(|x| x.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|x| x.as_str())
+                .map(normalize_package_name)
+                .collect(),
+        }
+    }
+
+    /// The global tools the project needs from `tool.rye.tools`.
+    ///
+    /// Versions are specifiers, a plain version like `"3.6"` means that
+    /// release series (`==3.6.*`) and `"*"` any version.
+    pub fn tool_requirements(&self) -> Result<Vec<Requirement>, Error> {
+        let tools = match self
+            .doc
+            .get("tool")
+            .and_then(|x| x.get("rye"))
+            .and_then(|x| x.get("tools"))
+            .and_then(|x| x.as_table_like())
+        {
+            Some(tools) => tools,
+            None => return Ok(Vec::new()),
+        };
+        let mut rv = Vec::new();
+        for (name, version) in tools.iter() {
+            let version = version
+                .as_str()
+                .ok_or_else(|| anyhow!("version of tool {} must be a string", name))?
+                .trim();
+            let spec = if version == "*" || version.is_empty() {
+                String::new()
+            } else if version.starts_with(|c: char| c.is_ascii_digit()) {
+                if version.split('.').count() < 3 && !version.ends_with(".*") {
+                    format!("=={}.*", version)
+                } else {
+                    format!("=={}", version)
+                }
+            } else {
+                version.to_string()
+            };
+            rv.push(
+                Requirement::from_str(&format!("{}{}", name, spec))
+                    .with_context(|| format!("invalid version '{}' of tool {}", version, name))?,
+            );
+        }
+        Ok(rv)
+    }
+
+    /// The parts of the `pyproject.toml` which decide what gets locked, as
+    /// text to tell whether the lockfiles have to be resolved again.
+    pub fn lock_inputs(&self) -> String {
+        lock_inputs(&self.doc)
+    }
+
+    /// The names of the optional dependency groups.
+    pub fn optional_dependency_sections(&self) -> Vec<String> {
+        self.doc
+            .get("project")
+            .and_then(|x| x.get("optional-dependencies"))
+            .and_then(|x| x.as_table_like())
+            .map(|x| x.iter().map(|x| x.0.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Save back changes
+    pub fn save(&self) -> Result<(), Error> {
+        fs::write(self.toml_path(), self.doc.to_string()).with_context(|| {
+            format!("unable to write changes to {}", self.toml_path().display())
+        })?;
+        Ok(())
+    }
+}
+
+/// The folder the state of a project in a read-only checkout is kept in
+/// with `behavior.read-only-fallback`.
+///
+/// Returns `None` if the project root is writable or the fallback is off.
+/// The folder is below the cache folder and named after the project root,
+/// so that every checkout gets its own.
+pub fn read_only_fallback_dir(root: &Path) -> Option<PathBuf> {
+    if !Config::current().is_ok_and(|x| x.read_only_fallback())
+        || access(root, AccessFlags::W_OK).is_ok()
+    {
+        return None;
+    }
+    Some(fallback_dir_in(&get_cache_dir().ok()?, root))
+}
+
+/// The folder of a checkout below the `projects` folder of the cache.
+fn fallback_dir_in(cache_dir: &Path, root: &Path) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(root.as_os_str().as_bytes());
+    let key = format!("{:x}", hasher.finalize());
+    cache_dir.join("projects").join(&key[..16])
+}
+
+/// Returns the virtualenv path of a project or workspace root.
+///
+/// A virtualenv that already exists in a read-only checkout is used as is,
+/// otherwise one in the fallback folder is.
+fn venv_path_for(root: &Path) -> PathBuf {
+    let venv = root.join(".venv");
+    match read_only_fallback_dir(root) {
+        Some(dir) if !venv.is_dir() => dir.join(".venv"),
+        _ => venv,
+    }
+}
+
+pub fn normalize_package_name(x: &str) -> String {
+    NORMALIZATION_SPLIT_RE
+        .split(x)
+        .fold(String::new(), |mut acc, item| {
+            if !acc.is_empty() {
+                acc.push('-');
+            }
+            acc.push_str(&item.to_ascii_lowercase());
+            acc
+        })
+}
+
+fn set_dependency(deps: &mut Array, req: &Requirement) {
+    let mut to_replace = None;
+    for (idx, dep) in deps.iter().enumerate() {
+        if let Some(dep) = dep.as_str() {
+            if let Ok(dep_req) = Requirement::from_str(dep) {
+                if dep_req.name.eq_ignore_ascii_case(&req.name) {
+                    to_replace = Some(idx);
+                    break;
+                }
+            }
+        }
+    }
+
+    let formatted = format_requirement(req).to_string();
+    if let Some(idx) = to_replace {
+        deps.replace(idx, formatted);
+    } else {
+        deps.push(formatted);
+    }
+}
+
+fn replace_dependency(deps: &mut Array, old: &Requirement, new: &Requirement) -> bool {
+    let idx = deps.iter().position(|dep| {
+        dep.as_str()
+            .and_then(|x| Requirement::from_str(x).ok())
+            .is_some_and(|x| &x == old)
+    });
+    match idx {
+        Some(idx) => {
+            deps.replace(idx, format_requirement(new).to_string());
+            true
+        }
+        None => false,
+    }
+}
+
+fn remove_dependency(deps: &mut Array, req: &Requirement) -> Option<Requirement> {
+    let mut to_remove = None;
+    for (idx, dep) in deps.iter().enumerate() {
+        if let Some(dep) = dep.as_str() {
+            if let Ok(dep_req) = Requirement::from_str(dep) {
+                if dep_req.name.eq_ignore_ascii_case(&req.name) {
+                    to_remove = Some(idx);
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Some(idx) = to_remove {
+        deps.remove(idx)
+            .as_str()
+            .and_then(|x| Requirement::from_str(x).ok())
+    } else {
+        None
+    }
+}
+
+/// Uses the given folder as project root instead of discovering it from the
+/// current working directory.
+pub fn set_explicit_project_root(root: &Path) -> Result<(), Error> {
+    let root = env::current_dir()?.join(root);
+    if !root.join("pyproject.toml").is_file() {
+        bail!("no pyproject.toml found in {}", root.display());
+    }
+    EXPLICIT_PROJECT_ROOT
+        .set(root)
+        .map_err(|_| anyhow!("project root was already set"))
+}
+
+/// Returns the folder in which the discovery of project files starts.
+///
+/// This is the explicitly set project root, or the current working directory.
+pub fn get_discovery_start() -> Option<PathBuf> {
+    match EXPLICIT_PROJECT_ROOT.get() {
+        Some(root) => Some(root.clone()),
+        None => env::current_dir().ok(),
+    }
+}
+
+pub fn find_project_root() -> Option<PathBuf> {
+    if let Some(root) = EXPLICIT_PROJECT_ROOT.get() {
+        return Some(root.clone());
+    }
+    let mut here = env::current_dir().ok()?;
+
+    loop {
+        let project_file = here.join("pyproject.toml");
+        if project_file.is_file() {
+            return Some(here.to_path_buf());
+        }
+
+        if is_auto_discovery_disabled() || is_discovery_boundary(&here) || !here.pop() {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Checks if `RYE_NO_AUTO_DISCOVER` disables looking at parent folders.
+fn is_auto_discovery_disabled() -> bool {
+    env::var("RYE_NO_AUTO_DISCOVER").is_ok_and(|x| !x.is_empty() && x != "0")
+}
+
+/// Discovery never leaves the git repository it started in, nor goes past
+/// a project with `discovery-stop = true`.
+pub(crate) fn is_discovery_boundary(path: &Path) -> bool {
+    path.join(".git").exists()
+        || fs::read_to_string(path.join("pyproject.toml"))
+            .ok()
+            .and_then(|x| x.parse::<Document>().ok())
+            .is_some_and(|doc| has_discovery_stop(&doc))
+}
+
+/// The lock inputs (see [`PyProject::lock_inputs`]) of a parsed
+/// `pyproject.toml`.
+pub(crate) fn lock_inputs(doc: &Document) -> String {
+    let mut rv = String::new();
+    for path in [
+        &["project", "dependencies"][..],
+        &["project", "optional-dependencies"],
+        &["project", "requires-python"],
+        &["build-system", "requires"],
+        &["tool", "rye", "dev-dependencies"],
+        &["tool", "rye", "lock"],
+        &["tool", "rye", "workspace"],
+    ] {
+        let item = path
+            .iter()
+            .try_fold(doc.as_item(), |item, key| item.get(key));
+        rv.push_str(&path.join("."));
+        rv.push('=');
+        if let Some(item) = item {
+            rv.push_str(item.to_string().trim());
+        }
+        rv.push('\n');
+    }
+    rv
+}
+
+/// Checks for `discovery-stop = true` in `[tool.rye]`, which keeps a project
+/// from being attached to a workspace in a parent folder.
+fn has_discovery_stop(doc: &Document) -> bool {
+    doc.get("tool")
+        .and_then(|x| x.get("rye"))
+        .and_then(|x| x.get("discovery-stop"))
+        .and_then(|x| x.as_bool())
+        .unwrap_or(false)
+}
+
+#[test]
+fn test_replace_dependency() {
+    let mut deps = Array::new();
+    deps.push("numpy>=1.20; python_version < '3.12'");
+    deps.push("numpy>=1.26; python_version >= '3.12'");
+    let old = Requirement::from_str("numpy>=1.26; python_version >= '3.12'").unwrap();
+    let new = Requirement::from_str("numpy>=2.0; python_version >= '3.12'").unwrap();
+
+    assert!(replace_dependency(&mut deps, &old, &new));
+    let deps = deps.iter().filter_map(|x| x.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        deps,
+        [
+            "numpy>=1.20; python_version < '3.12'",
+            "numpy>=2.0 ; python_version >= '3.12'"
+        ]
+    );
+    assert!(!replace_dependency(&mut Array::from_iter(deps), &old, &new));
+}
+
+#[test]
+fn test_discovery_boundaries() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    fs::write(
+        root.join("pyproject.toml"),
+        "[project]\nname = \"root\"\n[tool.rye.workspace]\nmembers = [\"*\"]\n",
+    )
+    .unwrap();
+    let member = |name: &str, extra: &str| {
+        let dir = root.join(name);
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("pyproject.toml");
+        fs::write(&path, format!("[project]\nname = \"{}\"\n{}", name, extra)).unwrap();
+        path
+    };
+
+    let attached = member("attached", "");
+    assert!(PyProject::load(&attached).unwrap().workspace().is_some());
+
+    let stopped = member("stopped", "[tool.rye]\ndiscovery-stop = true\n");
+    assert!(PyProject::load(&stopped).unwrap().workspace().is_none());
+    // neither is what is below it
+    let nested = member("stopped/nested", "");
+    assert!(PyProject::load(&nested).unwrap().workspace().is_none());
+    assert!(is_discovery_boundary(&root.join("stopped")));
+    assert!(!is_discovery_boundary(&root.join("attached")));
+
+    // a nested git checkout is a project of its own
+    let checkout = member("checkout", "");
+    fs::create_dir(root.join("checkout/.git")).unwrap();
+    assert!(PyProject::load(&checkout).unwrap().workspace().is_none());
+}
+
+#[test]
+fn test_build_system_dependencies() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("pyproject.toml");
+    fs::write(
+        &path,
+        "[project]\nname = \"app\"\ndependencies = []\n\n\
+         [build-system]\nrequires = [\"hatchling\"]\nbuild-backend = \"hatchling.build\"\n",
+    )
+    .unwrap();
+    let mut project = PyProject::load(&path).unwrap();
+    let req = Requirement::from_str("hatch-vcs>=0.3").unwrap();
+    project
+        .add_dependency(&req, DependencyKind::BuildSystem)
+        .unwrap();
+    let names = |project: &PyProject, kind| {
+        project
+            .iter_dependencies(kind)
+            .map(|x| x.name)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(&project, DependencyKind::BuildSystem),
+        ["hatchling", "hatch-vcs"]
+    );
+    assert!(names(&project, DependencyKind::Normal).is_empty());
+
+    let removed = project
+        .remove_dependency(&"hatchling".parse().unwrap(), DependencyKind::BuildSystem)
+        .unwrap();
+    assert_eq!(removed.map(|x| x.name).as_deref(), Some("hatchling"));
+    assert_eq!(names(&project, DependencyKind::BuildSystem), ["hatch-vcs"]);
+}
+
+#[test]
+fn test_tool_requirements() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("pyproject.toml");
+    fs::write(
+        &path,
+        "[project]\nname = \"app\"\n\n\
+         [tool.rye.tools]\npre-commit = \"3.6\"\nblack = \"23.7.0\"\n\
+         ruff = \">=0.1.5\"\nhttpie = \"*\"\nmypy = \"1.*\"\n",
+    )
+    .unwrap();
+    let project = PyProject::load(&path).unwrap();
+    let tools = project
+        .tool_requirements()
+        .unwrap()
+        .iter()
+        .map(|x| crate::utils::format_requirement(x).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tools,
+        [
+            "pre-commit==3.6.*",
+            "black==23.7.0",
+            "ruff>=0.1.5",
+            "httpie",
+            "mypy==1.*"
+        ]
+    );
+
+    fs::write(&path, "[tool.rye.tools]\nruff = 1\n").unwrap();
+    assert!(PyProject::load(&path).unwrap().tool_requirements().is_err());
+    fs::write(&path, "[tool.rye.tools]\nruff = \"not a version\"\n").unwrap();
+    assert!(PyProject::load(&path).unwrap().tool_requirements().is_err());
+}
+
+#[test]
+fn test_read_only_fallback_dir() {
+    let tmp = tempfile::tempdir().unwrap();
+    // writable checkouts keep their state
+    assert_eq!(read_only_fallback_dir(tmp.path()), None);
+
+    let cache = Path::new("/cache");
+    let dir = fallback_dir_in(cache, Path::new("/mnt/ci/project"));
+    assert!(dir.starts_with("/cache/projects"));
+    assert_eq!(dir, fallback_dir_in(cache, Path::new("/mnt/ci/project")));
+    assert_ne!(dir, fallback_dir_in(cache, Path::new("/mnt/ci/other")));
+}
+
+#[test]
+fn test_call_scripts() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("pyproject.toml");
+    fs::write(
+        &path,
+        "[project]\nname = \"app\"\n\n\
+         [tool.rye.scripts]\n\
+         serve = { call = \"app.server:main\" }\n\
+         lint = { cmd = \"ruff check\" }\n\
+         broken = { call = 42 }\n",
+    )
+    .unwrap();
+    let project = PyProject::load(&path).unwrap();
+    let script = project.get_configured_script("serve").unwrap();
+    assert!(matches!(script, Script::Call(ref target) if target == "app.server:main"));
+    assert_eq!(script.to_string(), "call: app.server:main");
+    assert!(matches!(
+        project.get_configured_script("lint"),
+        Some(Script::Cmd(ref args)) if args == &["ruff", "check"]
+    ));
+    assert!(project.get_configured_script("broken").is_none());
+}
@@ -0,0 +1,227 @@
+//! Adding the shims folder to the `PATH` in shell profiles.
+//!
+//! Rye edits a profile file only within a block delimited by marker
+//! comments, so running the setup again updates the block in place and
+//! removing it leaves the rest of the file alone.
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Error};
+
+use crate::utils::write_atomic;
+
+const BLOCK_START: &str = "# >>> rye >>>";
+const BLOCK_END: &str = "# <<< rye <<<";
+
+/// The shells rye knows how to set up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    /// Any other POSIX shell, set up through `~/.profile`.
+    Sh,
+}
+
+impl Shell {
+    /// All supported shells.
+    pub fn all() -> [Shell; 5] {
+        [
+            Shell::Bash,
+            Shell::Zsh,
+            Shell::Fish,
+            Shell::PowerShell,
+            Shell::Sh,
+        ]
+    }
+
+    /// Detects the shell of the user from the environment.
+    pub fn detect() -> Shell {
+        // powershell does not change `SHELL` but always exports this
+        if env::var_os("PSModulePath").is_some() {
+            return Shell::PowerShell;
+        }
+        let shell = env::var("SHELL").unwrap_or_default();
+        match shell.rsplit('/').next().unwrap_or("") {
+            "bash" => Shell::Bash,
+            "zsh" => Shell::Zsh,
+            "fish" => Shell::Fish,
+            "pwsh" | "powershell" => Shell::PowerShell,
+            _ => Shell::Sh,
+        }
+    }
+
+    /// The name of the shell.
+    pub fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::PowerShell => "powershell",
+            Shell::Sh => "sh",
+        }
+    }
+
+    /// The profile file the shell reads on startup.
+    pub fn profile_path(self) -> Result<PathBuf, Error> {
+        let home = simple_home_dir::home_dir().ok_or_else(|| anyhow!("cannot find home folder"))?;
+        Ok(match self {
+            Shell::Bash if cfg!(target_os = "macos") => home.join(".bash_profile"),
+            Shell::Bash => home.join(".bashrc"),
+            Shell::Zsh => env::var_os("ZDOTDIR")
+                .map(PathBuf::from)
+                .unwrap_or(home)
+                .join(".zshrc"),
+            Shell::Fish => home.join(".config/fish/config.fish"),
+            Shell::PowerShell => home.join(".config/powershell/Microsoft.PowerShell_profile.ps1"),
+            Shell::Sh => home.join(".profile"),
+        })
+    }
+
+    /// The line which puts a folder in front of the `PATH`.
+    pub fn path_snippet(self, dir: &Path) -> String {
+        let dir = dir.display();
+        match self {
+            Shell::Fish => format!("fish_add_path --prepend --path \"{}\"", dir),
+            Shell::PowerShell => format!(
+                "$env:PATH = \"{}\" + [IO.Path]::PathSeparator + $env:PATH",
+                dir
+            ),
+            _ => format!("export PATH=\"{}:$PATH\"", dir),
+        }
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Shell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pwsh" => Ok(Shell::PowerShell),
+            _ => Shell::all()
+                .into_iter()
+                .find(|x| x.name() == s)
+                .ok_or_else(|| anyhow!("unsupported shell '{}'", s)),
+        }
+    }
+}
+
+/// Is the folder on the `PATH` of the current process?
+pub fn is_on_path(dir: &Path) -> bool {
+    env::var_os("PATH").is_some_and(|path| env::split_paths(&path).any(|x| x == dir))
+}
+
+/// Replaces the rye block in the contents of a profile file, or removes it
+/// if `block` is `None`.
+fn update_profile(contents: &str, block: Option<&str>) -> Result<String, Error> {
+    let mut rv = String::new();
+    let mut lines = contents.lines();
+    let mut replaced = false;
+    while let Some(line) = lines.next() {
+        if line.trim_end() != BLOCK_START {
+            rv.push_str(line);
+            rv.push('\n');
+            continue;
+        }
+        if !lines.any(|x| x.trim_end() == BLOCK_END) {
+            bail!("unterminated rye block (missing '{}')", BLOCK_END);
+        }
+        if let Some(block) = block {
+            if !replaced {
+                rv.push_str(block);
+                replaced = true;
+            }
+        }
+    }
+    if let Some(block) = block {
+        if !replaced {
+            if !rv.is_empty() && !rv.ends_with("\n\n") {
+                rv.push('\n');
+            }
+            rv.push_str(block);
+        }
+    }
+    Ok(rv)
+}
+
+/// Adds the folder to the `PATH` in the profile of the shell.
+///
+/// Returns the profile file if it was changed, `None` if it was already set
+/// up.
+pub fn add_to_path(shell: Shell, dir: &Path) -> Result<Option<PathBuf>, Error> {
+    let profile = shell.profile_path()?;
+    let contents = match fs::read_to_string(&profile) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("unable to read {}", profile.display()))
+        }
+    };
+    let block = format!(
+        "{}\n{}\n{}\n",
+        BLOCK_START,
+        shell.path_snippet(dir),
+        BLOCK_END
+    );
+    let updated = update_profile(&contents, Some(&block))
+        .with_context(|| format!("unable to update {}", profile.display()))?;
+    if updated == contents {
+        return Ok(None);
+    }
+    if let Some(parent) = profile.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomic(&profile, updated.as_bytes())
+        .with_context(|| format!("unable to write {}", profile.display()))?;
+    Ok(Some(profile))
+}
+
+/// Removes the rye block from the profile of the shell.
+///
+/// Returns the profile file if it was changed.
+pub fn remove_from_path(shell: Shell) -> Result<Option<PathBuf>, Error> {
+    let profile = shell.profile_path()?;
+    let contents = match fs::read_to_string(&profile) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| format!("unable to read {}", profile.display()))
+        }
+    };
+    if !contents.lines().any(|x| x.trim_end() == BLOCK_START) {
+        return Ok(None);
+    }
+    let updated = update_profile(&contents, None)
+        .with_context(|| format!("unable to update {}", profile.display()))?;
+    // drop the blank line that separated the block from the rest
+    let mut updated = updated.trim_end().to_string();
+    if !updated.is_empty() {
+        updated.push('\n');
+    }
+    write_atomic(&profile, updated.as_bytes())
+        .with_context(|| format!("unable to write {}", profile.display()))?;
+    Ok(Some(profile))
+}
+
+#[test]
+fn test_update_profile() {
+    let block = format!("{}\nexport PATH=\"x:$PATH\"\n{}\n", BLOCK_START, BLOCK_END);
+    let added = update_profile("alias ll='ls -l'\n", Some(&block)).unwrap();
+    assert_eq!(added, format!("alias ll='ls -l'\n\n{}", block));
+    assert_eq!(update_profile(&added, Some(&block)).unwrap(), added);
+    assert_eq!(
+        update_profile(&added, None).unwrap(),
+        "alias ll='ls -l'\n\n"
+    );
+    assert!(update_profile(BLOCK_START, None).is_err());
+}
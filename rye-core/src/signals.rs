@@ -0,0 +1,173 @@
+//! Interrupt handling.
+//!
+//! By default a signal terminates rye right away.  Operations which leave
+//! staging folders or half built virtualenvs behind when cut short run
+//! within [`defer_interrupts`] instead: there a signal is forwarded to the
+//! child processes rye is waiting on and the operation fails with an error
+//! at the next opportunity, which gives the usual cleanup code a chance to
+//! run.  Once the command is done [`exit_if_interrupted`] terminates rye
+//! with the signal it received.  A second signal always terminates right
+//! away.
+use std::io;
+use std::process::{self, Child, Command, ExitStatus, Output};
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+use anyhow::{bail, Error};
+use nix::libc;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+/// How many child processes are tracked at most.
+const MAX_CHILDREN: usize = 32;
+
+static PENDING: AtomicI32 = AtomicI32::new(0);
+static DEFERRING: AtomicUsize = AtomicUsize::new(0);
+static CHILDREN: [AtomicI32; MAX_CHILDREN] = [const { AtomicI32::new(0) }; MAX_CHILDREN];
+
+extern "C" fn handle_signal(sig: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    let first = PENDING
+        .compare_exchange(0, sig, Ordering::SeqCst, Ordering::SeqCst)
+        .is_ok();
+    if !first || DEFERRING.load(Ordering::SeqCst) == 0 {
+        terminate(sig);
+        return;
+    }
+    // signals from the terminal reach the children on their own, only pass
+    // on those which were sent to rye directly.
+    if unsafe { info.as_ref() }.is_none_or(|info| is_sent_by_process(info.si_code)) {
+        for child in &CHILDREN {
+            let pid = child.load(Ordering::SeqCst);
+            if pid > 0 {
+                unsafe { libc::kill(pid, sig) };
+            }
+        }
+    }
+}
+
+/// Was the signal sent with `kill` rather than raised by the kernel (which is
+/// what the terminal does)?
+#[cfg(target_os = "linux")]
+fn is_sent_by_process(si_code: libc::c_int) -> bool {
+    // SI_USER, SI_QUEUE and SI_TKILL are at most zero, SI_KERNEL is positive
+    si_code <= 0
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_sent_by_process(si_code: libc::c_int) -> bool {
+    // SI_USER and SI_QUEUE on macOS and the BSDs
+    si_code == 0x10001 || si_code == 0x10002
+}
+
+/// Terminates the process with the default action of a signal.
+fn terminate(sig: libc::c_int) {
+    unsafe {
+        libc::signal(sig, libc::SIG_DFL);
+        libc::raise(sig);
+    }
+}
+
+/// Installs the handlers for `SIGINT`, `SIGTERM` and `SIGHUP`.
+pub fn install_handlers() -> Result<(), Error> {
+    let action = SigAction::new(
+        SigHandler::SigAction(handle_signal),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    for sig in [Signal::SIGINT, Signal::SIGTERM, Signal::SIGHUP] {
+        unsafe { sigaction(sig, &action) }?;
+    }
+    Ok(())
+}
+
+/// Postpones termination on signals while the returned guard is alive.
+#[must_use]
+pub fn defer_interrupts() -> DeferGuard {
+    DEFERRING.fetch_add(1, Ordering::SeqCst);
+    DeferGuard(())
+}
+
+/// Keeps signals from terminating rye right away, see [`defer_interrupts`].
+#[derive(Debug)]
+pub struct DeferGuard(());
+
+impl Drop for DeferGuard {
+    fn drop(&mut self) {
+        DEFERRING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The signal rye received, if any.
+pub fn interrupted() -> Option<i32> {
+    match PENDING.load(Ordering::SeqCst) {
+        0 => None,
+        sig => Some(sig),
+    }
+}
+
+/// Fails if a signal was received.
+pub fn check_interrupted() -> Result<(), Error> {
+    if interrupted().is_some() {
+        bail!("interrupted");
+    }
+    Ok(())
+}
+
+/// Terminates rye with the signal it received earlier, if any.
+pub fn exit_if_interrupted() {
+    if let Some(sig) = interrupted() {
+        terminate(sig);
+        process::exit(128 + sig);
+    }
+}
+
+/// Registers a child process so that signals are forwarded to it.
+#[must_use]
+pub fn track_child(child: &Child) -> ChildGuard {
+    let pid = child.id() as i32;
+    let slot = CHILDREN.iter().position(|x| {
+        x.compare_exchange(0, pid, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    });
+    ChildGuard { slot }
+}
+
+/// Stops forwarding signals to a child process when dropped.
+#[derive(Debug)]
+pub struct ChildGuard {
+    slot: Option<usize>,
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            CHILDREN[slot].store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Like [`Command::status`] but forwards signals to the child.
+pub fn status(cmd: &mut Command) -> io::Result<ExitStatus> {
+    let mut child = cmd.spawn()?;
+    let _guard = track_child(&child);
+    let rv = child.wait()?;
+    interrupted_error()?;
+    Ok(rv)
+}
+
+/// Like [`Command::output`] but forwards signals to the child.
+///
+/// Unlike [`Command::output`] the output streams are only captured if the
+/// command is configured to pipe them.
+pub fn output(cmd: &mut Command) -> io::Result<Output> {
+    let child = cmd.spawn()?;
+    let _guard = track_child(&child);
+    let rv = child.wait_with_output()?;
+    interrupted_error()?;
+    Ok(rv)
+}
+
+fn interrupted_error() -> io::Result<()> {
+    match interrupted() {
+        Some(_) => Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted")),
+        None => Ok(()),
+    }
+}
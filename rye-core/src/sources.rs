@@ -1,9 +1,11 @@
 use std::borrow::Cow;
 use std::env::consts::{ARCH, OS};
 use std::fmt;
+use std::fs;
 use std::str::FromStr;
 
 use anyhow::{anyhow, Error};
+use once_cell::sync::Lazy;
 use serde::{de, Deserialize, Serialize};
 
 mod indygreg_python {
@@ -13,6 +15,24 @@ mod indygreg_python {
 
 const DEFAULT_KIND: &str = "cpython";
 
+/// Build variants of CPython that can be requested as `cpython-<variant>@<version>`.
+///
+/// Only variants the download table has builds of are listed, the static
+/// builds are published for Windows.
+const CPYTHON_VARIANTS: &[&str] = &["static"];
+
+/// Normalizes the kind of a request and validates CPython build variants.
+fn normalize_kind(kind: &str) -> Result<Cow<'static, str>, Error> {
+    match kind.strip_prefix("cpython-") {
+        Some(variant) if !CPYTHON_VARIANTS.contains(&variant) => Err(anyhow!(
+            "unknown build variant '{}' (known variants: {})",
+            variant,
+            CPYTHON_VARIANTS.join(", ")
+        )),
+        _ => Ok(Cow::Owned(kind.to_string())),
+    }
+}
+
 /// Internal descriptor for a python version.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone)]
 pub struct PythonVersion {
@@ -25,7 +45,7 @@ pub struct PythonVersion {
 
 impl PythonVersion {
     /// Returns the latest version for this OS.
-    pub fn latest_cpython() -> PythonVersion {
+    pub fn latest_cpython() -> Result<PythonVersion, Error> {
         get_download_url(
             &PythonVersionRequest {
                 kind: None,
@@ -34,11 +54,20 @@ impl PythonVersion {
                 patch: None,
                 suffix: None,
             },
-            OS,
+            current_platform(),
             ARCH,
         )
-        .expect("unsupported platform")
-        .0
+        .map(|x| x.0)
+        .ok_or_else(|| no_builds_error(current_platform(), ARCH))
+    }
+
+    /// Returns the build variant (eg: `static`) of a CPython build.
+    ///
+    /// Default builds have no variant.
+    pub fn variant(&self) -> Option<&str> {
+        self.kind
+            .strip_prefix("cpython-")
+            .filter(|x| CPYTHON_VARIANTS.contains(x))
     }
 }
 
@@ -140,6 +169,8 @@ impl FromStr for PythonVersionRequest {
             Some((kind, version)) => (Some(kind), version),
             None => (None, s),
         };
+        // `latest` floats with the newest release
+        let version = if version == "latest" { "3" } else { version };
         let mut iter = version.split('.');
         let major = iter
             .next()
@@ -150,7 +181,7 @@ impl FromStr for PythonVersionRequest {
         let suffix = iter.next().map(|x| Cow::Owned(x.to_string()));
 
         Ok(PythonVersionRequest {
-            kind: kind.map(|x| x.to_string().into()),
+            kind: kind.map(normalize_kind).transpose()?,
             major,
             minor,
             patch,
@@ -175,7 +206,8 @@ impl fmt::Display for PythonVersionRequest {
     }
 }
 
-fn matches_version(req: &PythonVersionRequest, v: &PythonVersion) -> bool {
+/// Checks if a version satisfies a version request.
+pub fn matches_version(req: &PythonVersionRequest, v: &PythonVersion) -> bool {
     if req.kind.as_deref().unwrap_or(DEFAULT_KIND) != v.kind {
         return false;
     }
@@ -200,6 +232,44 @@ fn matches_version(req: &PythonVersionRequest, v: &PythonVersion) -> bool {
     true
 }
 
+static PLATFORM: Lazy<&'static str> = Lazy::new(|| {
+    if OS == "linux" && (cfg!(target_env = "musl") || is_musl_system(&loader_names())) {
+        "linux-musl"
+    } else {
+        OS
+    }
+});
+
+/// Returns the platform that toolchains are downloaded for.
+///
+/// This is the operating system, except for Linux systems that use musl libc
+/// (eg: Alpine), which are reported as `linux-musl` as they cannot run the
+/// regular glibc builds.
+pub fn current_platform() -> &'static str {
+    &PLATFORM
+}
+
+/// The file names in the folders the dynamic loaders are installed to.
+fn loader_names() -> Vec<String> {
+    ["/lib", "/lib64"]
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|x| x.ok())
+        .map(|x| x.file_name().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Is this a musl system, judging by its dynamic loaders?
+///
+/// A glibc system can have the musl loader installed next to its own (eg:
+/// with the `musl` package on Debian), so it only counts if there is no
+/// glibc loader.
+fn is_musl_system(loaders: &[String]) -> bool {
+    loaders.iter().any(|x| x.starts_with("ld-musl-"))
+        && !loaders.iter().any(|x| x.starts_with("ld-linux"))
+}
+
 /// Given a version, platform and architecture returns the download URL.
 pub fn get_download_url(
     requested_version: &PythonVersionRequest,
@@ -217,6 +287,26 @@ pub fn get_download_url(
     None
 }
 
+/// Are there any builds for the platform and architecture?
+///
+/// There are no musl builds for instance, and the glibc ones cannot run on
+/// musl systems.
+pub fn has_downloads(platform: &str, arch: &str) -> bool {
+    indygreg_python::CPYTHON_VERSIONS
+        .iter()
+        .any(|(_, it_arch, it_platform, _)| *it_arch == arch && *it_platform == platform)
+}
+
+/// The error for platforms without any builds (see [`has_downloads`]).
+pub fn no_builds_error(platform: &str, arch: &str) -> Error {
+    anyhow!(
+        "no python builds are available for this platform ({} {}), install python \
+         another way and register it with `rye toolchain register`",
+        platform,
+        arch
+    )
+}
+
 /// Returns an iterator over downloadable installations.
 pub fn iter_downloadable<'s>(
     platform: &'s str,
@@ -233,6 +323,28 @@ pub fn iter_downloadable<'s>(
     )
 }
 
+#[test]
+fn test_variant_requests() {
+    assert!("cpython-fast@3.13".parse::<PythonVersionRequest>().is_err());
+    // there are no builds of these
+    assert!("cpython-freethreaded@3.13"
+        .parse::<PythonVersionRequest>()
+        .is_err());
+    assert!("cpython-debug@3.11"
+        .parse::<PythonVersionRequest>()
+        .is_err());
+
+    let ver: PythonVersion = "cpython-static@3.11.1".parse().unwrap();
+    assert_eq!(ver.variant(), Some("static"));
+    assert!(!matches_version(&"3.11".parse().unwrap(), &ver));
+    assert!(matches_version(
+        &"cpython-static@3.11".parse().unwrap(),
+        &ver
+    ));
+    let ver: PythonVersion = "custom-cpython@3.11.1".parse().unwrap();
+    assert_eq!(ver.variant(), None);
+}
+
 #[test]
 fn test_get_download_url() {
     let url = get_download_url(&"3.8.14".parse().unwrap(), "macos", "aarch64");
@@ -240,5 +352,55 @@ fn test_get_download_url() {
     let url = get_download_url(&"3.8".parse().unwrap(), "macos", "aarch64");
     assert_eq!(url, Some((PythonVersion { kind: "cpython".into(), major: 3, minor: 8, patch: 16, suffix: None }, "https://github.com/indygreg/python-build-standalone/releases/download/20221220/cpython-3.8.16%2B20221220-aarch64-apple-darwin-pgo-full.tar.zst")));
     let url = get_download_url(&"3".parse().unwrap(), "macos", "aarch64");
+    let latest = get_download_url(&"latest".parse().unwrap(), "macos", "aarch64");
+    assert_eq!(url, latest);
     assert_eq!(url, Some((PythonVersion { kind: "cpython".into(), major: 3, minor: 11, patch: 1, suffix: None }, "https://github.com/indygreg/python-build-standalone/releases/download/20230116/cpython-3.11.1%2B20230116-aarch64-apple-darwin-pgo-full.tar.zst")));
 }
+
+#[test]
+fn test_musl_detection() {
+    fn names(x: &[&str]) -> Vec<String> {
+        x.iter().map(|x| x.to_string()).collect()
+    }
+
+    // alpine
+    assert!(is_musl_system(&names(&[
+        "ld-musl-x86_64.so.1",
+        "libc.musl-x86_64.so.1"
+    ])));
+    // debian with the musl package installed
+    assert!(!is_musl_system(&names(&[
+        "ld-linux-x86-64.so.2",
+        "ld-musl-x86_64.so.1"
+    ])));
+    assert!(!is_musl_system(&names(&["ld-linux-aarch64.so.1"])));
+    assert!(!is_musl_system(&[]));
+}
+
+#[test]
+fn test_platforms_without_builds() {
+    let req: PythonVersionRequest = "3.11.1".parse().unwrap();
+    assert!(get_download_url(&req, "linux", "x86_64").is_some());
+    assert!(has_downloads("linux", "x86_64"));
+    // glibc builds cannot run on musl systems, so they are never picked there
+    for (platform, arch) in [("linux-musl", "x86_64"), ("linux", "riscv64")] {
+        if !has_downloads(platform, arch) {
+            assert_eq!(get_download_url(&req, platform, arch), None);
+            assert!(iter_downloadable(platform, arch).next().is_none());
+        }
+    }
+}
+
+#[test]
+fn test_variant_downloads() {
+    let req: PythonVersionRequest = "cpython-static@3.11".parse().unwrap();
+    let (version, url) = get_download_url(&req, "windows", "x86_64").unwrap();
+    assert_eq!(version.variant(), Some("static"));
+    assert!(url.ends_with("-x86_64-pc-windows-msvc-static-noopt-full.tar.zst"));
+    assert!(
+        iter_downloadable("windows", "x86").any(|x| x.variant() == Some("static") && x.minor == 10)
+    );
+
+    // a variant never falls back to the default build
+    assert_eq!(get_download_url(&req, "linux", "x86_64"), None);
+}
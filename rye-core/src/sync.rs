@@ -0,0 +1,1993 @@
+use std::env::consts::ARCH;
+use std::fmt;
+use std::io::Write;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::time::{Duration, SystemTime};
+use std::{env, fs};
+
+use anyhow::{bail, Context, Error};
+use console::style;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use crate::bootstrap::{ensure_self_venv, fetch, get_pip_module, link_venv_shims};
+use crate::builddeps::{detect_missing_build_deps, BuildDep, OsFamily};
+use crate::cache::{dir_size, format_size, get_cache_dir, prune_cache_if_due};
+use crate::conda::{find_conda_env, get_conda_python_version, CondaEnvRequest};
+use crate::config::{
+    apply_env_config, apply_pip_config, get_canonical_py_path, get_default_python_version,
+    get_default_toolchain_request, get_py_bin, load_python_version_requests,
+    resolve_newest_python_version, resolve_python_version, Config, EnvTarget,
+};
+use crate::disk::remember_project_venv;
+use crate::error::{ErrorKind, Failure};
+use crate::filelock::FileLock;
+use crate::lock::{
+    apply_build_constraints, explain_missing_wheels, is_lockfile_current, refresh_dependency_notes,
+    update_build_requirements_lockfile, update_single_project_lockfile, update_workspace_lockfile,
+    write_workspace_constraints, LockChange, LockMode, LockOptions, LockedPackage, Lockfile,
+    BUILD_REQUIREMENTS_LOCK, WORKSPACE_CONSTRAINTS,
+};
+use crate::namespaces::{
+    find_namespace_portions, find_shadowed_portions, with_strict_editables, NamespacePortion,
+    STRICT_EDITABLE,
+};
+use crate::pylock::pylock_to_requirements;
+use crate::pyproject::{normalize_package_name, PyProject};
+use crate::signals;
+use crate::sources::{
+    current_platform, get_download_url, matches_version, PythonVersion, PythonVersionRequest,
+};
+use crate::timings;
+use crate::utils::{format_timestamp, run_capturing_stderr, write_atomic, CommandOutput};
+
+/// Controls the sync mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum SyncMode {
+    /// Just ensures Python is there
+    #[default]
+    PythonOnly,
+    /// Lock only
+    LockOnly,
+    /// Lock and download the packages into the cache without installing
+    /// them
+    DownloadOnly,
+    /// Update dependencies
+    Regular,
+    /// recreate everything
+    Full,
+}
+
+/// Updates the virtualenv based on the pyproject.toml
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// How verbose should the sync be?
+    pub output: CommandOutput,
+    /// Include dev dependencies?
+    pub dev: bool,
+    /// Only install what the dev lockfile adds to an existing virtualenv,
+    /// leaving the production packages alone.
+    pub dev_only: bool,
+    /// Which sync mode should be used?
+    pub mode: SyncMode,
+    /// Forces venv creation even when unsafe.
+    pub force: bool,
+    /// Controls locking.
+    pub lock_options: LockOptions,
+    /// Move to the newest release matching the toolchain pin instead of
+    /// keeping the version the virtualenv was created with.
+    pub upgrade_toolchain: bool,
+    /// Install into this conda environment instead of the virtualenv.
+    pub conda_env: Option<CondaEnvRequest>,
+    /// Install from this `pylock.toml` instead of the lockfiles.
+    pub from_pylock: Option<PathBuf>,
+    /// Keep lockfiles that were resolved from the current dependencies
+    /// instead of resolving again.  `None` leaves it to
+    /// `behavior.relock-if-needed`.
+    pub relock_if_needed: Option<bool>,
+    /// How verbose installing the packages should be.  `None` follows
+    /// `output`, the resolver is controlled by the lock options.
+    pub install_output: Option<CommandOutput>,
+}
+
+impl SyncOptions {
+    /// Only sync the Python itself.
+    pub fn python_only() -> SyncOptions {
+        SyncOptions {
+            mode: SyncMode::PythonOnly,
+            ..Default::default()
+        }
+    }
+}
+
+/// The arguments virtualenvs are created with.
+const VIRTUALENV_ARGS: &[&str] = &["--no-seed"];
+
+/// Config written into the virtualenv (as `rye-venv.json`) for sync purposes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VenvMarker {
+    pub python: PythonVersion,
+    /// What the dependencies were last installed from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced: Option<SyncedState>,
+    /// The conda environment the `.venv` links to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conda: Option<PathBuf>,
+    /// How the virtualenv was created.  Older versions of rye did not
+    /// record this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<VenvProvenance>,
+}
+
+/// The state of the project the virtualenv was last fully synced with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SyncedState {
+    /// The hash of the project files, see [`hash_sync_inputs`].
+    pub hash: String,
+    /// Were the dev dependencies installed?
+    pub dev: bool,
+}
+
+/// Where the python of a virtualenv comes from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolchainSource {
+    /// A toolchain downloaded by rye.
+    Downloaded,
+    /// An interpreter registered with `rye toolchain register`.
+    Registered,
+    /// The python of a conda environment.
+    Conda,
+}
+
+impl ToolchainSource {
+    fn of(version: &PythonVersion) -> ToolchainSource {
+        // registered interpreters are linked in place of the install folder
+        match get_canonical_py_path(version) {
+            Ok(path) if path.is_file() => ToolchainSource::Registered,
+            _ => ToolchainSource::Downloaded,
+        }
+    }
+}
+
+impl fmt::Display for ToolchainSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ToolchainSource::Downloaded => "downloaded",
+            ToolchainSource::Registered => "registered",
+            ToolchainSource::Conda => "conda",
+        })
+    }
+}
+
+/// How and by what a virtualenv was created.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VenvProvenance {
+    /// When the virtualenv was created (RFC 3339, UTC).
+    pub created_at: String,
+    /// The version of rye that created it.
+    pub rye_version: String,
+    pub toolchain_source: ToolchainSource,
+    /// The interpreter the virtualenv was created from.
+    pub interpreter: PathBuf,
+    /// The arguments virtualenv was invoked with, which decide how it is
+    /// seeded.
+    pub virtualenv_args: Vec<String>,
+}
+
+impl VenvProvenance {
+    fn new(source: ToolchainSource, interpreter: PathBuf, virtualenv_args: &[&str]) -> Self {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        VenvProvenance {
+            created_at: format_timestamp(now),
+            rye_version: env!("CARGO_PKG_VERSION").to_string(),
+            toolchain_source: source,
+            interpreter,
+            virtualenv_args: virtualenv_args.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+}
+
+/// Where the marker of a virtualenv is kept.
+///
+/// That is inside the virtualenv, except for links to conda environments
+/// which rye does not own.  Their marker goes into the `.rye` folder next to
+/// the link.
+pub fn venv_marker_path(venv: &Path) -> PathBuf {
+    if venv.is_symlink() {
+        venv.parent()
+            .unwrap_or(venv)
+            .join(".rye")
+            .join("rye-venv.json")
+    } else {
+        venv.join("rye-venv.json")
+    }
+}
+
+/// Reads the marker rye writes for the virtualenv of a project.
+pub fn read_venv_marker(pyproject: &PyProject) -> Option<VenvMarker> {
+    fs::read(venv_marker_path(&pyproject.venv_path()))
+        .ok()
+        .and_then(|x| serde_json::from_slice(&x).ok())
+}
+
+/// Returns the python version the virtualenv of the project was created with.
+pub fn get_venv_python_version(pyproject: &PyProject) -> Option<PythonVersion> {
+    read_venv_marker(pyproject).map(|x| x.python)
+}
+
+/// Hashes the files a sync installs from: the `pyproject.toml` files of the
+/// project (or all workspace members) and the lockfiles.
+fn hash_sync_inputs(pyproject: &PyProject) -> Result<String, Error> {
+    let mut paths = vec![
+        pyproject.lockfile_dir().join("requirements.lock"),
+        pyproject.lockfile_dir().join("requirements-dev.lock"),
+        pyproject.lockfile_dir().join(BUILD_REQUIREMENTS_LOCK),
+    ];
+    match pyproject.workspace() {
+        Some(workspace) => {
+            paths.push(workspace.path().join("pyproject.toml"));
+            paths.push(workspace.lockfile_dir().join(WORKSPACE_CONSTRAINTS));
+            for project in workspace.iter_projects() {
+                paths.push(project?.toml_path().into_owned());
+            }
+        }
+        None => paths.push(pyproject.toml_path().into_owned()),
+    }
+    paths.sort();
+    paths.dedup();
+
+    let mut hasher = Sha256::new();
+    for path in &paths {
+        hasher.update(path.as_os_str().to_string_lossy().as_bytes());
+        hasher.update(b"\0");
+        if let Ok(contents) = fs::read(path) {
+            hasher.update(&contents);
+        }
+        hasher.update(b"\0");
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Prepares the folder `behavior.read-only-fallback` redirects the state of
+/// a read-only checkout to.
+///
+/// The lockfiles of the checkout are copied over, so that they are
+/// installed from rather than resolving from scratch (see [`seed_lockfiles`]).
+fn seed_read_only_fallback(pyproject: &PyProject) -> Result<(), Error> {
+    let dir = pyproject.lockfile_dir();
+    if dir == pyproject.workspace_path() {
+        return Ok(());
+    }
+    seed_lockfiles(&pyproject.workspace_path(), &dir)
+}
+
+/// Copies the lockfiles of `source` to `dir`.
+///
+/// A lockfile is copied again when the one in `source` changed since it was
+/// last copied, as a read-only checkout can be updated in place (eg: a
+/// mounted CI artifact).  Otherwise the copy is kept, it might have been
+/// locked again in `dir`.
+fn seed_lockfiles(source: &Path, dir: &Path) -> Result<(), Error> {
+    fs::create_dir_all(dir).with_context(|| format!("unable to create {}", dir.display()))?;
+    for name in [
+        "requirements.lock",
+        "requirements-dev.lock",
+        BUILD_REQUIREMENTS_LOCK,
+        WORKSPACE_CONSTRAINTS,
+    ] {
+        let contents = match fs::read(source.join(name)) {
+            Ok(contents) => contents,
+            Err(_) => continue,
+        };
+        let hash = format!("{:x}", Sha256::digest(&contents));
+        let seeded = dir.join(format!(".{}.seeded", name));
+        if dir.join(name).is_file() && fs::read_to_string(&seeded).ok().as_deref() == Some(&hash) {
+            continue;
+        }
+        write_atomic(&dir.join(name), &contents)
+            .with_context(|| format!("unable to copy {}", source.join(name).display()))?;
+        write_atomic(&seeded, hash)?;
+    }
+    Ok(())
+}
+
+/// Checks if the virtualenv is out of date with the project files.
+///
+/// Returns the options for the sync that brings it up to date again, or
+/// `None` if nothing changed since the last sync.  A virtualenv that was
+/// never synced is brought up to date with the dev dependencies.
+pub fn find_stale_venv(pyproject: &PyProject) -> Result<Option<SyncOptions>, Error> {
+    let synced = read_venv_marker(pyproject).and_then(|x| x.synced);
+    if let Some(ref synced) = synced {
+        if synced.hash == hash_sync_inputs(pyproject)? {
+            return Ok(None);
+        }
+    }
+    Ok(Some(SyncOptions {
+        mode: SyncMode::Regular,
+        dev: synced.is_none_or(|x| x.dev),
+        ..SyncOptions::default()
+    }))
+}
+
+/// Synchronizes a project's virtualenv.
+pub fn sync(cmd: SyncOptions) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    // a conda environment synced into before keeps being used until the
+    // sync is forced.
+    let conda_env = cmd
+        .conda_env
+        .clone()
+        .or_else(|| pyproject.conda_env())
+        .or_else(|| {
+            if cmd.mode == SyncMode::Full {
+                return None;
+            }
+            read_venv_marker(&pyproject)
+                .and_then(|x| x.conda)
+                .map(|x| CondaEnvRequest::Named(x.display().to_string()))
+        });
+    seed_read_only_fallback(&pyproject)?;
+    if let Some(request) = conda_env {
+        return sync_conda_env(&cmd, &pyproject, &request);
+    }
+
+    // lockfiles for another platform are written next to the regular ones
+    // and cannot be installed here
+    if cmd.lock_options.has_target() && cmd.mode != SyncMode::LockOnly {
+        bail!("lockfiles for another platform or python version can only be locked");
+    }
+    let lockfile = pyproject
+        .lockfile_dir()
+        .join(cmd.lock_options.lockfile_name(LockMode::Production)?);
+    let dev_lockfile = pyproject
+        .lockfile_dir()
+        .join(cmd.lock_options.lockfile_name(LockMode::Dev)?);
+    let venv = pyproject.venv_path();
+    // held for the whole sync so that concurrent syncs don't trample on
+    // each other's virtualenv
+    let _lock = FileLock::acquire(&venv, cmd.output)?;
+    let _defer = signals::defer_interrupts();
+    let marker_file = venv_marker_path(&venv);
+    let output = cmd.output;
+
+    let mut corrupt_marker = false;
+    let marker = if venv.is_dir() && marker_file.is_file() {
+        let contents = fs::read(&marker_file).context("could not read venv marker file")?;
+        match serde_json::from_slice::<VenvMarker>(&contents) {
+            Ok(marker) => Some(marker),
+            // the marker is only ever written by rye, so the virtualenv is
+            // ours but we can't tell what it was created with.
+            Err(_) => {
+                corrupt_marker = true;
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // if the version the virtualenv was created with still satisfies the
+    // requested version we stick to it, even if a better match came along.
+    let py_ver = match (load_python_version_requests(), &marker) {
+        (Some(requests), _) if cmd.upgrade_toolchain => requests
+            .iter()
+            .find_map(resolve_newest_python_version)
+            .ok_or_else(|| {
+                Failure::new(
+                    ErrorKind::ToolchainMissing,
+                    "no toolchain satisfies the versions in .python-version",
+                )
+            })?,
+        (Some(requests), Some(marker))
+            if requests
+                .iter()
+                .any(|req| matches_version(req, &marker.python)) =>
+        {
+            marker.python.clone()
+        }
+        (Some(requests), _) => requests
+            .iter()
+            .find_map(resolve_python_version)
+            .ok_or_else(|| {
+                Failure::new(
+                    ErrorKind::ToolchainMissing,
+                    "no toolchain satisfies the versions in .python-version",
+                )
+            })?,
+        (None, _) => match get_default_toolchain_request()? {
+            Some(req) if cmd.upgrade_toolchain => {
+                resolve_newest_python_version(&req).ok_or_else(|| {
+                    Failure::new(
+                        ErrorKind::ToolchainMissing,
+                        format!("no toolchain satisfies the default toolchain {}", req),
+                    )
+                })?
+            }
+            _ => get_default_python_version()?,
+        },
+    };
+
+    // ensure we are bootstrapped
+    let self_venv = ensure_self_venv(output).context("could not sync because bootstrap failed")?;
+
+    let mut recreate = cmd.mode == SyncMode::Full;
+    if venv.is_dir() {
+        if corrupt_marker {
+            if cmd.output != CommandOutput::Quiet {
+                eprintln!(
+                    "{} venv marker file is corrupt, recreating virtualenv",
+                    style("warning:").yellow()
+                );
+            }
+            recreate = true;
+        } else if let Some(marker) = marker {
+            if marker.conda.is_some() {
+                if cmd.output != CommandOutput::Quiet {
+                    eprintln!("Replacing link to conda environment with a virtualenv");
+                }
+                recreate = true;
+            } else if marker.python != py_ver {
+                if cmd.output != CommandOutput::Quiet {
+                    eprintln!(
+                        "Python version mismatch (found {}, expect {}), recreating.",
+                        marker.python, py_ver
+                    );
+                }
+                recreate = true;
+            }
+        } else if cmd.force {
+            if cmd.output != CommandOutput::Quiet {
+                eprintln!("Forcing re-creation of non rye managed virtualenv");
+            }
+            recreate = true;
+        } else {
+            bail!(Failure::new(
+                ErrorKind::VenvUnmanaged,
+                "virtualenv is not managed by rye. Run `rye sync -f` to force."
+            ));
+        }
+    }
+
+    // make sure we have a compatible python version
+    let py_ver =
+        fetch(&py_ver.into(), output).context("failed fetching toolchain ahead of sync")?;
+
+    // move the virtualenv aside if we need to get rid of it.  It's put back
+    // if the sync does not go through.
+    let rebuild = if recreate && venv.is_dir() {
+        Some(VenvRebuild::begin(&venv, output)?)
+    } else {
+        None
+    };
+
+    let created = !venv.is_dir();
+    if !created {
+        // we only care about this output if regular syncs are used
+        if !matches!(cmd.mode, SyncMode::PythonOnly | SyncMode::LockOnly)
+            && output != CommandOutput::Quiet
+        {
+            eprintln!("Reusing already existing virtualenv");
+        }
+    } else {
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "Initializing new virtualenv in {}",
+                style(venv.display()).cyan()
+            );
+            eprintln!("Python version: {}", style(&py_ver).cyan());
+        }
+        create_virtualenv(output, &self_venv, &py_ver, &venv)
+            .context("failed creating virtualenv ahead of sync")?;
+        write_atomic(
+            &marker_file,
+            serde_json::to_string_pretty(&VenvMarker {
+                python: py_ver.clone(),
+                synced: None,
+                conda: None,
+                provenance: Some(VenvProvenance::new(
+                    ToolchainSource::of(&py_ver),
+                    get_py_bin(&py_ver)?,
+                    VIRTUALENV_ARGS,
+                )),
+            })?,
+        )
+        .context("failed writing venv marker file")?;
+        // only used to report disk usage
+        remember_project_venv(&venv).ok();
+    }
+
+    // prepare necessary utilities for pip-sync.  This is a super crude
+    // hack to make this work for now.  We basically sym-link pip itself
+    // into a folder all by itself and place a second file in there which we
+    // can pass to pip-sync to install the local package.
+    if recreate || cmd.mode != SyncMode::PythonOnly {
+        let dir = TempDir::new()?;
+        symlink(get_pip_module(&self_venv), dir.path().join("pip"))
+            .context("failed linking pip module into for pip-sync")?;
+
+        let pylock_requirements = match cmd.from_pylock {
+            Some(ref pylock) => Some(requirements_from_pylock(pylock, dir.path())?),
+            None => {
+                if lockfiles_are_current(&pyproject, (&lockfile, &dev_lockfile), &cmd)? {
+                    if output == CommandOutput::Verbose {
+                        eprintln!("Dependencies unchanged, reusing lockfiles");
+                    }
+                    refresh_dependency_notes(&pyproject, &lockfile, output)?;
+                    refresh_dependency_notes(&pyproject, &dev_lockfile, output)?;
+                } else {
+                    update_lockfiles(&pyproject, (&lockfile, &dev_lockfile), &cmd)?;
+                }
+                None
+            }
+        };
+
+        let install_output = cmd.install_output.unwrap_or(output);
+        let installs = !matches!(cmd.mode, SyncMode::LockOnly | SyncMode::DownloadOnly);
+        let before = installs.then(|| installed_distributions(&venv));
+        // on a virtualenv that was just created there are no production
+        // packages the dev ones could go on top of.
+        if cmd.mode == SyncMode::DownloadOnly {
+            download_packages(
+                &pyproject,
+                &self_venv,
+                &venv.join("bin/python"),
+                match pylock_requirements {
+                    Some(ref pylock) => &pylock.requirements,
+                    None if cmd.dev && dev_lockfile.is_file() => &dev_lockfile,
+                    None => &lockfile,
+                },
+                dir.path(),
+                &cmd.lock_options,
+                install_output,
+            )?;
+        } else if cmd.mode != SyncMode::LockOnly && cmd.dev_only && !created {
+            install_dev_delta(
+                &pyproject,
+                &self_venv,
+                &venv,
+                (&lockfile, &dev_lockfile),
+                &cmd.lock_options,
+                install_output,
+            )?;
+        } else if cmd.mode != SyncMode::LockOnly {
+            let _timing = timings::phase("install");
+            if install_output != CommandOutput::Quiet {
+                eprintln!("Installing dependencies");
+            }
+            let namespace_portions = find_namespace_portions(&pyproject)?;
+            let mut pip_sync_cmd = Command::new(self_venv.join("bin/pip-sync"));
+            pip_sync_cmd
+                .env("PYTHONPATH", dir.path())
+                .current_dir(pyproject.workspace_path())
+                .arg("--python-executable")
+                .arg(venv.join("bin/python"))
+                // note that the double quotes are necessary to properly handle
+                // spaces in paths
+                .arg(format!(
+                    "--pip-args=\"--python={}\" {}",
+                    venv.join("bin/python").display(),
+                    cmd.lock_options.binary_pip_args()?.join(" ")
+                ));
+
+            let requirements = match pylock_requirements {
+                Some(ref pylock) => &pylock.requirements,
+                None if cmd.dev && dev_lockfile.is_file() => &dev_lockfile,
+                None => &lockfile,
+            };
+            pip_sync_cmd.arg(requirements);
+
+            if install_output == CommandOutput::Verbose {
+                pip_sync_cmd.arg("--verbose");
+                if env::var("PIP_VERBOSE").is_err() {
+                    pip_sync_cmd.env("PIP_VERBOSE", "2");
+                }
+            } else if install_output != CommandOutput::Quiet {
+                pip_sync_cmd.env("PYTHONWARNINGS", "ignore");
+            } else {
+                pip_sync_cmd.arg("-q");
+            }
+            apply_pip_config(&mut pip_sync_cmd, install_output)?;
+            apply_build_constraints(&mut pip_sync_cmd, &pyproject);
+            let (mut status, stderr) =
+                run_install(&mut pip_sync_cmd, install_output).context("unable to run pip-sync")?;
+            if !status.success() {
+                if let Some(explanation) = explain_missing_wheels(&stderr, &cmd.lock_options)? {
+                    bail!("Installation of dependencies failed\n\n{}", explanation);
+                }
+                let missing = detect_missing_build_deps(&stderr);
+                if missing.is_empty() {
+                    bail!("Installation of dependencies failed");
+                }
+                print_build_dep_hints(&missing);
+                match Config::current()?.build_bootstrap_command() {
+                    Some(bootstrap) => {
+                        run_build_bootstrap(bootstrap, install_output)?;
+                        status =
+                            signals::status(&mut pip_sync_cmd).context("unable to run pip-sync")?;
+                        if !status.success() {
+                            bail!("Installation of dependencies failed");
+                        }
+                    }
+                    None => bail!("Installation of dependencies failed"),
+                }
+            }
+
+            if let Some(unhashed) = pylock_requirements
+                .as_ref()
+                .and_then(|x| x.unhashed.as_ref())
+            {
+                install_local_requirements(
+                    &pyproject,
+                    &self_venv,
+                    &venv.join("bin/python"),
+                    unhashed,
+                    &cmd.lock_options,
+                    install_output,
+                )?;
+            }
+            reinstall_strict_editables(
+                &pyproject,
+                &self_venv,
+                &venv.join("bin/python"),
+                requirements,
+                &cmd.lock_options,
+                install_output,
+            )?;
+            build_native_projects(&pyproject, &self_venv, &venv, install_output)?;
+            warn_shadowed_portions(
+                &venv.join("bin/python"),
+                &namespace_portions,
+                install_output,
+            );
+
+            // remembered so that `behavior.autosync` can tell when the
+            // virtualenv goes out of date.
+            write_atomic(
+                &marker_file,
+                serde_json::to_string_pretty(&VenvMarker {
+                    python: py_ver.clone(),
+                    // the lockfiles were not installed, so autosync should
+                    // not assume they were
+                    synced: match pylock_requirements {
+                        Some(_) => None,
+                        None => Some(SyncedState {
+                            hash: hash_sync_inputs(&pyproject)?,
+                            dev: cmd.dev,
+                        }),
+                    },
+                    conda: None,
+                    provenance: read_venv_marker(&pyproject).and_then(|x| x.provenance),
+                })?,
+            )
+            .context("failed writing venv marker file")?;
+
+            if Config::current()?.venv_shims() {
+                update_venv_shims(&venv, install_output);
+            }
+        }
+        if let Some(before) = before {
+            report_sync(&pyproject, &venv, &before, install_output);
+        }
+    }
+
+    // keeping the caches in check is not worth failing the sync for
+    if let Err(err) = prune_cache_if_due(output) {
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "{} failed to prune cache: {:#}",
+                style("warning:").yellow(),
+                err
+            );
+        }
+    }
+
+    if let Some(rebuild) = rebuild {
+        rebuild.commit();
+    }
+
+    if output != CommandOutput::Quiet && cmd.mode != SyncMode::PythonOnly {
+        hint_newer_patch_release(&py_ver);
+        eprintln!("Done!");
+    }
+
+    Ok(())
+}
+
+/// How often [`watch_and_sync`] looks for changes to the project files.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Syncs and then syncs again whenever `pyproject.toml` or the lockfiles
+/// change, until rye is interrupted.
+///
+/// A failing sync is reported and the next change is waited for, so that a
+/// typo in `pyproject.toml` does not end the watch.  Packages to update and
+/// `force` only apply to the first sync, later ones are incremental.
+pub fn watch_and_sync(mut cmd: SyncOptions) -> Result<(), Error> {
+    let mut pyproject = PyProject::discover()?;
+    let output = cmd.output;
+    loop {
+        if let Err(err) = sync(cmd.clone()) {
+            if signals::interrupted().is_some() {
+                return Err(err);
+            }
+            eprintln!("{} {:#}", style("error:").red(), err);
+        }
+        cmd.mode = SyncMode::Regular;
+        cmd.force = false;
+        cmd.upgrade_toolchain = false;
+        cmd.lock_options.update_all = false;
+        cmd.lock_options.update.clear();
+
+        // members might have been added to the workspace
+        if let Ok(project) = PyProject::discover() {
+            pyproject = project;
+        }
+        let mut hash = watched_hash(&pyproject);
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "{} for changes to pyproject.toml and the lockfiles (Ctrl+C to stop)",
+                style("Watching").cyan()
+            );
+        }
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let new_hash = watched_hash(&pyproject);
+            if new_hash != hash {
+                hash = new_hash;
+                break;
+            }
+        }
+        // editors tend to write files more than once, wait for them to settle
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let new_hash = watched_hash(&pyproject);
+            if new_hash == hash {
+                break;
+            }
+            hash = new_hash;
+        }
+        if output != CommandOutput::Quiet {
+            eprintln!("Project files changed, syncing");
+        }
+    }
+}
+
+/// Like [`hash_sync_inputs`] but a member that cannot be loaded counts as a
+/// change rather than ending the watch, the sync it triggers reports it.
+fn watched_hash(pyproject: &PyProject) -> String {
+    hash_sync_inputs(pyproject).unwrap_or_else(|err| format!("error: {:#}", err))
+}
+
+/// Removes the virtualenv of a project and with `lockfiles` the lockfiles.
+///
+/// Virtualenvs without the marker of rye are only removed with `force`.  If
+/// the virtualenv is a link to a conda environment only the link goes away,
+/// the environment itself is left alone.
+pub fn remove_venv(
+    pyproject: &PyProject,
+    force: bool,
+    lockfiles: bool,
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let venv = pyproject.venv_path();
+    let _lock = FileLock::acquire(&venv, output)?;
+    let _defer = signals::defer_interrupts();
+    let marker_file = venv_marker_path(&venv);
+
+    if venv.is_symlink() {
+        if marker_file.is_file() {
+            fs::remove_file(&marker_file).context("failed to remove venv marker file")?;
+        }
+        fs::remove_file(&venv).context("failed to remove conda environment link")?;
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "Removed link to conda environment {}",
+                style(venv.display()).cyan()
+            );
+        }
+    } else if venv.is_dir() {
+        if !marker_file.is_file() && !force {
+            bail!(Failure::new(
+                ErrorKind::VenvUnmanaged,
+                "virtualenv is not managed by rye. Run `rye sync --clean -f` to remove it anyways."
+            ));
+        }
+        let size = dir_size(&venv);
+        fs::remove_dir_all(&venv)
+            .with_context(|| format!("failed to remove virtualenv {}", venv.display()))?;
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "Removed virtualenv {} ({})",
+                style(venv.display()).cyan(),
+                format_size(size)
+            );
+        }
+    } else if output != CommandOutput::Quiet {
+        eprintln!("No virtualenv to remove");
+    }
+
+    if lockfiles {
+        for name in ["requirements.lock", "requirements-dev.lock"] {
+            let lockfile = pyproject.lockfile_dir().join(name);
+            if lockfile.is_file() {
+                fs::remove_file(&lockfile)
+                    .with_context(|| format!("failed to remove {}", lockfile.display()))?;
+                if output != CommandOutput::Quiet {
+                    eprintln!("Removed {}", style(lockfile.display()).cyan());
+                }
+            }
+        }
+    }
+
+    if output != CommandOutput::Quiet {
+        eprintln!("Run `rye sync` to start fresh");
+    }
+    Ok(())
+}
+
+/// Warns about portions of namespace packages that cannot be imported after
+/// installing, most likely because an editable install shadows them.
+fn warn_shadowed_portions(python: &Path, portions: &[NamespacePortion], output: CommandOutput) {
+    if output == CommandOutput::Quiet {
+        return;
+    }
+    let shadowed = match find_shadowed_portions(python, portions) {
+        Ok(shadowed) => shadowed,
+        Err(err) => {
+            eprintln!(
+                "{} could not check namespace packages: {:#}",
+                style("warning:").yellow(),
+                err
+            );
+            return;
+        }
+    };
+    for portion in shadowed {
+        eprintln!(
+            "{} {} from {} cannot be imported, another install of the namespace {} shadows it",
+            style("warning:").yellow(),
+            style(&portion.module).cyan(),
+            portion.project.display(),
+            portion.namespace
+        );
+    }
+}
+
+/// Puts shims for the scripts of the virtualenv into the shims folder
+/// (`behavior.venv-shims`).
+fn update_venv_shims(venv: &Path, output: CommandOutput) {
+    match link_venv_shims(venv) {
+        Ok(changes) => {
+            if output != CommandOutput::Quiet && !changes.added.is_empty() {
+                eprintln!("Added shims for {}", style(changes.added.join(", ")).cyan());
+            }
+            if output != CommandOutput::Quiet && !changes.removed.is_empty() {
+                eprintln!(
+                    "Removed shims for {}",
+                    style(changes.removed.join(", ")).cyan()
+                );
+            }
+        }
+        // a missing shim is not worth failing the sync for
+        Err(err) => {
+            if output != CommandOutput::Quiet {
+                eprintln!(
+                    "{} failed to add shims for the virtualenv: {:#}",
+                    style("warning:").yellow(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+/// Where the report of the last sync is written, relative to the folder
+/// of the lockfiles.
+const LAST_SYNC_REPORT: &str = ".rye/last-sync.json";
+
+/// What the last sync changed in the virtualenv, as written to
+/// `.rye/last-sync.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// When the sync finished (RFC 3339, UTC).
+    pub synced_at: String,
+    /// The virtualenv (or conda environment) that was synced.
+    pub venv: PathBuf,
+    pub installed: Vec<ReportedPackage>,
+    /// Also lists packages whose versions cannot be compared.
+    pub upgraded: Vec<ReportedPackage>,
+    pub downgraded: Vec<ReportedPackage>,
+    pub removed: Vec<ReportedPackage>,
+}
+
+/// A package in a [`SyncReport`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReportedPackage {
+    pub name: String,
+    /// The version before the sync, if it was installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_version: Option<String>,
+    /// The version after the sync, if it is installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+impl SyncReport {
+    /// Sorts the changes between two sets of installed distributions.
+    pub fn new(venv: &Path, changes: &[LockChange]) -> SyncReport {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        let mut rv = SyncReport {
+            synced_at: format_timestamp(now),
+            venv: venv.to_path_buf(),
+            ..SyncReport::default()
+        };
+        for change in changes {
+            let (old, new) = match change {
+                LockChange::Added(new) => (None, Some(new)),
+                LockChange::Removed(old) => (Some(old), None),
+                LockChange::Changed { old, new } => (Some(old), Some(new)),
+            };
+            let package = ReportedPackage {
+                name: change.name().to_string(),
+                old_version: old.and_then(|x| x.version.clone()),
+                version: new.and_then(|x| x.version.clone()),
+            };
+            match change.kind() {
+                "added" => rv.installed.push(package),
+                "removed" => rv.removed.push(package),
+                "downgraded" => rv.downgraded.push(package),
+                _ => rv.upgraded.push(package),
+            }
+        }
+        rv
+    }
+}
+
+/// Reads the report of the last sync of the project, if there is one.
+pub fn read_sync_report(pyproject: &PyProject) -> Option<SyncReport> {
+    fs::read(pyproject.lockfile_dir().join(LAST_SYNC_REPORT))
+        .ok()
+        .and_then(|x| serde_json::from_slice(&x).ok())
+}
+
+/// Lists the distributions installed in a virtualenv (or conda environment)
+/// by their `.dist-info` folders.
+fn installed_distributions(venv: &Path) -> Lockfile {
+    let mut packages = Vec::new();
+    let site_packages = fs::read_dir(venv.join("lib"))
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x.ok())
+        .map(|x| x.path().join("site-packages"));
+    for dir in site_packages {
+        for entry in fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|x| x.ok())
+        {
+            let file_name = entry.file_name();
+            // the name cannot contain dashes, the version never does
+            if let Some((name, version)) = file_name
+                .to_str()
+                .and_then(|x| x.strip_suffix(".dist-info"))
+                .and_then(|x| x.split_once('-'))
+            {
+                packages.push(LockedPackage {
+                    name: normalize_package_name(name),
+                    version: Some(version.to_string()),
+                    requirement: format!("{}=={}", normalize_package_name(name), version),
+                    via: Vec::new(),
+                    hashes: Vec::new(),
+                });
+            }
+        }
+    }
+    Lockfile::from_packages(packages)
+}
+
+/// Prints what a sync changed in the virtualenv and writes it to
+/// `.rye/last-sync.json`.
+fn report_sync(pyproject: &PyProject, venv: &Path, before: &Lockfile, output: CommandOutput) {
+    let changes = before.diff(&installed_distributions(venv));
+    if output != CommandOutput::Quiet && !changes.is_empty() {
+        eprintln!("Changes to the virtualenv:");
+        for change in &changes {
+            eprintln!("  {}", change.styled());
+        }
+    }
+    // the packages are installed at this point, so the sync did not fail
+    let path = pyproject.lockfile_dir().join(LAST_SYNC_REPORT);
+    if let Err(err) = write_sync_report(&path, &SyncReport::new(venv, &changes)) {
+        eprintln!(
+            "{} could not write {}: {:#}",
+            style("warning:").yellow(),
+            path.display(),
+            err
+        );
+    }
+}
+
+fn write_sync_report(path: &Path, report: &SyncReport) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomic(path, serde_json::to_string_pretty(report)?)?;
+    Ok(())
+}
+
+/// Downloads the packages of a lockfile for `python` into the pip cache
+/// without installing them, so that later syncs do not hit the network.
+///
+/// `pip wheel` rather than `pip download` is used, so that the wheels built
+/// from source distributions land in pip's wheel cache and are not built
+/// again at install time.
+///
+/// The local projects of the lockfile are left out as there is nothing to
+/// download for them.
+fn download_packages(
+    pyproject: &PyProject,
+    self_venv: &Path,
+    python: &Path,
+    lockfile: &Path,
+    scratch: &Path,
+    lock_options: &LockOptions,
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let _timing = timings::phase("download");
+    if output != CommandOutput::Quiet {
+        eprintln!("Downloading dependencies");
+    }
+    let contents = fs::read_to_string(lockfile)
+        .with_context(|| format!("unable to read {}", lockfile.display()))?;
+    let requirements = scratch.join("download-requirements.txt");
+    fs::write(&requirements, filter_local_requirements(&contents))
+        .context("unable to write requirements to download")?;
+
+    let mut pip_cmd = Command::new(self_venv.join("bin/pip"));
+    pip_cmd
+        .current_dir(pyproject.workspace_path())
+        .arg("--python")
+        .arg(python)
+        .arg("wheel")
+        .arg("--no-deps")
+        .arg("--wheel-dir")
+        .arg(scratch.join("downloads"))
+        .arg("-r")
+        .arg(&requirements)
+        .args(lock_options.binary_pip_args()?)
+        .env("PYTHONWARNINGS", "ignore");
+    if output == CommandOutput::Verbose {
+        pip_cmd.arg("--verbose");
+    } else {
+        pip_cmd.arg("-q");
+    }
+    apply_pip_config(&mut pip_cmd, output)?;
+    apply_build_constraints(&mut pip_cmd, pyproject);
+    let (status, stderr) = run_install(&mut pip_cmd, output).context("unable to run pip")?;
+    if !status.success() {
+        if let Some(explanation) = explain_missing_wheels(&stderr, lock_options)? {
+            bail!("Download of dependencies failed\n\n{}", explanation);
+        }
+        bail!("Download of dependencies failed");
+    }
+    Ok(())
+}
+
+/// Removes the editable and local path requirements from a lockfile.
+fn filter_local_requirements(contents: &str) -> String {
+    let mut rv = String::new();
+    for line in contents.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("-e ")
+            || trimmed.starts_with("file:")
+            || trimmed.starts_with('.')
+            || trimmed.starts_with('/')
+            || trimmed.contains(" @ file:")
+        {
+            continue;
+        }
+        rv.push_str(line);
+        rv.push('\n');
+    }
+    rv
+}
+
+/// Matches installer output caused by network errors that may go away when
+/// trying again.
+static TRANSIENT_NETWORK_ERROR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        r"(?i)connection (?:reset|refused|aborted)|read timed out|connecttimeout|",
+        r"temporary failure in name resolution|remote end closed connection|",
+        r"incompleteread|protocolerror|http error 5\d\d|\b50[234] server error",
+    ))
+    .unwrap()
+});
+
+/// Runs the installer, retrying it if it fails because of network errors.
+///
+/// The installer skips what is already installed, so a retry only fetches
+/// what failed before.  Returns the status and stderr of the last run.
+fn run_install(cmd: &mut Command, output: CommandOutput) -> Result<(ExitStatus, String), Error> {
+    let retries = Config::current()?.install_retries()?;
+    let mut attempt = 0;
+    loop {
+        let (status, stderr) = run_capturing_stderr(cmd)?;
+        if status.success() || attempt >= retries || !TRANSIENT_NETWORK_ERROR.is_match(&stderr) {
+            return Ok((status, stderr));
+        }
+        attempt += 1;
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "{} installation failed because of a network error, retrying ({}/{})",
+                style("warning:").yellow(),
+                attempt,
+                retries
+            );
+        }
+        std::thread::sleep(Duration::from_secs((1 << attempt.min(5)).min(30)));
+    }
+}
+
+/// Checks whether the lockfiles can be reused as they are with
+/// `behavior.relock-if-needed`.
+///
+/// That is the case if they were resolved from the dependencies the project
+/// has now and the sync does not ask for updates or other changes to them.
+fn lockfiles_are_current(
+    pyproject: &PyProject,
+    (lockfile, dev_lockfile): (&Path, &Path),
+    cmd: &SyncOptions,
+) -> Result<bool, Error> {
+    let relock_if_needed = match cmd.relock_if_needed {
+        Some(relock_if_needed) => relock_if_needed,
+        None => Config::current()?.relock_if_needed(),
+    };
+    if !relock_if_needed || cmd.lock_options.requests_resolution() {
+        return Ok(false);
+    }
+    Ok(is_lockfile_current(pyproject, lockfile)? && is_lockfile_current(pyproject, dev_lockfile)?)
+}
+
+/// Brings the production and dev lockfiles up to date.
+fn update_lockfiles(
+    pyproject: &PyProject,
+    (lockfile, dev_lockfile): (&Path, &Path),
+    cmd: &SyncOptions,
+) -> Result<(), Error> {
+    let load = |path: &Path| {
+        if path.is_file() {
+            Lockfile::load(path)
+        } else {
+            Ok(Lockfile::default())
+        }
+    };
+    let old = (load(lockfile)?, load(dev_lockfile)?);
+    if let Some(workspace) = pyproject.workspace() {
+        // make sure we have an up-to-date lockfile
+        update_workspace_lockfile(
+            workspace,
+            LockMode::Production,
+            lockfile,
+            cmd.output,
+            &cmd.lock_options,
+        )
+        .context("could not write production lockfile for workspace")?;
+        update_workspace_lockfile(
+            workspace,
+            LockMode::Dev,
+            dev_lockfile,
+            cmd.output,
+            &cmd.lock_options,
+        )
+        .context("could not write dev lockfile for workspace")?;
+        // the dev lockfile has everything, so shared dev dependencies are
+        // constrained as well.  Updated packages moved past their pins, so
+        // these are written again.
+        let options = &cmd.lock_options;
+        if !options.has_target()
+            && (options.update_constraints
+                || ((options.update_all || !options.update.is_empty())
+                    && workspace
+                        .lockfile_dir()
+                        .join(WORKSPACE_CONSTRAINTS)
+                        .is_file()))
+        {
+            let path = write_workspace_constraints(workspace, LockMode::Dev, dev_lockfile)?;
+            if cmd.output != CommandOutput::Quiet {
+                eprintln!("Wrote {}", style(path.display()).cyan());
+            }
+        }
+    } else {
+        // make sure we have an up-to-date lockfile
+        update_single_project_lockfile(
+            pyproject,
+            LockMode::Production,
+            lockfile,
+            cmd.output,
+            &cmd.lock_options,
+        )
+        .context("could not write production lockfile for project")?;
+        update_single_project_lockfile(
+            pyproject,
+            LockMode::Dev,
+            dev_lockfile,
+            cmd.output,
+            &cmd.lock_options,
+        )
+        .context("could not write dev lockfile for project")?;
+    }
+    // once there is a build requirements lockfile it is kept up to date, it
+    // is for building here
+    if !cmd.lock_options.has_target()
+        && (cmd.lock_options.build_requirements
+            || pyproject
+                .lockfile_dir()
+                .join(BUILD_REQUIREMENTS_LOCK)
+                .is_file())
+    {
+        update_build_requirements_lockfile(pyproject, dev_lockfile, cmd.output, &cmd.lock_options)
+            .context("could not write build requirements lockfile")?;
+    }
+    if cmd.output != CommandOutput::Quiet {
+        let changes = old.0.diff(&load(lockfile)?);
+        // the dev lockfile repeats the production changes
+        let dev_changes: Vec<_> = old
+            .1
+            .diff(&load(dev_lockfile)?)
+            .into_iter()
+            .filter(|x| !changes.iter().any(|y| y.to_string() == x.to_string()))
+            .collect();
+        print_lock_changes(LockMode::Production, &changes);
+        print_lock_changes(LockMode::Dev, &dev_changes);
+    }
+    Ok(())
+}
+
+/// Prints a summary of how a lockfile changed.
+fn print_lock_changes(lock_mode: LockMode, changes: &[LockChange]) {
+    if changes.is_empty() {
+        return;
+    }
+    eprintln!("Updated {} lockfile:", lock_mode);
+    for change in changes {
+        eprintln!("  {}", change.styled());
+    }
+}
+
+/// The requirements files a `pylock.toml` was converted into.
+struct PylockFiles {
+    /// What pip-sync installs.
+    requirements: PathBuf,
+    /// The local packages that are installed afterwards because they cannot
+    /// be hash-checked (see [`crate::pylock::PylockRequirements`]).
+    unhashed: Option<PathBuf>,
+}
+
+/// Copies a requirements file with the local packages providing namespace
+/// portions switched to strict editable mode.
+fn strict_editables_file(
+    pyproject: &PyProject,
+    requirements: &Path,
+) -> Result<tempfile::NamedTempFile, Error> {
+    let contents = fs::read_to_string(requirements)
+        .with_context(|| format!("unable to read {}", requirements.display()))?;
+    let mut rv = tempfile::NamedTempFile::new()?;
+    rv.write_all(
+        with_strict_editables(
+            &contents,
+            &pyproject.workspace_path(),
+            &find_namespace_portions(pyproject)?,
+        )
+        .as_bytes(),
+    )?;
+    Ok(rv)
+}
+
+/// Reinstalls the local packages providing namespace portions which
+/// pip-sync installed in strict editable mode.
+///
+/// pip-sync drops per-requirement options when it hands the requirements to
+/// pip, so they are installed a second time on their own.
+fn reinstall_strict_editables(
+    pyproject: &PyProject,
+    self_venv: &Path,
+    python: &Path,
+    requirements: &Path,
+    lock_options: &LockOptions,
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let portions = find_namespace_portions(pyproject)?;
+    if portions.is_empty() {
+        return Ok(());
+    }
+    let contents = fs::read_to_string(requirements)
+        .with_context(|| format!("unable to read {}", requirements.display()))?;
+    let strict: String = with_strict_editables(&contents, &pyproject.workspace_path(), &portions)
+        .lines()
+        .filter(|x| x.contains(STRICT_EDITABLE))
+        .map(|x| format!("{}\n", x))
+        .collect();
+    if strict.is_empty() {
+        return Ok(());
+    }
+    let mut req_file = tempfile::NamedTempFile::new()?;
+    req_file.write_all(strict.as_bytes())?;
+    install_local_requirements(
+        pyproject,
+        self_venv,
+        python,
+        req_file.path(),
+        lock_options,
+        output,
+    )
+}
+
+/// Converts a `pylock.toml` into requirements files in `dir`.
+fn requirements_from_pylock(pylock: &Path, dir: &Path) -> Result<PylockFiles, Error> {
+    let contents = fs::read_to_string(pylock)
+        .with_context(|| format!("unable to read {}", pylock.display()))?;
+    let base = pylock
+        .canonicalize()?
+        .parent()
+        .map(|x| x.to_path_buf())
+        .unwrap_or_default();
+    let converted = pylock_to_requirements(&contents, &base)
+        .with_context(|| format!("unable to install from {}", pylock.display()))?;
+    let requirements = dir.join("pylock.txt");
+    fs::write(&requirements, converted.requirements)?;
+    let unhashed = if converted.unhashed.is_empty() {
+        None
+    } else {
+        let path = dir.join("pylock-unhashed.txt");
+        fs::write(&path, converted.unhashed)?;
+        Some(path)
+    };
+    Ok(PylockFiles {
+        requirements,
+        unhashed,
+    })
+}
+
+/// Installs local packages without their dependencies, which an earlier
+/// install already brought in.  This is used for the local packages of a
+/// hashed `pylock.toml` and for strict editables.
+fn install_local_requirements(
+    pyproject: &PyProject,
+    self_venv: &Path,
+    python: &Path,
+    requirements: &Path,
+    lock_options: &LockOptions,
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let mut pip_cmd = Command::new(self_venv.join("bin/pip"));
+    pip_cmd
+        .current_dir(pyproject.workspace_path())
+        .arg("--python")
+        .arg(python)
+        .arg("install")
+        .arg("--no-deps")
+        .arg("-r")
+        .arg(strict_editables_file(pyproject, requirements)?.path())
+        .args(lock_options.binary_pip_args()?)
+        .env("PYTHONWARNINGS", "ignore");
+    if output == CommandOutput::Verbose {
+        pip_cmd.arg("--verbose");
+    } else {
+        pip_cmd.arg("-q");
+    }
+    apply_pip_config(&mut pip_cmd, output)?;
+    apply_build_constraints(&mut pip_cmd, pyproject);
+    let (status, _) = run_install(&mut pip_cmd, output).context("unable to run pip")?;
+    if !status.success() {
+        bail!("Installation of local packages failed");
+    }
+    Ok(())
+}
+
+/// Syncs the project into a conda environment instead of its virtualenv.
+///
+/// The locked packages are installed on top of what conda installed, without
+/// removing anything, as the environment is shared with conda.
+fn sync_conda_env(
+    cmd: &SyncOptions,
+    pyproject: &PyProject,
+    request: &CondaEnvRequest,
+) -> Result<(), Error> {
+    let output = cmd.output;
+    let prefix = find_conda_env(request)?;
+    let lockfile = pyproject.lockfile_dir().join("requirements.lock");
+    let dev_lockfile = pyproject.lockfile_dir().join("requirements-dev.lock");
+    let venv = pyproject.venv_path();
+    let _lock = FileLock::acquire(&venv, output)?;
+    let _defer = signals::defer_interrupts();
+
+    let py_ver = get_conda_python_version(&prefix)?;
+    if let Some(requests) = load_python_version_requests() {
+        if output != CommandOutput::Quiet && !requests.iter().any(|x| matches_version(x, &py_ver)) {
+            eprintln!(
+                "{} conda environment has python {} which does not match .python-version",
+                style("warning:").yellow(),
+                py_ver
+            );
+        }
+    }
+    let self_venv = ensure_self_venv(output).context("could not sync because bootstrap failed")?;
+
+    let linked = link_conda_env(&venv, &prefix, cmd.force, output)?;
+    let marker_file = venv_marker_path(&venv);
+    if linked || !marker_file.is_file() {
+        // older versions wrote the marker into the conda environment
+        fs::remove_file(prefix.join("rye-venv.json")).ok();
+        if let Some(parent) = marker_file.parent() {
+            fs::create_dir_all(parent).context("failed to create .rye folder")?;
+        }
+        write_atomic(
+            &marker_file,
+            serde_json::to_string_pretty(&VenvMarker {
+                python: py_ver.clone(),
+                synced: None,
+                conda: Some(prefix.clone()),
+                provenance: Some(VenvProvenance::new(
+                    ToolchainSource::Conda,
+                    prefix.join("bin/python"),
+                    &[],
+                )),
+            })?,
+        )
+        .context("failed writing venv marker file")?;
+    }
+    if cmd.mode == SyncMode::PythonOnly {
+        return Ok(());
+    }
+
+    let scratch = TempDir::new()?;
+    let pylock_requirements = match cmd.from_pylock {
+        Some(ref pylock) => Some(requirements_from_pylock(pylock, scratch.path())?),
+        None => {
+            update_lockfiles(pyproject, (&lockfile, &dev_lockfile), cmd)?;
+            None
+        }
+    };
+    let install_output = cmd.install_output.unwrap_or(output);
+    if cmd.mode == SyncMode::DownloadOnly {
+        download_packages(
+            pyproject,
+            &self_venv,
+            &prefix.join("bin/python"),
+            match pylock_requirements {
+                Some(ref pylock) => &pylock.requirements,
+                None if cmd.dev && dev_lockfile.is_file() => &dev_lockfile,
+                None => &lockfile,
+            },
+            scratch.path(),
+            &cmd.lock_options,
+            install_output,
+        )?;
+    } else if cmd.mode != SyncMode::LockOnly {
+        let before = installed_distributions(&prefix);
+        let _timing = timings::phase("install");
+        if install_output != CommandOutput::Quiet {
+            eprintln!(
+                "Installing dependencies into conda environment {}",
+                style(prefix.display()).cyan()
+            );
+        }
+        let namespace_portions = find_namespace_portions(pyproject)?;
+        let mut pip_cmd = Command::new(self_venv.join("bin/pip"));
+        pip_cmd
+            .current_dir(pyproject.workspace_path())
+            .arg("--python")
+            .arg(prefix.join("bin/python"))
+            .arg("install")
+            .arg("--no-deps")
+            .arg("-r")
+            .arg(
+                strict_editables_file(
+                    pyproject,
+                    match pylock_requirements {
+                        Some(ref pylock) => &pylock.requirements,
+                        None if cmd.dev && dev_lockfile.is_file() => &dev_lockfile,
+                        None => &lockfile,
+                    },
+                )?
+                .path(),
+            )
+            .args(cmd.lock_options.binary_pip_args()?)
+            .env("PYTHONWARNINGS", "ignore");
+        if install_output == CommandOutput::Verbose {
+            pip_cmd.arg("--verbose");
+        } else {
+            pip_cmd.arg("-q");
+        }
+        apply_pip_config(&mut pip_cmd, install_output)?;
+        apply_build_constraints(&mut pip_cmd, pyproject);
+        let (status, stderr) =
+            run_install(&mut pip_cmd, install_output).context("unable to run pip")?;
+        if !status.success() {
+            if let Some(explanation) = explain_missing_wheels(&stderr, &cmd.lock_options)? {
+                bail!("Installation of dependencies failed\n\n{}", explanation);
+            }
+            bail!("Installation of dependencies failed");
+        }
+        if let Some(unhashed) = pylock_requirements
+            .as_ref()
+            .and_then(|x| x.unhashed.as_ref())
+        {
+            install_local_requirements(
+                pyproject,
+                &self_venv,
+                &prefix.join("bin/python"),
+                unhashed,
+                &cmd.lock_options,
+                install_output,
+            )?;
+        }
+        warn_shadowed_portions(
+            &prefix.join("bin/python"),
+            &namespace_portions,
+            install_output,
+        );
+        write_atomic(
+            &marker_file,
+            serde_json::to_string_pretty(&VenvMarker {
+                python: py_ver,
+                synced: match pylock_requirements {
+                    Some(_) => None,
+                    None => Some(SyncedState {
+                        hash: hash_sync_inputs(pyproject)?,
+                        dev: cmd.dev,
+                    }),
+                },
+                conda: Some(prefix.clone()),
+                provenance: read_venv_marker(pyproject).and_then(|x| x.provenance),
+            })?,
+        )
+        .context("failed writing venv marker file")?;
+        report_sync(pyproject, &prefix, &before, install_output);
+    }
+
+    if output != CommandOutput::Quiet {
+        eprintln!("Done!");
+    }
+    Ok(())
+}
+
+/// Points the `.venv` of the project to a conda environment.
+///
+/// An existing virtualenv is only replaced if `force` is set.  Returns `true`
+/// if the link was created or changed.
+fn link_conda_env(
+    venv: &Path,
+    prefix: &Path,
+    force: bool,
+    output: CommandOutput,
+) -> Result<bool, Error> {
+    match fs::read_link(venv) {
+        Ok(target) if target == prefix => return Ok(false),
+        Ok(_) => fs::remove_file(venv).context("failed to remove old conda environment link")?,
+        Err(_) if venv.is_dir() => {
+            if !force {
+                bail!(
+                    "a virtualenv already exists in {}. Run `rye sync -f` to replace it \
+                     with the conda environment.",
+                    venv.display()
+                );
+            }
+            fs::remove_dir_all(venv).context("failed to remove old virtualenv")?;
+        }
+        Err(_) => {}
+    }
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "Linking {} to conda environment {}",
+            venv.display(),
+            style(prefix.display()).cyan()
+        );
+    }
+    symlink(prefix, venv).context("failed to link conda environment")?;
+    Ok(true)
+}
+
+/// How often rye points out newer patch releases of the toolchain.
+const PATCH_RELEASE_HINT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Points out a newer patch release of the toolchain in the catalogue of
+/// downloadable toolchains, at most once a day.
+fn hint_newer_patch_release(py_ver: &PythonVersion) {
+    if !Config::current().is_ok_and(|x| x.check_toolchain_updates()) {
+        return;
+    }
+    let req = PythonVersionRequest {
+        kind: Some(py_ver.kind.clone()),
+        major: py_ver.major,
+        minor: Some(py_ver.minor),
+        patch: None,
+        suffix: None,
+    };
+    let newest = match get_download_url(&req, current_platform(), ARCH) {
+        Some((newest, _)) if newest > *py_ver => newest,
+        _ => return,
+    };
+
+    let marker = match get_cache_dir() {
+        Ok(dir) => dir.join(".last-toolchain-hint"),
+        Err(_) => return,
+    };
+    let due = fs::metadata(&marker)
+        .and_then(|x| x.modified())
+        .ok()
+        .and_then(|x| SystemTime::now().duration_since(x).ok())
+        .is_none_or(|x| x > PATCH_RELEASE_HINT_INTERVAL);
+    if !due {
+        return;
+    }
+    if let Some(parent) = marker.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    fs::write(&marker, b"").ok();
+
+    let floating = load_python_version_requests()
+        .is_none_or(|requests| requests.iter().any(|x| x.patch.is_none()));
+    eprintln!(
+        "{} {} is available (using {}), run `{}` to upgrade",
+        style("note:").cyan(),
+        newest,
+        py_ver,
+        if floating {
+            "rye toolchain upgrade".to_string()
+        } else {
+            format!("rye pin {}", newest)
+        }
+    );
+}
+
+/// Moves a virtualenv aside while it is rebuilt so that it can be restored
+/// when the rebuild fails or is interrupted.
+struct VenvRebuild<'a> {
+    venv: &'a Path,
+    backup: PathBuf,
+    output: CommandOutput,
+    committed: bool,
+}
+
+impl<'a> VenvRebuild<'a> {
+    fn begin(venv: &'a Path, output: CommandOutput) -> Result<VenvRebuild<'a>, Error> {
+        let mut name = venv.file_name().unwrap_or_default().to_os_string();
+        name.push(".rye-backup");
+        let backup = venv.with_file_name(name);
+        fs::remove_dir_all(&backup).ok();
+        fs::rename(venv, &backup).context("failed to move old virtualenv aside")?;
+        Ok(VenvRebuild {
+            venv,
+            backup,
+            output,
+            committed: false,
+        })
+    }
+
+    fn commit(mut self) {
+        self.committed = true;
+        fs::remove_dir_all(&self.backup).ok();
+    }
+}
+
+impl Drop for VenvRebuild<'_> {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        fs::remove_dir_all(self.venv).ok();
+        if fs::rename(&self.backup, self.venv).is_ok() && self.output != CommandOutput::Quiet {
+            eprintln!("Restored previous virtualenv");
+        }
+    }
+}
+
+/// Installs the packages the dev lockfile adds or pins differently than the
+/// production lockfile, without touching anything else in the virtualenv.
+fn install_dev_delta(
+    pyproject: &PyProject,
+    self_venv: &Path,
+    venv: &Path,
+    (lockfile, dev_lockfile): (&Path, &Path),
+    lock_options: &LockOptions,
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let _timing = timings::phase("install");
+    let delta: Vec<_> = Lockfile::load(lockfile)?
+        .diff(&Lockfile::load(dev_lockfile)?)
+        .into_iter()
+        .filter_map(|change| match change {
+            LockChange::Added(pkg) | LockChange::Changed { new: pkg, .. } => Some(pkg),
+            LockChange::Removed(_) => None,
+        })
+        .collect();
+    if delta.is_empty() {
+        if output != CommandOutput::Quiet {
+            eprintln!("No dev dependencies to install");
+        }
+        return Ok(());
+    }
+    if output != CommandOutput::Quiet {
+        eprintln!("Installing {} dev dependencies", delta.len());
+    }
+
+    // the locked lines are installed as they are, so that URL and path
+    // requirements do not come from the index instead
+    let mut requirements = String::new();
+    for pkg in &delta {
+        requirements.push_str(&pkg.requirement);
+        for hash in &pkg.hashes {
+            requirements.push_str(&format!(" --hash={}", hash));
+        }
+        requirements.push('\n');
+    }
+    let requirements = with_strict_editables(
+        &requirements,
+        &pyproject.workspace_path(),
+        &find_namespace_portions(pyproject)?,
+    );
+    let mut req_file = tempfile::NamedTempFile::new()?;
+    req_file.write_all(requirements.as_bytes())?;
+
+    let mut pip_cmd = Command::new(self_venv.join("bin/pip"));
+    pip_cmd
+        .current_dir(pyproject.workspace_path())
+        .arg("--python")
+        .arg(venv.join("bin/python"))
+        .arg("install")
+        .arg("--no-deps")
+        .arg("-r")
+        .arg(req_file.path())
+        .args(lock_options.binary_pip_args()?)
+        .env("PYTHONWARNINGS", "ignore");
+    if output == CommandOutput::Verbose {
+        pip_cmd.arg("--verbose");
+    } else {
+        pip_cmd.arg("-q");
+    }
+    apply_pip_config(&mut pip_cmd, output)?;
+    apply_build_constraints(&mut pip_cmd, pyproject);
+    let (status, stderr) = run_install(&mut pip_cmd, output).context("unable to run pip")?;
+    if !status.success() {
+        if let Some(explanation) = explain_missing_wheels(&stderr, lock_options)? {
+            bail!("Installation of dev dependencies failed\n\n{}", explanation);
+        }
+        bail!("Installation of dev dependencies failed");
+    }
+    Ok(())
+}
+
+/// Explains how to install prerequisites a failed build was missing.
+fn print_build_dep_hints(missing: &[BuildDep]) {
+    let os = OsFamily::current();
+    eprintln!(
+        "{} building a package failed because of missing system prerequisites:",
+        style("error:").red()
+    );
+    for dep in missing {
+        eprintln!("  {}: {}", dep, style(dep.remediation(os)).cyan());
+    }
+}
+
+/// Runs the configured `build.bootstrap-command` through the shell.
+fn run_build_bootstrap(bootstrap: &str, output: CommandOutput) -> Result<(), Error> {
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "Running build bootstrap command: {}",
+            style(bootstrap).cyan()
+        );
+    }
+    let status = signals::status(Command::new("sh").arg("-c").arg(bootstrap))
+        .context("unable to run build bootstrap command")?;
+    if !status.success() {
+        bail!("build bootstrap command failed with {}", status);
+    }
+    Ok(())
+}
+
+/// Rebuilds the extension modules of local maturin projects.
+///
+/// pip-sync only installs editable packages once, so without this changes
+/// to the Rust code would not show up in the virtualenv.
+fn build_native_projects(
+    pyproject: &PyProject,
+    self_venv: &Path,
+    venv: &Path,
+    output: CommandOutput,
+) -> Result<(), Error> {
+    let members;
+    let projects = match pyproject.workspace() {
+        Some(workspace) => {
+            members = workspace.iter_projects().collect::<Result<Vec<_>, _>>()?;
+            members.iter().collect()
+        }
+        None => vec![pyproject],
+    };
+    for project in projects.into_iter().filter(|x| x.is_maturin()) {
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "Building native extension for {}",
+                style(project.name().unwrap_or("<unnamed>")).cyan()
+            );
+        }
+        let mut pip_cmd = Command::new(self_venv.join("bin/pip"));
+        pip_cmd
+            .arg("--python")
+            .arg(venv.join("bin/python"))
+            .arg("install")
+            .arg("--no-deps")
+            .arg("-e")
+            .arg(&*project.root_path())
+            .env("PYTHONWARNINGS", "ignore");
+        if output == CommandOutput::Verbose {
+            pip_cmd.arg("--verbose");
+        } else {
+            pip_cmd.arg("-q");
+        }
+        apply_pip_config(&mut pip_cmd, output)?;
+        apply_build_constraints(&mut pip_cmd, pyproject);
+        let status = signals::status(&mut pip_cmd).context("unable to run pip")?;
+        if !status.success() {
+            bail!(
+                "failed to build native extension for {}",
+                project.root_path().display()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn create_virtualenv(
+    output: CommandOutput,
+    self_venv: &Path,
+    py_ver: &PythonVersion,
+    venv: &Path,
+) -> Result<(), Error> {
+    let _timing = timings::phase("create venv");
+    let py_bin = get_py_bin(py_ver)?;
+    let mut venv_cmd = Command::new(self_venv.join("bin/virtualenv"));
+    if output == CommandOutput::Verbose {
+        venv_cmd.arg("--verbose");
+    } else {
+        venv_cmd.arg("-q");
+        venv_cmd.env("PYTHONWARNINGS", "ignore");
+    }
+    venv_cmd.arg("-p");
+    venv_cmd.arg(&py_bin);
+    venv_cmd.args(VIRTUALENV_ARGS);
+    venv_cmd.arg("--");
+    venv_cmd.arg(venv);
+    apply_env_config(&mut venv_cmd, EnvTarget::Tools)?;
+    let status = signals::status(&mut venv_cmd).context("unable to invoke virtualenv command")?;
+    if !status.success() {
+        bail!("failed to initialize virtualenv");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_transient_network_error() {
+    assert!(TRANSIENT_NETWORK_ERROR.is_match(
+        "pip._vendor.urllib3.exceptions.ReadTimeoutError: \
+         HTTPSConnectionPool(host='files.pythonhosted.org', port=443): Read timed out."
+    ));
+    assert!(TRANSIENT_NETWORK_ERROR
+        .is_match("ConnectionResetError: [Errno 104] Connection reset by peer"));
+    assert!(TRANSIENT_NETWORK_ERROR
+        .is_match("ERROR: HTTP error 503 while getting https://example.com/x.whl"));
+    assert!(
+        !TRANSIENT_NETWORK_ERROR.is_match("ERROR: No matching distribution found for flask==99")
+    );
+}
+
+#[test]
+fn test_filter_local_requirements() {
+    let lockfile = "\
+# generated by rye
+-e file:.
+-e file:packages/child
+flask==2.2.3
+    # via my-project
+my-lib @ file:///src/my-lib
+markupsafe==2.1.3 \\
+    --hash=sha256:0576fe974b40a400449768941d5d0858cc624e3249dfd1e0c33674e5c7ca7aed
+";
+    assert_eq!(
+        filter_local_requirements(lockfile),
+        "\
+# generated by rye
+flask==2.2.3
+    # via my-project
+markupsafe==2.1.3 \\
+    --hash=sha256:0576fe974b40a400449768941d5d0858cc624e3249dfd1e0c33674e5c7ca7aed
+"
+    );
+}
+
+#[test]
+fn test_sync_report() {
+    let package = |name: &str, version: &str| LockedPackage {
+        name: name.into(),
+        version: Some(version.into()),
+        requirement: format!("{}=={}", name, version),
+        via: Vec::new(),
+        hashes: Vec::new(),
+    };
+    let before = Lockfile::from_packages(vec![
+        package("click", "8.1.3"),
+        package("flask", "2.2.3"),
+        package("werkzeug", "2.3.7"),
+    ]);
+    let after = Lockfile::from_packages(vec![
+        package("flask", "2.3.0"),
+        package("markupsafe", "2.1.3"),
+        package("werkzeug", "2.2.2"),
+    ]);
+    let report = SyncReport::new(Path::new(".venv"), &before.diff(&after));
+    fn names(packages: &[ReportedPackage]) -> Vec<&str> {
+        packages.iter().map(|x| x.name.as_str()).collect()
+    }
+    assert_eq!(names(&report.installed), ["markupsafe"]);
+    assert_eq!(names(&report.upgraded), ["flask"]);
+    assert_eq!(names(&report.downgraded), ["werkzeug"]);
+    assert_eq!(names(&report.removed), ["click"]);
+    assert_eq!(report.upgraded[0].old_version.as_deref(), Some("2.2.3"));
+    assert_eq!(report.upgraded[0].version.as_deref(), Some("2.3.0"));
+    assert_eq!(report.removed[0].version, None);
+}
+
+#[test]
+fn test_venv_marker_path() {
+    let tmp = tempfile::tempdir().unwrap();
+    let venv = tmp.path().join(".venv");
+    fs::create_dir(&venv).unwrap();
+    assert_eq!(venv_marker_path(&venv), venv.join("rye-venv.json"));
+
+    let prefix = tmp.path().join("conda-env");
+    fs::create_dir(&prefix).unwrap();
+    let linked = tmp.path().join("linked").join(".venv");
+    fs::create_dir(linked.parent().unwrap()).unwrap();
+    symlink(&prefix, &linked).unwrap();
+    assert_eq!(
+        venv_marker_path(&linked),
+        tmp.path().join("linked/.rye/rye-venv.json")
+    );
+}
+
+#[test]
+fn test_seed_lockfiles() {
+    let tmp = tempfile::tempdir().unwrap();
+    let checkout = tmp.path().join("checkout");
+    let dir = tmp.path().join("fallback");
+    fs::create_dir(&checkout).unwrap();
+    fs::write(checkout.join("requirements.lock"), "flask==2.2.2\n").unwrap();
+
+    seed_lockfiles(&checkout, &dir).unwrap();
+    let read = || fs::read_to_string(dir.join("requirements.lock")).unwrap();
+    assert_eq!(read(), "flask==2.2.2\n");
+    assert!(!dir.join("requirements-dev.lock").exists());
+
+    // locked again in the fallback folder: the checkout did not change
+    fs::write(dir.join("requirements.lock"), "flask==2.2.3\n").unwrap();
+    seed_lockfiles(&checkout, &dir).unwrap();
+    assert_eq!(read(), "flask==2.2.3\n");
+
+    // the checkout was updated in place
+    fs::write(checkout.join("requirements.lock"), "flask==2.3.0\n").unwrap();
+    seed_lockfiles(&checkout, &dir).unwrap();
+    assert_eq!(read(), "flask==2.3.0\n");
+}
@@ -0,0 +1,76 @@
+//! Wall time spent in the phases of a command, as reported by `--timings`.
+//!
+//! Operations mark their phases with [`phase`].  Nothing is recorded unless
+//! [`enable`] was called, so the guards are cheap to leave in place.
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Serialize, Serializer};
+
+static TIMINGS: Mutex<Option<Vec<Timing>>> = Mutex::new(None);
+
+/// The time spent in one phase, summed over all times it ran.
+#[derive(Debug, Clone, Serialize)]
+pub struct Timing {
+    pub phase: &'static str,
+    #[serde(rename = "seconds", serialize_with = "serialize_seconds")]
+    pub duration: Duration,
+    /// How often the phase ran.
+    pub count: usize,
+}
+
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Starts recording timings.
+pub fn enable() {
+    TIMINGS.lock().unwrap().get_or_insert_with(Vec::new);
+}
+
+/// Marks the start of a phase, which ends when the guard is dropped.
+///
+/// Phases can nest, the outer phase includes the time of the inner ones.
+#[must_use]
+pub fn phase(name: &'static str) -> PhaseGuard {
+    let mut start = None;
+    if let Some(ref mut timings) = *TIMINGS.lock().unwrap() {
+        // added right away so that phases are listed in the order they start
+        if !timings.iter().any(|x| x.phase == name) {
+            timings.push(Timing {
+                phase: name,
+                duration: Duration::ZERO,
+                count: 0,
+            });
+        }
+        start = Some(Instant::now());
+    }
+    PhaseGuard { name, start }
+}
+
+/// Records the time of a phase when dropped, see [`phase`].
+#[derive(Debug)]
+pub struct PhaseGuard {
+    name: &'static str,
+    start: Option<Instant>,
+}
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        let start = match self.start {
+            Some(start) => start,
+            None => return,
+        };
+        if let Some(ref mut timings) = *TIMINGS.lock().unwrap() {
+            if let Some(timing) = timings.iter_mut().find(|x| x.phase == self.name) {
+                timing.duration += start.elapsed();
+                timing.count += 1;
+            }
+        }
+    }
+}
+
+/// Returns the recorded timings in the order the phases first started.
+pub fn recorded() -> Vec<Timing> {
+    TIMINGS.lock().unwrap().clone().unwrap_or_default()
+}
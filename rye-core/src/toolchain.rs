@@ -0,0 +1,79 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Error;
+
+use crate::bootstrap::fetch;
+use crate::config::{get_canonical_py_path, get_py_bin, list_known_toolchains};
+use crate::sources::{matches_version, PythonVersion, PythonVersionRequest};
+use crate::utils::CommandOutput;
+
+/// A Python toolchain managed by rye.
+///
+/// Toolchains are either downloaded into `~/.rye/py` or registered links to
+/// interpreters installed elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toolchain {
+    version: PythonVersion,
+    path: PathBuf,
+}
+
+impl Toolchain {
+    /// Returns the toolchain for an exact version if it is installed.
+    pub fn get(version: &PythonVersion) -> Result<Option<Toolchain>, Error> {
+        let path = get_canonical_py_path(version)?;
+        if path.is_dir() || path.is_file() {
+            Ok(Some(Toolchain {
+                version: version.clone(),
+                path,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns all installed toolchains, newest first.
+    pub fn list_installed() -> Result<Vec<Toolchain>, Error> {
+        let mut rv = Vec::new();
+        for version in list_known_toolchains()? {
+            if let Some(toolchain) = Toolchain::get(&version)? {
+                rv.push(toolchain);
+            }
+        }
+        rv.sort_by(|a, b| b.version.cmp(&a.version));
+        Ok(rv)
+    }
+
+    /// Returns the newest installed toolchain matching the request.
+    pub fn find_installed(req: &PythonVersionRequest) -> Result<Option<Toolchain>, Error> {
+        Ok(Toolchain::list_installed()?
+            .into_iter()
+            .find(|x| matches_version(req, &x.version)))
+    }
+
+    /// Downloads a toolchain matching the request unless it is installed.
+    pub fn fetch(req: &PythonVersionRequest, output: CommandOutput) -> Result<Toolchain, Error> {
+        let version = fetch(req, output)?;
+        let path = get_canonical_py_path(&version)?;
+        Ok(Toolchain { version, path })
+    }
+
+    /// The version of the toolchain.
+    pub fn version(&self) -> &PythonVersion {
+        &self.version
+    }
+
+    /// The folder of the toolchain, or the interpreter for registered ones.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Is this a registered interpreter rather than a downloaded one?
+    pub fn is_registered(&self) -> bool {
+        self.path.is_file()
+    }
+
+    /// Returns the path to the Python interpreter.
+    pub fn python_bin(&self) -> Result<PathBuf, Error> {
+        get_py_bin(&self.version)
+    }
+}
@@ -0,0 +1,400 @@
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Cursor, Write};
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, ExitStatus, Stdio};
+use std::{env, fmt, fs};
+
+use anyhow::{anyhow, bail, Error};
+use pep508_rs::{Requirement, VersionOrUrl};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::signals;
+
+/// Controls the fetch output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum CommandOutput {
+    /// Regular output
+    #[default]
+    Normal,
+    /// Extra verbose output
+    Verbose,
+    /// No output
+    Quiet,
+}
+
+impl CommandOutput {
+    /// Returns the preferred command output for those flags.
+    pub fn from_quiet_and_verbose(quiet: bool, verbose: bool) -> CommandOutput {
+        if quiet {
+            CommandOutput::Quiet
+        } else if verbose {
+            CommandOutput::Verbose
+        } else {
+            CommandOutput::Normal
+        }
+    }
+}
+
+/// Formats a Python requirement.
+pub fn format_requirement(req: &Requirement) -> impl fmt::Display + '_ {
+    struct Helper<'x>(&'x Requirement);
+
+    impl<'x> fmt::Display for Helper<'x> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0.name)?;
+            if let Some(extras) = &self.0.extras {
+                write!(f, "[{}]", extras.join(","))?;
+            }
+            if let Some(version_or_url) = &self.0.version_or_url {
+                match version_or_url {
+                    VersionOrUrl::VersionSpecifier(version_specifier) => {
+                        let version_specifier: Vec<String> =
+                            version_specifier.iter().map(ToString::to_string).collect();
+                        write!(f, "{}", version_specifier.join(", "))?;
+                    }
+                    VersionOrUrl::Url(url) => {
+                        write!(f, " @ {}", url)?;
+                    }
+                }
+            }
+            if let Some(marker) = &self.0.marker {
+                write!(f, " ; {}", marker)?;
+            }
+            Ok(())
+        }
+    }
+
+    Helper(req)
+}
+
+/// Creates a curl handle for a URL that honors the network config.
+pub fn make_curl_handle(url: &str) -> Result<curl::easy::Easy, Error> {
+    let config = Config::current()?;
+    let mut handle = curl::easy::Easy::new();
+    handle.url(url)?;
+    handle.follow_location(true)?;
+    if let Some(ca_bundle) = config.ca_bundle() {
+        handle.cainfo(ca_bundle)?;
+    }
+    if let Ok(parsed) = url::Url::parse(url) {
+        if let Some(host) = parsed.host_str() {
+            let host_with_port = parsed.port().map(|port| format!("{}:{}", host, port));
+            if config
+                .trusted_hosts()
+                .iter()
+                .any(|x| *x == host || Some(*x) == host_with_port.as_deref())
+            {
+                handle.ssl_verify_peer(false)?;
+                handle.ssl_verify_host(false)?;
+            }
+        }
+    }
+    Ok(handle)
+}
+
+/// Removes the credentials (`user:token@`) from a URL so that it can be
+/// written to files that get committed.
+///
+/// Anything that is not a URL with credentials, like a find-links folder, is
+/// returned unchanged.
+pub fn redact_url(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) if !parsed.username().is_empty() || parsed.password().is_some() => {
+            // only fails for URLs that cannot have credentials
+            parsed.set_username("").ok();
+            parsed.set_password(None).ok();
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Parses the contents of a `.env` file.
+///
+/// Supports `KEY=value` lines with an optional `export` prefix, comments and
+/// single or double quoted values.  Double quoted values understand `\n`,
+/// `\t`, `\"` and `\\` escapes.
+pub fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut rv = Vec::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected KEY=value", idx + 1))?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            bail!("line {}: invalid variable name '{}'", idx + 1, key);
+        }
+        let value = value.trim();
+        let value = if let Some(rest) = value.strip_prefix('"') {
+            let mut rv = String::new();
+            let mut chars = rest.chars();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => rv.push('\n'),
+                        Some('t') => rv.push('\t'),
+                        Some(c) => rv.push(c),
+                        None => bail!("line {}: unterminated string", idx + 1),
+                    },
+                    Some(c) => rv.push(c),
+                    None => bail!("line {}: unterminated string", idx + 1),
+                }
+            }
+            rv
+        } else if let Some(rest) = value.strip_prefix('\'') {
+            rest.split_once('\'')
+                .ok_or_else(|| anyhow!("line {}: unterminated string", idx + 1))?
+                .0
+                .to_string()
+        } else {
+            // unquoted values end at an inline comment
+            match value.split_once(" #") {
+                Some((value, _)) => value.trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+        rv.push((key.to_string(), value));
+    }
+    Ok(rv)
+}
+
+/// Unpacks a tarball.
+///
+/// Today this assumes that the tarball is zstd compressed which happens
+/// to be what the indygreg python builds use.
+pub fn unpack_tarball(contents: &[u8], dst: &Path, strip_components: usize) -> Result<(), Error> {
+    let reader = Cursor::new(contents);
+    let decoder = zstd::stream::read::Decoder::with_buffer(reader)?;
+    let mut archive = tar::Archive::new(decoder);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?;
+        let mut components = name.components();
+        for _ in 0..strip_components {
+            components.next();
+        }
+        let path = dst.join(components.as_path());
+
+        // only unpack if it's save to do so
+        if path != Path::new("") && path.strip_prefix(dst).is_ok() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).ok();
+            }
+            entry.unpack(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Recursively copies a directory.
+///
+/// Symlinks are recreated rather than followed and file permissions are
+/// retained.
+pub fn copy_dir(src: &Path, dst: &Path) -> Result<(), Error> {
+    for entry in walkdir::WalkDir::new(src) {
+        let entry = entry?;
+        let target = dst.join(entry.path().strip_prefix(src)?);
+        if entry.path_is_symlink() {
+            symlink(fs::read_link(entry.path())?, &target)?;
+        } else if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a file through a temporary file next to it which is then renamed
+/// into place, so that an interrupted write never leaves a truncated file.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(format!(".tmp-{}", process::id()));
+    let tmp = path.with_file_name(tmp_name);
+    let rv = fs::File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_ref())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+    if rv.is_err() {
+        fs::remove_file(&tmp).ok();
+    }
+    rv
+}
+
+/// Returns the hex encoded SHA-256 digest of a file.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    Ok(format!("{:x}", Sha256::digest(fs::read(path)?)))
+}
+
+/// Formats seconds since the epoch as UTC timestamp in RFC 3339 format.
+pub fn format_timestamp(secs: u64) -> String {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    let time = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Formats a GitHub Actions workflow command which shows a message as an
+/// annotation.  `level` is `error`, `warning` or `notice`.
+///
+/// The file is made relative to the checkout (`GITHUB_WORKSPACE`, or else
+/// the current folder), as that is what the annotation has to refer to.
+/// The line (1-based) is only used together with a file.
+pub fn github_annotation(
+    level: &str,
+    file: Option<&Path>,
+    line: Option<usize>,
+    title: &str,
+    message: &str,
+) -> String {
+    fn escape(value: &str, property: bool) -> String {
+        let rv = value
+            .replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A");
+        if property {
+            rv.replace(':', "%3A").replace(',', "%2C")
+        } else {
+            rv
+        }
+    }
+    let mut properties = Vec::new();
+    if let Some(file) = file {
+        let base = env::var_os("GITHUB_WORKSPACE")
+            .map(PathBuf::from)
+            .or_else(|| env::current_dir().ok());
+        let file = base
+            .and_then(|base| pathdiff::diff_paths(file, base))
+            .unwrap_or_else(|| file.to_path_buf());
+        properties.push(format!(
+            "file={}",
+            escape(&file.display().to_string(), true)
+        ));
+        if let Some(line) = line {
+            properties.push(format!("line={}", line));
+        }
+    }
+    properties.push(format!("title={}", escape(title, true)));
+    format!(
+        "::{} {}::{}",
+        level,
+        properties.join(","),
+        escape(message, false)
+    )
+}
+
+/// Runs a command and returns its stderr next to the exit status.
+///
+/// The stderr is still forwarded as it comes in.
+pub fn run_capturing_stderr(cmd: &mut Command) -> Result<(ExitStatus, String), Error> {
+    let mut child = cmd.stderr(Stdio::piped()).spawn()?;
+    let _guard = signals::track_child(&child);
+    let mut captured = String::new();
+    for line in BufReader::new(child.stderr.take().unwrap())
+        .lines()
+        .map_while(Result::ok)
+    {
+        eprintln!("{}", line);
+        captured.push_str(&line);
+        captured.push('\n');
+    }
+    let status = child.wait()?;
+    signals::check_interrupted()?;
+    Ok((status, captured))
+}
+
+#[test]
+fn test_parse_env_file() {
+    let env = parse_env_file(
+        "# comment\n\
+         PLAIN=value # trailing\n\
+         export EXPORTED=1\n\
+         SINGLE='a # b'\n\
+         DOUBLE=\"line\\nbreak \\\"q\\\"\"\n\
+         EMPTY=\n",
+    )
+    .unwrap();
+    assert_eq!(
+        env,
+        vec![
+            ("PLAIN".to_string(), "value".to_string()),
+            ("EXPORTED".to_string(), "1".to_string()),
+            ("SINGLE".to_string(), "a # b".to_string()),
+            ("DOUBLE".to_string(), "line\nbreak \"q\"".to_string()),
+            ("EMPTY".to_string(), "".to_string()),
+        ]
+    );
+    assert!(parse_env_file("NOPE").is_err());
+}
+
+#[test]
+fn test_write_atomic() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("requirements.lock");
+    fs::write(&path, "old").unwrap();
+    write_atomic(&path, "new").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_format_timestamp() {
+    assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+    assert_eq!(format_timestamp(1760467894), "2025-10-14T18:51:34Z");
+}
+
+#[test]
+fn test_github_annotation() {
+    assert_eq!(
+        github_annotation(
+            "error",
+            Some(Path::new("/nonexistent/requirements.lock")),
+            None,
+            "rye: lockfile",
+            "out of date:\n  ~ flask 2.2.3 -> 2.3.2 (100%)"
+        )
+        .split_once(',')
+        .map(|x| x.1),
+        Some("title=rye%3A lockfile::out of date:%0A  ~ flask 2.2.3 -> 2.3.2 (100%25)")
+    );
+    assert_eq!(
+        github_annotation("warning", None, Some(3), "check", "a, b"),
+        "::warning title=check::a, b"
+    );
+    assert!(github_annotation(
+        "warning",
+        Some(Path::new("/nonexistent/pyproject.toml")),
+        Some(3),
+        "check",
+        "a"
+    )
+    .contains("pyproject.toml,line=3,title=check"));
+}
@@ -0,0 +1,181 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Error};
+
+use crate::pyproject::{normalize_package_name, DependencyKind, PyProject, Workspace};
+
+/// A member of a workspace together with its local dependencies.
+#[derive(Debug)]
+pub struct Member {
+    /// The normalized name of the member (or its path if it has no name).
+    pub name: String,
+    /// The loaded project.
+    pub project: PyProject,
+    /// Indexes of the members this member depends on.
+    pub deps: BTreeSet<usize>,
+}
+
+/// The dependency graph between the members of a workspace.
+#[derive(Debug)]
+pub struct WorkspaceGraph {
+    workspace: Arc<Workspace>,
+    members: Vec<Member>,
+}
+
+impl WorkspaceGraph {
+    /// Loads all members of a workspace and resolves their dependencies.
+    ///
+    /// A member depends on another member if any of its normal or dev
+    /// dependencies refers to the other member by name.
+    pub fn load(workspace: &Arc<Workspace>) -> Result<WorkspaceGraph, Error> {
+        let mut projects = workspace.iter_projects().collect::<Result<Vec<_>, _>>()?;
+        projects.sort_by(|a, b| a.root_path().cmp(&b.root_path()));
+
+        let mut members: Vec<Member> = projects
+            .into_iter()
+            .map(|project| Member {
+                name: project.normalized_name().unwrap_or_else(|| {
+                    project
+                        .root_path()
+                        .strip_prefix(workspace.path())
+                        .unwrap_or(&project.root_path())
+                        .display()
+                        .to_string()
+                }),
+                project,
+                deps: BTreeSet::new(),
+            })
+            .collect();
+
+        let by_name: HashMap<String, usize> = members
+            .iter()
+            .enumerate()
+            .map(|(idx, member)| (member.name.clone(), idx))
+            .collect();
+        for member in members.iter_mut() {
+            for kind in [DependencyKind::Normal, DependencyKind::Dev] {
+                for dep in member.project.iter_dependencies(kind) {
+                    if let Some(&idx) = by_name.get(&normalize_package_name(&dep.name)) {
+                        if by_name.get(&member.name) != Some(&idx) {
+                            member.deps.insert(idx);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(WorkspaceGraph {
+            workspace: workspace.clone(),
+            members,
+        })
+    }
+
+    /// Returns all members.
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    /// Returns the given members plus everything that transitively depends on them.
+    pub fn with_dependents(&self, members: &BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut rv = members.clone();
+        loop {
+            let before = rv.len();
+            for (idx, member) in self.members.iter().enumerate() {
+                if member.deps.iter().any(|dep| rv.contains(dep)) {
+                    rv.insert(idx);
+                }
+            }
+            if rv.len() == before {
+                return rv;
+            }
+        }
+    }
+
+    /// Groups the members into levels in dependency order.
+    ///
+    /// Every member only depends on members of earlier levels, so members
+    /// within the same level are independent of each other.
+    pub fn topological_levels(&self) -> Result<Vec<Vec<usize>>, Error> {
+        let mut levels: Vec<Option<usize>> = vec![None; self.members.len()];
+        let mut rv: Vec<Vec<usize>> = Vec::new();
+        let mut remaining = self.members.len();
+
+        while remaining > 0 {
+            let mut current = Vec::new();
+            for (idx, member) in self.members.iter().enumerate() {
+                if levels[idx].is_none()
+                    && member
+                        .deps
+                        .iter()
+                        .all(|&dep| levels[dep].is_some_and(|x| x < rv.len()))
+                {
+                    current.push(idx);
+                }
+            }
+            if current.is_empty() {
+                let cycle = self
+                    .members
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, _)| levels[*idx].is_none())
+                    .map(|(_, member)| member.name.as_str())
+                    .collect::<Vec<_>>();
+                bail!(
+                    "dependency cycle between workspace members: {}",
+                    cycle.join(", ")
+                );
+            }
+            for &idx in &current {
+                levels[idx] = Some(rv.len());
+            }
+            remaining -= current.len();
+            rv.push(current);
+        }
+
+        Ok(rv)
+    }
+
+    /// Finds the members touched by changes since the given git ref.
+    ///
+    /// This includes uncommitted changes to files already tracked by git.
+    pub fn changed_since(&self, git_ref: &str) -> Result<BTreeSet<usize>, Error> {
+        let root = self.workspace.path();
+        let output = Command::new("git")
+            .arg("diff")
+            .arg("--name-only")
+            .arg("--relative")
+            .arg(git_ref)
+            .arg("--")
+            .current_dir(&*root)
+            .output()
+            .context("unable to run git")?;
+        if !output.status.success() {
+            bail!(
+                "unable to determine changes since '{}': {}",
+                git_ref,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let mut rv = BTreeSet::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if let Some(idx) = self.find_member_for_path(&root.join(line)) {
+                rv.insert(idx);
+            }
+        }
+        Ok(rv)
+    }
+
+    /// Returns the innermost member that contains the given path.
+    fn find_member_for_path(&self, path: &Path) -> Option<usize> {
+        self.members
+            .iter()
+            .enumerate()
+            .filter(|(_, member)| path.starts_with(member.project.root_path()))
+            .max_by_key(|(_, member)| member.project.root_path().components().count())
+            .map(|(idx, _)| idx)
+    }
+}
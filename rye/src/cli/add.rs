@@ -3,13 +3,18 @@ use std::str::FromStr;
 
 use anyhow::{bail, Context, Error};
 use clap::Parser;
+use console::{style, Term};
 use pep440_rs::VersionSpecifiers;
 use pep508_rs::{Requirement, VersionOrUrl};
 use serde::Deserialize;
 
-use crate::bootstrap::ensure_self_venv;
-use crate::pyproject::{DependencyKind, PyProject};
-use crate::utils::{format_requirement, CommandOutput};
+use rye_core::bootstrap::ensure_self_venv;
+use rye_core::check::{check_requirement_policy, load_dependency_policy};
+use rye_core::config::{apply_env_config, EnvTarget};
+use rye_core::imports::find_undeclared_imports;
+use rye_core::index::find_projects;
+use rye_core::pyproject::{normalize_package_name, DependencyKind, PyProject};
+use rye_core::utils::{format_requirement, CommandOutput};
 
 #[derive(Deserialize, Debug)]
 struct Match {
@@ -28,9 +33,18 @@ pub struct Args {
     /// Add this to an optional dependency group.
     #[arg(long, conflicts_with = "dev")]
     optional: Option<String>,
+    /// Add this to the requirements of the build backend (`build-system.requires`).
+    #[arg(long, conflicts_with_all = ["dev", "optional", "from_import"])]
+    build_backend_dep: bool,
     /// Adds a dependency with a specific feature.
     #[arg(short, long)]
     features: Vec<String>,
+    /// Search the index for the given names and pick packages interactively.
+    #[arg(short, long)]
+    interactive: bool,
+    /// Propose the packages the project's code imports but does not declare.
+    #[arg(long, conflicts_with_all = ["requirements", "interactive"])]
+    from_import: bool,
     /// Enables verbose diagnostics.
     #[arg(short, long)]
     verbose: bool,
@@ -47,8 +61,20 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
     unearth_path.push("unearth");
 
     let mut pyproject_toml = PyProject::discover()?;
+    let policy = load_dependency_policy(&pyproject_toml)?;
+
+    let requirements = if cmd.from_import {
+        propose_from_imports(&pyproject_toml, output)?
+    } else if cmd.interactive {
+        cmd.requirements
+            .iter()
+            .map(|query| select_interactively(query))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        cmd.requirements
+    };
 
-    for str_requirement in cmd.requirements {
+    for str_requirement in requirements {
         let mut requirement = Requirement::from_str(&str_requirement)?;
         for feature in cmd.features.iter().flat_map(|x| x.split(',')) {
             let feature = feature.trim();
@@ -58,11 +84,13 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
             }
         }
 
-        let unearth = Command::new(&unearth_path)
+        let mut unearth_cmd = Command::new(&unearth_path);
+        unearth_cmd
             .arg("--")
             .arg(&str_requirement)
-            .stdout(Stdio::piped())
-            .output()?;
+            .stdout(Stdio::piped());
+        apply_env_config(&mut unearth_cmd, EnvTarget::Tools)?;
+        let unearth = unearth_cmd.output()?;
         if !unearth.status.success() {
             bail!("did not find package {}", format_requirement(&requirement));
         }
@@ -74,11 +102,19 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
             ));
         }
         requirement.name = m.name;
+        if policy.enforce_on_add {
+            let violations = check_requirement_policy(&policy, &requirement);
+            if !violations.is_empty() {
+                bail!("{} (enforced by tool.rye.policy)", violations.join(", "));
+            }
+        }
 
         pyproject_toml.add_dependency(
             &requirement,
             if cmd.dev {
                 DependencyKind::Dev
+            } else if cmd.build_backend_dep {
+                DependencyKind::BuildSystem
             } else if let Some(ref section) = cmd.optional {
                 DependencyKind::Optional(section.into())
             } else {
@@ -98,3 +134,122 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Lists the packages the project imports without declaring them and asks
+/// whether to add them.  Outside of a terminal they are only listed.
+fn propose_from_imports(
+    pyproject: &PyProject,
+    output: CommandOutput,
+) -> Result<Vec<String>, Error> {
+    let root = pyproject.root_path();
+    let mut proposed: Vec<String> = Vec::new();
+    for import in find_undeclared_imports(pyproject)? {
+        let path = import.path.strip_prefix(&root).unwrap_or(&import.path);
+        match import.distribution {
+            Some(dist) => {
+                if proposed.is_empty() && output != CommandOutput::Quiet {
+                    eprintln!("Imports without a declared dependency:");
+                }
+                if output != CommandOutput::Quiet {
+                    eprintln!(
+                        "  {} {} {}",
+                        style(&import.module).cyan(),
+                        style(&dist).bold(),
+                        style(format!("(imported in {})", path.display())).dim()
+                    );
+                }
+                if !proposed
+                    .iter()
+                    .any(|x| normalize_package_name(x) == normalize_package_name(&dist))
+                {
+                    proposed.push(dist);
+                }
+            }
+            None => {
+                if output != CommandOutput::Quiet {
+                    eprintln!(
+                        "{} no package found for import {} in {}",
+                        style("warning:").yellow(),
+                        style(&import.module).cyan(),
+                        path.display()
+                    );
+                }
+            }
+        }
+    }
+
+    if proposed.is_empty() {
+        if output != CommandOutput::Quiet {
+            eprintln!("Nothing to add");
+        }
+        return Ok(proposed);
+    }
+
+    let term = Term::stderr();
+    if !term.is_term() {
+        if output != CommandOutput::Quiet {
+            eprintln!("To add them run: rye add {}", proposed.join(" "));
+        }
+        return Ok(Vec::new());
+    }
+    term.write_str("Add these dependencies? [Y/n]: ")?;
+    let answer = term.read_line()?;
+    if matches!(answer.trim().to_ascii_lowercase().as_str(), "n" | "no") {
+        return Ok(Vec::new());
+    }
+    Ok(proposed)
+}
+
+/// Searches the index for a query and lets the user pick a package and
+/// constraint style.  Returns the resulting requirement string.
+fn select_interactively(query: &str) -> Result<String, Error> {
+    let term = Term::stderr();
+    let projects = find_projects(query)?;
+    if projects.is_empty() {
+        bail!("no packages found matching '{}'", query);
+    }
+
+    term.write_line(&format!("Packages matching {}:", style(query).cyan()))?;
+    for (idx, project) in projects.iter().enumerate() {
+        term.write_line(&format!(
+            "  {}) {} {} {}",
+            idx + 1,
+            style(&project.info.name).cyan(),
+            style(&project.info.version).dim(),
+            project.info.summary.as_deref().unwrap_or("")
+        ))?;
+    }
+    let project = &projects[prompt_choice(&term, "Select package", projects.len())?];
+    let version = &project.info.version;
+
+    let styles = [
+        format!("~={}", version),
+        format!(">={}", version),
+        format!("=={}", version),
+    ];
+    term.write_line("Version constraint:")?;
+    for (idx, constraint) in styles.iter().enumerate() {
+        term.write_line(&format!("  {}) {}", idx + 1, constraint))?;
+    }
+    let constraint = &styles[prompt_choice(&term, "Select constraint", styles.len())?];
+
+    Ok(format!("{}{}", project.info.name, constraint))
+}
+
+/// Prompts for a number between 1 and `count` and returns the zero based index.
+///
+/// An empty answer picks the first entry.
+fn prompt_choice(term: &Term, prompt: &str, count: usize) -> Result<usize, Error> {
+    loop {
+        term.write_str(&format!("{} [1]: ", prompt))?;
+        let answer = term.read_line()?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(0);
+        }
+        match answer.parse::<usize>() {
+            Ok(choice) if choice >= 1 && choice <= count => return Ok(choice - 1),
+            _ => term.write_line(&format!("Please enter a number from 1 to {}", count))?,
+        }
+    }
+}
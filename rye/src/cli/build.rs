@@ -0,0 +1,268 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Error};
+use clap::Parser;
+use console::style;
+
+use rye_core::bootstrap::{ensure_self_venv, fetch};
+use rye_core::config::{
+    apply_env_config, get_default_python_version, get_py_bin, load_python_version, EnvTarget,
+};
+use rye_core::lock::apply_build_constraints;
+use rye_core::pyproject::PyProject;
+use rye_core::sources::{PythonVersion, PythonVersionRequest};
+use rye_core::utils::{sha256_file, CommandOutput};
+
+/// The earliest timestamp zip files can store (1980-01-01).
+const ZIP_EPOCH: u64 = 315532800;
+
+/// Rewrites wheels with fixed timestamps and permissions.
+const NORMALIZE_WHEEL_SCRIPT: &str = r#"
+import sys
+import time
+import zipfile
+
+date_time = time.gmtime(int(sys.argv[1]))[:6]
+for path in sys.argv[2:]:
+    with zipfile.ZipFile(path) as src:
+        entries = [(info, src.read(info)) for info in src.infolist()]
+    with zipfile.ZipFile(path, "w", zipfile.ZIP_DEFLATED) as dst:
+        for info, data in entries:
+            new = zipfile.ZipInfo(info.filename, date_time)
+            new.create_system = 3
+            if info.is_dir():
+                mode = 0o40755
+            elif (info.external_attr >> 16) & 0o111:
+                mode = 0o100755
+            else:
+                mode = 0o100644
+            new.external_attr = mode << 16
+            new.compress_type = zipfile.ZIP_DEFLATED
+            dst.writestr(new, data)
+"#;
+
+/// Builds a wheel of the project.
+///
+/// The wheel is built by the project's build backend.  For maturin projects
+/// the interpreter and Rust target decide which native extension is built.
+/// `SOURCE_DATE_EPOCH` defaults to the time of the last git commit and the
+/// timestamps and permissions in the wheel are normalized, so that building
+/// the same sources gives the same wheel.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// Where to place the wheel (defaults to `dist`).
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+    /// The Python version to build for (defaults to the pinned one).
+    #[arg(short, long)]
+    python: Option<String>,
+    /// The Rust target triple to build the extension module for (maturin only).
+    #[arg(long)]
+    target: Option<String>,
+    /// Build twice and fail if the wheels differ.
+    #[arg(long)]
+    verify_reproducible: bool,
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let pyproject = PyProject::discover()?;
+    let out_dir = match cmd.out {
+        Some(ref path) => env::current_dir()?.join(path),
+        None => pyproject.workspace_path().join("dist"),
+    };
+
+    if cmd.target.is_some() && !pyproject.is_maturin() {
+        bail!("--target is only supported for projects built with maturin");
+    }
+
+    let py_ver: PythonVersionRequest = match cmd.python {
+        Some(ref py) => py.parse()?,
+        None => load_python_version()
+            .map_or_else(get_default_python_version, Ok)?
+            .into(),
+    };
+    let py_ver = fetch(&py_ver, output).context("failed fetching toolchain")?;
+    let source_date_epoch = get_source_date_epoch(&pyproject.root_path());
+    if output == CommandOutput::Verbose {
+        eprintln!("SOURCE_DATE_EPOCH: {}", source_date_epoch);
+    }
+
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "Building {} for {}",
+            style(pyproject.name().unwrap_or("<unnamed>")).cyan(),
+            style(&py_ver).cyan()
+        );
+    }
+    let build_dir = tempfile::tempdir()?;
+    let wheels = build_wheels(
+        &cmd,
+        &pyproject,
+        &py_ver,
+        source_date_epoch,
+        build_dir.path(),
+        output,
+    )?;
+
+    if cmd.verify_reproducible {
+        if output != CommandOutput::Quiet {
+            eprintln!("Building again to verify the build is reproducible");
+        }
+        let verify_dir = tempfile::tempdir()?;
+        let again = build_wheels(
+            &cmd,
+            &pyproject,
+            &py_ver,
+            source_date_epoch,
+            verify_dir.path(),
+            output,
+        )?;
+        let mut differs = false;
+        for wheel in &wheels {
+            let name = wheel.file_name().unwrap_or_default();
+            let digest = sha256_file(wheel)?;
+            let digest_again = match again.iter().find(|x| x.file_name() == Some(name)) {
+                Some(path) => Some(sha256_file(path)?),
+                None => None,
+            };
+            if digest_again.as_ref() == Some(&digest) {
+                if output != CommandOutput::Quiet {
+                    eprintln!("  {} sha256:{}", name.to_string_lossy(), digest);
+                }
+            } else {
+                differs = true;
+                eprintln!(
+                    "{} {} differs between builds (sha256:{} and {})",
+                    style("error:").red(),
+                    name.to_string_lossy(),
+                    digest,
+                    digest_again.map_or("missing".into(), |x| format!("sha256:{}", x))
+                );
+            }
+        }
+        if differs || again.len() != wheels.len() {
+            bail!("build is not reproducible");
+        }
+    }
+
+    fs::create_dir_all(&out_dir)
+        .with_context(|| format!("unable to create {}", out_dir.display()))?;
+    for wheel in &wheels {
+        fs::copy(wheel, out_dir.join(wheel.file_name().unwrap_or_default()))
+            .context("unable to copy wheel")?;
+    }
+
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "{} Built wheel into {}",
+            style("success:").green(),
+            out_dir.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the `SOURCE_DATE_EPOCH` to build with.
+///
+/// An explicitly set one wins, otherwise it is the time of the last commit.
+/// Outside of git the earliest zip timestamp is used so the result is still
+/// reproducible.  Zip files cannot go back further than that either.
+fn get_source_date_epoch(root: &Path) -> u64 {
+    let from_env = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|x| x.trim().parse().ok());
+    let from_git = || {
+        Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["log", "-1", "--format=%ct"])
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|x| x.status.success())
+            .and_then(|x| String::from_utf8_lossy(&x.stdout).trim().parse().ok())
+    };
+    from_env
+        .or_else(from_git)
+        .unwrap_or(ZIP_EPOCH)
+        .max(ZIP_EPOCH)
+}
+
+/// Builds the wheels of the project into `dir` and normalizes them.
+fn build_wheels(
+    cmd: &Args,
+    pyproject: &PyProject,
+    py_ver: &PythonVersion,
+    source_date_epoch: u64,
+    dir: &Path,
+    output: CommandOutput,
+) -> Result<Vec<PathBuf>, Error> {
+    let self_venv = ensure_self_venv(output)?;
+    let py_bin = get_py_bin(py_ver)?;
+    let mut pip_cmd = Command::new(self_venv.join("bin/pip"));
+    pip_cmd
+        .arg("--python")
+        .arg(&py_bin)
+        .arg("wheel")
+        .arg("--no-deps")
+        .arg("--wheel-dir")
+        .arg(dir)
+        .arg(&*pyproject.root_path())
+        .env("PYTHONWARNINGS", "ignore")
+        .env("SOURCE_DATE_EPOCH", source_date_epoch.to_string());
+    if let Some(ref target) = cmd.target {
+        // maturin's build backend picks up extra arguments from here
+        let mut maturin_args = env::var("MATURIN_PEP517_ARGS").unwrap_or_default();
+        if !maturin_args.is_empty() {
+            maturin_args.push(' ');
+        }
+        maturin_args.push_str("--target ");
+        maturin_args.push_str(target);
+        pip_cmd.env("MATURIN_PEP517_ARGS", maturin_args);
+    }
+    if output == CommandOutput::Verbose {
+        pip_cmd.arg("--verbose");
+    } else {
+        pip_cmd.arg("-q");
+    }
+    apply_env_config(&mut pip_cmd, EnvTarget::Tools)?;
+    apply_build_constraints(&mut pip_cmd, pyproject);
+    let status = pip_cmd.status().context("unable to run pip")?;
+    if !status.success() {
+        bail!("failed to build wheel");
+    }
+
+    let mut wheels = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|x| x == "whl") {
+            wheels.push(path);
+        }
+    }
+    wheels.sort();
+    if wheels.is_empty() {
+        return Ok(wheels);
+    }
+    let status = Command::new(&py_bin)
+        .arg("-c")
+        .arg(NORMALIZE_WHEEL_SCRIPT)
+        .arg(source_date_epoch.to_string())
+        .args(&wheels)
+        .status()
+        .context("unable to run python")?;
+    if !status.success() {
+        bail!("failed to normalize wheel");
+    }
+    Ok(wheels)
+}
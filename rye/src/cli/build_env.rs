@@ -0,0 +1,201 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Error};
+use clap::Parser;
+use console::style;
+use tempfile::NamedTempFile;
+
+use rye_core::bootstrap::{ensure_self_venv, fetch};
+use rye_core::config::{
+    apply_env_config, get_canonical_py_path, get_default_python_version, get_py_bin,
+    load_python_version, EnvTarget,
+};
+use rye_core::lock::write_non_editable_lockfile;
+use rye_core::pyproject::PyProject;
+use rye_core::sync::{create_virtualenv, sync, SyncMode, SyncOptions};
+use rye_core::utils::{copy_dir, CommandOutput};
+
+/// Shebang that locates the interpreter relative to the script itself.
+///
+/// This is the same trick that distlib uses: the file is a valid shell script
+/// that re-executes itself with the neighbouring python, and python sees the
+/// second and third line as a harmless string literal.
+fn relocatable_shebang(python: &str) -> String {
+    format!(
+        "#!/bin/sh\n'''exec' \"$(dirname -- \"$0\")/{}\" \"$0\" \"$@\"\n' '''\n",
+        python
+    )
+}
+
+const ACTIVATE_SCRIPT: &str = r#"# This file must be used with "source bin/activate"
+_RYE_ENV="$(cd "$(dirname -- "${BASH_SOURCE:-$0}")/.." && pwd)"
+export VIRTUAL_ENV="$_RYE_ENV"
+export PATH="$_RYE_ENV/bin:$PATH"
+unset PYTHONHOME
+unset _RYE_ENV
+"#;
+
+/// Materializes the locked environment into a standalone directory.
+///
+/// With `--relocatable` the interpreter is copied into the target directory
+/// and all scripts refer to it by relative path so that the folder can be
+/// archived and moved to another machine of the same platform.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The directory to create the environment in.
+    path: PathBuf,
+    /// Make the environment relocatable.
+    #[arg(long)]
+    relocatable: bool,
+    /// Include dev dependencies.
+    #[arg(long)]
+    dev: bool,
+    /// Replace the target directory if it already exists.
+    #[arg(short, long)]
+    force: bool,
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let pyproject = PyProject::discover()?;
+    let lockfile = pyproject.lockfile_dir().join(if cmd.dev {
+        "requirements-dev.lock"
+    } else {
+        "requirements.lock"
+    });
+    let target = std::env::current_dir()?.join(&cmd.path);
+
+    if target.exists() {
+        if !cmd.force {
+            bail!(
+                "{} already exists. Use --force to replace it.",
+                target.display()
+            );
+        }
+        fs::remove_dir_all(&target)
+            .with_context(|| format!("unable to remove {}", target.display()))?;
+    }
+
+    // make sure the lockfiles are up to date
+    sync(SyncOptions {
+        output,
+        mode: SyncMode::LockOnly,
+        ..SyncOptions::default()
+    })
+    .context("failed to lock ahead of building environment")?;
+
+    let self_venv = ensure_self_venv(output)?;
+    let py_ver = load_python_version().map_or_else(get_default_python_version, Ok)?;
+    let py_ver = fetch(&py_ver.into(), output).context("failed fetching toolchain")?;
+
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "Creating {}environment in {}",
+            if cmd.relocatable { "relocatable " } else { "" },
+            style(target.display()).cyan()
+        );
+    }
+
+    let python = if cmd.relocatable {
+        let toolchain = get_canonical_py_path(&py_ver)?;
+        if toolchain.is_file() {
+            bail!(
+                "registered toolchain {} cannot be made relocatable; use a downloaded one",
+                py_ver
+            );
+        }
+        copy_dir(&toolchain.join("install"), &target)
+            .context("failed to copy toolchain into target")?;
+        target
+            .join("bin")
+            .join(get_py_bin(&py_ver)?.file_name().unwrap())
+    } else {
+        create_virtualenv(output, &self_venv, &py_ver, &target)?;
+        target.join("bin/python")
+    };
+
+    let mut req_file = NamedTempFile::new()?;
+    write_non_editable_lockfile(&lockfile, &pyproject.workspace_path(), &mut req_file)?;
+
+    if output != CommandOutput::Quiet {
+        eprintln!("Installing dependencies");
+    }
+    let mut pip_cmd = Command::new(self_venv.join("bin/pip"));
+    pip_cmd
+        .arg("--python")
+        .arg(&python)
+        .arg("install")
+        .arg("--no-deps")
+        .arg("-r")
+        .arg(req_file.path())
+        .current_dir(pyproject.workspace_path())
+        .env("PYTHONWARNINGS", "ignore");
+    if output == CommandOutput::Verbose {
+        pip_cmd.arg("--verbose");
+    } else {
+        pip_cmd.arg("-q");
+    }
+    apply_env_config(&mut pip_cmd, EnvTarget::Tools)?;
+    let status = pip_cmd.status().context("unable to run pip")?;
+    if !status.success() {
+        bail!("Installation of dependencies failed");
+    }
+
+    if cmd.relocatable {
+        make_scripts_relocatable(&target, &python)?;
+        fs::write(target.join("bin/activate"), ACTIVATE_SCRIPT)
+            .context("failed to write activate script")?;
+    }
+
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "{} Built environment in {}",
+            style("success:").green(),
+            target.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Rewrites absolute python shebangs of all scripts in `bin` so that they
+/// locate the interpreter relative to the script.
+fn make_scripts_relocatable(target: &Path, python: &Path) -> Result<(), Error> {
+    let bin = target.join("bin");
+    let shebang = relocatable_shebang(&python.file_name().unwrap().to_string_lossy());
+    for entry in fs::read_dir(&bin)? {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let mut reader = BufReader::new(fs::File::open(&path)?);
+        let mut first_line = Vec::new();
+        reader.read_until(b'\n', &mut first_line)?;
+        let is_local_python = first_line
+            .strip_prefix(b"#!")
+            .and_then(|x| std::str::from_utf8(x).ok())
+            .is_some_and(|x| Path::new(x.trim()).starts_with(&bin));
+        if !is_local_python {
+            continue;
+        }
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest)?;
+        // truncating keeps the permissions of the existing file intact
+        let mut out = fs::File::create(&path)?;
+        out.write_all(shebang.as_bytes())?;
+        out.write_all(&rest)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,296 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use anyhow::{bail, Error};
+use clap::Parser;
+use console::style;
+use pep440_rs::{Operator, Version, VersionSpecifier, VersionSpecifiers};
+use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
+
+use rye_core::lock::{lock_marker_environment, LockOptions, Lockfile};
+use rye_core::pyproject::{normalize_package_name, DependencyKind, PyProject};
+use rye_core::sync::{sync, SyncMode, SyncOptions};
+use rye_core::utils::{format_requirement, CommandOutput};
+
+/// Raises the lower bounds of the dependencies to the locked versions.
+///
+/// Every `>=` and `>` constraint in `pyproject.toml` which is below the
+/// version in the lockfile is bumped to it, and `~=` constraints move to the
+/// locked release with the same precision.  Exact pins, URL dependencies,
+/// dependencies without a lower bound and dependencies whose markers do not
+/// apply to the environment the lockfile was resolved for are left alone.  The lockfiles are
+/// updated afterwards and the changes to them are printed.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// Only bump these packages (defaults to all of them).
+    packages: Vec<String>,
+    /// Leave the dev dependencies alone.
+    #[arg(long)]
+    no_dev: bool,
+    /// Update all packages to the latest versions first.
+    #[arg(long)]
+    update_all: bool,
+    /// Only print the constraints that would be bumped.
+    #[arg(long, conflicts_with = "update_all")]
+    dry_run: bool,
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let pyproject = PyProject::discover()?;
+    let lockfile_dir = pyproject.lockfile_dir().into_owned();
+    let lockfiles = [
+        lockfile_dir.join("requirements.lock"),
+        lockfile_dir.join("requirements-dev.lock"),
+    ];
+    let old_lockfiles = lockfiles
+        .iter()
+        .map(|x| x.is_file().then(|| Lockfile::load(x)).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if cmd.update_all {
+        sync(SyncOptions {
+            output,
+            mode: SyncMode::LockOnly,
+            lock_options: LockOptions {
+                update_all: true,
+                ..LockOptions::default()
+            },
+            relock_if_needed: Some(false),
+            ..SyncOptions::default()
+        })?;
+    }
+
+    // the dev lockfile has everything the production one has
+    let locked = match lockfiles.iter().rev().find(|x| x.is_file()) {
+        Some(lockfile) => Lockfile::load(lockfile)?,
+        None => bail!("no lockfile found, run `rye lock` first"),
+    };
+    let env = lock_marker_environment(&LockOptions::default(), output)?;
+    let only: HashSet<String> = cmd
+        .packages
+        .iter()
+        .map(|x| normalize_package_name(x))
+        .collect();
+
+    let mut projects = match pyproject.workspace() {
+        Some(workspace) => workspace.iter_projects().collect::<Result<Vec<_>, _>>()?,
+        None => vec![pyproject],
+    };
+    let mut bumped = 0;
+    for project in &mut projects {
+        let mut kinds = vec![DependencyKind::Normal];
+        if !cmd.no_dev {
+            kinds.push(DependencyKind::Dev);
+        }
+        kinds.extend(
+            project
+                .optional_dependency_sections()
+                .into_iter()
+                .map(|x| DependencyKind::Optional(x.into())),
+        );
+
+        let mut changes = Vec::new();
+        for kind in kinds {
+            for req in project.iter_dependencies(kind.clone()) {
+                let name = normalize_package_name(&req.name);
+                if !only.is_empty() && !only.contains(&name) {
+                    continue;
+                }
+                let version = match locked
+                    .get(&name)
+                    .and_then(|x| x.version.as_deref())
+                    .and_then(|x| Version::from_str(x).ok())
+                {
+                    Some(version) => version,
+                    None => continue,
+                };
+                if let Some(new) = bump_requirement(&req, &version, &env) {
+                    changes.push((kind.clone(), req, new));
+                }
+            }
+        }
+        if changes.is_empty() {
+            continue;
+        }
+
+        for (kind, old, new) in changes {
+            if output != CommandOutput::Quiet {
+                println!(
+                    "{}: {} -> {}",
+                    style(project.name().unwrap_or("<unnamed>")).cyan(),
+                    format_requirement(&old),
+                    style(format_requirement(&new)).green()
+                );
+            }
+            project.replace_dependency(&old, &new, kind)?;
+            bumped += 1;
+        }
+        if !cmd.dry_run {
+            project.save()?;
+        }
+    }
+
+    if bumped == 0 {
+        if output != CommandOutput::Quiet {
+            eprintln!("All constraints are up to date with the lockfile");
+        }
+        return Ok(());
+    } else if cmd.dry_run {
+        return Ok(());
+    }
+
+    sync(SyncOptions {
+        output,
+        mode: SyncMode::LockOnly,
+        relock_if_needed: Some(false),
+        ..SyncOptions::default()
+    })?;
+
+    if output != CommandOutput::Quiet {
+        for (lockfile, old) in lockfiles.iter().zip(old_lockfiles) {
+            let changes = old
+                .unwrap_or_else(|| Lockfile::from_packages(Vec::new()))
+                .diff(&Lockfile::load(lockfile)?);
+            if changes.is_empty() {
+                continue;
+            }
+            println!(
+                "{}:",
+                style(lockfile.file_name().unwrap().to_string_lossy()).cyan()
+            );
+            for change in changes {
+                println!("  {}", change.styled());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Raises the lower bounds of a requirement to the locked version.
+///
+/// Returns `None` if there is nothing to bump, which includes requirements
+/// that are pinned, that do not have a lower bound or that the locked
+/// version does not satisfy.  Requirements for other environments than the
+/// one of the lockfile are skipped too, as `numpy>=1.20; python_version <
+/// '3.12'` would otherwise be raised to a version only newer pythons have.
+fn bump_requirement(
+    req: &Requirement,
+    locked: &Version,
+    env: &MarkerEnvironment,
+) -> Option<Requirement> {
+    if !req.evaluate_markers(env, Vec::new()) {
+        return None;
+    }
+    let specs = match req.version_or_url {
+        Some(VersionOrUrl::VersionSpecifier(ref specs)) => specs,
+        _ => return None,
+    };
+    if locked.is_local() || locked.any_prerelease() || !specs.contains(locked) {
+        return None;
+    }
+    if specs.iter().any(|x| {
+        matches!(
+            x.operator(),
+            Operator::Equal | Operator::EqualStar | Operator::ExactEqual
+        )
+    }) {
+        return None;
+    }
+
+    let mut changed = false;
+    let mut new_specs = Vec::new();
+    for spec in specs.iter() {
+        let new_spec = match spec.operator() {
+            Operator::GreaterThanEqual | Operator::GreaterThan if spec.version() < locked => {
+                VersionSpecifier::new(Operator::GreaterThanEqual, locked.clone(), false).ok()
+            }
+            Operator::TildeEqual if locked.epoch == 0 && spec.version().epoch == 0 => {
+                // keep the precision, `~=2.2` allows `2.*` and `~=2.2.0` `2.2.*`
+                let mut release = locked.release.clone();
+                release.resize(spec.version().release.len(), 0);
+                let version = Version::from_release(release);
+                if &version > spec.version() {
+                    VersionSpecifier::new(Operator::TildeEqual, version, false).ok()
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        match new_spec {
+            Some(new_spec) => {
+                changed = true;
+                new_specs.push(new_spec);
+            }
+            None => new_specs.push(spec.clone()),
+        }
+    }
+    if !changed {
+        return None;
+    }
+
+    let mut rv = req.clone();
+    rv.version_or_url = Some(VersionOrUrl::VersionSpecifier(
+        VersionSpecifiers::from_iter(new_specs),
+    ));
+    Some(rv)
+}
+
+#[test]
+fn test_bump_requirement() {
+    let env = MarkerEnvironment {
+        implementation_name: "cpython".into(),
+        implementation_version: ("3.12.1".into(), "3.12.1".parse().unwrap()),
+        os_name: "posix".into(),
+        platform_machine: "x86_64".into(),
+        platform_python_implementation: "CPython".into(),
+        platform_release: "6.1.0".into(),
+        platform_system: "Linux".into(),
+        platform_version: "#1 SMP".into(),
+        python_full_version: ("3.12.1".into(), "3.12.1".parse().unwrap()),
+        python_version: ("3.12".into(), "3.12".parse().unwrap()),
+        sys_platform: "linux".into(),
+    };
+    let bump = |req: &str, locked: &str| -> Option<String> {
+        let req = Requirement::from_str(req).unwrap();
+        bump_requirement(&req, &locked.parse().unwrap(), &env)
+            .map(|x| format_requirement(&x).to_string())
+    };
+
+    assert_eq!(bump("flask>=2.0", "2.3.2").as_deref(), Some("flask>=2.3.2"));
+    assert_eq!(bump("flask>2.0", "2.3.2").as_deref(), Some("flask>=2.3.2"));
+    assert_eq!(
+        bump("flask>=2.0,<3", "2.3.2").as_deref(),
+        Some("flask>=2.3.2, <3")
+    );
+    assert_eq!(bump("flask~=2.2", "2.3.7").as_deref(), Some("flask~=2.3"));
+    assert_eq!(
+        bump("flask~=2.2.0", "2.2.5").as_deref(),
+        Some("flask~=2.2.5")
+    );
+    assert_eq!(
+        bump("flask[async]>=2.0 ; python_version >= '3.8'", "2.3.2").as_deref(),
+        Some("flask[async]>=2.3.2 ; python_version >= '3.8'")
+    );
+
+    // nothing to bump
+    assert_eq!(bump("flask>=2.3.2", "2.3.2"), None);
+    assert_eq!(bump("flask~=2.3", "2.3.7"), None);
+    assert_eq!(bump("flask", "2.3.2"), None);
+    assert_eq!(bump("flask<3", "2.3.2"), None);
+    assert_eq!(bump("flask==2.0.1", "2.0.1"), None);
+    assert_eq!(bump("flask>=2.0", "3.0.0rc1"), None);
+    assert_eq!(bump("flask>=2.0", "2.3.2+local"), None);
+    assert_eq!(bump("flask>=3.0", "2.3.2"), None);
+    // the lockfile was resolved for another python
+    assert_eq!(bump("numpy>=1.20 ; python_version < '3.12'", "2.0.0"), None);
+    assert_eq!(bump("pywin32>=300 ; sys_platform == 'win32'", "306"), None);
+}
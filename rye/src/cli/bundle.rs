@@ -0,0 +1,172 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Context, Error};
+use clap::Parser;
+use console::style;
+use tempfile::NamedTempFile;
+
+use rye_core::bootstrap::{ensure_self_venv, fetch};
+use rye_core::config::{
+    apply_env_config, get_default_python_version, get_py_bin, load_python_version, EnvTarget,
+};
+use rye_core::lock::write_non_editable_lockfile;
+use rye_core::pyproject::PyProject;
+use rye_core::sources::PythonVersionRequest;
+use rye_core::sync::{sync, SyncMode, SyncOptions};
+use rye_core::utils::CommandOutput;
+
+/// Bundles the project and its locked dependencies into a zipapp.
+///
+/// The resulting file can be run directly by any matching Python interpreter
+/// without installing anything.  As Python cannot import native extensions
+/// from a zip file, this only works for pure Python dependencies.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The entrypoint as `module:function`.  Defaults to the console script
+    /// named after the project.
+    #[arg(short, long)]
+    main: Option<String>,
+    /// Where to write the bundle (defaults to `dist/<name>.pyz`).
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// The Python version to target (defaults to the pinned one).
+    #[arg(short, long)]
+    python: Option<String>,
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let pyproject = PyProject::discover()?;
+    let lockfile = pyproject.lockfile_dir().join("requirements.lock");
+    let name = pyproject
+        .normalized_name()
+        .ok_or_else(|| anyhow!("project has no name"))?;
+
+    let main = match cmd.main {
+        Some(main) => main,
+        None => find_entrypoint(&pyproject, &name).ok_or_else(|| {
+            anyhow!("could not determine entrypoint, pass one with --main module:function")
+        })?,
+    };
+    let out_path = match cmd.output {
+        Some(path) => env::current_dir()?.join(path),
+        None => pyproject
+            .workspace_path()
+            .join("dist")
+            .join(format!("{}.pyz", name)),
+    };
+
+    // make sure the lockfiles are up to date
+    sync(SyncOptions {
+        output,
+        mode: SyncMode::LockOnly,
+        ..SyncOptions::default()
+    })
+    .context("failed to lock ahead of bundling")?;
+
+    let self_venv = ensure_self_venv(output)?;
+    let py_ver: PythonVersionRequest = match cmd.python {
+        Some(ref py) => py.parse()?,
+        None => load_python_version()
+            .map_or_else(get_default_python_version, Ok)?
+            .into(),
+    };
+    let py_ver = fetch(&py_ver, output).context("failed fetching toolchain")?;
+    let py_bin = get_py_bin(&py_ver)?;
+
+    let staging = tempfile::tempdir()?;
+    let mut req_file = NamedTempFile::new()?;
+    write_non_editable_lockfile(&lockfile, &pyproject.workspace_path(), &mut req_file)?;
+
+    if output != CommandOutput::Quiet {
+        eprintln!("Collecting dependencies for {}", style(&py_ver).cyan());
+    }
+    let mut pip_cmd = Command::new(self_venv.join("bin/pip"));
+    pip_cmd
+        .arg("--python")
+        .arg(&py_bin)
+        .arg("install")
+        .arg("--no-deps")
+        .arg("--no-compile")
+        .arg("--target")
+        .arg(staging.path())
+        .arg("-r")
+        .arg(req_file.path())
+        .current_dir(pyproject.workspace_path())
+        .env("PYTHONWARNINGS", "ignore");
+    if output == CommandOutput::Verbose {
+        pip_cmd.arg("--verbose");
+    } else {
+        pip_cmd.arg("-q");
+    }
+    apply_env_config(&mut pip_cmd, EnvTarget::Tools)?;
+    let status = pip_cmd.status().context("unable to run pip")?;
+    if !status.success() {
+        bail!("Installation of dependencies failed");
+    }
+
+    if output != CommandOutput::Quiet {
+        let has_native = walkdir::WalkDir::new(staging.path())
+            .into_iter()
+            .filter_map(|x| x.ok())
+            .any(|x| x.path().extension().is_some_and(|ext| ext == "so"));
+        if has_native {
+            eprintln!(
+                "{} bundle contains native extensions which cannot be imported from a zipapp",
+                style("warning:").yellow()
+            );
+        }
+    }
+
+    if let Some(parent) = out_path.parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+    let status = Command::new(&py_bin)
+        .arg("-mzipapp")
+        .arg(staging.path())
+        .arg("-o")
+        .arg(&out_path)
+        .arg("-p")
+        .arg(format!(
+            "/usr/bin/env python{}.{}",
+            py_ver.major, py_ver.minor
+        ))
+        .arg("-m")
+        .arg(&main)
+        .status()
+        .context("unable to run zipapp")?;
+    if !status.success() {
+        bail!("failed to create zipapp");
+    }
+
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "{} Bundled {} into {}",
+            style("success:").green(),
+            style(&main).cyan(),
+            out_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Picks the console script named after the project, or the only one there is.
+fn find_entrypoint(pyproject: &PyProject, name: &str) -> Option<String> {
+    let scripts = pyproject.console_scripts();
+    if let Some((_, target)) = scripts.iter().find(|x| x.0 == name) {
+        return Some(target.clone());
+    }
+    match &scripts[..] {
+        [(_, target)] => Some(target.clone()),
+        _ => None,
+    }
+}
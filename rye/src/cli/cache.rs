@@ -0,0 +1,65 @@
+use anyhow::Error;
+use clap::Parser;
+use console::style;
+
+use rye_core::cache::{
+    format_size, get_cache_dir, parse_age, parse_size, prune_cache, PruneLimits,
+};
+use rye_core::config::Config;
+
+/// Manages the caches of downloads and environments.
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    command: SubCommand,
+}
+
+/// Evicts cache entries to stay within size and age limits.
+///
+/// Limits not given on the command line are taken from the `[cache]`
+/// section of the config.  The least recently used entries go first.
+#[derive(Parser, Debug)]
+pub struct PruneCommand {
+    /// The size the cache may take up (for instance `5GB`).
+    #[arg(long, value_parser = parse_size)]
+    max_size: Option<u64>,
+    /// Removes entries not used for this long (for instance `30d`).
+    #[arg(long, value_parser = parse_age)]
+    max_age: Option<std::time::Duration>,
+}
+
+#[derive(Parser, Debug)]
+enum SubCommand {
+    Prune(PruneCommand),
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    match cmd.command {
+        SubCommand::Prune(args) => prune(args),
+    }
+}
+
+fn prune(cmd: PruneCommand) -> Result<(), Error> {
+    let config = PruneLimits::from_config(Config::current()?)?;
+    let limits = PruneLimits {
+        max_size: cmd.max_size.or(config.max_size),
+        max_age: cmd.max_age.or(config.max_age),
+    };
+    if limits.is_empty() {
+        eprintln!(
+            "{} no limits given, pass --max-size or --max-age or configure them in [cache]",
+            style("warning:").yellow()
+        );
+        return Ok(());
+    }
+
+    let rv = prune_cache(&limits)?;
+    eprintln!(
+        "Removed {} entries from {} ({} freed, {} remaining)",
+        rv.removed,
+        get_cache_dir()?.display(),
+        style(format_size(rv.freed)).cyan(),
+        format_size(rv.remaining)
+    );
+    Ok(())
+}
@@ -0,0 +1,307 @@
+use anyhow::{bail, Error};
+use clap::Parser;
+use console::style;
+
+use rye_core::check::{
+    find_policy_violations, load_dependency_policy, run_checks, CheckKind, CheckOptions,
+    CheckStatus,
+};
+use rye_core::imports::{find_transitive_imports, find_unused_dependencies};
+use rye_core::lock::LockOptions;
+use rye_core::pyproject::PyProject;
+use rye_core::utils::{github_annotation, CommandOutput};
+
+/// Runs checks on the project.
+///
+/// Without a subcommand the lockfiles, the virtualenv, the `requires-python`
+/// ranges, the workspace constraints and the dependency policy are checked.
+/// The dependency checks (unused-deps, missing-deps) and the check of the git
+/// index (staged) are opt-in.
+#[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Option<SubCommand>,
+    /// Only run the given check (can be repeated).
+    #[arg(short, long = "check", value_name = "CHECK")]
+    checks: Vec<CheckKind>,
+    /// Run all checks including the dependency checks.
+    #[arg(long, conflicts_with = "checks")]
+    all: bool,
+    /// Only check that the staged lockfiles were locked from the staged
+    /// `pyproject.toml` files (same as `--check staged`).
+    #[arg(long, conflicts_with_all = ["checks", "all"])]
+    staged: bool,
+    /// Like `--staged`, but check the lockfiles of the checkout if they or
+    /// the `pyproject.toml` files changed since a git revision (for CI).
+    #[arg(long, value_name = "REV", conflicts_with_all = ["checks", "all", "staged"])]
+    since: Option<String>,
+    /// Check the lockfiles locked for another platform (see `rye lock
+    /// --target-platform`).
+    #[arg(long, value_name = "PLATFORM")]
+    target_platform: Option<String>,
+    /// Check the lockfiles locked for another python version (see `rye lock
+    /// --target-python`).
+    #[arg(long, value_name = "VERSION")]
+    target_python: Option<String>,
+    /// Skip the given check (can be repeated).
+    #[arg(long, value_name = "CHECK")]
+    skip: Vec<CheckKind>,
+    /// Print the results as JSON.
+    #[arg(long)]
+    json: bool,
+    /// Also report warnings and errors as GitHub Actions annotations.
+    #[arg(long, conflicts_with = "json")]
+    github_annotations: bool,
+    /// Fail on warnings too.
+    #[arg(long)]
+    strict: bool,
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+/// Reports dependencies which the project's code never imports.
+///
+/// Dependencies which are used without an import (servers, database
+/// drivers, type stubs ...) can be listed in `tool.rye.check.allow-unused`.
+#[derive(Parser, Debug)]
+pub struct UnusedDepsCommand {
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+/// Reports imports of packages which are only installed as dependencies of
+/// other packages.
+///
+/// Such imports break when the package pulling them in stops depending on
+/// them.  Declare them with `rye add` instead.
+#[derive(Parser, Debug)]
+pub struct MissingDepsCommand {
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+/// Reports dependencies which break the rules in `tool.rye.policy`.
+///
+/// The policy can require an upper bound on every dependency and forbid
+/// dependencies which allow any version or use wildcards.
+#[derive(Parser, Debug)]
+pub struct PolicyCommand {
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+#[derive(Parser, Debug)]
+enum SubCommand {
+    MissingDeps(MissingDepsCommand),
+    Policy(PolicyCommand),
+    UnusedDeps(UnusedDepsCommand),
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    match cmd.command {
+        Some(SubCommand::MissingDeps(args)) => missing_deps(args),
+        Some(SubCommand::Policy(args)) => policy(args),
+        Some(SubCommand::UnusedDeps(args)) => unused_deps(args),
+        None => check_all(cmd),
+    }
+}
+
+fn check_all(cmd: Args) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let pyproject = PyProject::discover()?;
+    let kinds: Vec<_> = CheckKind::all()
+        .into_iter()
+        .filter(|kind| {
+            if cmd.staged || cmd.since.is_some() {
+                *kind == CheckKind::Staged
+            } else if cmd.checks.is_empty() {
+                cmd.all || kind.is_default()
+            } else {
+                cmd.checks.contains(kind)
+            }
+        })
+        .filter(|kind| !cmd.skip.contains(kind))
+        .collect();
+    let reports = run_checks(
+        &pyproject,
+        &kinds,
+        &CheckOptions {
+            since: cmd.since.clone(),
+            lock_options: LockOptions {
+                target_platform: cmd.target_platform.clone(),
+                target_python: cmd.target_python.clone(),
+                ..LockOptions::default()
+            },
+        },
+    );
+
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else if output != CommandOutput::Quiet {
+        for report in &reports {
+            let status = match report.status {
+                CheckStatus::Ok => style(report.status).green(),
+                CheckStatus::Skipped => style(report.status).dim(),
+                CheckStatus::Warning => style(report.status).yellow(),
+                CheckStatus::Error => style(report.status).red(),
+            };
+            println!("{}: {}", style(report.kind).cyan(), status);
+            if report.status != CheckStatus::Skipped || output == CommandOutput::Verbose {
+                for message in &report.messages {
+                    println!("  {}", message);
+                }
+            }
+        }
+    }
+
+    if cmd.github_annotations {
+        let toml_path = pyproject.toml_path();
+        for report in &reports {
+            let level = match report.status {
+                CheckStatus::Warning => "warning",
+                CheckStatus::Error => "error",
+                CheckStatus::Ok | CheckStatus::Skipped => continue,
+            };
+            for finding in &report.messages {
+                // findings without a file of their own go on pyproject.toml
+                let location = finding.location.as_ref();
+                println!(
+                    "{}",
+                    github_annotation(
+                        level,
+                        Some(location.map_or(&*toml_path, |x| &x.file)),
+                        location.and_then(|x| x.line),
+                        &format!("rye check: {}", report.kind),
+                        &finding.message
+                    )
+                );
+            }
+        }
+    }
+
+    let threshold = if cmd.strict {
+        CheckStatus::Warning
+    } else {
+        CheckStatus::Error
+    };
+    let failed = reports.iter().filter(|x| x.status >= threshold).count();
+    if failed > 0 {
+        bail!("{} of {} checks failed", failed, reports.len());
+    }
+    Ok(())
+}
+
+fn missing_deps(cmd: MissingDepsCommand) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let pyproject = PyProject::discover()?;
+    let transitive = find_transitive_imports(&pyproject)?;
+    if transitive.is_empty() {
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "{} all imported packages are declared",
+                style("success:").green()
+            );
+        }
+        return Ok(());
+    }
+
+    if output != CommandOutput::Quiet {
+        let root = pyproject.root_path();
+        for import in &transitive {
+            eprintln!(
+                "{} {} is imported but {} is not a declared dependency",
+                style("warning:").yellow(),
+                style(&import.module).cyan(),
+                style(&import.distribution).cyan()
+            );
+            if !import.via.is_empty() {
+                eprintln!("  installed via {}", import.via.join(", "));
+            }
+            if output == CommandOutput::Verbose {
+                let path = import.path.strip_prefix(&root).unwrap_or(&import.path);
+                eprintln!("  imported in {}", path.display());
+            }
+        }
+    }
+    bail!("found {} undeclared dependencies", transitive.len());
+}
+
+fn policy(cmd: PolicyCommand) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let pyproject = PyProject::discover()?;
+    if load_dependency_policy(&pyproject)?.is_empty() {
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "{} no policy configured in {}",
+                style("note:").cyan(),
+                style("tool.rye.policy").bold()
+            );
+        }
+        return Ok(());
+    }
+    let violations = find_policy_violations(&pyproject)?;
+    if violations.is_empty() {
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "{} all dependencies follow the policy",
+                style("success:").green()
+            );
+        }
+        return Ok(());
+    }
+
+    if output != CommandOutput::Quiet {
+        for violation in &violations {
+            eprintln!("{} {}", style("error:").red(), violation);
+        }
+    }
+    bail!("found {} policy violations", violations.len());
+}
+
+fn unused_deps(cmd: UnusedDepsCommand) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let pyproject = PyProject::discover()?;
+    let unused = find_unused_dependencies(&pyproject)?;
+    if unused.is_empty() {
+        if output != CommandOutput::Quiet {
+            eprintln!("{} no unused dependencies", style("success:").green());
+        }
+        return Ok(());
+    }
+
+    if output != CommandOutput::Quiet {
+        for dep in &unused {
+            eprintln!(
+                "{} {} appears unused",
+                style("warning:").yellow(),
+                style(&dep.name).cyan()
+            );
+            if output == CommandOutput::Verbose {
+                eprintln!("  no import of {}", dep.modules.join(", "));
+            }
+        }
+        eprintln!(
+            "{} add false positives to {} in pyproject.toml",
+            style("note:").cyan(),
+            style("tool.rye.check.allow-unused").bold()
+        );
+    }
+    bail!("found {} unused dependencies", unused.len());
+}
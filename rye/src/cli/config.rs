@@ -0,0 +1,49 @@
+use anyhow::{bail, Error};
+use clap::Parser;
+use console::style;
+
+use rye_core::config::Config;
+
+/// Shows the effective configuration.
+///
+/// Settings from the global config (`~/.rye/config.toml`) take precedence
+/// over the ones from the `rye.toml` of the repository.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// Only show this setting (eg: `behavior.autosync`).
+    key: Option<String>,
+    /// Show the config file each setting comes from.
+    #[arg(long)]
+    show_origin: bool,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let config = Config::current()?;
+    let entries: Vec<_> = config
+        .entries()
+        .into_iter()
+        .filter(|entry| match cmd.key {
+            Some(ref key) => entry.key == *key || entry.key.starts_with(&format!("{}.", key)),
+            None => true,
+        })
+        .collect();
+    if let Some(ref key) = cmd.key {
+        if entries.is_empty() {
+            bail!("{} is not set", key);
+        }
+    }
+
+    for entry in entries {
+        if cmd.show_origin {
+            println!(
+                "{}\t{} = {}",
+                style(entry.origin.display()).dim(),
+                entry.key,
+                entry.value
+            );
+        } else {
+            println!("{} = {}", entry.key, entry.value);
+        }
+    }
+    Ok(())
+}
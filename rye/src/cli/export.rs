@@ -0,0 +1,228 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Error};
+use clap::{Parser, ValueEnum};
+use minijinja::{context, Environment};
+use serde_json::json;
+
+use rye_core::config::{get_default_python_version, load_python_version};
+use rye_core::lock::Lockfile;
+use rye_core::pylock::export_pylock;
+use rye_core::pyproject::PyProject;
+use rye_core::sources::PythonVersion;
+use rye_core::sync::get_venv_python_version;
+
+/// The folder the generated environments create their virtualenv in, so
+/// that it does not clash with the one rye manages in `.venv`.
+const DEV_ENV_VENV: &str = ".venv-dev-env";
+
+/// The `flake.nix` for `--format nix`.
+///
+/// nixpkgs only has one python per minor version, so only that is matched.
+/// The patch release comes from the nixpkgs revision in `flake.lock`, the
+/// exact version rye uses is noted next to it.
+const FLAKE_TEMPLATE: &str = r#"{
+  description = "Development environment for {{ name }}, generated by rye";
+
+  inputs.nixpkgs.url = "github:NixOS/nixpkgs/nixos-unstable";
+  inputs.flake-utils.url = "github:numtide/flake-utils";
+
+  outputs = { self, nixpkgs, flake-utils }:
+    flake-utils.lib.eachDefaultSystem (system:
+      let
+        pkgs = nixpkgs.legacyPackages.${system};
+        # rye uses {{ python }}
+        python = pkgs.python{{ major }}{{ minor }};
+      in {
+        devShells.default = pkgs.mkShell {
+          packages = [ python ];
+          shellHook = ''
+            if [ ! -d {{ venv }} ]; then
+              ${python}/bin/python -m venv {{ venv }}
+            fi
+            if ! cmp -s {{ lockfile }} {{ venv }}/installed.lock; then
+              {{ venv }}/bin/pip install --quiet --no-deps -r {{ lockfile }} \
+                && cp {{ lockfile }} {{ venv }}/installed.lock
+            fi
+            source {{ venv }}/bin/activate
+          '';
+        };
+      });
+}
+
+"#;
+
+/// Exports the locked dependencies for other tools.
+///
+/// The lockfile is exported as it is, run `rye lock` first to bring it up
+/// to date.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The format to export to.
+    #[arg(long, value_enum, default_value_t = ExportFormat::Requirements)]
+    format: ExportFormat,
+    /// Export the production instead of the dev lockfile.
+    #[arg(long)]
+    no_dev: bool,
+    /// Write to this file instead of stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    /// A requirements file as understood by pip.
+    Requirements,
+    /// A `pylock.toml` file (PEP 751).
+    Pylock,
+    /// A `flake.nix` with a dev shell installing from the lockfile.
+    Nix,
+    /// A `devcontainer.json` installing from the lockfile.
+    Devcontainer,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    let lockfile_name = if cmd.no_dev {
+        "requirements.lock"
+    } else {
+        "requirements-dev.lock"
+    };
+    let lockfile = pyproject.lockfile_dir().join(lockfile_name);
+    if !lockfile.is_file() {
+        bail!("no lockfile found, run `rye lock` first");
+    }
+    let output = match cmd.output {
+        Some(path) => Some(env::current_dir()?.join(path)),
+        None => None,
+    };
+
+    let contents = match cmd.format {
+        ExportFormat::Requirements => fs::read_to_string(&lockfile)
+            .with_context(|| format!("unable to read lockfile {}", lockfile.display()))?,
+        ExportFormat::Pylock => {
+            // paths in a pylock.toml are relative to the file itself
+            let base = match output.as_ref().and_then(|x| x.parent()) {
+                Some(parent) => parent.to_path_buf(),
+                None => env::current_dir()?,
+            };
+            export_pylock(
+                &Lockfile::load(&lockfile)?,
+                pyproject.requires_python(),
+                &pyproject.workspace_path(),
+                &base,
+            )?
+        }
+        ExportFormat::Nix => render_flake(
+            pyproject.name().unwrap_or("project"),
+            &dev_env_python(&pyproject)?,
+            lockfile_name,
+        )?,
+        ExportFormat::Devcontainer => render_devcontainer(
+            pyproject.name().unwrap_or("project"),
+            &dev_env_python(&pyproject)?,
+            lockfile_name,
+        )?,
+    };
+
+    match output {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("unable to create {}", parent.display()))?;
+            }
+            fs::write(&path, contents)
+                .with_context(|| format!("unable to write {}", path.display()))?
+        }
+        None => print!("{}", contents),
+    }
+    Ok(())
+}
+
+/// The interpreter the generated environments pin: the one of the
+/// virtualenv, or else the one rye would pick for it.
+fn dev_env_python(pyproject: &PyProject) -> Result<PythonVersion, Error> {
+    let python = match get_venv_python_version(pyproject).or_else(load_python_version) {
+        Some(python) => python,
+        None => get_default_python_version()?,
+    };
+    if python.kind != "cpython" {
+        bail!("only cpython can be exported, the project uses {}", python);
+    }
+    Ok(python)
+}
+
+/// Renders the `flake.nix` of `--format nix`.
+fn render_flake(name: &str, python: &PythonVersion, lockfile_name: &str) -> Result<String, Error> {
+    Ok(Environment::new().render_named_str(
+        "flake.nix",
+        FLAKE_TEMPLATE,
+        context! {
+            name => name,
+            python => python.to_string(),
+            major => python.major,
+            minor => python.minor,
+            venv => DEV_ENV_VENV,
+            lockfile => lockfile_name,
+        },
+    )?)
+}
+
+/// Renders the `devcontainer.json` of `--format devcontainer`.
+fn render_devcontainer(
+    name: &str,
+    python: &PythonVersion,
+    lockfile_name: &str,
+) -> Result<String, Error> {
+    let venv = format!("${{containerWorkspaceFolder}}/{}", DEV_ENV_VENV);
+    let rv = json!({
+        "name": name,
+        "image": "mcr.microsoft.com/devcontainers/base:bookworm",
+        "features": {
+            "ghcr.io/devcontainers/features/python:1": {
+                "version": format!("{}.{}.{}", python.major, python.minor, python.patch),
+                "installTools": false,
+            },
+        },
+        "postCreateCommand": format!(
+            "python -m venv {venv} && {venv}/bin/pip install --no-deps -r {lockfile}",
+            venv = DEV_ENV_VENV,
+            lockfile = lockfile_name,
+        ),
+        "containerEnv": {
+            "VIRTUAL_ENV": venv,
+        },
+        "customizations": {
+            "vscode": {
+                "settings": {
+                    "python.defaultInterpreterPath": format!("{}/bin/python", venv),
+                },
+            },
+        },
+    });
+    Ok(serde_json::to_string_pretty(&rv)? + "\n")
+}
+
+#[test]
+fn test_render_dev_envs() {
+    let python: PythonVersion = "cpython@3.11.7".parse().unwrap();
+
+    let flake = render_flake("my-app", &python, "requirements-dev.lock").unwrap();
+    assert!(flake.contains("Development environment for my-app"));
+    assert!(flake.contains("python = pkgs.python311;"));
+    assert!(flake.contains("# rye uses cpython@3.11.7"));
+    assert!(flake.contains("-r requirements-dev.lock"));
+
+    let devcontainer = render_devcontainer("my-app", &python, "requirements.lock").unwrap();
+    let rv: serde_json::Value = serde_json::from_str(&devcontainer).unwrap();
+    assert_eq!(
+        rv["features"]["ghcr.io/devcontainers/features/python:1"]["version"],
+        "3.11.7"
+    );
+    assert_eq!(
+        rv["postCreateCommand"],
+        "python -m venv .venv-dev-env && .venv-dev-env/bin/pip install --no-deps -r requirements.lock"
+    );
+}
@@ -1,8 +1,8 @@
 use anyhow::{Context, Error};
 use clap::Parser;
 
-use crate::bootstrap::fetch;
-use crate::utils::CommandOutput;
+use rye_core::bootstrap::fetch;
+use rye_core::utils::CommandOutput;
 
 /// Fetches a Python interpreter for the local machine.
 #[derive(Parser, Debug)]
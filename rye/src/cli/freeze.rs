@@ -0,0 +1,87 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{bail, Context, Error};
+use clap::Parser;
+use console::style;
+
+use rye_core::bootstrap::ensure_self_venv;
+use rye_core::lock::{write_frozen_lockfile, LockHeader, LockOptions};
+use rye_core::pyproject::PyProject;
+use rye_core::sync::get_venv_python_version;
+use rye_core::utils::CommandOutput;
+
+/// Captures the packages installed in the virtualenv.
+///
+/// This includes packages installed with pip by hand.  The result is
+/// printed as a requirements file or written into the lockfile.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// Write the requirements to this file instead of stdout.
+    #[arg(short, long, conflicts_with = "update_lock")]
+    output: Option<PathBuf>,
+    /// Replace the pins of the lockfile with the installed packages.
+    #[arg(long)]
+    update_lock: bool,
+    /// Update the production instead of the dev lockfile.
+    #[arg(long, requires = "update_lock")]
+    no_dev: bool,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    let python = pyproject.venv_bin_path().join("python");
+    if !python.is_file() {
+        bail!("project has no virtualenv yet, run `rye sync` first");
+    }
+
+    let self_venv = ensure_self_venv(CommandOutput::Normal)?;
+    let rv = Command::new(self_venv.join("bin/pip"))
+        .arg("--python")
+        .arg(&python)
+        .arg("freeze")
+        .arg("--exclude-editable")
+        .env("PYTHONWARNINGS", "ignore")
+        .output()
+        .context("unable to run pip")?;
+    if !rv.status.success() {
+        bail!(
+            "failed to list installed packages: {}",
+            String::from_utf8_lossy(&rv.stderr).trim()
+        );
+    }
+    let frozen = String::from_utf8_lossy(&rv.stdout);
+
+    if cmd.update_lock {
+        let lockfile = pyproject.lockfile_dir().join(if cmd.no_dev {
+            "requirements.lock"
+        } else {
+            "requirements-dev.lock"
+        });
+        // the pins come from the virtualenv, not from a resolver
+        let header = LockHeader {
+            resolver: "pip freeze".into(),
+            python: get_venv_python_version(&pyproject)
+                .map(|x| format!("{}.{}.{}", x.major, x.minor, x.patch))
+                .unwrap_or_else(|| "unknown".into()),
+            options: Vec::new(),
+            ..LockHeader::current(&LockOptions::default(), CommandOutput::Normal)?
+        };
+        write_frozen_lockfile(&lockfile, &frozen, &header)?;
+        eprintln!(
+            "{} Pinned the installed packages in {}",
+            style("success:").green(),
+            lockfile.display()
+        );
+    } else if let Some(path) = cmd.output {
+        let path = env::current_dir()?.join(path);
+        fs::write(&path, frozen.as_bytes())
+            .with_context(|| format!("unable to write {}", path.display()))?;
+    } else {
+        print!("{}", frozen);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use anyhow::{bail, Error};
+use clap::Parser;
+use console::style;
+
+use rye_core::index::{get_project_metadata, get_release_metadata};
+use rye_core::lock::Lockfile;
+use rye_core::pyproject::PyProject;
+
+/// Prints metadata of a package from the index.
+///
+/// If the package is locked in the current project, the locked release is
+/// shown, otherwise the latest one.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The name of the package.
+    package: String,
+    /// Show this version instead of the locked or latest one.
+    #[arg(long)]
+    version: Option<String>,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let latest = match get_project_metadata(&cmd.package)? {
+        Some(metadata) => metadata,
+        None => bail!("package {} not found on the index", cmd.package),
+    };
+    let locked_version = find_locked_version(&cmd.package);
+
+    let version = cmd
+        .version
+        .as_ref()
+        .or(locked_version.as_ref())
+        .unwrap_or(&latest.info.version);
+    let release = if version == &latest.info.version {
+        latest.clone()
+    } else {
+        match get_release_metadata(&cmd.package, version)? {
+            Some(release) => release,
+            None => bail!("version {} of {} not found", version, latest.info.name),
+        }
+    };
+    let info = &release.info;
+
+    println!(
+        "{} {}",
+        style(&info.name).cyan(),
+        style(&info.version).cyan()
+    );
+    if let Some(ref summary) = info.summary {
+        println!("{}", summary);
+    }
+    println!("latest: {}", latest.info.version);
+    if let Some(ref locked) = locked_version {
+        println!("locked: {}", locked);
+    }
+    if info.yanked {
+        println!(
+            "yanked: {}",
+            style(info.yanked_reason.as_deref().unwrap_or("yes")).red()
+        );
+    }
+    println!(
+        "requires-python: {}",
+        info.requires_python
+            .as_deref()
+            .filter(|x| !x.is_empty())
+            .unwrap_or("any")
+    );
+
+    let deps = info.requires_dist.as_deref().unwrap_or_default();
+    if !deps.is_empty() {
+        println!("dependencies:");
+        for dep in deps {
+            println!("  {}", dep);
+        }
+    }
+
+    let mut tags = release
+        .urls
+        .iter()
+        .filter_map(|x| x.wheel_tags())
+        .collect::<Vec<_>>();
+    tags.sort();
+    tags.dedup();
+    if !tags.is_empty() {
+        println!("wheels:");
+        for tag in tags {
+            println!("  {}", tag);
+        }
+    }
+    let has_sdist = release.urls.iter().any(|x| x.packagetype == "sdist");
+    println!("sdist: {}", if has_sdist { "yes" } else { "no" });
+
+    Ok(())
+}
+
+/// Looks up the version of a package in the lockfiles of the current project.
+fn find_locked_version(package: &str) -> Option<String> {
+    let pyproject = PyProject::discover().ok()?;
+    ["requirements.lock", "requirements-dev.lock"]
+        .into_iter()
+        .filter_map(|x| Lockfile::load(&pyproject.lockfile_dir().join(x)).ok())
+        .find_map(|lockfile| lockfile.get(package).and_then(|x| x.version.clone()))
+}
@@ -1,13 +1,17 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
 use std::{env, fs};
 
-use anyhow::{bail, Context, Error};
+use anyhow::{anyhow, bail, Context, Error};
 use clap::{Parser, ValueEnum};
-use console::style;
-use minijinja::{context, Environment};
+use console::{style, Term};
+use minijinja::{context, AutoEscape, Environment};
 use serde::Serialize;
+use tempfile::TempDir;
+use toml_edit::Document;
 
-use crate::config::get_default_author;
+use rye_core::config::{get_default_author, get_default_python_version, Config};
 
 #[derive(ValueEnum, Copy, Clone, Serialize, Debug)]
 #[value(rename_all = "snake_case")]
@@ -15,7 +19,10 @@ use crate::config::get_default_author;
 pub enum BuildSystem {
     Hatchling,
     Setuptools,
-    Filt,
+    #[value(alias = "filt")]
+    Flit,
+    Pdm,
+    Maturin,
 }
 
 /// Creates a new python project.
@@ -24,12 +31,20 @@ pub struct Args {
     /// Where to place the project (defaults to current path)
     #[arg(default_value = ".")]
     path: PathBuf,
-    /// Which interpreter version should be used?
+    /// Which interpreter version should be used? (defaults to the version of
+    /// `default.toolchain` from the config or 3.8)
     #[arg(short, long)]
     py: Option<String>,
-    /// Which build system should be used?
-    #[arg(long, default_value = "hatchling")]
-    build_system: BuildSystem,
+    /// Which build system should be used? (defaults to `default.build-system`
+    /// from the config or hatchling)
+    #[arg(long)]
+    build_system: Option<BuildSystem>,
+    /// Render the project from a template folder or git URL.
+    #[arg(long)]
+    template: Option<String>,
+    /// Sets a template variable instead of prompting for it.
+    #[arg(short = 'D', long = "define", value_name = "KEY=VALUE")]
+    defines: Vec<String>,
 }
 
 /// The pyproject.toml template
@@ -56,13 +71,30 @@ build-backend = "hatchling.build"
 {%- elif build_system == "setuptools" %}
 requires = ["setuptools>=61.0"]
 build-backend = "setuptools.build_meta"
-{%- elif build_system == "filt" %}
+{%- elif build_system == "flit" %}
 requires = ["flit_core>=3.4"]
-build-backend = "filt_core.buildapi"
+build-backend = "flit_core.buildapi"
+{%- elif build_system == "pdm" %}
+requires = ["pdm-backend"]
+build-backend = "pdm.backend"
+{%- elif build_system == "maturin" %}
+requires = ["maturin>=1.0,<2.0"]
+build-backend = "maturin"
 {%- endif %}
 
 [tool.rye]
 managed = true
+{%- if build_system == "hatchling" %}
+
+[tool.hatch.metadata]
+allow-direct-references = true
+{%- elif build_system == "maturin" %}
+
+[tool.maturin]
+python-source = "python"
+module-name = {{ module_name ~ "._lowlevel" }}
+features = ["pyo3/extension-module"]
+{%- endif %}
 
 "#;
 
@@ -85,6 +117,51 @@ wheels/
 # venv
 .venv
 
+# state of the last sync
+.rye/
+{%- if build_system == "maturin" %}
+
+# rust build artifacts
+target/
+{%- endif %}
+
+"#;
+
+const CARGO_TOML_TEMPLATE: &str = r#"[package]
+name = {{ name }}
+version = {{ version }}
+edition = "2021"
+
+[lib]
+name = "_lowlevel"
+crate-type = ["cdylib"]
+
+[dependencies]
+pyo3 = "0.19.0"
+
+"#;
+
+const LIB_RS_TEMPLATE: &str = r#"use pyo3::prelude::*;
+
+/// Prints a greeting.
+#[pyfunction]
+fn hello() -> PyResult<String> {
+    Ok("Hello from {{ name }}!".into())
+}
+
+/// A Python module implemented in Rust.
+#[pymodule]
+fn _lowlevel(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(hello, m)?)?;
+    Ok(())
+}
+
+"#;
+
+const INIT_PY_TEMPLATE: &str = r#"from {{ module_name }}._lowlevel import hello
+
+__all__ = ["hello"]
+
 "#;
 
 pub fn execute(cmd: Args) -> Result<(), Error> {
@@ -98,30 +175,97 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
         bail!("pyproject.toml already exists");
     }
 
+    let build_system = match cmd.build_system {
+        Some(build_system) => build_system,
+        None => match Config::current()?.default_build_system() {
+            Some(name) => BuildSystem::from_str(name, true).map_err(|_| {
+                anyhow!(
+                    "unknown build system '{}' in config (default.build-system)",
+                    name
+                )
+            })?,
+            None => BuildSystem::Hatchling,
+        },
+    };
+
     // fail silently if it already exists or cannot be created.
     fs::create_dir_all(&dir).ok();
 
-    // Write pyproject.toml
-    let py = cmd.py.as_deref().unwrap_or("3.8");
+    let default_py = match Config::current()?.default_toolchain() {
+        Some(_) => {
+            let version = get_default_python_version()?;
+            Some(format!("{}.{}", version.major, version.minor))
+        }
+        None => None,
+    };
+    let py = cmd.py.as_deref().or(default_py.as_deref()).unwrap_or("3.8");
     let name = slug::slugify(dir.file_name().unwrap().to_string_lossy());
     let version = "0.1.0";
     let requires_python = format!(">= {}", py);
     let author = get_default_author();
     let license = "MIT";
+    let module_name = name.replace('-', "_");
 
-    let rv = env.render_named_str(
-        "pyproject.json",
-        TOML_TEMPLATE,
-        context! {
-            name,
-            version,
-            author,
-            requires_python,
-            license,
-            build_system => cmd.build_system,
-        },
-    )?;
-    fs::write(&toml, rv).context("failed to write pyproject.toml")?;
+    if let Some(ref template) = cmd.template {
+        let mut vars = BTreeMap::new();
+        vars.insert("name".to_string(), name.clone());
+        vars.insert("version".to_string(), version.to_string());
+        vars.insert("requires_python".to_string(), requires_python.clone());
+        vars.insert("license".to_string(), license.to_string());
+        if let Some((ref author_name, ref author_email)) = author {
+            vars.insert("author_name".to_string(), author_name.clone());
+            vars.insert("author_email".to_string(), author_email.clone());
+        }
+        for define in &cmd.defines {
+            let (key, value) = define
+                .split_once('=')
+                .ok_or_else(|| anyhow!("invalid define '{}', expected KEY=VALUE", define))?;
+            vars.insert(key.to_string(), value.to_string());
+        }
+        render_template(template, &dir, vars)?;
+    }
+
+    // Write pyproject.toml unless the template provided one
+    if !toml.is_file() {
+        let rv = env.render_named_str(
+            "pyproject.json",
+            TOML_TEMPLATE,
+            context! {
+                name,
+                version,
+                author,
+                requires_python,
+                license,
+                build_system,
+                module_name,
+            },
+        )?;
+        fs::write(&toml, rv).context("failed to write pyproject.toml")?;
+    }
+
+    // mixed Rust/Python projects need a crate and a python package
+    let cargo_toml = dir.join("Cargo.toml");
+    if matches!(build_system, BuildSystem::Maturin) && !cargo_toml.is_file() {
+        let rv = env.render_named_str(
+            "Cargo.json",
+            CARGO_TOML_TEMPLATE,
+            context! { name, version },
+        )?;
+        fs::write(&cargo_toml, rv).context("failed to write Cargo.toml")?;
+        let lib_rs = dir.join("src").join("lib.rs");
+        if !lib_rs.is_file() {
+            fs::create_dir_all(lib_rs.parent().unwrap())?;
+            let rv = env.render_named_str("lib.rs", LIB_RS_TEMPLATE, context! { name })?;
+            fs::write(&lib_rs, rv).context("failed to write src/lib.rs")?;
+        }
+        let init_py = dir.join("python").join(&module_name).join("__init__.py");
+        if !init_py.is_file() {
+            fs::create_dir_all(init_py.parent().unwrap())?;
+            let rv =
+                env.render_named_str("__init__.py", INIT_PY_TEMPLATE, context! { module_name })?;
+            fs::write(&init_py, rv).context("failed to write __init__.py")?;
+        }
+    }
 
     // create a readme if one is missing
     if !readme.is_file() {
@@ -138,7 +282,11 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
 
     // create a .gitignore if one is missing
     if !gitignore.is_file() {
-        let rv = env.render_named_str("gitignore.txt", GITIGNORE_TEMPLATE, ())?;
+        let rv = env.render_named_str(
+            "gitignore.txt",
+            GITIGNORE_TEMPLATE,
+            context! { build_system },
+        )?;
         fs::write(&gitignore, rv).context("failed to write .gitignore")?;
     }
 
@@ -150,3 +298,168 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// The file in a template folder declaring its variables.
+const TEMPLATE_CONFIG: &str = "rye-template.toml";
+
+/// Renders a project template into `dir`.
+///
+/// File names and contents are rendered with the variables.  Variables
+/// declared in the `[variables]` table of `rye-template.toml` which were
+/// not defined on the command line are prompted for.  Existing files are
+/// left alone.
+fn render_template(
+    source: &str,
+    dir: &Path,
+    mut vars: BTreeMap<String, String>,
+) -> Result<(), Error> {
+    let (template_dir, _checkout) = fetch_template(source)?;
+    let mut env = Environment::new();
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+
+    let config_path = template_dir.join(TEMPLATE_CONFIG);
+    if config_path.is_file() {
+        let config: Document = fs::read_to_string(&config_path)?
+            .parse()
+            .with_context(|| format!("failed to parse {}", config_path.display()))?;
+        let term = Term::stderr();
+        let declared = config.get("variables").and_then(|x| x.as_table_like());
+        for (key, default) in declared.into_iter().flat_map(|x| x.iter()) {
+            if vars.contains_key(key) {
+                continue;
+            }
+            // defaults can refer to the variables declared before them
+            let default = match default.as_str() {
+                Some(default) => env.render_str(default, &vars)?,
+                None => default
+                    .as_value()
+                    .map_or(String::new(), |x| x.to_string().trim().to_string()),
+            };
+            let value = if term.is_term() {
+                term.write_str(&format!("{} [{}]: ", key, default))?;
+                let answer = term.read_line()?;
+                if answer.trim().is_empty() {
+                    default
+                } else {
+                    answer.trim().to_string()
+                }
+            } else {
+                default
+            };
+            vars.insert(key.to_string(), value);
+        }
+    }
+
+    for entry in walkdir::WalkDir::new(&template_dir)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|x| x.file_name() != ".git")
+    {
+        let entry = entry?;
+        let rel_path = entry.path().strip_prefix(&template_dir).unwrap();
+        if rel_path == Path::new(TEMPLATE_CONFIG) {
+            continue;
+        }
+        let rendered_path = env.render_str(&rel_path.to_string_lossy(), &vars)?;
+        let target = template_target(dir, &rendered_path)
+            .with_context(|| format!("invalid file name in template: {}", rel_path.display()))?;
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if target.exists() {
+            continue;
+        }
+        let contents = fs::read(entry.path())?;
+        match String::from_utf8(contents) {
+            Ok(source) => {
+                let mut rv = env
+                    .render_named_str(&rel_path.to_string_lossy(), &source, &vars)
+                    .with_context(|| format!("failed to render {}", rel_path.display()))?;
+                if source.ends_with('\n') && !rv.ends_with('\n') {
+                    rv.push('\n');
+                }
+                fs::write(&target, rv)?;
+            }
+            Err(err) => fs::write(&target, err.into_bytes())?,
+        }
+        fs::set_permissions(&target, entry.metadata()?.permissions())?;
+    }
+
+    Ok(())
+}
+
+/// Returns where a file of a template with the rendered relative path goes.
+///
+/// The variables are free text, so the rendered path may not leave `dir`.
+fn template_target(dir: &Path, rendered_path: &str) -> Result<PathBuf, Error> {
+    let rendered_path = Path::new(rendered_path);
+    let mut has_name = false;
+    for component in rendered_path.components() {
+        match component {
+            Component::Normal(_) => has_name = true,
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                bail!(
+                    "'{}' points outside of the project",
+                    rendered_path.display()
+                )
+            }
+        }
+    }
+    if !has_name {
+        bail!("'{}' renders to an empty name", rendered_path.display());
+    }
+    Ok(dir.join(rendered_path))
+}
+
+/// Resolves a template source to a folder, cloning git URLs first.
+///
+/// The returned temporary directory holds the checkout and has to be kept
+/// alive while the template is used.
+fn fetch_template(source: &str) -> Result<(PathBuf, Option<TempDir>), Error> {
+    let is_git = ["https://", "http://", "ssh://", "file://", "git@", "git+"]
+        .iter()
+        .any(|x| source.starts_with(x))
+        || source.ends_with(".git");
+    if !is_git {
+        let path = env::current_dir()?.join(source);
+        if !path.is_dir() {
+            bail!("template folder {} does not exist", path.display());
+        }
+        return Ok((path, None));
+    }
+
+    let url = source.strip_prefix("git+").unwrap_or(source);
+    let checkout = tempfile::tempdir()?;
+    let status = Command::new("git")
+        .arg("clone")
+        .arg("--depth=1")
+        .arg("--quiet")
+        .arg(url)
+        .arg(checkout.path())
+        .status()
+        .context("unable to run git")?;
+    if !status.success() {
+        bail!("failed to clone template from {}", url);
+    }
+    Ok((checkout.path().to_path_buf(), Some(checkout)))
+}
+
+#[test]
+fn test_template_target() {
+    let dir = Path::new("/tmp/project");
+    assert_eq!(
+        template_target(dir, "src/my_app/__init__.py").unwrap(),
+        dir.join("src/my_app/__init__.py")
+    );
+    assert_eq!(
+        template_target(dir, "./README.md").unwrap(),
+        dir.join("./README.md")
+    );
+    assert!(template_target(dir, "../outside.py").is_err());
+    assert!(template_target(dir, "src/../../outside.py").is_err());
+    assert!(template_target(dir, "/etc/passwd").is_err());
+    assert!(template_target(dir, "").is_err());
+    assert!(template_target(dir, "src/.").is_ok());
+}
@@ -2,15 +2,19 @@ use anyhow::Error;
 use clap::Parser;
 use pep508_rs::Requirement;
 
-use crate::installer::install;
-use crate::sources::PythonVersionRequest;
-use crate::utils::CommandOutput;
+use rye_core::installer::install;
+use rye_core::sources::PythonVersionRequest;
+use rye_core::utils::CommandOutput;
 
 /// Installs a package as global tool.
 #[derive(Parser, Debug)]
 pub struct Args {
     /// The package to install.
     requirement: String,
+    /// Also install this package into the virtualenv of the tool (eg: a
+    /// plugin).  Can be passed multiple times.
+    #[arg(long = "with", value_name = "REQUIREMENT")]
+    with: Vec<String>,
     /// Optionally the Python version to use.
     #[arg(short, long)]
     python: Option<String>,
@@ -38,7 +42,12 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
         },
     };
     let requirement: Requirement = cmd.requirement.parse()?;
+    let with = cmd
+        .with
+        .iter()
+        .map(|x| x.parse())
+        .collect::<Result<Vec<Requirement>, _>>()?;
 
-    install(requirement, &py_ver, cmd.force, output)?;
+    install(requirement, &with, &py_ver, cmd.force, output)?;
     Ok(())
 }
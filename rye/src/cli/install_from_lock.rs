@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use anyhow::Error;
+use clap::Parser;
+use console::style;
+
+use rye_core::config::get_default_toolchain_request;
+use rye_core::installer::{install_lockfile, InstallTarget};
+use rye_core::sources::PythonVersionRequest;
+use rye_core::utils::CommandOutput;
+
+/// Installs a lockfile into a folder, without a project or virtualenv.
+///
+/// This is useful to build vendored `site-packages` trees or layers for
+/// serverless platforms.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The lockfile to install.
+    lockfile: PathBuf,
+    /// Install the packages flat into this folder (like `pip install --target`).
+    #[arg(long, value_name = "DIR", required_unless_present = "prefix")]
+    target: Option<PathBuf>,
+    /// Install into this prefix, with `bin` and `lib/pythonX.Y/site-packages`.
+    #[arg(long, value_name = "DIR", conflicts_with = "target")]
+    prefix: Option<PathBuf>,
+    /// The Python version to install for.
+    #[arg(short, long)]
+    python: Option<String>,
+    /// Only install wheels for this platform (eg: `manylinux2014_x86_64`).
+    #[arg(long)]
+    platform: Vec<String>,
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let py_ver: PythonVersionRequest = match cmd.python {
+        Some(ref py) => py.parse()?,
+        None => match get_default_toolchain_request()? {
+            Some(req) => req,
+            None => PythonVersionRequest {
+                kind: None,
+                major: 3,
+                minor: None,
+                patch: None,
+                suffix: None,
+            },
+        },
+    };
+    let (target, dir) = match (cmd.target, cmd.prefix) {
+        (Some(dir), _) => (InstallTarget::Target(dir.clone()), dir),
+        (None, Some(dir)) => (InstallTarget::Prefix(dir.clone()), dir),
+        (None, None) => unreachable!(),
+    };
+
+    install_lockfile(&cmd.lockfile, &target, &py_ver, &cmd.platform, output)?;
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "{} Installed {} into {}",
+            style("success:").green(),
+            cmd.lockfile.display(),
+            dir.display()
+        );
+    }
+    Ok(())
+}
@@ -1,19 +1,52 @@
-use anyhow::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Error};
 use clap::Parser;
+use console::style;
+use serde_json::json;
+
+use rye_core::config::Config;
+use rye_core::error::{ErrorKind, Failure};
+use rye_core::lock::{
+    merge_lockfiles, preview_lockfile_changes, split_workspace_lockfile,
+    update_single_project_lockfile, update_workspace_lockfile, LockChange, LockHeader, LockMode,
+    LockOptions, Lockfile,
+};
+use rye_core::pyproject::{normalize_package_name, PyProject, UnsafePackages};
+use rye_core::sync::{sync, SyncMode, SyncOptions};
+use rye_core::utils::{github_annotation, write_atomic, CommandOutput};
 
-use crate::lock::LockOptions;
-use crate::sync::{sync, SyncMode, SyncOptions};
-use crate::utils::CommandOutput;
+use super::sync::{OutputPhase, PhaseOutputs};
 
 /// Updates the lockfiles without installing dependencies.
 #[derive(Parser, Debug)]
 pub struct Args {
-    /// Enables verbose diagnostics.
-    #[arg(short, long)]
-    verbose: bool,
-    /// Turns off all output.
-    #[arg(short, long, conflicts_with = "verbose")]
-    quiet: bool,
+    #[command(subcommand)]
+    command: Option<SubCommand>,
+    /// Enables verbose diagnostics, or only for the resolver (`resolver`).
+    #[arg(
+        short,
+        long,
+        value_name = "PHASE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all"
+    )]
+    verbose: Vec<OutputPhase>,
+    /// Turns off all output, or only the output of the resolver
+    /// (`resolver`).
+    #[arg(
+        short,
+        long,
+        value_name = "PHASE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all"
+    )]
+    quiet: Vec<OutputPhase>,
     /// Update a specific package.
     #[arg(long)]
     update: Vec<String>,
@@ -23,19 +56,494 @@ pub struct Args {
     /// Update to pre-release versions
     #[arg(long)]
     pre: bool,
+    /// Ignore cached responses of the package index.
+    #[arg(long)]
+    refresh: bool,
+    /// Resolve from scratch instead of keeping the versions of the lockfile.
+    #[arg(long)]
+    no_reuse: bool,
+    /// Only install this package from wheels (`:all:` for all packages).
+    #[arg(long, value_name = "PACKAGE")]
+    only_binary: Vec<String>,
+    /// Always build this package from source (`:all:` for all packages).
+    #[arg(long, value_name = "PACKAGE")]
+    no_binary: Vec<String>,
+    /// Warn about locked releases that were yanked from the index.
+    ///
+    /// Every locked package is looked up on the index for this.  Defaults to
+    /// `behavior.check-yanked`.
+    #[arg(long)]
+    check_yanked: bool,
+    /// Fail if the lockfile contains yanked releases.
+    #[arg(long)]
+    strict: bool,
+    /// Re-resolve packages whose locked release was yanked.
+    #[arg(long)]
+    refresh_yanked: bool,
+    /// Lock for another platform (eg: `linux-x86_64` or `macos-aarch64`).
+    ///
+    /// The lockfiles get the target in their name (eg:
+    /// `requirements-linux-x86_64.lock`).
+    #[arg(long, value_name = "PLATFORM")]
+    target_platform: Option<String>,
+    /// Lock for another python version (eg: `3.11`).
+    ///
+    /// The lockfiles get the target in their name (eg:
+    /// `requirements-py3.11.lock`).
+    #[arg(long, value_name = "VERSION")]
+    target_python: Option<String>,
+    /// Resolves a merge conflict in a lockfile (for use as git merge driver).
+    ///
+    /// The merged pins of both sides are re-resolved against the current
+    /// pyproject.toml and the result is written to OURS.  PATH is the path of
+    /// the lockfile in the repository which tells dev and production
+    /// lockfiles apart.
+    #[arg(long, num_args = 3..=4, value_names = ["OURS", "THEIRS", "BASE", "PATH"])]
+    merge: Option<Vec<PathBuf>>,
+    /// Fail if the lockfiles are out of date instead of updating them.
+    #[arg(long, conflicts_with = "merge")]
+    check: bool,
+    /// With --check, also report outdated lockfiles as GitHub Actions
+    /// annotations.
+    #[arg(long, requires = "check")]
+    github_annotations: bool,
+    /// With --check, append a markdown report to this file (eg:
+    /// `$GITHUB_STEP_SUMMARY`).
+    #[arg(long, value_name = "PATH", requires = "check")]
+    summary_file: Option<PathBuf>,
+    /// Fail if the lockfiles were generated in a different environment.
+    ///
+    /// Compares the rye and resolver versions, the python version, the
+    /// platform, the indexes and the resolver options recorded in the
+    /// lockfile headers with the current ones.
+    #[arg(long, conflicts_with_all = ["merge", "check"])]
+    verify_header: bool,
+    /// Also write a lockfile per workspace member with only the packages it
+    /// needs (`requirements.MEMBER.lock`).
+    #[arg(
+        long,
+        conflicts_with_all = ["merge", "check", "verify_header", "target_platform", "target_python"]
+    )]
+    split_per_member: bool,
+    /// Re-resolve the workspace without its constraints.lock and write the
+    /// versions of the dependencies shared by members to it again.
+    #[arg(
+        long,
+        conflicts_with_all = ["merge", "check", "verify_header", "target_platform", "target_python"]
+    )]
+    update_constraints: bool,
+    /// Also lock the requirements of the build backends of the local packages
+    /// and of the locked packages that are built from source into
+    /// build-requirements.lock.
+    ///
+    /// Once the file exists it is kept up to date by every lock.
+    #[arg(
+        long,
+        conflicts_with_all = ["merge", "check", "verify_header", "target_platform", "target_python"]
+    )]
+    build_requirements: bool,
+    /// Pin the packages pip-compile considers unsafe (pip, setuptools and
+    /// distribute), or with a list only these.
+    ///
+    /// Defaults to `tool.rye.lock.allow-unsafe`, which pins all of them.
+    #[arg(
+        long,
+        value_name = "PACKAGE",
+        num_args = 0..,
+        require_equals = true,
+        value_delimiter = ','
+    )]
+    allow_unsafe: Option<Vec<String>>,
+    /// Leave the unsafe packages out of the lockfiles.
+    #[arg(long, conflicts_with = "allow_unsafe")]
+    no_allow_unsafe: bool,
+}
+
+#[derive(Parser, Debug)]
+enum SubCommand {
+    Diff(DiffCommand),
+}
+
+/// Shows how the locked dependencies differ between two lockfile revisions.
+///
+/// Revisions are lockfiles or git refs, in which case the lockfile is read
+/// from that commit.
+#[derive(Parser, Debug)]
+pub struct DiffCommand {
+    /// The old revision.
+    old: String,
+    /// The new revision (defaults to the lockfile in the working tree).
+    new: Option<String>,
+    /// Compare the dev lockfiles of git refs.
+    #[arg(long)]
+    dev: bool,
+    /// Print the changes as JSON.
+    #[arg(long)]
+    json: bool,
 }
 
 pub fn execute(cmd: Args) -> Result<(), Error> {
-    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    if let Some(SubCommand::Diff(args)) = cmd.command {
+        return diff(args);
+    }
+    let outputs = PhaseOutputs::from_quiet_and_verbose(&cmd.quiet, &cmd.verbose)?;
+    if outputs.install.is_some() {
+        bail!("rye lock does not install packages, use `rye sync` for the install output");
+    }
+    let output = outputs.output;
+    let lock_options = LockOptions {
+        update: cmd.update,
+        update_all: cmd.update_all,
+        pre: cmd.pre,
+        refresh: cmd.refresh,
+        no_reuse: cmd.no_reuse,
+        check_yanked: cmd.check_yanked || Config::current()?.check_yanked(),
+        strict: cmd.strict,
+        refresh_yanked: cmd.refresh_yanked,
+        target_platform: cmd.target_platform,
+        target_python: cmd.target_python,
+        only_binary: cmd.only_binary,
+        no_binary: cmd.no_binary,
+        update_constraints: cmd.update_constraints,
+        build_requirements: cmd.build_requirements,
+        allow_unsafe: match cmd.allow_unsafe {
+            Some(packages) if packages.is_empty() => Some(UnsafePackages::All),
+            Some(packages) => Some(UnsafePackages::Only(
+                packages.iter().map(|x| normalize_package_name(x)).collect(),
+            )),
+            None if cmd.no_allow_unsafe => Some(UnsafePackages::None),
+            None => None,
+        },
+        resolver_output: outputs.resolver,
+    };
+    if let Some(paths) = cmd.merge {
+        return merge(&paths, output, &lock_options);
+    }
+    if cmd.check {
+        return check(
+            output,
+            &lock_options,
+            cmd.github_annotations,
+            cmd.summary_file.as_deref(),
+        );
+    }
+    if cmd.verify_header {
+        return verify_header(output, &lock_options);
+    }
+    let pyproject = PyProject::discover()?;
+    let lockfile_dir = pyproject.lockfile_dir();
+    let workspace = match pyproject.workspace() {
+        Some(workspace) => Some(workspace.clone()),
+        None if cmd.split_per_member => bail!("--split-per-member requires a workspace"),
+        None if cmd.update_constraints => bail!("--update-constraints requires a workspace"),
+        None => None,
+    };
     sync(SyncOptions {
         output,
         mode: SyncMode::LockOnly,
-        lock_options: LockOptions {
-            update: cmd.update,
-            update_all: cmd.update_all,
-            pre: cmd.pre,
-        },
+        lock_options,
+        // locking explicitly always resolves
+        relock_if_needed: Some(false),
         ..SyncOptions::default()
     })?;
+    if let (true, Some(workspace)) = (cmd.split_per_member, workspace) {
+        for (filename, mode) in [
+            ("requirements.lock", LockMode::Production),
+            ("requirements-dev.lock", LockMode::Dev),
+        ] {
+            let lockfile = lockfile_dir.join(filename);
+            for path in split_workspace_lockfile(&workspace, mode, &lockfile)? {
+                if output != CommandOutput::Quiet {
+                    eprintln!("Wrote {}", style(path.display()).cyan());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that locking would not change the lockfiles.
+///
+/// The outdated lockfiles can also be reported as GitHub Actions annotations
+/// and as markdown report appended to `summary_file`.
+pub(crate) fn check(
+    output: CommandOutput,
+    lock_options: &LockOptions,
+    github_annotations: bool,
+    summary_file: Option<&Path>,
+) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    let mut up_to_date = true;
+    let mut summary = String::from("## Lockfiles\n\n");
+    for lock_mode in [LockMode::Production, LockMode::Dev] {
+        let changes = preview_lockfile_changes(&pyproject, lock_mode, output, lock_options)?;
+        if changes.is_empty() {
+            summary.push_str(&format!("- {} lockfile is up to date\n", lock_mode));
+            continue;
+        }
+        up_to_date = false;
+        if output != CommandOutput::Quiet {
+            eprintln!("{} lockfile is out of date:", lock_mode);
+            for change in &changes {
+                eprintln!("  {}", change);
+            }
+        }
+        if github_annotations {
+            let lockfile = pyproject
+                .lockfile_dir()
+                .join(lock_options.lockfile_name(lock_mode)?);
+            let message = changes
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            println!(
+                "{}",
+                github_annotation(
+                    "error",
+                    Some(&lockfile),
+                    None,
+                    &format!("{} lockfile is out of date, run `rye lock`", lock_mode),
+                    &message
+                )
+            );
+        }
+        summary.push_str(&format!(
+            "- **{} lockfile is out of date**\n\n  | Package | Change |\n  | --- | --- |\n",
+            lock_mode
+        ));
+        for change in &changes {
+            summary.push_str(&format!("  | `{}` | {} |\n", change.name(), change.kind()));
+        }
+        summary.push('\n');
+    }
+    if let Some(path) = summary_file {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("unable to open {}", path.display()))?;
+        writeln!(file, "{}", summary)
+            .with_context(|| format!("unable to write {}", path.display()))?;
+    }
+    if !up_to_date {
+        bail!(Failure::new(
+            ErrorKind::OutOfSync,
+            "lockfiles are out of date, run `rye lock` to update them"
+        ));
+    }
+    if output != CommandOutput::Quiet {
+        eprintln!("{} Lockfiles are up to date", style("success:").green());
+    }
+    Ok(())
+}
+
+/// Checks that the lockfiles were generated in the current environment.
+fn verify_header(output: CommandOutput, lock_options: &LockOptions) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    // the unsafe packages are configured for the whole workspace
+    let allow_unsafe = match pyproject.workspace() {
+        Some(workspace) => {
+            PyProject::load(&workspace.path().join("pyproject.toml"))?.lock_allow_unsafe()?
+        }
+        None => pyproject.lock_allow_unsafe()?,
+    };
+    let lock_options = &lock_options.with_allow_unsafe(allow_unsafe);
+    let current = LockHeader::current(lock_options, output)?;
+    let mut matches = true;
+    let mut lockfiles = Vec::new();
+    for lock_mode in [LockMode::Production, LockMode::Dev] {
+        let lockfile = pyproject
+            .lockfile_dir()
+            .join(lock_options.lockfile_name(lock_mode)?);
+        if lockfile.is_file() {
+            lockfiles.push(lockfile);
+        }
+    }
+    if lockfiles.is_empty() {
+        bail!("no lockfiles found, run `rye lock` first");
+    }
+    for lockfile in lockfiles {
+        let contents = fs::read_to_string(&lockfile)
+            .with_context(|| format!("unable to read lockfile {}", lockfile.display()))?;
+        let differences = match LockHeader::parse(&contents) {
+            Some(header) => header.differences(&current),
+            None => {
+                matches = false;
+                if output != CommandOutput::Quiet {
+                    eprintln!(
+                        "{} has no reproducibility header, run `rye lock` to add one",
+                        lockfile.display()
+                    );
+                }
+                continue;
+            }
+        };
+        if differences.is_empty() {
+            continue;
+        }
+        matches = false;
+        if output != CommandOutput::Quiet {
+            eprintln!(
+                "{} was generated in a different environment:",
+                lockfile.display()
+            );
+            for (field, locked, now) in differences {
+                eprintln!("  {}: {} (locked with {})", field, now, locked);
+            }
+        }
+    }
+    if !matches {
+        bail!(Failure::new(
+            ErrorKind::OutOfSync,
+            "lockfiles do not match the current environment"
+        ));
+    }
+    if output != CommandOutput::Quiet {
+        eprintln!(
+            "{} Lockfiles match the current environment",
+            style("success:").green()
+        );
+    }
+    Ok(())
+}
+
+fn merge(
+    paths: &[PathBuf],
+    output: CommandOutput,
+    lock_options: &LockOptions,
+) -> Result<(), Error> {
+    let read = |path: &PathBuf| {
+        fs::read_to_string(path).with_context(|| format!("unable to read {}", path.display()))
+    };
+    let (ours, theirs, base) = (read(&paths[0])?, read(&paths[1])?, read(&paths[2])?);
+    let pyproject = PyProject::discover()?;
+    let lock_mode = find_lock_mode(&pyproject, paths.get(3), &ours)?;
+
+    write_atomic(&paths[0], merge_lockfiles(&ours, &theirs, &base))
+        .with_context(|| format!("unable to write {}", paths[0].display()))?;
+
+    let rv = if let Some(workspace) = pyproject.workspace() {
+        update_workspace_lockfile(workspace, lock_mode, &paths[0], output, lock_options)
+    } else {
+        update_single_project_lockfile(&pyproject, lock_mode, &paths[0], output, lock_options)
+    };
+    if let Err(err) = rv {
+        // the merged pins are left behind so that the conflict can be
+        // finished with `rye lock` once pyproject.toml is sorted out.
+        eprintln!(
+            "{} could not re-resolve merged lockfile: {:#}",
+            style("error:").red(),
+            err
+        );
+        bail!("lockfile merge needs manual resolution, run `rye lock` afterwards");
+    }
     Ok(())
 }
+
+/// Figures out which lockfile is being merged.
+///
+/// Without the path in the repository, the lockfile is identified by
+/// comparing our side with the lockfiles in the working tree which git has
+/// not touched yet.
+fn find_lock_mode(
+    pyproject: &PyProject,
+    path: Option<&PathBuf>,
+    ours: &str,
+) -> Result<LockMode, Error> {
+    if let Some(path) = path {
+        return Ok(
+            if path
+                .file_name()
+                .is_some_and(|x| x == "requirements-dev.lock")
+            {
+                LockMode::Dev
+            } else {
+                LockMode::Production
+            },
+        );
+    }
+    let root = pyproject.lockfile_dir();
+    for (filename, mode) in [
+        ("requirements.lock", LockMode::Production),
+        ("requirements-dev.lock", LockMode::Dev),
+    ] {
+        if fs::read_to_string(root.join(filename)).is_ok_and(|x| x == ours) {
+            return Ok(mode);
+        }
+    }
+    bail!("cannot tell which lockfile is merged, pass its path as fourth argument to --merge");
+}
+
+fn diff(cmd: DiffCommand) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    let lockfile = pyproject.lockfile_dir().join(if cmd.dev {
+        "requirements-dev.lock"
+    } else {
+        "requirements.lock"
+    });
+    let old = load_revision(&cmd.old, &lockfile)?;
+    let new = match cmd.new {
+        Some(ref new) => load_revision(new, &lockfile)?,
+        None => Lockfile::load(&lockfile)?,
+    };
+    let changes = old.diff(&new);
+
+    if cmd.json {
+        let rv: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                let (old, new) = match change {
+                    LockChange::Added(pkg) => (None, Some(pkg)),
+                    LockChange::Removed(pkg) => (Some(pkg), None),
+                    LockChange::Changed { old, new } => (Some(old), Some(new)),
+                };
+                json!({
+                    "name": change.name(),
+                    "kind": change.kind(),
+                    "old_version": old.and_then(|x| x.version.as_deref()),
+                    "new_version": new.and_then(|x| x.version.as_deref()),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&rv)?);
+    } else if changes.is_empty() {
+        eprintln!("no changes");
+    } else {
+        for change in changes {
+            println!("{}", change.styled());
+        }
+    }
+    Ok(())
+}
+
+/// Loads a lockfile from a path, or from a git ref (`REF` or `REF:PATH`).
+fn load_revision(rev: &str, lockfile: &Path) -> Result<Lockfile, Error> {
+    let path = Path::new(rev);
+    if path.is_file() {
+        return Lockfile::load(path);
+    }
+    let object = if rev.contains(':') {
+        rev.to_string()
+    } else {
+        format!(
+            "{}:./{}",
+            rev,
+            lockfile.file_name().unwrap().to_string_lossy()
+        )
+    };
+    let rv = Command::new("git")
+        .arg("show")
+        .arg(&object)
+        .current_dir(lockfile.parent().unwrap())
+        .output()
+        .context("unable to run git")?;
+    if !rv.status.success() {
+        bail!(
+            "'{}' is neither a lockfile nor a git ref with a lockfile: {}",
+            rev,
+            String::from_utf8_lossy(&rv.stderr).trim()
+        );
+    }
+    Ok(Lockfile::parse(&String::from_utf8_lossy(&rv.stdout)))
+}
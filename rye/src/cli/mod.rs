@@ -1,24 +1,56 @@
-use anyhow::Error;
-use clap::Parser;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::{AsFd, AsRawFd};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Error};
+use clap::{Parser, ValueEnum};
+use console::style;
 
 mod add;
+mod build;
+mod build_env;
+mod bump_deps;
+mod bundle;
+mod cache;
+mod check;
+mod config;
+mod export;
 mod fetch;
+mod freeze;
+mod info;
 mod init;
 mod install;
+mod install_from_lock;
 mod lock;
 mod pin;
+mod plugin;
+mod pre_commit;
 mod remove;
 mod run;
 mod rye;
+mod serve;
 mod shim;
 mod show;
 mod sync;
 mod toolchain;
+mod tools;
 mod uninstall;
+mod why;
+mod workspace;
 
 use git_testament::git_testament;
 
-use crate::bootstrap::SELF_PYTHON_VERSION;
+use rye_core::bootstrap::SELF_PYTHON_VERSION;
+use rye_core::cache::get_cache_dir;
+use rye_core::error::{error_kind, ErrorKind};
+use rye_core::pyproject::set_explicit_project_root;
+use rye_core::signals;
+use rye_core::timings;
 
 git_testament!(TESTAMENT);
 
@@ -30,38 +62,123 @@ struct Args {
     /// Print the version
     #[arg(long)]
     version: bool,
+    /// Operate on the project in this folder instead of discovering it.
+    #[arg(long, global = true, value_name = "DIR")]
+    project: Option<PathBuf>,
+    /// Operate on the project defined by this pyproject.toml.
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "project")]
+    pyproject: Option<PathBuf>,
+    /// Machine mode: no diagnostics, a failure is reported as a single
+    /// `error[KIND]: MESSAGE` line and the exit status tells why rye failed.
+    #[arg(long)]
+    quiet: bool,
+    /// Print how long the phases of the command took (as table or JSON).
+    #[arg(
+        long,
+        global = true,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "table"
+    )]
+    timings: Option<TimingsFormat>,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug)]
+enum TimingsFormat {
+    Table,
+    Json,
 }
 
+/// The original stderr when diagnostics are silenced by `--quiet`.
+static MACHINE_STDERR: OnceLock<File> = OnceLock::new();
+
 #[derive(Parser, Debug)]
 enum Command {
     Add(add::Args),
+    Build(build::Args),
+    BuildEnv(build_env::Args),
+    BumpDeps(bump_deps::Args),
+    Bundle(bundle::Args),
+    Cache(cache::Args),
+    Check(check::Args),
+    Config(config::Args),
+    Export(export::Args),
     Fetch(fetch::Args),
+    Freeze(freeze::Args),
+    Info(info::Args),
+    #[command(alias = "new")]
     Init(init::Args),
     Install(install::Args),
+    InstallFromLock(install_from_lock::Args),
     Lock(lock::Args),
     Pin(pin::Args),
     Remove(remove::Args),
     Run(run::Args),
+    Serve(serve::Args),
     Show(show::Args),
     Sync(sync::Args),
     Toolchain(toolchain::Args),
     #[command(name = "self")]
     Rye(rye::Args),
+    Tools(tools::Args),
     Uninstall(uninstall::Args),
+    Why(why::Args),
+    Workspace(workspace::Args),
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
+}
+
+/// The project folder `--project` or `--pyproject` asks for.
+fn explicit_project_root(args: &Args) -> Result<Option<&Path>, Error> {
+    if let Some(ref root) = args.project {
+        return Ok(Some(root));
+    }
+    match args.pyproject {
+        Some(ref pyproject) => {
+            if pyproject.file_name() != Some(OsStr::new("pyproject.toml")) {
+                bail!("--pyproject must point to a pyproject.toml file");
+            }
+            Ok(Some(pyproject.parent().unwrap_or(Path::new("."))))
+        }
+        None => Ok(None),
+    }
 }
 
 pub fn execute() -> Result<(), Error> {
     // if we're shimmed, execute the shim.  This won't return.
     shim::execute_shim()?;
+    signals::install_handlers()?;
 
     let args = Args::parse();
 
+    // pip's cache lives next to rye's other caches so it can be pruned
+    if env::var_os("PIP_CACHE_DIR").is_none() {
+        env::set_var("PIP_CACHE_DIR", get_cache_dir()?.join("pip"));
+    }
+    if args.quiet {
+        MACHINE_STDERR.set(silence_stderr()?).ok();
+    }
+    if let Some(root) = explicit_project_root(&args)? {
+        set_explicit_project_root(root)?;
+    }
+
+    if !args.quiet && matches!(args.command, Some(ref cmd) if !matches!(cmd, Command::Rye(_))) {
+        rye::first_run_setup()?;
+    }
+
+    let timings_format = args.timings;
+    if timings_format.is_some() {
+        timings::enable();
+    }
+    let start = Instant::now();
+
     let cmd = if args.version {
         eprintln!("rye {}", env!("CARGO_PKG_VERSION"));
         eprintln!("commit: {}", TESTAMENT.commit);
         eprintln!(
             "platform: {} ({})",
-            std::env::consts::OS,
+            rye_core::sources::current_platform(),
             std::env::consts::ARCH
         );
         eprintln!("self-python: {}", SELF_PYTHON_VERSION);
@@ -72,19 +189,127 @@ pub fn execute() -> Result<(), Error> {
         unreachable!()
     };
 
-    match cmd {
+    let rv = match cmd {
         Command::Add(cmd) => add::execute(cmd),
+        Command::Build(cmd) => build::execute(cmd),
+        Command::BuildEnv(cmd) => build_env::execute(cmd),
+        Command::BumpDeps(cmd) => bump_deps::execute(cmd),
+        Command::Bundle(cmd) => bundle::execute(cmd),
+        Command::Cache(cmd) => cache::execute(cmd),
+        Command::Check(cmd) => check::execute(cmd),
+        Command::Config(cmd) => config::execute(cmd),
+        Command::Export(cmd) => export::execute(cmd),
         Command::Fetch(cmd) => fetch::execute(cmd),
+        Command::Freeze(cmd) => freeze::execute(cmd),
+        Command::Info(cmd) => info::execute(cmd),
         Command::Init(cmd) => init::execute(cmd),
         Command::Install(cmd) => install::execute(cmd),
+        Command::InstallFromLock(cmd) => install_from_lock::execute(cmd),
         Command::Lock(cmd) => lock::execute(cmd),
         Command::Pin(cmd) => pin::execute(cmd),
         Command::Remove(cmd) => remove::execute(cmd),
         Command::Run(cmd) => run::execute(cmd),
+        Command::Serve(cmd) => serve::execute(cmd),
         Command::Show(cmd) => show::execute(cmd),
         Command::Sync(cmd) => sync::execute(cmd),
         Command::Toolchain(cmd) => toolchain::execute(cmd),
         Command::Rye(cmd) => rye::execute(cmd),
+        Command::Tools(cmd) => tools::execute(cmd),
         Command::Uninstall(cmd) => uninstall::execute(cmd),
+        Command::Why(cmd) => why::execute(cmd),
+        Command::Workspace(cmd) => workspace::execute(cmd),
+        Command::External(args) => plugin::execute(args),
+    };
+    if let Some(format) = timings_format {
+        print_timings(format, start.elapsed())?;
+    }
+    rv
+}
+
+/// Prints the recorded timings to stderr.
+fn print_timings(format: TimingsFormat, total: Duration) -> Result<(), Error> {
+    let recorded = timings::recorded();
+    match format {
+        TimingsFormat::Json => {
+            let rv = serde_json::json!({
+                "phases": recorded,
+                "total": total.as_secs_f64(),
+            });
+            eprintln!("{}", serde_json::to_string_pretty(&rv)?);
+        }
+        TimingsFormat::Table => {
+            eprintln!("{}", style("Timings:").bold());
+            let width = recorded
+                .iter()
+                .map(|x| x.phase.len())
+                .max()
+                .unwrap_or(0)
+                .max("total".len());
+            for timing in &recorded {
+                let count = if timing.count > 1 {
+                    format!(" ({}x)", timing.count)
+                } else {
+                    String::new()
+                };
+                eprintln!(
+                    "  {:width$}  {:>8.2}s{}",
+                    timing.phase,
+                    timing.duration.as_secs_f64(),
+                    style(count).dim(),
+                    width = width
+                );
+            }
+            eprintln!(
+                "  {:width$}  {:>8.2}s",
+                style("total").bold(),
+                total.as_secs_f64(),
+                width = width
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Points stderr to `/dev/null`, returning a handle to the original stderr.
+///
+/// This also silences the tools rye invokes.
+fn silence_stderr() -> Result<File, Error> {
+    let devnull = File::options().write(true).open("/dev/null")?;
+    let stderr = io::stderr().as_fd().try_clone_to_owned()?;
+    nix::unistd::dup2(devnull.as_raw_fd(), io::stderr().as_raw_fd())?;
+    Ok(File::from(stderr))
+}
+
+/// Reports a failed command on stderr.
+pub fn report_error(err: &Error) {
+    match MACHINE_STDERR.get() {
+        Some(mut stderr) => {
+            let kind = error_kind(err).map_or("other", ErrorKind::name);
+            let message = format!("{:#}", err)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(stderr, "error[{}]: {}", kind, message).ok();
+        }
+        None => eprintln!("Error: {:?}", err),
     }
 }
+
+#[test]
+fn test_explicit_project_root() {
+    let root = |args: &[&str]| {
+        let args = Args::try_parse_from(args).unwrap();
+        explicit_project_root(&args).map(|x| x.map(Path::to_path_buf))
+    };
+    assert_eq!(root(&["rye", "sync"]).unwrap(), None);
+    assert_eq!(
+        root(&["rye", "sync", "--project", "app"]).unwrap(),
+        Some(PathBuf::from("app"))
+    );
+    assert_eq!(
+        root(&["rye", "--pyproject", "app/pyproject.toml", "sync"]).unwrap(),
+        Some(PathBuf::from("app"))
+    );
+    assert!(root(&["rye", "sync", "--pyproject", "app/setup.cfg"]).is_err());
+    assert!(Args::try_parse_from(["rye", "sync", "--project", "a", "--pyproject", "b"]).is_err());
+}
@@ -2,24 +2,67 @@ use std::env;
 use std::fs;
 
 use anyhow::Context;
-use anyhow::{anyhow, Error};
+use anyhow::Error;
 use clap::Parser;
+use console::style;
 
-use crate::config::get_pinnable_version;
-use crate::pyproject::PyProject;
-use crate::sources::PythonVersionRequest;
+use rye_core::config::{get_pinnable_version, resolve_python_version};
+use rye_core::error::{ErrorKind, Failure};
+use rye_core::pyproject::PyProject;
+use rye_core::sources::PythonVersionRequest;
 
 /// Pins a Python version to this project.
 #[derive(Parser, Debug)]
 pub struct Args {
     /// The version of Python to pin.
     version: String,
+    /// Pin the version as requested (eg: `3.11`) instead of the exact release.
+    #[arg(long)]
+    relaxed: bool,
+    /// Write the pin into the `[tool.rye]` section of pyproject.toml instead
+    /// of a `.python-version` file.
+    #[arg(long)]
+    into_pyproject: bool,
 }
 
 pub fn execute(cmd: Args) -> Result<(), Error> {
     let req: PythonVersionRequest = cmd.version.parse()?;
-    let to_write = get_pinnable_version(&req)
-        .ok_or_else(|| anyhow!("unsupported/unknown version for this platform"))?;
+    let to_write = if cmd.relaxed {
+        resolve_python_version(&req).ok_or_else(|| {
+            Failure::new(
+                ErrorKind::ToolchainMissing,
+                "unsupported/unknown version for this platform",
+            )
+        })?;
+        let serialized = req.to_string();
+        match serialized.strip_prefix("cpython@") {
+            Some(rest) => rest.to_string(),
+            None => serialized,
+        }
+    } else {
+        get_pinnable_version(&req).ok_or_else(|| {
+            Failure::new(
+                ErrorKind::ToolchainMissing,
+                "unsupported/unknown version for this platform",
+            )
+        })?
+    };
+
+    if cmd.into_pyproject {
+        let mut pyproject = PyProject::discover()?;
+        pyproject.set_toolchain(&to_write);
+        pyproject.save()?;
+        eprintln!("pinned {} in {}", to_write, pyproject.toml_path().display());
+        let version_file = pyproject.root_path().join(".python-version");
+        if version_file.is_file() {
+            eprintln!(
+                "{} {} takes precedence over the pin in pyproject.toml",
+                style("warning:").yellow(),
+                version_file.display()
+            );
+        }
+        return Ok(());
+    }
 
     let version_file = match PyProject::discover() {
         Ok(proj) => proj.root_path().join(".python-version"),
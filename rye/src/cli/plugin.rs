@@ -0,0 +1,69 @@
+use std::env;
+use std::ffi::{CString, OsString};
+use std::os::unix::prelude::OsStrExt;
+
+use anyhow::{bail, Context, Error};
+use serde_json::{json, Map, Value};
+
+use rye_core::config::{get_app_dir, load_python_version};
+use rye_core::pyproject::PyProject;
+
+/// Runs an external `rye-<name>` command found on `PATH`.
+///
+/// The project context is passed to the plugin in `RYE_*` environment
+/// variables and as a JSON object in `RYE_CONTEXT`.
+pub fn execute(args: Vec<OsString>) -> Result<(), Error> {
+    let name = args[0].to_string_lossy().into_owned();
+    let plugin = match which::which(format!("rye-{}", name)) {
+        Ok(plugin) => plugin,
+        Err(_) => bail!(
+            "no such command: '{}' (no rye-{} executable on PATH)",
+            name,
+            name
+        ),
+    };
+
+    let context = plugin_context()?;
+    for (key, value) in &context {
+        if let Some(value) = value.as_str() {
+            env::set_var(format!("RYE_{}", key.to_ascii_uppercase()), value);
+        }
+    }
+    env::set_var("RYE_CONTEXT", Value::Object(context).to_string());
+
+    let path = CString::new(plugin.as_os_str().as_bytes())?;
+    let args = Some(path.clone())
+        .into_iter()
+        .chain(
+            args[1..]
+                .iter()
+                .filter_map(|x| CString::new(x.as_bytes()).ok()),
+        )
+        .collect::<Vec<_>>();
+    let Err(err) = nix::unistd::execv(&path, &args);
+    Err(err).with_context(|| format!("unable to spawn plugin {}", plugin.display()))
+}
+
+/// Collects what a plugin needs to know about rye and the current project.
+fn plugin_context() -> Result<Map<String, Value>, Error> {
+    let mut rv = Map::new();
+    rv.insert(
+        "bin".into(),
+        env::current_exe()?.to_string_lossy().into_owned().into(),
+    );
+    rv.insert("version".into(), env!("CARGO_PKG_VERSION").into());
+    rv.insert(
+        "home".into(),
+        get_app_dir()?.to_string_lossy().into_owned().into(),
+    );
+    if let Ok(project) = PyProject::discover() {
+        rv.insert("project_root".into(), json!(project.root_path()));
+        rv.insert("pyproject".into(), json!(project.toml_path()));
+        rv.insert("workspace_root".into(), json!(project.workspace_path()));
+        rv.insert("venv".into(), json!(project.venv_path()));
+    }
+    if let Some(version) = load_python_version() {
+        rv.insert("toolchain".into(), version.to_string().into());
+    }
+    Ok(rv)
+}
@@ -0,0 +1,291 @@
+use std::ffi::OsString;
+use std::fs;
+use std::process::Command;
+
+use anyhow::{bail, Context, Error};
+use clap::{Parser, ValueEnum};
+use console::style;
+use pep508_rs::Requirement;
+
+use rye_core::error::{ErrorKind, Failure};
+use rye_core::installer::ensure_tool_env;
+use rye_core::lock::LockOptions;
+use rye_core::pyproject::PyProject;
+use rye_core::signals;
+use rye_core::sources::PythonVersionRequest;
+use rye_core::sync::{find_stale_venv, read_venv_marker};
+use rye_core::utils::{write_atomic, CommandOutput};
+
+/// The name of the pre-commit config file.
+const CONFIG_FILENAME: &str = ".pre-commit-config.yaml";
+
+/// The lines enclosing the hooks rye manages in the pre-commit config.
+const BLOCK_START: &str = "# begin rye hooks (managed by `rye self pre-commit install`)";
+const BLOCK_END: &str = "# end rye hooks";
+
+/// Which files affect the lockfiles and the virtualenv.
+const PROJECT_FILES: &str = r"(^|/)(pyproject\.toml|requirements(-dev)?\.lock)$";
+
+/// Pre-commit hooks implemented by rye.
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    command: SubCommand,
+}
+
+#[derive(Parser, Debug)]
+enum SubCommand {
+    Install(InstallCommand),
+    Run(RunCommand),
+}
+
+/// Writes the hooks of rye into the `.pre-commit-config.yaml` of the project.
+///
+/// The file is created if it does not exist yet.  Otherwise the hooks are
+/// added to it, or updated if they were added before.
+#[derive(Parser, Debug)]
+pub struct InstallCommand {
+    /// Only install these hooks (defaults to all of them).
+    #[arg(long, value_enum)]
+    hook: Vec<Hook>,
+}
+
+/// Runs a hook, this is what the entries in the pre-commit config invoke.
+#[derive(Parser, Debug)]
+pub struct RunCommand {
+    /// The hook to run.
+    #[arg(value_enum)]
+    hook: Hook,
+    /// The files pre-commit passes to the hook.
+    files: Vec<OsString>,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum Hook {
+    /// Fails if the lockfiles are out of date with pyproject.toml.
+    LockCheck,
+    /// Fails if the virtualenv is out of date with the lockfiles.
+    SyncCheck,
+    /// Formats the python files with ruff.
+    Fmt,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::LockCheck => "lock-check",
+            Hook::SyncCheck => "sync-check",
+            Hook::Fmt => "fmt",
+        }
+    }
+
+    /// The entry of the hook in the `hooks` of a pre-commit repo.
+    fn config(self, indent: &str) -> String {
+        let (description, filter, pass_filenames) = match self {
+            Hook::LockCheck => (
+                "rye lockfiles are up to date",
+                format!("files: '{}'", PROJECT_FILES),
+                false,
+            ),
+            Hook::SyncCheck => (
+                "rye virtualenv is in sync",
+                format!("files: '{}'", PROJECT_FILES),
+                false,
+            ),
+            Hook::Fmt => ("rye fmt", "types: [python]".to_string(), true),
+        };
+        let mut rv = format!("{indent}    -   id: rye-{}\n", self.name());
+        for line in [
+            format!("name: {}", description),
+            format!("entry: rye self pre-commit run {}", self.name()),
+            "language: system".to_string(),
+            filter,
+            format!("pass_filenames: {}", pass_filenames),
+        ] {
+            rv.push_str(&format!("{indent}        {}\n", line));
+        }
+        rv
+    }
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    match cmd.command {
+        SubCommand::Install(cmd) => install(cmd),
+        SubCommand::Run(cmd) => run(cmd),
+    }
+}
+
+fn install(cmd: InstallCommand) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    let path = pyproject.workspace_path().join(CONFIG_FILENAME);
+    let hooks = if cmd.hook.is_empty() {
+        vec![Hook::LockCheck, Hook::SyncCheck, Hook::Fmt]
+    } else {
+        cmd.hook
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("unable to read {}", path.display())),
+    };
+    let updated = update_config(&contents, &hooks)?;
+    if updated == contents {
+        eprintln!("{} is up to date", CONFIG_FILENAME);
+        return Ok(());
+    }
+    write_atomic(&path, updated).with_context(|| format!("unable to write {}", path.display()))?;
+    eprintln!("Wrote {}", style(path.display()).cyan());
+    Ok(())
+}
+
+/// Adds the block with the hooks of rye to a pre-commit config, replacing
+/// the one added before.
+///
+/// The block is a `local` repo which goes first into `repos`, indented like
+/// the repos already there.
+fn update_config(contents: &str, hooks: &[Hook]) -> Result<String, Error> {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut in_block = false;
+    let mut block_at = None;
+    let mut block_indent = None;
+    for line in contents.lines() {
+        if line.trim() == BLOCK_START {
+            in_block = true;
+            block_at = Some(lines.len());
+            block_indent = Some(&line[..line.len() - line.trim_start().len()]);
+        } else if line.trim() == BLOCK_END {
+            in_block = false;
+        } else if !in_block {
+            lines.push(line);
+        }
+    }
+    if in_block {
+        bail!("{} has no end for the rye hooks", CONFIG_FILENAME);
+    }
+
+    let block_at = match block_at {
+        Some(idx) => idx,
+        None => match lines.iter().position(|x| x.trim_end() == "repos:") {
+            Some(idx) => idx + 1,
+            None if contents.trim().is_empty() => {
+                lines.clear();
+                lines.push("repos:");
+                1
+            }
+            None => bail!("{} has no top-level repos", CONFIG_FILENAME),
+        },
+    };
+    // a new item of the sequence has to be indented like the others
+    let indent = block_indent.unwrap_or_else(|| {
+        lines[block_at..]
+            .iter()
+            .find(|x| !x.trim().is_empty() && !x.trim_start().starts_with('#'))
+            .filter(|x| x.trim_start().starts_with('-'))
+            .map(|x| &x[..x.len() - x.trim_start().len()])
+            .unwrap_or("")
+    });
+
+    let mut block = format!("{indent}{}\n", BLOCK_START);
+    block.push_str(&format!("{indent}-   repo: local\n{indent}    hooks:\n"));
+    for hook in hooks {
+        block.push_str(&hook.config(indent));
+    }
+    block.push_str(&format!("{indent}{}\n", BLOCK_END));
+
+    let mut rv = String::new();
+    for (idx, line) in lines.iter().enumerate() {
+        if idx == block_at {
+            rv.push_str(&block);
+        }
+        rv.push_str(line);
+        rv.push('\n');
+    }
+    if block_at == lines.len() {
+        rv.push_str(&block);
+    }
+    Ok(rv)
+}
+
+fn run(cmd: RunCommand) -> Result<(), Error> {
+    let output = CommandOutput::Normal;
+    let pyproject = PyProject::discover()?;
+    match cmd.hook {
+        Hook::LockCheck => super::lock::check(output, &LockOptions::default(), false, None),
+        Hook::SyncCheck => {
+            // fresh clones and the environments pre-commit runs in on CI
+            // have nothing to check
+            if read_venv_marker(&pyproject).is_none() {
+                eprintln!(
+                    "{} no virtualenv, skipping the sync check",
+                    style("note:").cyan()
+                );
+                return Ok(());
+            }
+            if find_stale_venv(&pyproject)?.is_some() {
+                bail!(Failure::new(
+                    ErrorKind::OutOfSync,
+                    "virtualenv is out of date, run `rye sync` to update it"
+                ));
+            }
+            Ok(())
+        }
+        Hook::Fmt => {
+            if cmd.files.is_empty() {
+                return Ok(());
+            }
+            // a ruff from the dev dependencies wins, so that the version
+            // the project is formatted with is the locked one.  Otherwise
+            // it is whatever ruff the tool env got when it was created.
+            let venv_ruff = pyproject.venv_bin_path().join("ruff");
+            let ruff = if venv_ruff.is_file() {
+                venv_ruff
+            } else {
+                let requirement: Requirement = "ruff".parse()?;
+                let py_ver = PythonVersionRequest {
+                    kind: None,
+                    major: 3,
+                    minor: None,
+                    patch: None,
+                    suffix: None,
+                };
+                ensure_tool_env(&requirement, &py_ver, output)?.join("bin/ruff")
+            };
+            let status = signals::status(Command::new(ruff).arg("format").args(&cmd.files))
+                .context("unable to run ruff")?;
+            if !status.success() {
+                bail!("formatting failed");
+            }
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_update_config() {
+    let hooks = [Hook::LockCheck];
+    let fresh = update_config("", &hooks).unwrap();
+    assert!(fresh.starts_with("repos:\n# begin rye hooks"));
+    assert!(fresh.contains("\n-   repo: local\n    hooks:\n    -   id: rye-lock-check\n"));
+    assert!(fresh.ends_with("# end rye hooks\n"));
+
+    let existing = "\
+repos:
+  - repo: https://github.com/pre-commit/pre-commit-hooks
+    rev: v4.4.0
+    hooks:
+      - id: trailing-whitespace
+";
+    let updated = update_config(existing, &hooks).unwrap();
+    assert!(updated.starts_with("repos:\n  # begin rye hooks"));
+    assert!(updated.contains("\n  -   repo: local\n"));
+    assert!(updated.ends_with("  # end rye hooks\n  - repo: https://github.com/pre-commit/pre-commit-hooks\n    rev: v4.4.0\n    hooks:\n      - id: trailing-whitespace\n"));
+
+    // updating again replaces the block instead of adding another one
+    let again = update_config(&updated, &[Hook::LockCheck, Hook::Fmt]).unwrap();
+    assert_eq!(again.matches("repo: local").count(), 1);
+    assert!(again.contains("id: rye-fmt"));
+    assert_eq!(
+        update_config(&again, &[Hook::LockCheck, Hook::Fmt]).unwrap(),
+        again
+    );
+}
@@ -4,8 +4,8 @@ use anyhow::Error;
 use clap::Parser;
 use pep508_rs::Requirement;
 
-use crate::pyproject::{DependencyKind, PyProject};
-use crate::utils::{format_requirement, CommandOutput};
+use rye_core::pyproject::{DependencyKind, PyProject};
+use rye_core::utils::{format_requirement, CommandOutput};
 
 /// Removes a package from this project.
 #[derive(Parser, Debug)]
@@ -18,6 +18,9 @@ pub struct Args {
     /// Remove this from an optional dependency group.
     #[arg(long, conflicts_with = "dev")]
     optional: Option<String>,
+    /// Remove this from the requirements of the build backend (`build-system.requires`).
+    #[arg(long, conflicts_with_all = ["dev", "optional"])]
+    build_backend_dep: bool,
     /// Enables verbose diagnostics.
     #[arg(short, long)]
     verbose: bool,
@@ -37,6 +40,8 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
             &requirement,
             if cmd.dev {
                 DependencyKind::Dev
+            } else if cmd.build_backend_dep {
+                DependencyKind::BuildSystem
             } else if let Some(ref section) = cmd.optional {
                 DependencyKind::Optional(section.into())
             } else {
@@ -1,13 +1,35 @@
-use std::env;
 use std::ffi::{CString, OsString};
 use std::os::unix::prelude::OsStrExt;
+use std::path::Path;
+use std::process::Command as ProcessCommand;
+use std::{env, io};
 
-use anyhow::{bail, Context, Error};
+use anyhow::{anyhow, bail, Context, Error};
 use clap::Parser;
 use console::style;
 
-use crate::pyproject::{PyProject, Script};
-use crate::sync::{sync, SyncOptions};
+use rye_core::config::{apply_env_config_to_process, Config, EnvTarget};
+use rye_core::overlay::{ensure_overlay, overlay_env};
+use rye_core::pyproject::{PyProject, Script};
+use rye_core::sync::{find_stale_venv, sync, SyncOptions};
+use rye_core::utils::CommandOutput;
+
+/// Calls the function of a `call` script the way the wrapper of a console
+/// script would.  Invoked with the target, the script name and its arguments.
+const CALL_SCRIPT: &str = r#"
+import importlib
+import sys
+
+target = sys.argv[1]
+module, sep, attrs = target.partition(":")
+if not sep or not module or not attrs:
+    sys.exit(f"invalid call target '{target}' (expected module:function)")
+sys.argv = sys.argv[2:]
+func = importlib.import_module(module.strip())
+for attr in attrs.strip().split("."):
+    func = getattr(func, attr)
+sys.exit(func())
+"#;
 
 /// Runs a command installed into this package.
 #[derive(Parser, Debug)]
@@ -16,6 +38,16 @@ pub struct Args {
     /// List all commands
     #[arg(short, long)]
     list: bool,
+    /// Do not load the env file configured for the script.
+    #[arg(long)]
+    no_env_file: bool,
+    /// Run with an extra package installed, without adding it to the
+    /// virtualenv.  Can be passed multiple times.
+    #[arg(long = "with", value_name = "REQUIREMENT")]
+    with: Vec<String>,
+    /// Do not sync the virtualenv first, even if `behavior.autosync` is on.
+    #[arg(long)]
+    no_sync: bool,
     /// The command to run
     #[command(subcommand)]
     cmd: Option<Command>,
@@ -30,6 +62,13 @@ enum Command {
 pub fn execute(cmd: Args) -> Result<(), Error> {
     let pyproject = PyProject::discover()?;
 
+    if !cmd.no_sync && Config::current()?.autosync() {
+        if let Some(options) = find_stale_venv(&pyproject)? {
+            eprintln!("Virtualenv is out of date, syncing");
+            sync(options).context("failed to sync ahead of run")?;
+        }
+    }
+
     // make sure we have the minimal virtualenv.
     sync(SyncOptions::python_only()).context("failed to sync ahead of run")?;
     let venv_bin = pyproject.venv_bin_path();
@@ -43,8 +82,74 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
     };
 
     let short_name = args[0].to_string_lossy().to_string();
+    let load_env_file = !cmd.no_env_file;
+
+    apply_env_config_to_process(EnvTarget::Scripts)?;
+
+    // when we spawn into a script, we implicitly activate the virtualenv to make
+    // the life of tools easier that expect to be in one.
+    env::set_var("VIRTUAL_ENV", &*pyproject.venv_path());
+    if let Some(path) = env::var_os("PATH") {
+        let mut new_path = venv_bin.as_os_str().to_owned();
+        new_path.push(":");
+        new_path.push(path);
+        env::set_var("PATH", new_path);
+    } else {
+        env::set_var("PATH", &*venv_bin);
+    }
+    env::remove_var("PYTHONHOME");
+
+    if !cmd.with.is_empty() {
+        let overlay = ensure_overlay(&pyproject.venv_path(), &cmd.with, CommandOutput::Normal)?;
+        for (key, value) in overlay_env(&overlay) {
+            env::set_var(key, value);
+        }
+        let overlay_script = overlay.join("bin").join(&args[0]);
+        if overlay_script.is_file() {
+            args[0] = overlay_script.into();
+        }
+    }
+
+    // chains and scripts with hooks run as a sequence of child processes,
+    // everything else replaces this process.
+    if matches!(
+        pyproject.get_script_cmd(&short_name),
+        Some(Script::Chain(_))
+    ) || has_hooks(&pyproject, &short_name)
+    {
+        let code = run_script(
+            &pyproject,
+            &short_name,
+            &args[1..],
+            load_env_file,
+            &mut Vec::new(),
+        )?;
+        if code != 0 {
+            std::process::exit(code);
+        }
+        return Ok(());
+    }
+
+    let script_env = pyproject.get_script_env(&short_name, load_env_file)?;
+    let args = resolve_script_args(&pyproject, args)
+        .iter()
+        .filter_map(|x| CString::new(x.as_bytes()).ok())
+        .collect::<Vec<_>>();
+    let path = CString::new(args[0].as_bytes())?;
+    for (key, value) in script_env {
+        env::set_var(key, value);
+    }
+
+    let Err(err) = nix::unistd::execv(&path, &args);
+    if err == nix::Error::ENOENT {
+        bail!("No script with name '{}' found in virtualenv", short_name);
+    }
+    Err(err.into())
+}
 
-    // do we have a custom script to invoke?
+/// Turns a script name and its arguments into the final command line.
+fn resolve_script_args(pyproject: &PyProject, mut args: Vec<OsString>) -> Vec<OsString> {
+    let venv_bin = pyproject.venv_bin_path();
     match pyproject.get_script_cmd(&args[0].to_string_lossy()) {
         Some(Script::Cmd(script_args)) if !script_args.is_empty() => {
             let script_target = venv_bin.join(&script_args[0]);
@@ -65,36 +170,120 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
         Some(Script::External(_)) => {
             args[0] = venv_bin.join(&args[0]).into();
         }
+        Some(Script::Call(target)) => {
+            let name = args.remove(0);
+            args = call_script_args(&venv_bin, &target, name)
+                .into_iter()
+                .chain(args)
+                .collect();
+        }
         _ => {}
     }
+    args
+}
 
-    let args = args
-        .iter()
-        .filter_map(|x| CString::new(x.as_bytes()).ok())
-        .collect::<Vec<_>>();
-    let path = CString::new(args[0].as_bytes())?;
+/// The command line that calls the function of a `call` script.
+pub(crate) fn call_script_args(venv_bin: &Path, target: &str, name: OsString) -> Vec<OsString> {
+    vec![
+        venv_bin.join("python").into(),
+        "-c".into(),
+        CALL_SCRIPT.into(),
+        target.into(),
+        name,
+    ]
+}
 
-    // when we spawn into a script, we implicitly activate the virtualenv to make
-    // the life of tools easier that expect to be in one.
-    env::set_var("VIRTUAL_ENV", &*pyproject.venv_path());
-    if let Some(path) = env::var_os("PATH") {
-        let mut new_path = venv_bin.as_os_str().to_owned();
-        new_path.push(":");
-        new_path.push(path);
-        env::set_var("PATH", new_path);
+fn has_hooks(pyproject: &PyProject, name: &str) -> bool {
+    ["pre", "post"].iter().any(|prefix| {
+        pyproject
+            .get_configured_script(&format!("{}-{}", prefix, name))
+            .is_some()
+    })
+}
+
+/// Runs a script with its `pre-` and `post-` hooks as child processes.
+///
+/// Returns the exit code of the first step that failed or `0`.
+fn run_script(
+    pyproject: &PyProject,
+    name: &str,
+    extra_args: &[OsString],
+    load_env_file: bool,
+    stack: &mut Vec<String>,
+) -> Result<i32, Error> {
+    if stack.iter().any(|x| x == name) {
+        bail!("script '{}' ends up calling itself", name);
+    }
+    stack.push(name.to_string());
+
+    let pre_hook = format!("pre-{}", name);
+    if pyproject.get_configured_script(&pre_hook).is_some() {
+        let code = run_script(pyproject, &pre_hook, &[], load_env_file, stack)?;
+        if code != 0 {
+            return Ok(code);
+        }
+    }
+
+    if let Some(Script::Chain(steps)) = pyproject.get_script_cmd(name) {
+        if !extra_args.is_empty() {
+            bail!("chained script '{}' does not accept arguments", name);
+        }
+        for step in steps {
+            let step_args = shlex::split(&step)
+                .filter(|x| !x.is_empty())
+                .ok_or_else(|| anyhow!("invalid step '{}' in script '{}'", step, name))?;
+            let step_args: Vec<OsString> = step_args.into_iter().map(Into::into).collect();
+            let code = run_script(
+                pyproject,
+                &step_args[0].to_string_lossy(),
+                &step_args[1..],
+                load_env_file,
+                stack,
+            )?;
+            if code != 0 {
+                return Ok(code);
+            }
+        }
     } else {
-        env::set_var("PATH", &*venv_bin);
+        let args = resolve_script_args(
+            pyproject,
+            Some(OsString::from(name))
+                .into_iter()
+                .chain(extra_args.iter().cloned())
+                .collect(),
+        );
+        let status = ProcessCommand::new(&args[0])
+            .args(&args[1..])
+            .envs(pyproject.get_script_env(name, load_env_file)?)
+            .status();
+        let status = match status {
+            Ok(status) => status,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                bail!("No script with name '{}' found in virtualenv", name)
+            }
+            Err(err) => return Err(err).context(format!("unable to run script '{}'", name)),
+        };
+        if !status.success() {
+            eprintln!(
+                "{} script '{}' failed with {}",
+                style("error:").red(),
+                name,
+                status
+            );
+            return Ok(status.code().unwrap_or(1));
+        }
     }
-    env::remove_var("PYTHONHOME");
 
-    if let Err(err) = nix::unistd::execv(&path, &args) {
-        if err == nix::Error::ENOENT {
-            bail!("No script with name '{}' found in virtualenv", short_name);
+    let post_hook = format!("post-{}", name);
+    if pyproject.get_configured_script(&post_hook).is_some() {
+        let code = run_script(pyproject, &post_hook, &[], load_env_file, stack)?;
+        if code != 0 {
+            return Ok(code);
         }
-        return Err(err.into());
     }
 
-    Ok(())
+    stack.pop();
+    Ok(0)
 }
 
 fn list_scripts(pyproject: &PyProject) -> Result<(), Error> {
@@ -106,7 +295,7 @@ fn list_scripts(pyproject: &PyProject) -> Result<(), Error> {
             Some((name, script))
         })
         .collect();
-    scripts.sort_by(|a, b| a.0.to_ascii_lowercase().cmp(&b.0.to_ascii_lowercase()));
+    scripts.sort_by_key(|a| a.0.to_ascii_lowercase());
     for (name, script) in scripts {
         if matches!(script, Script::External(_)) {
             println!("{}", name);
@@ -1,7 +1,27 @@
+use std::env;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::PathBuf;
 use std::process::Command;
 
 use anyhow::{bail, Context, Error};
 use clap::Parser;
+use console::{style, Term};
+use serde::Deserialize;
+
+use rye_core::bootstrap::{ensure_self_venv, find_self_venv_issue, install_self_tools, SELF_TOOLS};
+use rye_core::builddeps::{BuildDep, OsFamily};
+use rye_core::cache::format_size;
+use rye_core::config::{
+    get_app_dir, get_canonical_py_path, get_config_path, list_known_toolchains,
+    load_python_version, Config,
+};
+use rye_core::disk::disk_usage;
+use rye_core::pyproject::normalize_package_name;
+use rye_core::shell::{add_to_path, is_on_path, remove_from_path, Shell};
+use rye_core::utils::CommandOutput;
+
+use super::toolchain::register_toolchain;
 
 /// Rye self management
 #[derive(Parser, Debug)]
@@ -27,17 +47,551 @@ pub struct UpdateCommand {
     force: bool,
 }
 
+/// Rebuilds the internal virtualenv of rye.
+///
+/// Rye rebuilds it automatically if it detects a problem, this forces it.
+#[derive(Parser, Debug)]
+pub struct RebuildCommand {
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+/// Checks the rye installation for problems.
+#[derive(Parser, Debug)]
+pub struct DoctorCommand {
+    /// Also check for the system prerequisites of building packages from source.
+    #[arg(long)]
+    build_deps: bool,
+}
+
+/// Shows where rye lives and what it has installed.
+#[derive(Parser, Debug)]
+pub struct StatusCommand {
+    /// Show how much disk space rye takes up and how to reclaim it.
+    #[arg(long)]
+    disk: bool,
+}
+
+/// Manages the tools rye uses internally (pip, pip-tools, virtualenv, unearth).
+#[derive(Parser, Debug)]
+pub struct ToolsCommand {
+    #[command(subcommand)]
+    command: ToolsSubCommand,
+}
+
+/// Upgrades the internal tools to the latest versions allowed by the
+/// `[internal-tools]` section of the config.
+#[derive(Parser, Debug)]
+pub struct ToolsUpgradeCommand {
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+#[derive(Parser, Debug)]
+enum ToolsSubCommand {
+    Upgrade(ToolsUpgradeCommand),
+}
+
+/// Adds the shims folder to the PATH in the profile of your shell.
+///
+/// The shell is detected from the environment.  Running this again does not
+/// add the folder twice.
+#[derive(Parser, Debug)]
+pub struct SetupPathCommand {
+    /// The shell to set up (bash, zsh, fish, powershell or sh).
+    #[arg(long)]
+    shell: Option<Shell>,
+    /// Only print what to add to the profile instead of changing it.
+    #[arg(long)]
+    no_modify_path: bool,
+    /// Remove the shims folder from the PATH again.
+    #[arg(long, conflicts_with = "no_modify_path")]
+    remove: bool,
+}
+
+/// Sets up rye by writing the global config.
+///
+/// Asks whether to download toolchains or use the python on the `PATH`,
+/// whether to add the shims folder to the `PATH` and whether to check for
+/// toolchain updates.  This runs by itself the first time rye is used in a
+/// terminal.  With `--yes` nothing is asked; `RYE_TOOLCHAIN` and
+/// `RYE_NO_MODIFY_PATH` can be set instead of the options.
+#[derive(Parser, Debug)]
+pub struct InstallCommand {
+    /// Do not ask, go with the defaults and the given options.
+    #[arg(short, long)]
+    yes: bool,
+    /// Use this interpreter as default toolchain instead of downloading one.
+    #[arg(long, value_name = "PATH")]
+    toolchain: Option<PathBuf>,
+    /// Do not add the shims folder to the PATH.
+    #[arg(long)]
+    no_modify_path: bool,
+    /// Do not check for new toolchain releases.
+    #[arg(long)]
+    no_update_check: bool,
+}
+
+/// Removes rye's folder with all toolchains, tools and caches and undoes
+/// the PATH setup.
+#[derive(Parser, Debug)]
+pub struct UninstallCommand {
+    /// Do not ask for confirmation.
+    #[arg(short, long)]
+    yes: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct PipListEntry {
+    name: String,
+    version: String,
+}
+
 #[derive(Parser, Debug)]
 enum SubCommand {
+    Doctor(DoctorCommand),
+    Install(InstallCommand),
+    PreCommit(super::pre_commit::Args),
+    Rebuild(RebuildCommand),
+    SetupPath(SetupPathCommand),
+    Status(StatusCommand),
+    Tools(ToolsCommand),
+    Uninstall(UninstallCommand),
     Update(UpdateCommand),
 }
 
 pub fn execute(cmd: Args) -> Result<(), Error> {
     match cmd.command {
+        SubCommand::Doctor(args) => doctor(args),
+        SubCommand::Install(args) => install(args),
+        SubCommand::PreCommit(args) => super::pre_commit::execute(args),
+        SubCommand::Rebuild(args) => rebuild(args),
+        SubCommand::SetupPath(args) => setup_path(args),
+        SubCommand::Status(args) => status(args),
+        SubCommand::Tools(args) => match args.command {
+            ToolsSubCommand::Upgrade(args) => upgrade_tools(args),
+        },
+        SubCommand::Uninstall(args) => uninstall(args),
         SubCommand::Update(args) => update(args),
     }
 }
 
+fn doctor(args: DoctorCommand) -> Result<(), Error> {
+    let self_venv = get_app_dir()?.join("self");
+    let issue = if self_venv.is_dir() {
+        find_self_venv_issue(&self_venv)
+    } else {
+        Some("not bootstrapped yet".into())
+    };
+    match issue {
+        None => println!("rye internals: {}", style("ok").green()),
+        Some(issue) => println!(
+            "rye internals: {} (run `rye self rebuild`)",
+            style(issue).yellow()
+        ),
+    }
+
+    let toolchain = load_python_version().map(|x| (get_canonical_py_path(&x), x));
+    match toolchain {
+        Some((Ok(ref path), ref version)) if path.exists() => {
+            println!(
+                "toolchain: {} {}",
+                style(version).cyan(),
+                style("ok").green()
+            )
+        }
+        Some((_, ref version)) => println!(
+            "toolchain: {} {} (run `rye fetch`)",
+            style(version).cyan(),
+            style("not installed").yellow()
+        ),
+        None => println!("toolchain: {}", style("not pinned").dim()),
+    }
+
+    if args.build_deps {
+        let os = OsFamily::current();
+        let toolchain_path = toolchain.and_then(|x| x.0.ok());
+        println!("build prerequisites:");
+        for dep in BuildDep::all() {
+            if dep.is_installed(toolchain_path.as_deref()) {
+                println!("  {}: {}", dep, style("ok").green());
+            } else {
+                println!(
+                    "  {}: {} ({})",
+                    dep,
+                    style("missing").yellow(),
+                    dep.remediation(os)
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn status(args: StatusCommand) -> Result<(), Error> {
+    let app_dir = get_app_dir()?;
+    let count = |dir: &str| fs::read_dir(app_dir.join(dir)).map_or(0, |x| x.count());
+    println!("version: {}", style(env!("CARGO_PKG_VERSION")).cyan());
+    println!("home: {}", app_dir.display());
+    println!(
+        "shims on PATH: {}",
+        if is_on_path(&app_dir.join("shims")) {
+            style("yes").green()
+        } else {
+            style("no").yellow()
+        }
+    );
+    println!("toolchains: {}", count("py"));
+    println!("global tools: {}", count("tools"));
+
+    if args.disk {
+        let mut total = 0;
+        for category in disk_usage()? {
+            let size = category.size();
+            total += size;
+            println!();
+            println!(
+                "{} ({})",
+                style(category.title).bold(),
+                style(format_size(size)).cyan()
+            );
+            for entry in &category.entries {
+                println!("  {:>10}  {}", format_size(entry.size), entry.name);
+            }
+            if let Some(hint) = category.hint {
+                if size > 0 {
+                    println!("  {}", style(format!("reclaim with {}", hint)).dim());
+                }
+            }
+        }
+        println!();
+        println!("total: {}", style(format_size(total)).cyan());
+    }
+    Ok(())
+}
+
+fn rebuild(args: RebuildCommand) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(args.quiet, args.verbose);
+    let dir = get_app_dir()?.join("self");
+    if dir.is_dir() {
+        fs::remove_dir_all(&dir).context("failed to remove self venv")?;
+    }
+    ensure_self_venv(output)?;
+    if output != CommandOutput::Quiet {
+        eprintln!("{} Rebuilt rye internals", style("success:").green());
+    }
+    Ok(())
+}
+
+fn setup_path(args: SetupPathCommand) -> Result<(), Error> {
+    let shims = get_app_dir()?.join("shims");
+
+    if args.remove {
+        let shells = match args.shell {
+            Some(shell) => vec![shell],
+            None => Shell::all().to_vec(),
+        };
+        let mut removed = false;
+        for shell in shells {
+            if let Some(profile) = remove_from_path(shell)? {
+                eprintln!("Removed the PATH setup from {}", profile.display());
+                removed = true;
+            }
+        }
+        if !removed {
+            eprintln!("No PATH setup found");
+        }
+        return Ok(());
+    }
+
+    let shell = args.shell.unwrap_or_else(Shell::detect);
+    let profile = shell.profile_path()?;
+    if args.no_modify_path {
+        eprintln!(
+            "To use the shims with {} add this to {}:",
+            shell,
+            profile.display()
+        );
+        println!("{}", shell.path_snippet(&shims));
+        return Ok(());
+    }
+
+    match add_to_path(shell, &shims)? {
+        Some(profile) => {
+            eprintln!(
+                "{} added {} to the PATH in {}",
+                style("success:").green(),
+                shims.display(),
+                profile.display()
+            );
+            eprintln!(
+                "{} open a new shell for the change to take effect",
+                style("note:").cyan()
+            );
+        }
+        None => eprintln!(
+            "{} is already on the PATH in {}",
+            shims.display(),
+            profile.display()
+        ),
+    }
+    Ok(())
+}
+
+/// The choices of the setup.
+#[derive(Debug)]
+struct Setup {
+    /// The interpreter to use as default toolchain, if not downloading.
+    toolchain: Option<PathBuf>,
+    modify_path: bool,
+    update_check: bool,
+}
+
+impl Setup {
+    /// The defaults, as changed by the environment variables.
+    fn from_env() -> Setup {
+        Setup {
+            toolchain: env::var_os("RYE_TOOLCHAIN")
+                .filter(|x| !x.is_empty())
+                .map(PathBuf::from),
+            modify_path: env::var_os("RYE_NO_MODIFY_PATH").is_none_or(|x| x.is_empty()),
+            update_check: true,
+        }
+    }
+}
+
+fn install(args: InstallCommand) -> Result<(), Error> {
+    let mut setup = Setup::from_env();
+    if let Some(toolchain) = args.toolchain {
+        setup.toolchain = Some(toolchain);
+    }
+    setup.modify_path &= !args.no_modify_path;
+    setup.update_check &= !args.no_update_check;
+    if !args.yes {
+        if !is_interactive() {
+            bail!("not running in a terminal, pass --yes to set up without asking");
+        }
+        setup = ask_setup(setup)?;
+    }
+    apply_setup(&setup)
+}
+
+/// Runs the setup the first time rye is used.
+///
+/// Nothing happens outside of a terminal, where the defaults apply.
+pub fn first_run_setup() -> Result<(), Error> {
+    let app_dir = get_app_dir()?;
+    if get_config_path()?.exists() || app_dir.join("self").is_dir() || !is_interactive() {
+        return Ok(());
+    }
+    eprintln!("Welcome to rye! A few questions before the first run.");
+    eprintln!("To change the answers later, run `rye self install`.");
+    let setup = ask_setup(Setup::from_env())?;
+    apply_setup(&setup)?;
+    eprintln!();
+    Ok(())
+}
+
+fn is_interactive() -> bool {
+    Term::stderr().is_term() && std::io::stdin().is_terminal()
+}
+
+fn ask_setup(mut setup: Setup) -> Result<Setup, Error> {
+    let term = Term::stderr();
+    if let Some(system) = setup.toolchain.clone().or_else(find_system_python) {
+        term.write_line("Where should Python toolchains come from?")?;
+        term.write_line("  1) download standalone builds (recommended)")?;
+        term.write_line(&format!("  2) use {}", system.display()))?;
+        let default = if setup.toolchain.is_some() { 2 } else { 1 };
+        setup.toolchain = match prompt_choice(&term, "Select", 2, default)? {
+            2 => Some(system),
+            _ => None,
+        };
+    }
+
+    let shims = get_app_dir()?.join("shims");
+    if is_on_path(&shims) {
+        setup.modify_path = false;
+    } else {
+        let profile = Shell::detect().profile_path()?;
+        setup.modify_path = confirm(
+            &term,
+            &format!(
+                "Add {} to the PATH in {}?",
+                shims.display(),
+                profile.display()
+            ),
+            setup.modify_path,
+        )?;
+    }
+
+    setup.update_check = confirm(
+        &term,
+        "Check for new toolchain releases (at most once a day)?",
+        setup.update_check,
+    )?;
+    Ok(setup)
+}
+
+/// Writes the config and sets up the PATH according to the setup.
+fn apply_setup(setup: &Setup) -> Result<(), Error> {
+    let mut config = Config::from_path(&get_config_path()?)?;
+    if let Some(ref path) = setup.toolchain {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("toolchain {} does not exist", path.display()))?;
+        // choosing the same interpreter again reuses the registration
+        let existing = list_known_toolchains()?.into_iter().find(|version| {
+            get_canonical_py_path(version)
+                .and_then(|x| Ok(x.canonicalize()?))
+                .is_ok_and(|x| x == path)
+        });
+        let version = match existing {
+            Some(version) => version,
+            None => register_toolchain(&path, None)?,
+        };
+        config.set_default_toolchain(Some(&version.to_string()));
+        eprintln!(
+            "Using {} as default toolchain ({})",
+            path.display(),
+            style(&version).cyan()
+        );
+    }
+    config.set_check_toolchain_updates(setup.update_check);
+    config.save()?;
+
+    if setup.modify_path {
+        let shims = get_app_dir()?.join("shims");
+        if let Some(profile) = add_to_path(Shell::detect(), &shims)? {
+            eprintln!(
+                "Added {} to the PATH in {}, open a new shell for it to take effect",
+                shims.display(),
+                profile.display()
+            );
+        }
+    }
+    eprintln!(
+        "{} wrote {} (rye collects no telemetry)",
+        style("success:").green(),
+        config.path().display()
+    );
+    Ok(())
+}
+
+/// Finds the `python3` on the `PATH`, skipping rye's own shims.
+fn find_system_python() -> Option<PathBuf> {
+    let shims = get_app_dir().ok()?.join("shims");
+    env::split_paths(&env::var_os("PATH")?)
+        .filter(|dir| *dir != shims)
+        .map(|dir| dir.join("python3"))
+        .find(|x| x.is_file())
+}
+
+/// Asks a yes/no question, an empty answer picks the default.
+fn confirm(term: &Term, prompt: &str, default: bool) -> Result<bool, Error> {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    loop {
+        term.write_str(&format!("{} {}: ", prompt, hint))?;
+        match term.read_line()?.trim().to_ascii_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => term.write_line("Please answer yes or no")?,
+        }
+    }
+}
+
+/// Prompts for a number between 1 and `count`, an empty answer picks the
+/// default.
+fn prompt_choice(term: &Term, prompt: &str, count: usize, default: usize) -> Result<usize, Error> {
+    loop {
+        term.write_str(&format!("{} [{}]: ", prompt, default))?;
+        let answer = term.read_line()?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(default);
+        }
+        match answer.parse::<usize>() {
+            Ok(choice) if choice >= 1 && choice <= count => return Ok(choice),
+            _ => term.write_line(&format!("Please enter a number from 1 to {}", count))?,
+        }
+    }
+}
+
+fn uninstall(args: UninstallCommand) -> Result<(), Error> {
+    let app_dir = get_app_dir()?;
+    if !args.yes {
+        let term = Term::stderr();
+        if !term.is_term() {
+            bail!("refusing to uninstall without confirmation, pass --yes");
+        }
+        term.write_str(&format!(
+            "Remove {} with all toolchains, tools and caches? [y/N]: ",
+            app_dir.display()
+        ))?;
+        let answer = term.read_line()?;
+        if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+            bail!("aborted");
+        }
+    }
+
+    for shell in Shell::all() {
+        if let Some(profile) = remove_from_path(shell)? {
+            eprintln!("Removed the PATH setup from {}", profile.display());
+        }
+    }
+    if app_dir.is_dir() {
+        fs::remove_dir_all(app_dir)
+            .with_context(|| format!("failed to remove {}", app_dir.display()))?;
+    }
+    eprintln!(
+        "{} removed {}",
+        style("success:").green(),
+        app_dir.display()
+    );
+    eprintln!(
+        "{} the rye binary itself was installed with cargo, remove it with `cargo uninstall rye`",
+        style("note:").cyan()
+    );
+    Ok(())
+}
+
+fn upgrade_tools(args: ToolsUpgradeCommand) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(args.quiet, args.verbose);
+    let dir = ensure_self_venv(output)?;
+    install_self_tools(&dir, output)?;
+    if output == CommandOutput::Quiet {
+        return Ok(());
+    }
+
+    let pip_list = Command::new(dir.join("bin/pip"))
+        .arg("list")
+        .arg("--format=json")
+        .env("PYTHONWARNINGS", "ignore")
+        .output()
+        .context("unable to list internal tools")?;
+    let installed: Vec<PipListEntry> =
+        serde_json::from_slice(&pip_list.stdout).context("could not parse pip output")?;
+    for tool in SELF_TOOLS {
+        if let Some(entry) = installed
+            .iter()
+            .find(|x| normalize_package_name(&x.name) == *tool)
+        {
+            eprintln!("{} {}", entry.name, style(&entry.version).cyan());
+        }
+    }
+    Ok(())
+}
+
 fn update(args: UpdateCommand) -> Result<(), Error> {
     let mut cmd = Command::new("cargo");
     cmd.arg("install")
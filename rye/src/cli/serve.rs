@@ -0,0 +1,297 @@
+use std::borrow::Cow;
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+
+use anyhow::{Context, Error};
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use rye_core::config::load_python_version;
+use rye_core::lock::Lockfile;
+use rye_core::pyproject::{DependencyKind, PyProject};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const FAILED: i64 = -32000;
+
+/// Serves rye operations over JSON-RPC on stdin/stdout.
+///
+/// Messages are JSON-RPC 2.0 objects, one per line.  This is meant to be
+/// spawned by editors and language servers which want to drive rye.
+#[derive(Parser, Debug)]
+pub struct Args {}
+
+#[derive(Deserialize, Debug)]
+struct Request {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct SyncParams {
+    no_dev: bool,
+    force: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct DependenciesParams {
+    dev: bool,
+    optional: Option<String>,
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl From<Error> for RpcError {
+    fn from(err: Error) -> RpcError {
+        RpcError {
+            code: FAILED,
+            message: format!("{:#}", err),
+        }
+    }
+}
+
+struct Server<W> {
+    out: Mutex<W>,
+}
+
+pub fn execute(_cmd: Args) -> Result<(), Error> {
+    Server::new(io::stdout()).serve(io::stdin().lock())
+}
+
+impl<W: Write + Send> Server<W> {
+    fn new(out: W) -> Server<W> {
+        Server {
+            out: Mutex::new(out),
+        }
+    }
+
+    fn serve(&self, input: impl BufRead) -> Result<(), Error> {
+        for line in input.lines() {
+            let line = line.context("failed to read from stdin")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let req: Request = match serde_json::from_str::<Value>(&line) {
+                Ok(value) => match serde_json::from_value(value) {
+                    Ok(req) => req,
+                    Err(err) => {
+                        self.respond(&Value::Null, Err(rpc_error(INVALID_REQUEST, err)))?;
+                        continue;
+                    }
+                },
+                Err(err) => {
+                    self.respond(&Value::Null, Err(rpc_error(PARSE_ERROR, err)))?;
+                    continue;
+                }
+            };
+
+            let shutdown = req.method == "shutdown";
+            let rv = self.handle(&req);
+            // requests without an id are notifications and get no response
+            if let Some(ref id) = req.id {
+                self.respond(id, rv)?;
+            }
+            if shutdown {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&self, req: &Request) -> Result<Value, RpcError> {
+        match req.method.as_str() {
+            "initialize" => Ok(json!({
+                "name": "rye",
+                "version": env!("CARGO_PKG_VERSION"),
+            })),
+            "shutdown" => Ok(Value::Null),
+            "project/resolve" => resolve_project(),
+            "project/interpreter" => interpreter(),
+            "project/dependencies" => dependencies(parse_params(&req.params)?),
+            "project/sync" => self.sync(req.id.as_ref(), parse_params(&req.params)?),
+            other => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("unknown method '{}'", other),
+            }),
+        }
+    }
+
+    /// Syncs by running `rye sync` and forwards its output as progress.
+    fn sync(&self, id: Option<&Value>, params: SyncParams) -> Result<Value, RpcError> {
+        let project = PyProject::discover()?;
+        let mut sync_cmd = Command::new(env::current_exe().context("unable to find rye")?);
+        sync_cmd
+            .arg("--project")
+            .arg(&*project.root_path())
+            .arg("sync");
+        if params.no_dev {
+            sync_cmd.arg("--no-dev");
+        }
+        if params.force {
+            sync_cmd.arg("--force");
+        }
+        let mut child = sync_cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("unable to spawn rye sync")?;
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+
+        let token = id.cloned().unwrap_or(Value::Null);
+        thread::scope(|scope| {
+            scope.spawn(|| self.forward_progress(&token, stdout));
+            scope.spawn(|| self.forward_progress(&token, stderr));
+        });
+
+        let status = child.wait().context("failed to wait for rye sync")?;
+        if status.success() {
+            Ok(json!({ "success": true }))
+        } else {
+            Err(RpcError {
+                code: FAILED,
+                message: format!("sync failed with {}", status),
+            })
+        }
+    }
+
+    fn forward_progress(&self, token: &Value, stream: impl Read) {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            self.send(&json!({
+                "jsonrpc": "2.0",
+                "method": "$/progress",
+                "params": { "token": token, "message": line },
+            }))
+            .ok();
+        }
+    }
+
+    fn respond(&self, id: &Value, rv: Result<Value, RpcError>) -> Result<(), Error> {
+        self.send(&match rv {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": err.code, "message": err.message },
+            }),
+        })
+    }
+
+    fn send(&self, msg: &Value) -> Result<(), Error> {
+        let mut out = self.out.lock().unwrap();
+        writeln!(out, "{}", msg)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+fn rpc_error(code: i64, err: impl std::fmt::Display) -> RpcError {
+    RpcError {
+        code,
+        message: err.to_string(),
+    }
+}
+
+fn parse_params<T: for<'de> Deserialize<'de> + Default>(params: &Value) -> Result<T, RpcError> {
+    if params.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(params.clone()).map_err(|err| rpc_error(INVALID_PARAMS, err))
+}
+
+fn resolve_project() -> Result<Value, RpcError> {
+    let project = PyProject::discover()?;
+    let members = match project.workspace() {
+        Some(workspace) => workspace
+            .iter_projects()
+            .map(|x| x.map(|x| json!({ "name": x.name(), "root": x.root_path() })))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    Ok(json!({
+        "name": project.name(),
+        "root": project.root_path(),
+        "pyproject": project.toml_path(),
+        "workspace": project.workspace().map(|_| project.workspace_path()),
+        "members": members,
+        "venv": project.venv_path(),
+    }))
+}
+
+fn interpreter() -> Result<Value, RpcError> {
+    let project = PyProject::discover()?;
+    let path = project.venv_bin_path().join("python");
+    Ok(json!({
+        "path": path,
+        "exists": path.is_file(),
+        "version": load_python_version().map(|x| x.to_string()),
+    }))
+}
+
+fn dependencies(params: DependenciesParams) -> Result<Value, RpcError> {
+    let project = PyProject::discover()?;
+    let kind = match (params.dev, params.optional) {
+        (true, _) => DependencyKind::Dev,
+        (false, Some(extra)) => DependencyKind::Optional(Cow::Owned(extra)),
+        (false, None) => DependencyKind::Normal,
+    };
+    let declared: Vec<_> = project
+        .iter_dependencies(kind)
+        .map(|x| x.to_string())
+        .collect();
+    let lockfile = project.lockfile_dir().join(if params.dev {
+        "requirements-dev.lock"
+    } else {
+        "requirements.lock"
+    });
+    let locked: Vec<_> = Lockfile::load(&lockfile)
+        .map(|x| x.packages().to_vec())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|x| json!({ "name": x.name, "version": x.version }))
+        .collect();
+    Ok(json!({ "declared": declared, "locked": locked }))
+}
+
+#[test]
+fn test_serve_protocol() {
+    let input = [
+        "{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"initialize\"}",
+        "not json",
+        "{\"jsonrpc\": \"2.0\", \"id\": 2, \"method\": \"project/frobnicate\"}",
+        "{\"jsonrpc\": \"2.0\", \"method\": \"initialize\"}",
+        "{\"jsonrpc\": \"2.0\", \"id\": 3, \"method\": \"shutdown\"}",
+        "{\"jsonrpc\": \"2.0\", \"id\": 4, \"method\": \"initialize\"}",
+    ]
+    .join("\n");
+    let server = Server::new(Vec::new());
+    server.serve(input.as_bytes()).unwrap();
+    let out = String::from_utf8(server.out.into_inner().unwrap()).unwrap();
+    let msgs: Vec<Value> = out
+        .lines()
+        .map(|x| serde_json::from_str(x).unwrap())
+        .collect();
+    assert_eq!(msgs.len(), 4);
+    assert_eq!(msgs[0]["id"], 1);
+    assert_eq!(msgs[0]["result"]["name"], "rye");
+    assert_eq!(msgs[1]["error"]["code"], PARSE_ERROR);
+    assert_eq!(msgs[2]["id"], 2);
+    assert_eq!(msgs[2]["error"]["code"], METHOD_NOT_FOUND);
+    assert_eq!(msgs[3]["id"], 3);
+    assert_eq!(msgs[3]["result"], Value::Null);
+}
@@ -5,10 +5,11 @@ use std::os::unix::prelude::OsStrExt;
 use anyhow::{bail, Context, Error};
 use same_file::is_same_file;
 
-use crate::bootstrap::{ensure_self_venv, get_pip_runner};
-use crate::pyproject::PyProject;
-use crate::sync::{sync, SyncOptions};
-use crate::utils::CommandOutput;
+use rye_core::bootstrap::{ensure_self_venv, fetch, get_pip_runner};
+use rye_core::config::{get_default_python_version, get_py_bin, Config};
+use rye_core::pyproject::PyProject;
+use rye_core::sync::{sync, SyncOptions};
+use rye_core::utils::CommandOutput;
 
 fn detect_shim() -> Option<(String, Vec<OsString>)> {
     // Shims are detected if the executable is linked into
@@ -74,15 +75,45 @@ fn find_shadowed_target(
     Ok(None)
 }
 
+/// Points the python shims to the default toolchain outside of projects.
+///
+/// Returns `None` if no default toolchain is configured.
+fn get_default_toolchain_target(
+    target: &str,
+    mut args: Vec<OsString>,
+) -> Result<Option<Vec<OsString>>, Error> {
+    if !matches!(target, "python" | "python3") || Config::current()?.default_toolchain().is_none() {
+        return Ok(None);
+    }
+    let version = fetch(&get_default_python_version()?.into(), CommandOutput::Normal)
+        .context("failed fetching default toolchain")?;
+    args[0] = get_py_bin(&version)?.into();
+    Ok(Some(args))
+}
+
+/// Whether the shim is one of the python (or secret pip) shims rather than a
+/// shim of a virtualenv script.
+fn is_python_shim(target: &str) -> bool {
+    matches!(target, "python" | "python3" | "pip" | "pip3")
+}
+
 /// Figures out where a shim should point to.
 fn get_shim_target(target: &str, mut args: Vec<OsString>) -> Result<Option<Vec<OsString>>, Error> {
     let pyproject = match PyProject::discover() {
         Ok(project) => project,
-        Err(_) => return find_shadowed_target(target, args),
+        Err(_) => {
+            return match get_default_toolchain_target(target, args.clone())? {
+                Some(args) => Ok(Some(args)),
+                None => find_shadowed_target(target, args),
+            }
+        }
     };
 
-    // make sure we have the minimal virtualenv.
-    sync(SyncOptions::python_only()).context("sync ahead of shim resolution failed")?;
+    // make sure we have the minimal virtualenv.  The shims of virtualenv
+    // scripts (`behavior.venv-shims`) only look into an existing one.
+    if is_python_shim(target) {
+        sync(SyncOptions::python_only()).context("sync ahead of shim resolution failed")?;
+    }
 
     let path = pyproject.venv_path().join("bin").join(target);
 
@@ -96,7 +127,9 @@ fn get_shim_target(target: &str, mut args: Vec<OsString>) -> Result<Option<Vec<O
         return Ok(Some(get_pip_shim(&pyproject, args, CommandOutput::Normal)?));
     }
 
-    Ok(None)
+    // shims of other virtualenvs (`behavior.venv-shims`) fall through to
+    // whatever they shadow.
+    find_shadowed_target(target, args)
 }
 
 /// This replaces ourselves with the shim target for when the
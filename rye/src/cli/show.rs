@@ -1,14 +1,19 @@
+use std::fs;
 use std::path::Path;
 use std::process::Command;
 
-use anyhow::{bail, Error};
+use anyhow::{bail, Context, Error};
 use clap::Parser;
 use console::style;
+use serde_json::json;
 
-use crate::bootstrap::ensure_self_venv;
-use crate::config::load_python_version;
-use crate::pyproject::PyProject;
-use crate::utils::CommandOutput;
+use rye_core::bootstrap::ensure_self_venv;
+use rye_core::config::load_python_version;
+use rye_core::index::{find_release_files, select_distribution, supported_tags as python_tags};
+use rye_core::lock::parse_lockfile;
+use rye_core::pyproject::{normalize_package_name, DependencyKind, PyProject};
+use rye_core::sync::{read_sync_report, read_venv_marker};
+use rye_core::utils::CommandOutput;
 
 /// Prints the current state of the project.
 #[derive(Parser, Debug)]
@@ -16,6 +21,20 @@ pub struct Args {
     /// Print the installed dependencies from the venv
     #[arg(long)]
     installed_deps: bool,
+    /// Print the declared dependencies with their notes from
+    /// `tool.rye.dependency-notes`.
+    #[arg(long, conflicts_with = "installed_deps")]
+    deps: bool,
+    /// Print the state as JSON, including how the virtualenv was created.
+    #[arg(long, conflicts_with_all = ["installed_deps", "deps"])]
+    json: bool,
+    /// Print the wheel tags the virtualenv's python supports and which file
+    /// of each locked package gets installed on this platform.
+    #[arg(long, conflicts_with_all = ["installed_deps", "deps", "json"])]
+    tags: bool,
+    /// With --tags, look at the production lockfile instead of the dev one.
+    #[arg(long, requires = "tags")]
+    no_dev: bool,
 }
 
 pub fn execute(cmd: Args) -> Result<(), Error> {
@@ -24,6 +43,15 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
     if cmd.installed_deps {
         return print_installed_deps(&project);
     }
+    if cmd.deps {
+        return print_deps(&project);
+    }
+    if cmd.json {
+        return print_json(&project);
+    }
+    if cmd.tags {
+        return print_tags(&project, cmd.no_dev);
+    }
 
     println!(
         "project: {}",
@@ -34,6 +62,21 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
     if let Some(ver) = load_python_version() {
         println!("pinned python: {}", style(ver).cyan());
     }
+    if let Some(marker) = read_venv_marker(&project) {
+        println!("venv python: {}", style(&marker.python).cyan());
+        if let Some(ref conda) = marker.conda {
+            println!("  conda environment: {}", style(conda.display()).cyan());
+        }
+        if let Some(ref provenance) = marker.provenance {
+            println!(
+                "  created: {} by rye {} from {} ({})",
+                provenance.created_at,
+                provenance.rye_version,
+                style(provenance.interpreter.display()).dim(),
+                provenance.toolchain_source
+            );
+        }
+    }
 
     if let Some(workspace) = project.workspace() {
         println!(
@@ -61,6 +104,65 @@ pub fn execute(cmd: Args) -> Result<(), Error> {
     Ok(())
 }
 
+fn print_json(project: &PyProject) -> Result<(), Error> {
+    let workspace = match project.workspace() {
+        Some(workspace) => {
+            let mut members = Vec::new();
+            for child in workspace.iter_projects() {
+                let child = child?;
+                members.push(json!({
+                    "name": child.name(),
+                    "path": child.root_path(),
+                }));
+            }
+            json!({
+                "path": project.workspace_path(),
+                "members": members,
+            })
+        }
+        None => serde_json::Value::Null,
+    };
+    let rv = json!({
+        "project": project.name(),
+        "path": project.root_path(),
+        "venv": project.venv_path(),
+        "pinned_python": load_python_version().map(|x| x.to_string()),
+        "workspace": workspace,
+        "venv_marker": read_venv_marker(project),
+        "last_sync": read_sync_report(project),
+    });
+    println!("{}", serde_json::to_string_pretty(&rv)?);
+    Ok(())
+}
+
+fn print_deps(project: &PyProject) -> Result<(), Error> {
+    let notes = project.dependency_notes()?;
+    let mut sections = vec![
+        ("dependencies".to_string(), DependencyKind::Normal),
+        ("dev-dependencies".to_string(), DependencyKind::Dev),
+    ];
+    for name in project.optional_dependency_sections() {
+        sections.push((
+            format!("optional-dependencies.{}", name),
+            DependencyKind::Optional(name.into()),
+        ));
+    }
+    for (title, kind) in sections {
+        let deps = project.iter_dependencies(kind).collect::<Vec<_>>();
+        if deps.is_empty() {
+            continue;
+        }
+        println!("{}:", style(title).yellow());
+        for dep in deps {
+            match notes.get(&normalize_package_name(&dep.name)) {
+                Some(note) => println!("  {}  {}", dep, style(format!("# {}", note)).dim()),
+                None => println!("  {}", dep),
+            }
+        }
+    }
+    Ok(())
+}
+
 fn print_installed_deps(project: &PyProject) -> Result<(), Error> {
     let python = project.venv_bin_path().join("python");
     if !python.is_file() {
@@ -81,3 +183,89 @@ fn print_installed_deps(project: &PyProject) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Returns the wheel tags the python of the virtualenv supports, the most
+/// preferred first.
+fn supported_tags(project: &PyProject) -> Result<Vec<String>, Error> {
+    let python = project.venv_bin_path().join("python");
+    if !python.is_file() {
+        bail!("no virtualenv found, run `rye sync` first");
+    }
+    python_tags(&python, CommandOutput::Normal)
+}
+
+fn print_tags(project: &PyProject, no_dev: bool) -> Result<(), Error> {
+    let tags = supported_tags(project)?;
+    println!("supported tags ({}, most preferred first):", tags.len());
+    for tag in &tags {
+        println!("  {}", tag);
+    }
+
+    let lockfile = project.lockfile_dir().join(if no_dev {
+        "requirements.lock"
+    } else {
+        "requirements-dev.lock"
+    });
+    let contents = fs::read_to_string(&lockfile).with_context(|| {
+        format!(
+            "unable to read lockfile {}. Run `rye lock` first.",
+            lockfile.display()
+        )
+    })?;
+
+    println!("selected distributions:");
+    for package in parse_lockfile(&contents) {
+        let version = match package.version {
+            Some(ref version) => version,
+            None => {
+                println!(
+                    "  {} {}",
+                    style(&package.name).cyan(),
+                    style("(local)").dim()
+                );
+                continue;
+            }
+        };
+        // one index that cannot be reached should not hide the rest
+        let files = match find_release_files(&package.name, version) {
+            Ok(release) => release.map(|x| x.files).unwrap_or_default(),
+            Err(err) => {
+                println!(
+                    "  {}=={}: {}",
+                    style(&package.name).cyan(),
+                    version,
+                    style(format!("unavailable ({:#})", err)).yellow()
+                );
+                continue;
+            }
+        };
+        match select_distribution(&files, &tags) {
+            Some(file) if file.packagetype == "sdist" => println!(
+                "  {}=={}: {} {}",
+                style(&package.name).cyan(),
+                version,
+                file.filename,
+                style("(sdist, no matching wheel)").yellow()
+            ),
+            Some(file) => println!(
+                "  {}=={}: {}",
+                style(&package.name).cyan(),
+                version,
+                file.filename
+            ),
+            None if files.is_empty() => println!(
+                "  {}=={}: {}",
+                style(&package.name).cyan(),
+                version,
+                style("not found on the index").yellow()
+            ),
+            None => println!(
+                "  {}=={}: {}",
+                style(&package.name).cyan(),
+                version,
+                style("no matching distribution").red()
+            ),
+        }
+    }
+    Ok(())
+}
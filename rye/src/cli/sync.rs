@@ -1,9 +1,16 @@
-use anyhow::Error;
-use clap::Parser;
+use std::path::PathBuf;
 
-use crate::lock::LockOptions;
-use crate::sync::{sync, SyncMode, SyncOptions};
-use crate::utils::CommandOutput;
+use anyhow::{bail, Error};
+use clap::{Parser, ValueEnum};
+use console::style;
+
+use rye_core::conda::CondaEnvRequest;
+use rye_core::config::Config;
+use rye_core::installer::sync_tools;
+use rye_core::lock::{preview_lockfile_changes, LockChange, LockMode, LockOptions};
+use rye_core::pyproject::PyProject;
+use rye_core::sync::{remove_venv, sync, watch_and_sync, SyncMode, SyncOptions};
+use rye_core::utils::CommandOutput;
 
 /// Updates the virtualenv based on the pyproject.toml
 #[derive(Parser, Debug)]
@@ -11,15 +18,45 @@ pub struct Args {
     /// Force the environment to be re-created
     #[arg(short, long)]
     force: bool,
-    /// Do not include dev dependencies.
+    /// Do not include dev dependencies (removes them if installed).
     #[arg(long)]
     no_dev: bool,
-    /// Enables verbose diagnostics.
-    #[arg(short, long)]
-    verbose: bool,
-    /// Turns off all output.
-    #[arg(short, long, conflicts_with = "verbose")]
-    quiet: bool,
+    /// Only install the dev dependencies on top of the production ones
+    /// already in the virtualenv.
+    #[arg(long, conflicts_with = "no_dev")]
+    dev_only: bool,
+    /// Install from a pylock.toml (PEP 751) instead of the lockfiles.
+    ///
+    /// The lockfiles are left alone.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["dev_only", "dry_run"])]
+    from_pylock: Option<PathBuf>,
+    /// Install into a conda environment instead of the virtualenv.
+    ///
+    /// Without a name the active conda environment is used.
+    #[arg(long, value_name = "NAME", num_args = 0..=1, default_missing_value = "")]
+    conda_env: Option<String>,
+    /// Enables verbose diagnostics, or only for a phase (`resolver` or
+    /// `install`).
+    #[arg(
+        short,
+        long,
+        value_name = "PHASE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all"
+    )]
+    verbose: Vec<OutputPhase>,
+    /// Turns off all output, or only the output of a phase (`resolver` or
+    /// `install`).
+    #[arg(
+        short,
+        long,
+        value_name = "PHASE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all"
+    )]
+    quiet: Vec<OutputPhase>,
     /// Update a specific package.
     #[arg(long)]
     update: Vec<String>,
@@ -29,24 +66,222 @@ pub struct Args {
     /// Update to pre-release versions
     #[arg(long)]
     pre: bool,
+    /// Ignore cached responses of the package index.
+    #[arg(long)]
+    refresh: bool,
+    /// Resolve from scratch instead of keeping the versions of the lockfile.
+    #[arg(long)]
+    no_reuse: bool,
+    /// Only install this package from wheels (`:all:` for all packages).
+    #[arg(long, value_name = "PACKAGE")]
+    only_binary: Vec<String>,
+    /// Always build this package from source (`:all:` for all packages).
+    #[arg(long, value_name = "PACKAGE")]
+    no_binary: Vec<String>,
+    /// Only resolve again if the dependencies in pyproject.toml changed
+    /// since the lockfiles were written.
+    ///
+    /// Defaults to `behavior.relock-if-needed`.  `rye lock` always resolves.
+    #[arg(long)]
+    relock_if_needed: bool,
+    /// Always resolve again, even if the dependencies did not change.
+    #[arg(long, conflicts_with = "relock_if_needed")]
+    no_relock_if_needed: bool,
+    /// Remove the virtualenv instead of syncing it.
+    ///
+    /// Only virtualenvs managed by rye are removed unless `--force` is passed.
+    #[arg(long, conflicts_with_all = ["dry_run", "dev_only", "from_pylock", "conda_env"])]
+    clean: bool,
+    /// With --clean, remove the lockfiles as well.
+    #[arg(long, requires = "clean")]
+    lockfiles: bool,
+    /// Keep running and sync again whenever pyproject.toml or the lockfiles
+    /// change.
+    #[arg(long, conflicts_with_all = ["dry_run", "clean", "from_pylock"])]
+    watch: bool,
+    /// Only show how the lockfiles would change.
+    #[arg(long)]
+    dry_run: bool,
+    /// Resolve and download the packages into the cache without installing
+    /// them (eg: to warm the cache in CI).
+    #[arg(long, conflicts_with_all = ["dry_run", "clean", "watch", "dev_only"])]
+    download_only: bool,
+    /// With --dry-run, also show why changed packages are required.
+    #[arg(long, requires = "dry_run")]
+    explain: bool,
+}
+
+/// The parts of the output `--verbose` and `--quiet` can be limited to.
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum OutputPhase {
+    /// All of the output.
+    All,
+    /// The output of the resolver (pip-compile).
+    Resolver,
+    /// The output of installing the packages.
+    Install,
+}
+
+/// The output of a command and of its phases that differ from it.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct PhaseOutputs {
+    pub output: CommandOutput,
+    pub resolver: Option<CommandOutput>,
+    pub install: Option<CommandOutput>,
+}
+
+impl PhaseOutputs {
+    /// Sorts the phases given to `--quiet` and `--verbose` out.
+    pub fn from_quiet_and_verbose(
+        quiet: &[OutputPhase],
+        verbose: &[OutputPhase],
+    ) -> Result<PhaseOutputs, Error> {
+        let pick = |phase| match (quiet.contains(&phase), verbose.contains(&phase)) {
+            (true, true) => bail!("output cannot be both quiet and verbose"),
+            (true, false) => Ok(Some(CommandOutput::Quiet)),
+            (false, true) => Ok(Some(CommandOutput::Verbose)),
+            (false, false) => Ok(None),
+        };
+        Ok(PhaseOutputs {
+            output: pick(OutputPhase::All)?.unwrap_or_default(),
+            resolver: pick(OutputPhase::Resolver)?,
+            install: pick(OutputPhase::Install)?,
+        })
+    }
 }
 
 pub fn execute(cmd: Args) -> Result<(), Error> {
-    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
-    sync(SyncOptions {
+    let outputs = PhaseOutputs::from_quiet_and_verbose(&cmd.quiet, &cmd.verbose)?;
+    let output = outputs.output;
+    let lock_options = LockOptions {
+        update: cmd.update,
+        update_all: cmd.update_all,
+        pre: cmd.pre,
+        refresh: cmd.refresh,
+        no_reuse: cmd.no_reuse,
+        only_binary: cmd.only_binary,
+        no_binary: cmd.no_binary,
+        resolver_output: outputs.resolver,
+        ..LockOptions::default()
+    };
+    if cmd.clean {
+        return remove_venv(&PyProject::discover()?, cmd.force, cmd.lockfiles, output);
+    }
+    if cmd.dry_run {
+        return preview(output, !cmd.no_dev, cmd.explain, &lock_options);
+    }
+    let options = SyncOptions {
         output,
         dev: !cmd.no_dev,
-        mode: if cmd.force {
+        dev_only: cmd.dev_only,
+        mode: if cmd.download_only {
+            SyncMode::DownloadOnly
+        } else if cmd.force {
             SyncMode::Full
         } else {
             SyncMode::Regular
         },
         force: cmd.force,
-        lock_options: LockOptions {
-            update: cmd.update,
-            update_all: cmd.update_all,
-            pre: cmd.pre,
+        lock_options,
+        from_pylock: cmd.from_pylock,
+        conda_env: cmd.conda_env.map(|name| match name.as_str() {
+            "" => CondaEnvRequest::Active,
+            _ => CondaEnvRequest::Named(name),
+        }),
+        relock_if_needed: match (cmd.relock_if_needed, cmd.no_relock_if_needed) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
         },
-    })?;
+        install_output: outputs.install,
+        ..SyncOptions::default()
+    };
+    if cmd.watch {
+        return watch_and_sync(options);
+    }
+    sync(options)?;
+    if cmd.download_only {
+        return Ok(());
+    }
+
+    // the global tools the project asks for in tool.rye.tools.  A freshly
+    // cloned repository should not install tools unasked.
+    let tools = PyProject::discover()?.tool_requirements()?;
+    if !tools.is_empty() {
+        if Config::current()?.sync_tools() {
+            sync_tools(&tools, output)?;
+        } else if output != CommandOutput::Quiet {
+            eprintln!(
+                "{} the project asks for global tools, run `rye tools sync` to install them \
+                 (or turn on behavior.sync-tools)",
+                style("note:").cyan()
+            );
+        }
+    }
     Ok(())
 }
+
+/// Prints the changes a relock would make to the lockfiles.
+fn preview(
+    output: CommandOutput,
+    dev: bool,
+    explain: bool,
+    lock_options: &LockOptions,
+) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    let mut modes = vec![LockMode::Production];
+    if dev {
+        modes.push(LockMode::Dev);
+    }
+    for lock_mode in modes {
+        let changes = preview_lockfile_changes(&pyproject, lock_mode, output, lock_options)?;
+        if changes.is_empty() {
+            println!("{} lockfile: no changes", lock_mode);
+            continue;
+        }
+        println!("{} lockfile:", lock_mode);
+        for change in changes {
+            println!("  {}", change.styled());
+            if explain {
+                if let LockChange::Added(ref pkg) | LockChange::Changed { new: ref pkg, .. } =
+                    change
+                {
+                    if !pkg.via.is_empty() {
+                        println!(
+                            "      {}",
+                            style(format!("via {}", pkg.via.join(", "))).dim()
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_phase_outputs() {
+    use OutputPhase::*;
+
+    assert_eq!(
+        PhaseOutputs::from_quiet_and_verbose(&[], &[]).unwrap(),
+        PhaseOutputs::default()
+    );
+    assert_eq!(
+        PhaseOutputs::from_quiet_and_verbose(&[Install], &[Resolver]).unwrap(),
+        PhaseOutputs {
+            output: CommandOutput::Normal,
+            resolver: Some(CommandOutput::Verbose),
+            install: Some(CommandOutput::Quiet),
+        }
+    );
+    assert_eq!(
+        PhaseOutputs::from_quiet_and_verbose(&[All], &[Resolver]).unwrap(),
+        PhaseOutputs {
+            output: CommandOutput::Quiet,
+            resolver: Some(CommandOutput::Verbose),
+            install: None,
+        }
+    );
+    assert!(PhaseOutputs::from_quiet_and_verbose(&[All], &[All]).is_err());
+}
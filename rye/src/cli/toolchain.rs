@@ -1,18 +1,28 @@
 use std::cmp::Reverse;
 use std::collections::HashMap;
-use std::env::consts::{ARCH, OS};
+use std::env::consts::ARCH;
 use std::fs;
+use std::io::Cursor;
 use std::os::unix::fs::symlink;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use anyhow::{bail, Context, Error};
+use anyhow::{anyhow, bail, Context, Error};
 use clap::Parser;
 use console::style;
 use serde::Deserialize;
 
-use crate::config::{get_canonical_py_path, list_known_toolchains};
-use crate::sources::{iter_downloadable, PythonVersion};
+use rye_core::config::{
+    get_canonical_py_path, get_default_toolchain_request, list_known_toolchains,
+    load_python_version_requests, resolve_newest_python_version, resolve_python_version, Config,
+};
+use rye_core::error::{ErrorKind, Failure};
+use rye_core::pyproject::PyProject;
+use rye_core::sources::{
+    current_platform, iter_downloadable, matches_version, PythonVersion, PythonVersionRequest,
+};
+use rye_core::sync::{get_venv_python_version, sync, SyncMode, SyncOptions};
+use rye_core::utils::{unpack_tarball, CommandOutput};
 
 const INSPECT_SCRIPT: &str = r#"
 import json
@@ -57,6 +67,65 @@ pub struct RemoveCommand {
     version: String,
 }
 
+/// Shows or sets the default toolchain.
+///
+/// The default toolchain is used by projects which do not request a version,
+/// for new projects and by the python shims outside of projects.  Without a
+/// default the latest cpython release is used.
+#[derive(Parser, Debug)]
+pub struct DefaultCommand {
+    /// The toolchain to use by default (eg: `cpython@3.12`).
+    version: Option<String>,
+    /// Go back to using the latest cpython release.
+    #[arg(long, conflicts_with = "version")]
+    unset: bool,
+}
+
+/// Resolves the toolchain pin of the project to an exact release.
+///
+/// The virtualenv is created with the resolved release if it does not exist
+/// yet.  It keeps using that release, even when newer ones matching the
+/// pin come along, until it is moved with `rye toolchain upgrade`.
+#[derive(Parser, Debug)]
+pub struct ResolveCommand {}
+
+/// Moves a floating toolchain pin (eg: `3.12`) to the newest matching release.
+///
+/// The virtualenv of the project is re-created with the new release and
+/// its dependencies are installed again.
+#[derive(Parser, Debug)]
+pub struct UpgradeCommand {
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+/// Exports an installed toolchain as a tarball.
+///
+/// The tarball can be moved to a machine without internet access and
+/// installed there with `rye toolchain import`.
+#[derive(Parser, Debug)]
+pub struct ExportCommand {
+    /// The toolchain to export (eg: `cpython@3.11`).
+    version: String,
+    /// Where to write the tarball (defaults to `<version>.tar.zst`).
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Imports a toolchain from a tarball created by `rye toolchain export`.
+#[derive(Parser, Debug)]
+pub struct ImportCommand {
+    /// Path to the tarball.
+    path: PathBuf,
+    /// Replace the toolchain if it is already installed.
+    #[arg(short, long)]
+    force: bool,
+}
+
 /// List all registered toolchains
 #[derive(Parser, Debug)]
 pub struct ListCommand {
@@ -67,23 +136,40 @@ pub struct ListCommand {
 
 #[derive(Parser, Debug)]
 enum SubCommand {
+    Default(DefaultCommand),
+    Export(ExportCommand),
     Fetch(crate::cli::fetch::Args),
+    Import(ImportCommand),
     List(ListCommand),
     Register(RegisterCommand),
     Remove(RemoveCommand),
+    Resolve(ResolveCommand),
+    Upgrade(UpgradeCommand),
 }
 
 pub fn execute(cmd: Args) -> Result<(), Error> {
     match cmd.command {
         SubCommand::Register(args) => register(args),
+        SubCommand::Default(args) => default(args),
+        SubCommand::Export(args) => export(args),
         SubCommand::Fetch(args) => crate::cli::fetch::execute(args),
+        SubCommand::Import(args) => import(args),
         SubCommand::List(args) => list(args),
         SubCommand::Remove(args) => remove(args),
+        SubCommand::Resolve(args) => resolve(args),
+        SubCommand::Upgrade(args) => upgrade(args),
     }
 }
 
 fn register(cmd: RegisterCommand) -> Result<(), Error> {
-    let output = Command::new(&cmd.path)
+    let target_version = register_toolchain(&cmd.path, cmd.name.as_deref())?;
+    println!("Registered {} as {}", cmd.path.display(), target_version);
+    Ok(())
+}
+
+/// Registers an existing interpreter as toolchain and returns its version.
+pub fn register_toolchain(path: &Path, name: Option<&str>) -> Result<PythonVersion, Error> {
+    let output = Command::new(path)
         .arg("-c")
         .arg(INSPECT_SCRIPT)
         .output()
@@ -94,8 +180,8 @@ fn register(cmd: RegisterCommand) -> Result<(), Error> {
 
     let info: InspectInfo = serde_json::from_slice(&output.stdout)
         .context("could not parse interpreter output as json")?;
-    let target_version = match cmd.name {
-        Some(ref name) => format!("{}@{}", name, info.python_version),
+    let target_version = match name {
+        Some(name) => format!("{}@{}", name, info.python_version),
         None => {
             let name = if info.python_implementation.eq_ignore_ascii_case("cpython") {
                 "custom-cpython"
@@ -117,10 +203,8 @@ fn register(cmd: RegisterCommand) -> Result<(), Error> {
         fs::create_dir_all(parent).ok();
     }
 
-    symlink(&cmd.path, target).context("could not symlink interpreter")?;
-    println!("Registered {} as {}", cmd.path.display(), target_version);
-
-    Ok(())
+    symlink(path, target).context("could not symlink interpreter")?;
+    Ok(target_version)
 }
 
 pub fn remove(cmd: RemoveCommand) -> Result<(), Error> {
@@ -138,6 +222,184 @@ pub fn remove(cmd: RemoveCommand) -> Result<(), Error> {
     Ok(())
 }
 
+fn default(cmd: DefaultCommand) -> Result<(), Error> {
+    let mut config = Config::from_path(Config::current()?.path())?;
+    if cmd.unset {
+        config.set_default_toolchain(None);
+        config.save()?;
+        eprintln!("Default toolchain unset, using the latest cpython release");
+        return Ok(());
+    }
+
+    let req: PythonVersionRequest = match cmd.version {
+        Some(ref version) => version.parse()?,
+        None => {
+            match config.default_toolchain() {
+                Some(req) => println!("{}", req),
+                None => println!("latest cpython ({})", PythonVersion::latest_cpython()?),
+            }
+            return Ok(());
+        }
+    };
+    if resolve_python_version(&req).is_none() {
+        bail!(Failure::new(
+            ErrorKind::ToolchainMissing,
+            format!("no toolchain satisfies {}", req)
+        ));
+    }
+    config.set_default_toolchain(Some(&req.to_string()));
+    config.save()?;
+    eprintln!(
+        "{} Default toolchain set to {}",
+        style("success:").green(),
+        style(&req).cyan()
+    );
+    Ok(())
+}
+
+fn resolve(_cmd: ResolveCommand) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    sync(SyncOptions::python_only()).context("failed to create virtualenv")?;
+    let version = get_venv_python_version(&pyproject)
+        .ok_or_else(|| anyhow!("virtualenv has no recorded toolchain"))?;
+    println!("{}", version);
+    Ok(())
+}
+
+fn upgrade(cmd: UpgradeCommand) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let pyproject = PyProject::discover()?;
+    let requests = match load_python_version_requests() {
+        Some(requests) => requests,
+        None => get_default_toolchain_request()?.into_iter().collect(),
+    };
+    // without any pin the latest release is used
+    let newest = if requests.is_empty() {
+        PythonVersion::latest_cpython()?
+    } else if requests.iter().all(|x| x.patch.is_some()) {
+        if output != CommandOutput::Quiet {
+            eprintln!("Toolchain pin is an exact version, nothing to upgrade");
+        }
+        return Ok(());
+    } else {
+        requests
+            .iter()
+            .find_map(resolve_newest_python_version)
+            .ok_or_else(|| {
+                Failure::new(
+                    ErrorKind::ToolchainMissing,
+                    "no toolchain satisfies the pin",
+                )
+            })?
+    };
+    let current = get_venv_python_version(&pyproject);
+    if current.as_ref() == Some(&newest) {
+        if output != CommandOutput::Quiet {
+            eprintln!("Already using the newest matching toolchain {}", newest);
+        }
+        return Ok(());
+    }
+
+    if output != CommandOutput::Quiet {
+        match current {
+            Some(current) => eprintln!(
+                "Upgrading toolchain from {} to {}",
+                current,
+                style(&newest).cyan()
+            ),
+            None => eprintln!("Using toolchain {}", style(&newest).cyan()),
+        }
+    }
+    sync(SyncOptions {
+        output,
+        dev: true,
+        mode: SyncMode::Regular,
+        upgrade_toolchain: true,
+        ..SyncOptions::default()
+    })
+    .context("failed to sync with upgraded toolchain")?;
+    Ok(())
+}
+
+fn export(cmd: ExportCommand) -> Result<(), Error> {
+    let req: PythonVersionRequest = cmd.version.parse()?;
+    let version = list_known_toolchains()?
+        .into_iter()
+        .filter(|x| matches_version(&req, x))
+        .max()
+        .ok_or_else(|| anyhow!("toolchain {} is not installed", req))?;
+    let path = get_canonical_py_path(&version)?;
+    if path.is_file() {
+        bail!(
+            "toolchain {} is a registered interpreter and cannot be exported",
+            version
+        );
+    }
+
+    let out = cmd
+        .output
+        .unwrap_or_else(|| PathBuf::from(format!("{}.tar.zst", version)));
+    let file =
+        fs::File::create(&out).with_context(|| format!("unable to create {}", out.display()))?;
+    let mut builder = tar::Builder::new(zstd::Encoder::new(file, 0)?);
+    builder.follow_symlinks(false);
+    builder
+        .append_dir_all(version.to_string(), &path)
+        .context("failed to write toolchain into tarball")?;
+    builder.into_inner()?.finish()?;
+
+    eprintln!("Exported {} to {}", version, out.display());
+    Ok(())
+}
+
+fn import(cmd: ImportCommand) -> Result<(), Error> {
+    let contents =
+        fs::read(&cmd.path).with_context(|| format!("unable to read {}", cmd.path.display()))?;
+    let version = get_exported_version(&contents)
+        .with_context(|| format!("{} is not a toolchain exported by rye", cmd.path.display()))?;
+
+    let target = get_canonical_py_path(&version)?;
+    if target.is_file() || target.is_dir() {
+        if !cmd.force {
+            bail!(
+                "toolchain {} is already installed. Use --force to replace it.",
+                version
+            );
+        }
+        if target.is_file() {
+            fs::remove_file(&target)?;
+        } else {
+            fs::remove_dir_all(&target)?;
+        }
+    }
+
+    fs::create_dir_all(&target)
+        .with_context(|| format!("failed to create target folder {}", target.display()))?;
+    if let Err(err) = unpack_tarball(&contents, &target, 1) {
+        fs::remove_dir_all(&target).ok();
+        return Err(err.context("failed to unpack toolchain"));
+    }
+
+    eprintln!("Imported {}", version);
+    Ok(())
+}
+
+/// Exported toolchains keep their files in a folder named after the version.
+fn get_exported_version(contents: &[u8]) -> Result<PythonVersion, Error> {
+    let decoder = zstd::stream::read::Decoder::new(Cursor::new(contents))?;
+    let mut archive = tar::Archive::new(decoder);
+    let entry = archive
+        .entries()?
+        .next()
+        .ok_or_else(|| anyhow!("tarball is empty"))??;
+    let path = entry.path()?;
+    let name = path
+        .components()
+        .next()
+        .ok_or_else(|| anyhow!("tarball is empty"))?;
+    name.as_os_str().to_string_lossy().parse()
+}
+
 fn list(cmd: ListCommand) -> Result<(), Error> {
     let mut toolchains = list_known_toolchains()?
         .into_iter()
@@ -145,7 +407,7 @@ fn list(cmd: ListCommand) -> Result<(), Error> {
         .collect::<HashMap<_, _>>();
 
     if cmd.include_downloadable {
-        for version in iter_downloadable(OS, ARCH) {
+        for version in iter_downloadable(current_platform(), ARCH) {
             toolchains.entry(version).or_insert(false);
         }
     }
@@ -0,0 +1,163 @@
+use std::env;
+use std::ffi::{CString, OsString};
+use std::os::unix::prelude::OsStrExt;
+
+use anyhow::{bail, Error};
+use clap::Parser;
+use console::style;
+use pep508_rs::Requirement;
+
+use rye_core::installer::{ensure_tool_env, inject, sync_tools};
+use rye_core::pyproject::PyProject;
+use rye_core::sources::PythonVersionRequest;
+use rye_core::utils::CommandOutput;
+
+/// Runs tools without installing them and manages installed tools.
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    command: SubCommand,
+}
+
+#[derive(Parser, Debug)]
+enum SubCommand {
+    Run(RunCommand),
+    Inject(InjectCommand),
+    Sync(SyncCommand),
+}
+
+/// Runs a tool from a cached environment.
+///
+/// The tool is installed into an environment of its own the first time and
+/// neither the tools installed with `rye install` nor the virtualenv of the
+/// project are touched.
+#[derive(Parser, Debug)]
+pub struct RunCommand {
+    /// The package providing the command (defaults to the command).
+    #[arg(long, value_name = "REQUIREMENT")]
+    from: Option<String>,
+    /// Optionally the Python version to use.
+    #[arg(short, long)]
+    python: Option<String>,
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// The command (or requirement, eg: `ruff==0.1.5`) and its arguments.
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<OsString>,
+}
+
+/// Installs packages into the virtualenv of a tool installed with `rye install`.
+///
+/// This is for plugins, for instance `rye tools inject mkdocs mkdocs-material`.
+#[derive(Parser, Debug)]
+pub struct InjectCommand {
+    /// The installed tool.
+    tool: String,
+    /// The packages to install.
+    #[arg(required = true)]
+    requirements: Vec<String>,
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+/// Installs the global tools the project declares in `tool.rye.tools`.
+///
+/// Tools that are installed in a version not matching the declared one are
+/// reinstalled.  `rye sync` does this as well.
+#[derive(Parser, Debug)]
+pub struct SyncCommand {
+    /// Enables verbose diagnostics.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Turns off all output.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    match cmd.command {
+        SubCommand::Run(cmd) => run(cmd),
+        SubCommand::Inject(cmd) => {
+            let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+            let requirements = cmd
+                .requirements
+                .iter()
+                .map(|x| x.parse())
+                .collect::<Result<Vec<Requirement>, _>>()?;
+            inject(&cmd.tool, &requirements, output)
+        }
+        SubCommand::Sync(cmd) => {
+            let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+            let tools = PyProject::discover()?.tool_requirements()?;
+            if tools.is_empty() {
+                if output != CommandOutput::Quiet {
+                    eprintln!("No tools declared in tool.rye.tools");
+                }
+                return Ok(());
+            }
+            sync_tools(&tools, output)?;
+            if output != CommandOutput::Quiet {
+                eprintln!("{} Tools are up to date", style("success:").green());
+            }
+            Ok(())
+        }
+    }
+}
+
+fn run(cmd: RunCommand) -> Result<(), Error> {
+    let output = CommandOutput::from_quiet_and_verbose(cmd.quiet, cmd.verbose);
+    let py_ver: PythonVersionRequest = match cmd.python {
+        Some(ref py) => py.parse()?,
+        None => PythonVersionRequest {
+            kind: None,
+            major: 3,
+            minor: None,
+            patch: None,
+            suffix: None,
+        },
+    };
+    let mut args = cmd.args;
+    let (requirement, command): (Requirement, String) = match cmd.from {
+        Some(ref from) => (from.parse()?, args[0].to_string_lossy().into_owned()),
+        None => {
+            let requirement: Requirement = args[0].to_string_lossy().parse()?;
+            let command = requirement.name.clone();
+            (requirement, command)
+        }
+    };
+
+    let tool_env = ensure_tool_env(&requirement, &py_ver, output)?;
+    let bin = tool_env.join("bin");
+    let script = bin.join(&command);
+    if !script.is_file() {
+        bail!(
+            "{} does not provide a command named {}",
+            requirement,
+            command
+        );
+    }
+    args[0] = script.into_os_string();
+
+    let mut path = bin.into_os_string();
+    if let Some(existing) = env::var_os("PATH") {
+        path.push(":");
+        path.push(existing);
+    }
+    env::set_var("PATH", path);
+    env::remove_var("PYTHONHOME");
+
+    let args = args
+        .iter()
+        .filter_map(|x| CString::new(x.as_bytes()).ok())
+        .collect::<Vec<_>>();
+    let Err(err) = nix::unistd::execv(&args[0], &args);
+    Err(err.into())
+}
@@ -1,8 +1,8 @@
 use anyhow::Error;
 use clap::Parser;
 
-use crate::installer::uninstall;
-use crate::utils::CommandOutput;
+use rye_core::installer::uninstall;
+use rye_core::utils::CommandOutput;
 
 /// Uninstalls a global tool.
 #[derive(Parser, Debug)]
@@ -0,0 +1,104 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use anyhow::{bail, Context, Error};
+use clap::Parser;
+use console::style;
+
+use rye_core::lock::{parse_lockfile, LockedPackage};
+use rye_core::pyproject::{normalize_package_name, PyProject};
+
+/// Explains why a package is part of the lockfile.
+///
+/// This prints every chain of requirements from a project in the workspace
+/// down to the package, together with the notes from
+/// `tool.rye.dependency-notes`.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The package to explain.
+    package: String,
+    /// Look at the dev lockfile instead.
+    #[arg(long)]
+    dev: bool,
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let pyproject = PyProject::discover()?;
+    let lockfile = pyproject.lockfile_dir().join(if cmd.dev {
+        "requirements-dev.lock"
+    } else {
+        "requirements.lock"
+    });
+    let contents = fs::read_to_string(&lockfile).with_context(|| {
+        format!(
+            "unable to read lockfile {}. Run `rye lock` first.",
+            lockfile.display()
+        )
+    })?;
+
+    let packages = parse_lockfile(&contents);
+    let by_name: HashMap<&str, &LockedPackage> =
+        packages.iter().map(|x| (x.name.as_str(), x)).collect();
+    let name = normalize_package_name(&cmd.package);
+    let package = match by_name.get(name.as_str()) {
+        Some(package) => package,
+        None => bail!("{} is not in {}", cmd.package, lockfile.display()),
+    };
+
+    let notes = pyproject.workspace_dependency_notes()?;
+    print_package(package, &notes, 0);
+    print_via(
+        package,
+        &by_name,
+        &notes,
+        1,
+        &mut vec![package.name.as_str()],
+    );
+    Ok(())
+}
+
+fn print_package(package: &LockedPackage, notes: &BTreeMap<String, String>, depth: usize) {
+    match package.version {
+        Some(ref version) => println!(
+            "{}{}=={}",
+            "  ".repeat(depth),
+            style(&package.name).cyan(),
+            version
+        ),
+        None => println!("{}{}", "  ".repeat(depth), style(&package.name).cyan()),
+    }
+    if let Some(note) = notes.get(&package.name) {
+        println!(
+            "{}{}",
+            "  ".repeat(depth + 1),
+            style(format!("note: {}", note)).dim()
+        );
+    }
+}
+
+fn print_via<'a>(
+    package: &'a LockedPackage,
+    by_name: &HashMap<&str, &'a LockedPackage>,
+    notes: &BTreeMap<String, String>,
+    depth: usize,
+    stack: &mut Vec<&'a str>,
+) {
+    for via in &package.via {
+        match by_name.get(via.as_str()) {
+            // cycles can happen (eg: packages depending on their own extras)
+            Some(parent) if stack.contains(&parent.name.as_str()) => {}
+            Some(parent) => {
+                print_package(parent, notes, depth);
+                stack.push(&parent.name);
+                print_via(parent, by_name, notes, depth + 1, stack);
+                stack.pop();
+            }
+            None => println!(
+                "{}{} {}",
+                "  ".repeat(depth),
+                style(via).yellow(),
+                style("(project)").dim()
+            ),
+        }
+    }
+}
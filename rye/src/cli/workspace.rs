@@ -0,0 +1,454 @@
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Read};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::{env, thread};
+
+use anyhow::{anyhow, bail, Context, Error};
+use clap::{Parser, ValueEnum};
+use console::style;
+use serde::Serialize;
+
+use rye_core::config::{apply_env_config, EnvTarget};
+use rye_core::pyproject::{PyProject, Script};
+use rye_core::sync::{sync, SyncOptions};
+use rye_core::workspace::{Member, WorkspaceGraph};
+
+use super::run::call_script_args;
+
+/// Helper commands for working with workspaces.
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    command: SubCommand,
+}
+
+/// Lists the workspace members changed since a git ref.
+///
+/// Members that depend on a changed member are included as well, so the
+/// output is the set of members that need to be rebuilt or retested.
+#[derive(Parser, Debug)]
+pub struct ChangedCommand {
+    /// The git ref to compare against.
+    #[arg(long)]
+    since: String,
+    /// Do not include members depending on changed members.
+    #[arg(long)]
+    no_dependents: bool,
+    /// Print member paths instead of names.
+    #[arg(long)]
+    paths: bool,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug)]
+#[value(rename_all = "snake_case")]
+pub enum GraphFormat {
+    Text,
+    Dot,
+    Json,
+}
+
+/// Prints the dependency graph between workspace members.
+#[derive(Parser, Debug)]
+pub struct GraphCommand {
+    /// The output format.
+    #[arg(long, default_value = "text")]
+    format: GraphFormat,
+}
+
+#[derive(Serialize, Debug)]
+struct JsonMember<'a> {
+    name: &'a str,
+    path: String,
+    dependencies: Vec<&'a str>,
+}
+
+/// Runs a script in every workspace member in dependency order.
+///
+/// Members that do not define the script are skipped.  Once a script failed
+/// no further members are started.
+#[derive(Parser, Debug)]
+pub struct RunCommand {
+    /// The name of the script to run.
+    script: String,
+    /// Run members that do not depend on each other in parallel.
+    #[arg(long)]
+    parallel: bool,
+    /// Run at most this many members at once (implies --parallel).
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+    /// Do not load the env files configured for the script.
+    #[arg(long)]
+    no_env_file: bool,
+    /// Extra arguments passed to each script.
+    #[arg(last = true)]
+    args: Vec<OsString>,
+}
+
+#[derive(Parser, Debug)]
+enum SubCommand {
+    Changed(ChangedCommand),
+    Graph(GraphCommand),
+    Run(RunCommand),
+}
+
+pub fn execute(cmd: Args) -> Result<(), Error> {
+    let project = PyProject::discover()?;
+    let workspace = project
+        .workspace()
+        .ok_or_else(|| anyhow!("project is not part of a workspace"))?;
+    let graph = WorkspaceGraph::load(workspace)?;
+
+    match cmd.command {
+        SubCommand::Changed(args) => changed(&graph, &project.workspace_path(), args),
+        SubCommand::Graph(args) => print_graph(&graph, &project.workspace_path(), args),
+        SubCommand::Run(args) => run(&graph, &project, args),
+    }
+}
+
+fn relative_path(member: &Member, root: &Path) -> String {
+    let root_path = member.project.root_path();
+    Path::new(".")
+        .join(root_path.strip_prefix(root).unwrap_or(&root_path))
+        .display()
+        .to_string()
+}
+
+fn changed(graph: &WorkspaceGraph, root: &Path, cmd: ChangedCommand) -> Result<(), Error> {
+    let mut changed = graph.changed_since(&cmd.since)?;
+    if !cmd.no_dependents {
+        changed = graph.with_dependents(&changed);
+    }
+    for idx in changed {
+        let member = &graph.members()[idx];
+        if cmd.paths {
+            println!("{}", relative_path(member, root));
+        } else {
+            println!("{}", member.name);
+        }
+    }
+    Ok(())
+}
+
+fn print_graph(graph: &WorkspaceGraph, root: &Path, cmd: GraphCommand) -> Result<(), Error> {
+    let members = graph.members();
+    match cmd.format {
+        GraphFormat::Text => {
+            for idx in 0..members.len() {
+                print_tree(members, idx, 0, &mut BTreeSet::new());
+            }
+        }
+        GraphFormat::Dot => {
+            println!("digraph workspace {{");
+            for member in members {
+                println!("    {:?};", member.name);
+                for &dep in &member.deps {
+                    println!("    {:?} -> {:?};", member.name, members[dep].name);
+                }
+            }
+            println!("}}");
+        }
+        GraphFormat::Json => {
+            let rv: Vec<_> = members
+                .iter()
+                .map(|member| JsonMember {
+                    name: &member.name,
+                    path: relative_path(member, root),
+                    dependencies: member
+                        .deps
+                        .iter()
+                        .map(|&dep| members[dep].name.as_str())
+                        .collect(),
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&rv)?);
+        }
+    }
+    Ok(())
+}
+
+fn print_tree(members: &[Member], idx: usize, depth: usize, seen: &mut BTreeSet<usize>) {
+    let member = &members[idx];
+    if depth == 0 {
+        println!("{}", style(&member.name).cyan());
+    } else if seen.contains(&idx) {
+        println!(
+            "{}{} {}",
+            "  ".repeat(depth),
+            member.name,
+            style("(cycle)").red()
+        );
+        return;
+    } else {
+        println!("{}{}", "  ".repeat(depth), member.name);
+    }
+    seen.insert(idx);
+    for &dep in &member.deps {
+        print_tree(members, dep, depth + 1, seen);
+    }
+    seen.remove(&idx);
+}
+
+fn run(graph: &WorkspaceGraph, project: &PyProject, cmd: RunCommand) -> Result<(), Error> {
+    // make sure we have the minimal virtualenv.
+    sync(SyncOptions::python_only()).context("failed to sync ahead of run")?;
+
+    let members = graph.members();
+    let width = members.iter().map(|x| x.name.len()).max().unwrap_or(0);
+    let order = graph.topological_levels()?.concat();
+    let commands = members
+        .iter()
+        .map(|member| make_script_command(project, member, &cmd))
+        .collect::<Result<Vec<_>, _>>()?;
+    if commands.iter().all(|x| x.is_none()) {
+        bail!(
+            "no workspace member defines a script named '{}'",
+            cmd.script
+        );
+    }
+
+    let jobs = match (cmd.jobs, cmd.parallel) {
+        (Some(jobs), _) => jobs.get(),
+        (None, true) => usize::MAX,
+        (None, false) => 1,
+    };
+    let deps = members
+        .iter()
+        .map(|x| x.deps.iter().copied().collect())
+        .collect::<Vec<_>>();
+    let failed = run_jobs(&deps, &order, jobs, commands, |idx, command| {
+        run_prefixed(&members[idx].name, width, command)
+    })?;
+    if failed > 0 {
+        bail!("script '{}' failed in {} member(s)", cmd.script, failed);
+    }
+
+    Ok(())
+}
+
+/// The outcome of a job of [`run_jobs`], which hands back a sender for
+/// starting further jobs.
+struct JobResult(usize, Result<bool, Error>, mpsc::Sender<JobResult>);
+
+/// Runs a job per member with at most `jobs` of them at once.
+///
+/// A member is started as soon as all members it depends on succeeded,
+/// members without a job count as succeeded right away.  Members become
+/// ready in the given `order`.  After the first failure no further members
+/// are started, but the running ones are waited for.  Returns the number of
+/// failed jobs.
+fn run_jobs<T, F>(
+    deps: &[Vec<usize>],
+    order: &[usize],
+    jobs: usize,
+    mut pending: Vec<Option<T>>,
+    run: F,
+) -> Result<usize, Error>
+where
+    T: Send,
+    F: Fn(usize, T) -> Result<bool, Error> + Sync,
+{
+    let mut done = vec![false; deps.len()];
+    let mut started = vec![false; deps.len()];
+    let mut running = 0;
+    let mut failed = 0;
+    let mut error = None;
+    let run = &run;
+
+    thread::scope(|scope| {
+        // while waiting only the job threads hold senders, so that jobs
+        // that panic end the wait.  Every result hands a sender back.
+        let (tx, rx) = mpsc::channel();
+        let mut tx = Some(tx);
+        loop {
+            // members without a job can complete others, so keep going
+            // until nothing changes any more
+            let mut progress = true;
+            while progress && failed == 0 && error.is_none() {
+                progress = false;
+                for &idx in order {
+                    if started[idx] || !deps[idx].iter().all(|&dep| done[dep]) {
+                        continue;
+                    }
+                    match pending[idx].take() {
+                        None => {
+                            started[idx] = true;
+                            done[idx] = true;
+                            progress = true;
+                        }
+                        Some(job) if running < jobs => {
+                            started[idx] = true;
+                            running += 1;
+                            let tx = tx.clone().expect("a result handed back a sender");
+                            scope.spawn(move || {
+                                let rv = run(idx, job);
+                                tx.send(JobResult(idx, rv, tx.clone())).ok()
+                            });
+                        }
+                        Some(job) => pending[idx] = Some(job),
+                    }
+                }
+            }
+            if running == 0 {
+                break;
+            }
+            drop(tx.take());
+            let JobResult(idx, rv, sender) = match rx.recv() {
+                Ok(msg) => msg,
+                // the scope passes on the panic of the jobs
+                Err(_) => break,
+            };
+            tx = Some(sender);
+            running -= 1;
+            match rv {
+                Ok(true) => done[idx] = true,
+                Ok(false) => failed += 1,
+                Err(err) => {
+                    if error.is_none() {
+                        error = Some(err);
+                    }
+                }
+            }
+        }
+    });
+
+    match error {
+        Some(err) => Err(err),
+        None => Ok(failed),
+    }
+}
+
+/// Creates the command that invokes the script in the given member.
+fn make_script_command(
+    project: &PyProject,
+    member: &Member,
+    cmd: &RunCommand,
+) -> Result<Option<Command>, Error> {
+    let venv_bin = project.venv_bin_path();
+    let program: Vec<OsString> = match member.project.get_script_cmd(&cmd.script) {
+        Some(Script::Cmd(script_args)) if !script_args.is_empty() => {
+            let script_target = venv_bin.join(&script_args[0]);
+            let mut rv: Vec<OsString> = script_args.into_iter().map(OsString::from).collect();
+            if script_target.is_file() {
+                rv[0] = script_target.into();
+            }
+            rv
+        }
+        Some(Script::External(path)) => vec![path.into()],
+        Some(Script::Call(target)) => call_script_args(&venv_bin, &target, (&cmd.script).into()),
+        _ => return Ok(None),
+    };
+
+    let mut command = Command::new(&program[0]);
+    command
+        .args(&program[1..])
+        .args(&cmd.args)
+        .current_dir(member.project.root_path())
+        .env("VIRTUAL_ENV", &*project.venv_path())
+        .env_remove("PYTHONHOME");
+    let mut new_path = venv_bin.as_os_str().to_owned();
+    if let Some(path) = env::var_os("PATH") {
+        new_path.push(":");
+        new_path.push(path);
+    }
+    command.env("PATH", new_path);
+    command.envs(
+        member
+            .project
+            .get_script_env(&cmd.script, !cmd.no_env_file)?,
+    );
+    apply_env_config(&mut command, EnvTarget::Scripts)?;
+    Ok(Some(command))
+}
+
+/// Runs a command with its output prefixed by the member name.
+///
+/// Returns `false` if the command did not exit successfully.
+fn run_prefixed(name: &str, width: usize, mut command: Command) -> Result<bool, Error> {
+    let prefix = format!("{:width$} |", name, width = width);
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("unable to spawn script in {}", name))?;
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+
+    thread::scope(|scope| {
+        scope.spawn(|| forward_lines(&prefix, stdout, false));
+        scope.spawn(|| forward_lines(&prefix, stderr, true));
+    });
+
+    let status = child.wait()?;
+    if !status.success() {
+        eprintln!(
+            "{} {}",
+            style(&prefix).red(),
+            style(format!("failed with {}", status)).red()
+        );
+    }
+    Ok(status.success())
+}
+
+fn forward_lines(prefix: &str, stream: impl Read, to_stderr: bool) {
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        if to_stderr {
+            eprintln!("{} {}", style(prefix).dim(), line);
+        } else {
+            println!("{} {}", style(prefix).dim(), line);
+        }
+    }
+}
+
+#[test]
+fn test_run_jobs() {
+    use std::sync::Mutex;
+
+    // 0 and 1 are independent, 2 depends on both, 3 has no job and depends
+    // on 2, 4 depends on 3
+    let deps = vec![vec![], vec![], vec![0, 1], vec![2], vec![3]];
+    let order = [0, 1, 2, 3, 4];
+    let jobs = || vec![Some(()), Some(()), Some(()), None, Some(())];
+
+    let log = Mutex::new(Vec::new());
+    let failed = run_jobs(&deps, &order, 1, jobs(), |idx, ()| {
+        log.lock().unwrap().push(idx);
+        Ok(true)
+    })
+    .unwrap();
+    assert_eq!(failed, 0);
+    assert_eq!(*log.lock().unwrap(), vec![0, 1, 2, 4]);
+
+    // independent members run at the same time, they wait for each other
+    let barrier = std::sync::Barrier::new(2);
+    let failed = run_jobs(&deps, &order, 2, jobs(), |idx, ()| {
+        if idx < 2 {
+            barrier.wait();
+        }
+        Ok(true)
+    })
+    .unwrap();
+    assert_eq!(failed, 0);
+
+    // nothing depending on a failed member runs
+    let log = Mutex::new(Vec::new());
+    let failed = run_jobs(&deps, &order, 2, jobs(), |idx, ()| {
+        log.lock().unwrap().push(idx);
+        Ok(idx != 1)
+    })
+    .unwrap();
+    assert_eq!(failed, 1);
+    assert!(!log.lock().unwrap().contains(&2));
+
+    // a panicking job does not leave the others waiting forever
+    let rv = std::panic::catch_unwind(|| {
+        run_jobs(&deps, &order, 1, jobs(), |idx, ()| {
+            assert_ne!(idx, 0, "job failed");
+            Ok(true)
+        })
+    });
+    assert!(rv.is_err());
+}
@@ -1,13 +1,13 @@
-mod bootstrap;
+use std::process;
+
 mod cli;
-mod config;
-mod installer;
-mod lock;
-mod pyproject;
-mod sources;
-mod sync;
-mod utils;
 
-pub fn main() -> Result<(), anyhow::Error> {
-    cli::execute()
+pub fn main() {
+    let rv = cli::execute();
+    // an interrupted command has cleaned up after itself by now
+    rye_core::signals::exit_if_interrupted();
+    if let Err(err) = rv {
+        cli::report_error(&err);
+        process::exit(rye_core::error::exit_code(&err));
+    }
 }